@@ -1,8 +1,22 @@
 ///! Build script for CBXShell
 
+use std::path::Path;
+
+/// CLSID of the released shell extension
+const DEFAULT_CLSID: &str = "9E6ECB90-5A61-42BD-B851-D3297D9C7F39";
+
+/// Environment variable overriding the CLSID (for side-by-side dev installs)
+const CLSID_ENV_VAR: &str = "CBXSHELL_CLSID";
+
+/// Reproducible-build timestamp (seconds since the Unix epoch), used for the build date when set
+const SOURCE_DATE_EPOCH_VAR: &str = "SOURCE_DATE_EPOCH";
+
 fn main() {
     eprintln!("=== CBXShell Build Script Started ===");
 
+    generate_clsid();
+    emit_build_date();
+
     // Detect if building binary or library
     let target = std::env::var("CARGO_BIN_NAME").ok();
     let crate_name = std::env::var("CARGO_CRATE_NAME").unwrap_or_default();
@@ -52,3 +66,97 @@ fn main() {
 
     eprintln!("=== Build Script Completed ===");
 }
+
+/// Write `$OUT_DIR/clsid.rs` with the CLSID constants (see src/clsid.rs)
+///
+/// Fails the build if `CBXSHELL_CLSID` is set but is not a valid GUID.
+fn generate_clsid() {
+    println!("cargo:rerun-if-env-changed={}", CLSID_ENV_VAR);
+
+    let requested = std::env::var(CLSID_ENV_VAR)
+        .ok()
+        .filter(|value| !value.trim().is_empty());
+
+    let clsid = match &requested {
+        Some(value) => parse_guid(value).unwrap_or_else(|| {
+            panic!("{} is not a valid GUID: {:?}", CLSID_ENV_VAR, value)
+        }),
+        None => parse_guid(DEFAULT_CLSID).expect("default CLSID is valid"),
+    };
+
+    let clsid_str = format!(
+        "{{{:08X}-{:04X}-{:04X}-{:04X}-{:012X}}}",
+        clsid >> 96,
+        (clsid >> 80) & 0xFFFF,
+        (clsid >> 64) & 0xFFFF,
+        (clsid >> 48) & 0xFFFF,
+        clsid & 0xFFFF_FFFF_FFFF
+    );
+    eprintln!("CLSID: {} ({})", clsid_str, if requested.is_some() { "override" } else { "default" });
+
+    let generated = format!(
+        "/// Shell extension CLSID in registry form\n\
+         pub const CLSID_STR: &str = {clsid_str:?};\n\
+         /// Shell extension CLSID as a 128-bit value (for `GUID::from_u128`)\n\
+         pub const CLSID_U128: u128 = 0x{clsid:032X};\n\
+         /// Per-CLSID settings key under HKCU\n\
+         pub const CONFIG_KEY_PATH: &str = {config_key:?};\n",
+        config_key = format!("Software\\CBXShell-rs\\{}", clsid_str),
+    );
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    std::fs::write(Path::new(&out_dir).join("clsid.rs"), generated)
+        .expect("failed to write generated clsid.rs");
+}
+
+/// Set `CBXSHELL_BUILD_DATE` (YYYY-MM-DD, UTC) for the manager's About dialog
+///
+/// The date is `SOURCE_DATE_EPOCH` if set, otherwise when this script last ran.
+fn emit_build_date() {
+    println!("cargo:rerun-if-env-changed={}", SOURCE_DATE_EPOCH_VAR);
+
+    let seconds = std::env::var(SOURCE_DATE_EPOCH_VAR)
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| {
+            std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or(0)
+        });
+
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    println!("cargo:rustc-env=CBXSHELL_BUILD_DATE={:04}-{:02}-{:02}", year, month, day);
+}
+
+/// Gregorian (year, month, day) of a day count since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    // Howard Hinnant's algorithm, with eras of 400 years starting in March
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Parse a GUID ("XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX", braces optional)
+fn parse_guid(value: &str) -> Option<u128> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix('{')
+        .and_then(|v| v.strip_suffix('}'))
+        .unwrap_or(value);
+
+    let groups: Vec<&str> = value.split('-').collect();
+    let lengths: Vec<usize> = groups.iter().map(|g| g.len()).collect();
+    if lengths != [8, 4, 4, 4, 12] || !groups.iter().all(|g| g.chars().all(|c| c.is_ascii_hexdigit())) {
+        return None;
+    }
+
+    u128::from_str_radix(&groups.concat(), 16).ok()
+}
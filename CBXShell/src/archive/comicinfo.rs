@@ -0,0 +1,72 @@
+//! ComicInfo.xml front cover
+//!
+//! ComicRack-style archives carry a `ComicInfo.xml` whose `<Pages>` section
+//! describes each page, e.g. `<Page Image="2" Type="FrontCover"/>`. `Image`
+//! is the 0-based index into the archive's images in page (natural) order.
+//! The tags are scanned with the EPUB module's minimal XML helpers, so no
+//! XML parser dependency is required.
+
+use super::epub::{attribute, start_tags};
+use super::utils::{decode_text, find_entry_ci, is_image_file, natural_sort_cmp};
+use super::{Archive, ArchiveEntry};
+
+/// Metadata entry at the archive root
+const COMICINFO_PATH: &str = "ComicInfo.xml";
+
+/// Name of the image `ComicInfo.xml` marks as the front cover
+///
+/// Returns `None` if there is no `ComicInfo.xml`, it can't be read, no page
+/// is typed `FrontCover`, or its `Image` index is past the last image.
+pub fn declared_cover<A: Archive + ?Sized>(archive: &A, entries: &[ArchiveEntry]) -> Option<String> {
+    let files = || entries.iter().filter(|e| !e.is_directory);
+    let name = find_entry_ci(files().map(|e| e.name.as_str()), COMICINFO_PATH)?;
+    let entry = files().find(|e| e.name == name)?;
+    let data = archive.extract_entry(entry).ok()?;
+    let index = front_cover_index(&decode_text(&data))?;
+
+    let mut images: Vec<&str> = files()
+        .map(|e| e.name.as_str())
+        .filter(|name| is_image_file(name))
+        .collect();
+    images.sort_by(|a, b| natural_sort_cmp(a, b));
+
+    images.get(index).map(|name| name.to_string())
+}
+
+/// `Image` index of the first page typed `FrontCover`
+fn front_cover_index(xml: &str) -> Option<usize> {
+    start_tags(xml, "Page")
+        .into_iter()
+        .find(|tag| attribute(tag, "Type").is_some_and(|t| t.trim().eq_ignore_ascii_case("FrontCover")))
+        .and_then(|tag| attribute(tag, "Image"))
+        .and_then(|image| image.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_front_cover_index() {
+        let xml = r#"<?xml version="1.0"?>
+<ComicInfo xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance">
+  <Series>Example</Series>
+  <Pages>
+    <Page Image="0" Type="Advertisement" ImageSize="1024" />
+    <Page Image="1" ImageSize="2048"/>
+    <Page Type="FrontCover" Image="2" />
+    <Page Image="3" Type="FrontCover"/>
+  </Pages>
+</ComicInfo>"#;
+        assert_eq!(front_cover_index(xml), Some(2));
+    }
+
+    #[test]
+    fn test_front_cover_index_missing_or_invalid() {
+        assert_eq!(front_cover_index("<ComicInfo><Series>X</Series></ComicInfo>"), None);
+        assert_eq!(front_cover_index(r#"<Pages><Page Image="0" Type="Story"/></Pages>"#), None);
+        assert_eq!(front_cover_index(r#"<Pages><Page Image="first" Type="FrontCover"/></Pages>"#), None);
+        assert_eq!(front_cover_index(r#"<Pages><Page Type="FrontCover"/></Pages>"#), None);
+        assert_eq!(front_cover_index("not xml at all"), None);
+    }
+}
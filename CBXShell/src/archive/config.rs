@@ -1,61 +1,852 @@
 ///! Configuration management for archive processing
 ///!
 ///! Reads settings from the Windows registry
+///!
+///! Settings are re-read while Explorer keeps the DLL loaded: the thumbnail
+///! path reads a snapshot of the key that is reused for at most `CONFIG_TTL`,
+///! so changes made in the manager apply to new thumbnails within a second.
+///!
+///! Live (next extraction after the TTL): Enabled, NoSort, SortByBasename, PreferCoverName, HonorArchiveOrderCover,
+///! HonorNoThumbMarker, FormatPriority, SniffExtensionless, CoverStrategy,
+///! MaxEntrySizeMB, MaxTotalDecodeBytes, NoUpscale, TrimBorders, ThumbnailBitDepth,
+///! ThumbnailBackground, CoverPageIndex, CoverPercent, ArchivePassword.
+///! Per-extension overrides under `Extensions\<.ext>` (NoSort, CoverStrategy,
+///! CoverPercent, OpenStrategy) are read on every extraction.
+///!
+///! Require an Explorer restart: WorkerThreads (the pool is created once per
+///! process). EnabledExtensions takes effect when the manager re-registers the
+///! file associations. Thumbnails already in the Explorer cache are not
+///! regenerated until the cache is cleared.
+///!
+///! Consistency: `apply_config` writes every value in one registry
+///! transaction. Reading the values takes one call each, so a commit landing
+///! in the middle of a read would mix old and new settings; the key is
+///! therefore read until two reads in a row agree (at most
+///! `CONSISTENT_READ_ATTEMPTS` times). Registry errors other than a missing
+///! key are retried the same way. A read racing a writer returns either the
+///! old or the new settings, and the next snapshot after the TTL always has
+///! the new ones. The thumbnail and property paths take one snapshot per
+///! extraction (`ExtractionSettings`) and derive every setting they use from
+///! it, so one extraction never mixes two snapshots.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use winreg::RegKey;
 use winreg::enums::*;
+use winreg::transaction::Transaction;
+
+use crate::clsid::CONFIG_KEY_PATH;
+use crate::utils::error::{CbxError, Result};
+use super::utils::MAX_ENTRY_SIZE;
+use super::CoverOptions;
 
-const CONFIG_KEY_PATH: &str = "Software\\CBXShell-rs\\{9E6ECB90-5A61-42BD-B851-D3297D9C7F39}";
 const NO_SORT_VALUE: &str = "NoSort";
+const HONOR_ARCHIVE_ORDER_COVER_VALUE: &str = "HonorArchiveOrderCover";
+const HONOR_NO_THUMB_MARKER_VALUE: &str = "HonorNoThumbMarker";
+const FORMAT_PRIORITY_VALUE: &str = "FormatPriority";
+const SNIFF_EXTENSIONLESS_VALUE: &str = "SniffExtensionless";
+const SORT_BY_BASENAME_VALUE: &str = "SortByBasename";
+const PREFER_COVER_NAME_VALUE: &str = "PreferCoverName";
+const COVER_STRATEGY_VALUE: &str = "CoverStrategy";
+const ENABLED_EXTENSIONS_VALUE: &str = "EnabledExtensions";
+const MAX_ENTRY_SIZE_MB_VALUE: &str = "MaxEntrySizeMB";
+const ENABLED_VALUE: &str = "Enabled";
+const DEBUG_LOGGING_VALUE: &str = "DebugLogging";
+const DEBUG_LOG_PATH_VALUE: &str = "DebugLogPath";
+const WORKER_THREADS_VALUE: &str = "WorkerThreads";
+const MAX_TOTAL_DECODE_BYTES_VALUE: &str = "MaxTotalDecodeBytes";
+const NO_UPSCALE_VALUE: &str = "NoUpscale";
+const AUTO_LEVELS_VALUE: &str = "AutoLevels";
+const TRIM_BORDERS_VALUE: &str = "TrimBorders";
+const EMBED_COVER_CACHE_VALUE: &str = "EmbedCoverCache";
+const IN_MEMORY_THRESHOLD_MB_VALUE: &str = "InMemoryThresholdMB";
+const THUMBNAIL_BIT_DEPTH_VALUE: &str = "ThumbnailBitDepth";
+const THUMBNAIL_BACKGROUND_VALUE: &str = "ThumbnailBackground";
+const OPEN_STRATEGY_VALUE: &str = "OpenStrategy";
+const COVER_PAGE_INDEX_VALUE: &str = "CoverPageIndex";
+const COVER_PERCENT_VALUE: &str = "CoverPercent";
+const ARCHIVE_PASSWORD_VALUE: &str = "ArchivePassword";
+
+/// Subkey holding per-extension overrides, e.g. `Extensions\.epub`
+const EXTENSIONS_SUBKEY: &str = "Extensions";
+
+/// How long a registry snapshot is reused before the key is read again
+const CONFIG_TTL: Duration = Duration::from_secs(1);
+
+/// Snapshot used by the per-extraction readers below
+static CONFIG_CACHE: ConfigCache = ConfigCache::new(CONFIG_KEY_PATH, CONFIG_TTL);
+
+/// Default budget for all concurrent decode buffers (matches `memory_budget`)
+const DEFAULT_MAX_TOTAL_DECODE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// Upper bound for the decode worker pool size
+const MAX_WORKER_THREADS: u32 = 64;
+
+/// Streams up to this size are read into memory in one go (4MB)
+const DEFAULT_IN_MEMORY_THRESHOLD_MB: u32 = 4;
+
+/// Upper bound for the in-memory threshold (the whole archive is buffered)
+pub(crate) const MAX_IN_MEMORY_THRESHOLD_MB: u32 = 256;
+
+/// Thumbnail bitmap depth used unless configured otherwise (C++ behavior)
+const DEFAULT_THUMBNAIL_BIT_DEPTH: u32 = 32;
+
+/// Highest `ThumbnailBackground` value (2 = black)
+const MAX_THUMBNAIL_BACKGROUND: u32 = 2;
+
+/// Extensions handled by default (matches the manager's file type list)
+const DEFAULT_EXTENSIONS: &[&str] = &[".cbz", ".cbr", ".zip", ".phz", ".rar", ".7z", ".cb7"];
+
+/// Default per-entry size cap in megabytes
+const DEFAULT_MAX_ENTRY_SIZE_MB: u32 = (MAX_ENTRY_SIZE / (1024 * 1024)) as u32;
+
+/// Upper bound for the per-entry size cap (the entry is buffered in memory)
+const MAX_MAX_ENTRY_SIZE_MB: u32 = 256;
+
+/// Percentage used by `CoverStrategy::Percent` unless `CoverPercent` is set
+/// (far enough in to skip a title page in a typical issue)
+pub const DEFAULT_COVER_PERCENT: u8 = 10;
+
+/// How the cover image is chosen from an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CoverStrategy {
+    /// First image (natural-sorted when sorting is enabled, archive order otherwise)
+    #[default]
+    FirstImage,
+    /// Cover image declared in an EPUB's package document (OPF); archives
+    /// without one fall back to `FirstImage`
+    OpfCover,
+    /// Page typed `FrontCover` in the archive's `ComicInfo.xml`; archives
+    /// without one fall back to `FirstImage`
+    ComicInfo,
+    /// Most colorful of the first few images (`smart_cover::MAX_SAMPLED_IMAGES`),
+    /// skipping monochrome text pages; falls back to `FirstImage` if none
+    /// can be decoded
+    SmartCover,
+    /// Image this many percent into the images, i.e. at index
+    /// `floor(p * count / 100)`, clamped to the last image (0-100, `CoverPercent`)
+    Percent(u8),
+}
+
+impl CoverStrategy {
+    /// Registry string representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CoverStrategy::FirstImage => "FirstImage",
+            CoverStrategy::OpfCover => "OpfCover",
+            CoverStrategy::ComicInfo => "ComicInfo",
+            CoverStrategy::SmartCover => "SmartCover",
+            CoverStrategy::Percent(_) => "Percent",
+        }
+    }
+
+    /// Parse the registry string representation (case-insensitive)
+    ///
+    /// The percentage is stored separately (`CoverPercent`), so "Percent"
+    /// parses to `Percent(DEFAULT_COVER_PERCENT)`.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "firstimage" => Some(CoverStrategy::FirstImage),
+            "opfcover" => Some(CoverStrategy::OpfCover),
+            "comicinfo" => Some(CoverStrategy::ComicInfo),
+            "smartcover" => Some(CoverStrategy::SmartCover),
+            "percent" => Some(CoverStrategy::Percent(DEFAULT_COVER_PERCENT)),
+            _ => None,
+        }
+    }
+}
+
+/// How an archive handed over as a stream is read (`Extensions\<.ext>\OpenStrategy`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpenStrategy {
+    /// Read into memory up to `InMemoryThresholdMB`, stream larger archives
+    #[default]
+    Auto,
+    /// Always read from the stream on demand
+    Stream,
+    /// Read the whole archive into memory first (up to 256MB, larger
+    /// archives are streamed)
+    Memory,
+    /// Always copy the stream into a temp file and open that
+    TempFile,
+}
+
+impl OpenStrategy {
+    /// Registry string representation
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OpenStrategy::Auto => "Auto",
+            OpenStrategy::Stream => "Stream",
+            OpenStrategy::Memory => "Memory",
+            OpenStrategy::TempFile => "TempFile",
+        }
+    }
 
-/// Read the sorting preference from the registry
+    /// Parse the registry string representation (case-insensitive)
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "auto" => Some(OpenStrategy::Auto),
+            "stream" => Some(OpenStrategy::Stream),
+            "memory" => Some(OpenStrategy::Memory),
+            "tempfile" => Some(OpenStrategy::TempFile),
+            _ => None,
+        }
+    }
+
+    /// Strategy used for `extension` when it has no override
+    ///
+    /// RAR streams: the RAR backend copies the stream into its own temp file
+    /// (unrar needs a path), so reading it into memory first only adds a
+    /// copy. Everything else: `Auto`.
+    pub fn default_for_extension(extension: Option<&str>) -> Self {
+        match extension.map(extension_subkey_name).as_deref() {
+            Some(".rar" | ".cbr") => OpenStrategy::Stream,
+            _ => OpenStrategy::Auto,
+        }
+    }
+}
+
+/// Complete set of CBXShell settings stored under the CBXShell-rs key
 ///
-/// Returns `true` if images should be sorted alphabetically.
-/// Returns `false` if the first image encountered should be used (default for performance).
+/// Each field maps to one registry value, so the registry key itself is the
+/// serialized form. Missing or malformed values read back as defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CbxConfig {
+    /// Kill-switch: with `false` no thumbnails are extracted (`Enabled`)
+    pub enabled: bool,
+    /// Sort images by name before picking the cover (`NoSort`, inverted)
+    pub sort_images: bool,
+    /// With sorting off, a first-in-archive image is always the cover (`HonorArchiveOrderCover`)
+    pub honor_archive_order_cover: bool,
+    /// Skip archives containing a `.nothumb`/`NOTHUMB` marker entry (`HonorNoThumbMarker`)
+    pub honor_no_thumb_marker: bool,
+    /// Preferred cover extensions for images sharing a base name, e.g. ["png", "jpg"]
+    /// (`FormatPriority`, REG_SZ "png,jpg"); empty = pure natural sort
+    pub format_priority: Vec<String>,
+    /// Detect extensionless images by magic bytes (`SniffExtensionless`)
+    pub sniff_extensionless: bool,
+    /// Sort by file name before directory when picking the cover (`SortByBasename`)
+    pub sort_by_basename: bool,
+    /// Prefer the only image named like a cover (`PreferCoverName`)
+    pub prefer_cover_name: bool,
+    /// Cover selection strategy (`CoverStrategy`, REG_SZ; the percentage of
+    /// `Percent` is `CoverPercent`, DWORD)
+    pub cover_strategy: CoverStrategy,
+    /// Extensions with thumbnails enabled, e.g. ".cbz" (`EnabledExtensions`, REG_MULTI_SZ)
+    pub enabled_extensions: Vec<String>,
+    /// Maximum uncompressed size of a cover entry in MB (`MaxEntrySizeMB`)
+    pub max_entry_size_mb: u32,
+    /// Write the debug log file (`DebugLogging`)
+    pub debug_logging: bool,
+    /// Debug log file path (`DebugLogPath`); empty = `%TEMP%\cbxshell_debug.log`
+    pub debug_log_path: String,
+    /// Decode worker pool size, 0 = decode inline (`WorkerThreads`)
+    pub worker_threads: u32,
+    /// Total bytes all concurrent decodes may commit, 0 = unlimited (`MaxTotalDecodeBytes`, REG_QWORD)
+    pub max_total_decode_bytes: u64,
+    /// Never enlarge covers smaller than the requested thumbnail (`NoUpscale`)
+    pub no_upscale: bool,
+    /// Stretch the tonal range of dark covers before scaling (`AutoLevels`)
+    pub auto_levels: bool,
+    /// Crop uniform scan borders before scaling (`TrimBorders`)
+    pub trim_borders: bool,
+    /// Write and use `cover_cache.png` in writable ZIP archives (`EmbedCoverCache`)
+    pub embed_cover_cache: bool,
+    /// Streams up to this many megabytes are read into memory, 0 = always stream (`InMemoryThresholdMB`)
+    pub in_memory_threshold_mb: u32,
+    /// Bits per pixel of opaque thumbnails: 16, 24 or 32 (`ThumbnailBitDepth`)
+    pub thumbnail_bit_depth: u32,
+    /// Fill behind transparent covers: 0 = keep transparency, 1 = white, 2 = black (`ThumbnailBackground`)
+    pub thumbnail_background: u32,
+    /// 0-based index of the image used as the cover with `FirstImage` (`CoverPageIndex`)
+    pub cover_page_index: u32,
+    /// Password for encrypted ZIP entries (`ArchivePassword`, REG_SZ); empty = none
+    pub archive_password: String,
+}
+
+impl Default for CbxConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sort_images: false,
+            honor_archive_order_cover: false,
+            honor_no_thumb_marker: false,
+            format_priority: Vec::new(),
+            sniff_extensionless: false,
+            sort_by_basename: false,
+            prefer_cover_name: false,
+            cover_strategy: CoverStrategy::default(),
+            enabled_extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            max_entry_size_mb: DEFAULT_MAX_ENTRY_SIZE_MB,
+            debug_logging: false,
+            debug_log_path: String::new(),
+            worker_threads: 0,
+            max_total_decode_bytes: DEFAULT_MAX_TOTAL_DECODE_BYTES,
+            no_upscale: true,
+            auto_levels: false,
+            trim_borders: false,
+            embed_cover_cache: false,
+            in_memory_threshold_mb: DEFAULT_IN_MEMORY_THRESHOLD_MB,
+            thumbnail_bit_depth: DEFAULT_THUMBNAIL_BIT_DEPTH,
+            thumbnail_background: 0,
+            cover_page_index: 0,
+            archive_password: String::new(),
+        }
+    }
+}
+
+/// Read all settings from the registry
 ///
-/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\NoSort
-/// - Value 0 = sort enabled (true)
-/// - Value 1 or missing = sort disabled (false, default)
-pub fn should_sort_images() -> bool {
-    match read_no_sort_setting() {
-        Ok(no_sort) => !no_sort,  // Invert: NoSort=0 means sort=true
-        Err(_) => {
-            // Default to NOT sorting for better performance with large archives
-            tracing::debug!("Failed to read NoSort setting, defaulting to unsorted mode (fast)");
-            false
+/// Never fails: a missing key or value yields the corresponding default.
+pub fn read_config() -> CbxConfig {
+    read_config_from(CONFIG_KEY_PATH)
+}
+
+/// Write all settings to the registry in a single transaction
+///
+/// Either every value is written or none is; readers never observe a
+/// partially applied configuration. A `CoverStrategy::Percent` above 100 is
+/// rejected with `CbxError::Registry` (it would read back clamped).
+pub fn apply_config(config: &CbxConfig) -> Result<()> {
+    let result = apply_config_to(CONFIG_KEY_PATH, config);
+    CONFIG_CACHE.invalidate();
+    result
+}
+
+/// Registry snapshot reused for a short time
+///
+/// One thumbnail extraction consults several settings; caching avoids
+/// reopening the key for each of them while still picking up changes
+/// without an Explorer restart.
+struct ConfigCache {
+    key_path: &'static str,
+    ttl: Duration,
+    snapshot: Mutex<Option<(Instant, CbxConfig)>>,
+}
+
+impl ConfigCache {
+    const fn new(key_path: &'static str, ttl: Duration) -> Self {
+        Self {
+            key_path,
+            ttl,
+            snapshot: Mutex::new(None),
+        }
+    }
+
+    /// Current settings, re-read from the registry once the snapshot expires
+    fn get(&self) -> CbxConfig {
+        // The snapshot is only ever replaced whole, so a poisoned lock is still consistent
+        let mut snapshot = self.snapshot.lock().unwrap_or_else(|e| e.into_inner());
+
+        match snapshot.as_ref() {
+            Some((read_at, config)) if read_at.elapsed() < self.ttl => config.clone(),
+            _ => {
+                let config = read_config_from(self.key_path);
+                *snapshot = Some((Instant::now(), config.clone()));
+                config
+            }
         }
     }
+
+    /// Drop the snapshot so the next read goes to the registry
+    fn invalidate(&self) {
+        *self.snapshot.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
 }
 
-/// Read the NoSort registry value
+/// Settings for the current extraction (cached for at most `CONFIG_TTL`)
+fn current_config() -> CbxConfig {
+    CONFIG_CACHE.get()
+}
+
+/// Reads of the settings key before giving up on two in a row agreeing
+const CONSISTENT_READ_ATTEMPTS: usize = 4;
+
+/// Pause before reading again after a registry error
+const READ_RETRY_DELAY: Duration = Duration::from_millis(5);
+
+/// Read the settings under `key_path`, retrying until two reads agree
 ///
-/// Returns `Ok(true)` if NoSort=1 or missing (sorting disabled, default)
-/// Returns `Ok(false)` if NoSort=0 (sorting enabled)
-fn read_no_sort_setting() -> Result<bool, std::io::Error> {
+/// See the module docs for the consistency model. If the settings keep
+/// changing, the last read is returned; if the key can't be read at all,
+/// the defaults.
+fn read_config_from(key_path: &str) -> CbxConfig {
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let mut previous: Option<CbxConfig> = None;
 
-    match hkcu.open_subkey(CONFIG_KEY_PATH) {
-        Ok(key) => {
-            match key.get_value::<u32, _>(NO_SORT_VALUE) {
-                Ok(value) => Ok(value != 0),  // NonZero = true (don't sort)
-                Err(_) => Ok(true),  // Missing value = true (don't sort, for performance)
+    for _ in 0..CONSISTENT_READ_ATTEMPTS {
+        let config = match hkcu.open_subkey(key_path) {
+            Ok(key) => read_values(&key),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => CbxConfig::default(),
+            Err(e) => {
+                tracing::debug!("Failed to open {}, retrying: {}", key_path, e);
+                std::thread::sleep(READ_RETRY_DELAY);
+                continue;
             }
+        };
+
+        if previous.as_ref() == Some(&config) {
+            return config;
+        }
+        previous = Some(config);
+    }
+
+    tracing::debug!("Settings under {} changed while being read", key_path);
+    previous.unwrap_or_default()
+}
+
+/// Read every setting from an open key (missing or malformed values are defaults)
+fn read_values(key: &RegKey) -> CbxConfig {
+    let defaults = CbxConfig::default();
+
+    CbxConfig {
+        enabled: key
+            .get_value::<u32, _>(ENABLED_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.enabled),
+        sort_images: read_no_sort_setting(key).unwrap_or(defaults.sort_images),
+        honor_archive_order_cover: key
+            .get_value::<u32, _>(HONOR_ARCHIVE_ORDER_COVER_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.honor_archive_order_cover),
+        honor_no_thumb_marker: key
+            .get_value::<u32, _>(HONOR_NO_THUMB_MARKER_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.honor_no_thumb_marker),
+        format_priority: key
+            .get_value::<String, _>(FORMAT_PRIORITY_VALUE)
+            .map(|s| parse_format_priority(&s))
+            .unwrap_or(defaults.format_priority),
+        sniff_extensionless: key
+            .get_value::<u32, _>(SNIFF_EXTENSIONLESS_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.sniff_extensionless),
+        sort_by_basename: key
+            .get_value::<u32, _>(SORT_BY_BASENAME_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.sort_by_basename),
+        prefer_cover_name: key
+            .get_value::<u32, _>(PREFER_COVER_NAME_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.prefer_cover_name),
+        cover_strategy: read_cover_strategy(key).unwrap_or(defaults.cover_strategy),
+        enabled_extensions: key
+            .get_value::<Vec<String>, _>(ENABLED_EXTENSIONS_VALUE)
+            .unwrap_or(defaults.enabled_extensions),
+        max_entry_size_mb: key
+            .get_value::<u32, _>(MAX_ENTRY_SIZE_MB_VALUE)
+            .map(clamp_max_entry_size_mb)
+            .unwrap_or(defaults.max_entry_size_mb),
+        debug_logging: key
+            .get_value::<u32, _>(DEBUG_LOGGING_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.debug_logging),
+        debug_log_path: key
+            .get_value::<String, _>(DEBUG_LOG_PATH_VALUE)
+            .map(|path| path.trim().to_string())
+            .unwrap_or(defaults.debug_log_path),
+        worker_threads: key
+            .get_value::<u32, _>(WORKER_THREADS_VALUE)
+            .map(|v| v.min(MAX_WORKER_THREADS))
+            .unwrap_or(defaults.worker_threads),
+        max_total_decode_bytes: read_u64_value(key, MAX_TOTAL_DECODE_BYTES_VALUE)
+            .unwrap_or(defaults.max_total_decode_bytes),
+        no_upscale: key
+            .get_value::<u32, _>(NO_UPSCALE_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.no_upscale),
+        auto_levels: key
+            .get_value::<u32, _>(AUTO_LEVELS_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.auto_levels),
+        trim_borders: key
+            .get_value::<u32, _>(TRIM_BORDERS_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.trim_borders),
+        embed_cover_cache: key
+            .get_value::<u32, _>(EMBED_COVER_CACHE_VALUE)
+            .map(|v| v != 0)
+            .unwrap_or(defaults.embed_cover_cache),
+        in_memory_threshold_mb: key
+            .get_value::<u32, _>(IN_MEMORY_THRESHOLD_MB_VALUE)
+            .map(|v| v.min(MAX_IN_MEMORY_THRESHOLD_MB))
+            .unwrap_or(defaults.in_memory_threshold_mb),
+        thumbnail_bit_depth: key
+            .get_value::<u32, _>(THUMBNAIL_BIT_DEPTH_VALUE)
+            .ok()
+            .filter(|bits| matches!(bits, 16 | 24 | 32))
+            .unwrap_or(defaults.thumbnail_bit_depth),
+        thumbnail_background: key
+            .get_value::<u32, _>(THUMBNAIL_BACKGROUND_VALUE)
+            .ok()
+            .filter(|value| *value <= MAX_THUMBNAIL_BACKGROUND)
+            .unwrap_or(defaults.thumbnail_background),
+        cover_page_index: key
+            .get_value::<u32, _>(COVER_PAGE_INDEX_VALUE)
+            .unwrap_or(defaults.cover_page_index),
+        archive_password: key
+            .get_value::<String, _>(ARCHIVE_PASSWORD_VALUE)
+            .unwrap_or(defaults.archive_password),
+    }
+}
+
+fn apply_config_to(key_path: &str, config: &CbxConfig) -> Result<()> {
+    if let CoverStrategy::Percent(percent) = config.cover_strategy {
+        if percent > 100 {
+            return Err(CbxError::Registry(format!(
+                "{} must be 0-100, got {}",
+                COVER_PERCENT_VALUE, percent
+            )));
+        }
+    }
+
+    let registry_err = |e: std::io::Error| CbxError::registry_access(key_path, e);
+
+    let transaction = Transaction::new().map_err(registry_err)?;
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let (key, _) = hkcu
+        .create_subkey_transacted(key_path, &transaction)
+        .map_err(registry_err)?;
+
+    key.set_value(ENABLED_VALUE, &(config.enabled as u32))
+        .map_err(registry_err)?;
+    let no_sort: u32 = if config.sort_images { 0 } else { 1 };
+    key.set_value(NO_SORT_VALUE, &no_sort).map_err(registry_err)?;
+    key.set_value(HONOR_ARCHIVE_ORDER_COVER_VALUE, &(config.honor_archive_order_cover as u32))
+        .map_err(registry_err)?;
+    key.set_value(HONOR_NO_THUMB_MARKER_VALUE, &(config.honor_no_thumb_marker as u32))
+        .map_err(registry_err)?;
+    key.set_value(FORMAT_PRIORITY_VALUE, &config.format_priority.join(","))
+        .map_err(registry_err)?;
+    key.set_value(SNIFF_EXTENSIONLESS_VALUE, &(config.sniff_extensionless as u32))
+        .map_err(registry_err)?;
+    key.set_value(SORT_BY_BASENAME_VALUE, &(config.sort_by_basename as u32))
+        .map_err(registry_err)?;
+    key.set_value(PREFER_COVER_NAME_VALUE, &(config.prefer_cover_name as u32))
+        .map_err(registry_err)?;
+    key.set_value(COVER_STRATEGY_VALUE, &config.cover_strategy.as_str())
+        .map_err(registry_err)?;
+    if let CoverStrategy::Percent(percent) = config.cover_strategy {
+        key.set_value(COVER_PERCENT_VALUE, &u32::from(percent))
+            .map_err(registry_err)?;
+    }
+    key.set_value(ENABLED_EXTENSIONS_VALUE, &config.enabled_extensions)
+        .map_err(registry_err)?;
+    key.set_value(MAX_ENTRY_SIZE_MB_VALUE, &config.max_entry_size_mb)
+        .map_err(registry_err)?;
+    key.set_value(DEBUG_LOGGING_VALUE, &(config.debug_logging as u32))
+        .map_err(registry_err)?;
+    key.set_value(DEBUG_LOG_PATH_VALUE, &config.debug_log_path)
+        .map_err(registry_err)?;
+    key.set_value(WORKER_THREADS_VALUE, &config.worker_threads)
+        .map_err(registry_err)?;
+    key.set_value(MAX_TOTAL_DECODE_BYTES_VALUE, &config.max_total_decode_bytes)
+        .map_err(registry_err)?;
+    key.set_value(NO_UPSCALE_VALUE, &(config.no_upscale as u32))
+        .map_err(registry_err)?;
+    key.set_value(AUTO_LEVELS_VALUE, &(config.auto_levels as u32))
+        .map_err(registry_err)?;
+    key.set_value(TRIM_BORDERS_VALUE, &(config.trim_borders as u32))
+        .map_err(registry_err)?;
+    key.set_value(EMBED_COVER_CACHE_VALUE, &(config.embed_cover_cache as u32))
+        .map_err(registry_err)?;
+    key.set_value(IN_MEMORY_THRESHOLD_MB_VALUE, &config.in_memory_threshold_mb)
+        .map_err(registry_err)?;
+    key.set_value(THUMBNAIL_BIT_DEPTH_VALUE, &config.thumbnail_bit_depth)
+        .map_err(registry_err)?;
+    key.set_value(THUMBNAIL_BACKGROUND_VALUE, &config.thumbnail_background)
+        .map_err(registry_err)?;
+    key.set_value(COVER_PAGE_INDEX_VALUE, &config.cover_page_index)
+        .map_err(registry_err)?;
+    key.set_value(ARCHIVE_PASSWORD_VALUE, &config.archive_password)
+        .map_err(registry_err)?;
+
+    // Dropping an uncommitted transaction rolls it back
+    transaction.commit().map_err(registry_err)
+}
+
+/// Read the global kill-switch from the registry
+///
+/// Lets an administrator turn thumbnails off (e.g. by Group Policy) without
+/// touching the class registration; Explorer then shows the default icons.
+/// Like the other live settings, a change applies within `CONFIG_TTL`.
+///
+/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\Enabled
+/// - Value 1 or missing = thumbnails are extracted (default)
+/// - Value 0 = `GetThumbnail` declines every file
+pub fn is_enabled() -> bool {
+    current_config().enabled
+}
+
+/// Read `NoSort` from an open config key, returning whether sorting is enabled
+///
+/// The value is normally a DWORD, but older installs and hand edits store it
+/// as a string ("1"); both are accepted. Missing or malformed values give
+/// `None`, so the caller's default applies.
+pub fn read_no_sort_setting(key: &RegKey) -> Option<bool> {
+    let no_sort = match key.get_value::<u32, _>(NO_SORT_VALUE) {
+        Ok(no_sort) => no_sort,
+        Err(_) => {
+            let text = key.get_value::<String, _>(NO_SORT_VALUE).ok()?;
+            match text.trim().parse::<u32>() {
+                Ok(no_sort) => no_sort,
+                Err(_) => {
+                    tracing::warn!("Ignoring malformed {} value: {:?}", NO_SORT_VALUE, text);
+                    return None;
+                }
+            }
+        }
+    };
+    Some(no_sort == 0)
+}
+
+/// Parse "png, .JPG;webp" into ["png", "jpg", "webp"]
+fn parse_format_priority(s: &str) -> Vec<String> {
+    s.split([',', ';'])
+        .map(|ext| ext.trim().trim_start_matches('.').to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+/// Read `CoverStrategy` from an open config key
+///
+/// For `Percent`, the percentage comes from `CoverPercent` in the same key
+/// (DWORD, values above 100 are clamped); missing = `DEFAULT_COVER_PERCENT`.
+fn read_cover_strategy(key: &RegKey) -> Option<CoverStrategy> {
+    let strategy = CoverStrategy::parse(&key.get_value::<String, _>(COVER_STRATEGY_VALUE).ok()?)?;
+
+    Some(match strategy {
+        CoverStrategy::Percent(default) => CoverStrategy::Percent(
+            key.get_value::<u32, _>(COVER_PERCENT_VALUE)
+                .map(|percent| percent.min(100) as u8)
+                .unwrap_or(default),
+        ),
+        strategy => strategy,
+    })
+}
+
+/// The settings one extraction works with
+///
+/// One snapshot of the settings key plus the overrides for the archive's
+/// extension, taken once per extraction. Everything the extraction consults
+/// is derived from it, so a change applied meanwhile is seen either entirely
+/// or not at all (see the module docs).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtractionSettings {
+    pub config: CbxConfig,
+    /// Overrides under `Extensions\<extension>`; none if the extension is unknown
+    pub extension_override: ExtensionOverride,
+    extension: Option<String>,
+}
+
+impl ExtractionSettings {
+    /// Take the snapshot for an archive with `extension` (e.g. ".epub"), if known
+    pub fn read(extension: Option<&str>) -> Self {
+        Self::from_config(current_config(), extension, |ext| {
+            read_extension_override_from(CONFIG_KEY_PATH, ext)
+        })
+    }
+
+    fn from_config(
+        config: CbxConfig,
+        extension: Option<&str>,
+        read_override: impl FnOnce(&str) -> ExtensionOverride,
+    ) -> Self {
+        Self {
+            config,
+            extension_override: extension.map(read_override).unwrap_or_default(),
+            extension: extension.map(str::to_string),
         }
-        Err(_) => Ok(true),  // Missing key = true (don't sort, for performance)
+    }
+
+    /// Cover selection settings, with the extension's overrides applied
+    pub fn cover_options(&self) -> CoverOptions {
+        let mut options = CoverOptions {
+            sort: self.config.sort_images,
+            sort_by_basename: self.config.sort_by_basename,
+            prefer_cover_name: self.config.prefer_cover_name,
+            honor_archive_order: self.config.honor_archive_order_cover,
+            format_priority: self.config.format_priority.clone(),
+            sniff_extensionless: self.config.sniff_extensionless,
+            strategy: self.config.cover_strategy,
+            page_index: self.config.cover_page_index as usize,
+        };
+        self.extension_override.apply(&mut options);
+        options
+    }
+
+    /// How the archive's stream is opened
+    ///
+    /// Registry location: HKCU\Software\CBXShell-rs\{GUID}\Extensions\<.ext>\OpenStrategy
+    /// - REG_SZ "Auto", "Stream", "Memory" or "TempFile"
+    /// - Missing or unknown = `OpenStrategy::default_for_extension`
+    pub fn open_strategy(&self) -> OpenStrategy {
+        resolve_open_strategy(self.extension.as_deref(), self.extension_override.open_strategy)
+    }
+
+    /// Streams up to this many bytes are read into memory (see `read_in_memory_threshold`)
+    pub fn in_memory_threshold(&self) -> u64 {
+        u64::from(self.config.in_memory_threshold_mb) * 1024 * 1024
+    }
+}
+
+/// The overridden strategy if there is one, else the extension's default
+fn resolve_open_strategy(extension: Option<&str>, preferred: Option<OpenStrategy>) -> OpenStrategy {
+    preferred.unwrap_or_else(|| OpenStrategy::default_for_extension(extension))
+}
+
+/// Settings overridden for a single file extension
+///
+/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\Extensions\<.ext>
+/// - NoSort (DWORD or numeric REG_SZ): same meaning as the global value
+/// - CoverStrategy (REG_SZ): same values as the global setting
+/// - CoverPercent (DWORD): percentage for a `Percent` override (the global
+///   `CoverPercent` is not inherited)
+/// - OpenStrategy (REG_SZ): see `ExtractionSettings::open_strategy`
+///
+/// Unset values (or a missing subkey) keep the global setting.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExtensionOverride {
+    pub sort: Option<bool>,
+    pub cover_strategy: Option<CoverStrategy>,
+    pub open_strategy: Option<OpenStrategy>,
+}
+
+impl ExtensionOverride {
+    /// Replace the overridden settings in `options`
+    pub fn apply(&self, options: &mut CoverOptions) {
+        if let Some(sort) = self.sort {
+            options.sort = sort;
+        }
+        if let Some(strategy) = self.cover_strategy {
+            options.strategy = strategy;
+        }
+    }
+}
+
+/// Normalize an extension to the subkey name: lowercase with a leading dot
+fn extension_subkey_name(extension: &str) -> String {
+    format!(".{}", extension.trim().trim_start_matches('.').to_ascii_lowercase())
+}
+
+fn read_extension_override_from(key_path: &str, extension: &str) -> ExtensionOverride {
+    let subkey = format!("{}\\{}\\{}", key_path, EXTENSIONS_SUBKEY, extension_subkey_name(extension));
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+
+    let key = match hkcu.open_subkey(&subkey) {
+        Ok(key) => key,
+        Err(_) => return ExtensionOverride::default(),
+    };
+
+    ExtensionOverride {
+        sort: read_no_sort_setting(&key),
+        cover_strategy: read_cover_strategy(&key),
+        open_strategy: key
+            .get_value::<String, _>(OPEN_STRATEGY_VALUE)
+            .ok()
+            .and_then(|s| OpenStrategy::parse(&s)),
     }
 }
 
+/// Read the per-entry size cap from the registry, in bytes
+///
+/// Entries larger than this are not extracted, so a malicious archive can't
+/// make the thumbnailer allocate unbounded memory.
+///
+/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\MaxEntrySizeMB
+/// - Value N = entries up to N MB are extracted (capped at 256)
+/// - Value 0 or missing = 32MB (default)
+pub fn read_max_entry_size() -> u64 {
+    u64::from(current_config().max_entry_size_mb) * 1024 * 1024
+}
+
+/// Treat 0 as unset and cap `MaxEntrySizeMB` at `MAX_MAX_ENTRY_SIZE_MB`
+fn clamp_max_entry_size_mb(value: u32) -> u32 {
+    match value {
+        0 => DEFAULT_MAX_ENTRY_SIZE_MB,
+        v => v.min(MAX_MAX_ENTRY_SIZE_MB),
+    }
+}
+
+/// Read the in-memory threshold from the registry, in bytes
+///
+/// Reading a small archive in one go is cheaper than the many small reads
+/// streaming makes through the COM stream.
+///
+/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\InMemoryThresholdMB
+/// - Value N = streams up to N MB are read into memory (capped at 256)
+/// - Value 0 = always stream
+/// - Missing = 4MB (default)
+pub fn read_in_memory_threshold() -> u64 {
+    u64::from(current_config().in_memory_threshold_mb) * 1024 * 1024
+}
+
+/// Read the EmbedCoverCache preference from the registry
+///
+/// When enabled, `embed_cover_cache` may add a scaled `cover_cache.png` to
+/// writable ZIP archives, and thumbnails use that entry when present.
+///
+/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\EmbedCoverCache
+/// - Value 0 or missing = never write to archives (default)
+/// - Value 1 = write and use embedded cover caches
+pub fn should_embed_cover_cache() -> bool {
+    current_config().embed_cover_cache
+}
+
+/// Read the password for encrypted ZIP entries from the registry
+///
+/// Used for ZipCrypto and WinZip AES entries; every encrypted entry is tried
+/// with the same password. The value is stored in plain text.
+///
+/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\ArchivePassword
+/// - REG_SZ password (used as is, including surrounding spaces)
+/// - Empty or missing = `None` (encrypted entries fail with `CbxError::PasswordRequired`)
+pub fn read_archive_password() -> Option<String> {
+    Some(current_config().archive_password).filter(|password| !password.is_empty())
+}
+
+/// Read the DebugLogging preference from the registry
+///
+/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\DebugLogging
+/// - Value 1 = write the debug log
+/// - Value 0 or missing = no logging (default)
+pub fn should_debug_log() -> bool {
+    current_config().debug_logging
+}
+
+/// Read the debug log file path from the registry
+///
+/// Registry location: HKCU\Software\CBXShell-rs\{GUID}\DebugLogPath
+/// - REG_SZ path of the log file
+/// - Empty or missing = `None` (the log goes to the temp directory)
+pub fn read_debug_log_path() -> Option<String> {
+    Some(current_config().debug_log_path).filter(|path| !path.is_empty())
+}
+
+/// Read a REG_QWORD value, accepting REG_DWORD as well
+fn read_u64_value(key: &RegKey, name: &str) -> Option<u64> {
+    key.get_value::<u64, _>(name)
+        .or_else(|_| key.get_value::<u32, _>(name).map(u64::from))
+        .ok()
+}
+
 /// Set the sorting preference in the registry (for testing/configuration)
 ///
 /// If `sort` is true, sets NoSort=0 (sorting enabled)
 /// If `sort` is false, sets NoSort=1 (sorting disabled)
+///
+/// Fails with `CbxError::RegistryAccess` if the key can't be written.
 #[allow(dead_code)]
-pub fn set_should_sort_images(sort: bool) -> Result<(), std::io::Error> {
+pub fn set_should_sort_images(sort: bool) -> Result<()> {
+    let registry_err = |e: std::io::Error| CbxError::registry_access(CONFIG_KEY_PATH, e);
+
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let (key, _) = hkcu.create_subkey(CONFIG_KEY_PATH)?;
+    let (key, _) = hkcu.create_subkey(CONFIG_KEY_PATH).map_err(registry_err)?;
 
     let no_sort_value: u32 = if sort { 0 } else { 1 };
-    key.set_value(NO_SORT_VALUE, &no_sort_value)?;
+    key.set_value(NO_SORT_VALUE, &no_sort_value).map_err(registry_err)?;
+    CONFIG_CACHE.invalidate();
 
     Ok(())
 }
@@ -68,7 +859,7 @@ mod tests {
     fn test_read_no_sort_default() {
         // Should default to sorting if key doesn't exist
         // (This test will pass even if registry key exists)
-        let result = should_sort_images();
+        let result = current_config().sort_images;
         assert!(result == true || result == false);  // Just verify it doesn't crash
     }
 
@@ -76,14 +867,405 @@ mod tests {
     fn test_set_and_read_sorting() {
         // Test round-trip (might fail if no registry access)
         if set_should_sort_images(true).is_ok() {
-            assert_eq!(should_sort_images(), true);
+            assert_eq!(current_config().sort_images, true);
         }
 
         if set_should_sort_images(false).is_ok() {
-            assert_eq!(should_sort_images(), false);
+            assert_eq!(current_config().sort_images, false);
         }
 
         // Cleanup: restore to default (sorting disabled for performance)
         let _ = set_should_sort_images(false);
     }
+
+    #[test]
+    fn test_read_config_missing_key_is_default() {
+        let config = read_config_from("Software\\CBXShell-rs\\Test\\DoesNotExist");
+        assert_eq!(config, CbxConfig::default());
+    }
+
+    #[test]
+    fn test_apply_and_read_config_round_trip() {
+        const KEY_PATH: &str = "Software\\CBXShell-rs\\Test\\ConfigRoundTrip";
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+
+        let config = CbxConfig {
+            enabled: false,
+            sort_images: true,
+            honor_archive_order_cover: true,
+            honor_no_thumb_marker: true,
+            format_priority: vec!["png".to_string(), "jpg".to_string()],
+            sniff_extensionless: true,
+            sort_by_basename: true,
+            prefer_cover_name: true,
+            cover_strategy: CoverStrategy::Percent(25),
+            enabled_extensions: vec![".cbz".to_string(), ".cb7".to_string()],
+            max_entry_size_mb: 64,
+            debug_logging: true,
+            debug_log_path: "C:\\Logs\\cbxshell.log".to_string(),
+            worker_threads: 4,
+            max_total_decode_bytes: 128 * 1024 * 1024,
+            no_upscale: false,
+            auto_levels: true,
+            trim_borders: true,
+            embed_cover_cache: true,
+            in_memory_threshold_mb: 16,
+            thumbnail_bit_depth: 24,
+            thumbnail_background: 2,
+            cover_page_index: 2,
+            archive_password: "s3cret pass".to_string(),
+        };
+
+        // Might fail if no registry access (or KTM unavailable)
+        if apply_config_to(KEY_PATH, &config).is_ok() {
+            assert_eq!(read_config_from(KEY_PATH), config);
+
+            // Overwrite with defaults and read back again
+            apply_config_to(KEY_PATH, &CbxConfig::default()).unwrap();
+            assert_eq!(read_config_from(KEY_PATH), CbxConfig::default());
+        }
+
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+    }
+
+    #[test]
+    fn test_apply_config_rejects_percent_over_100() {
+        const KEY_PATH: &str = "Software\\CBXShell-rs\\Test\\RejectCoverPercent";
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+
+        let config = CbxConfig {
+            cover_strategy: CoverStrategy::Percent(250),
+            ..Default::default()
+        };
+        assert!(matches!(apply_config_to(KEY_PATH, &config), Err(CbxError::Registry(_))));
+        // Rejected before anything is written
+        assert!(hkcu.open_subkey(KEY_PATH).is_err());
+
+        // The largest valid percentage reads back unchanged
+        let config = CbxConfig {
+            cover_strategy: CoverStrategy::Percent(100),
+            ..Default::default()
+        };
+        if apply_config_to(KEY_PATH, &config).is_ok() {
+            assert_eq!(read_config_from(KEY_PATH), config);
+        }
+
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+    }
+
+    #[test]
+    fn test_concurrent_apply_and_read() {
+        const KEY_PATH: &str = "Software\\CBXShell-rs\\Test\\ConcurrentApply";
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+
+        let before = CbxConfig::default();
+        let after = CbxConfig {
+            sort_images: false,
+            format_priority: vec!["webp".to_string()],
+            cover_strategy: CoverStrategy::Percent(50),
+            max_entry_size_mb: 128,
+            no_upscale: false,
+            cover_page_index: 3,
+            archive_password: "swapped".to_string(),
+            ..CbxConfig::default()
+        };
+
+        // Might fail if no registry access (or KTM unavailable)
+        if apply_config_to(KEY_PATH, &before).is_ok() {
+            let writing = std::sync::atomic::AtomicBool::new(true);
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    for i in 0..100 {
+                        let config = if i % 2 == 0 { &after } else { &before };
+                        apply_config_to(KEY_PATH, config).unwrap();
+                    }
+                    apply_config_to(KEY_PATH, &after).unwrap();
+                    writing.store(false, std::sync::atomic::Ordering::Release);
+                });
+
+                for _ in 0..4 {
+                    scope.spawn(|| {
+                        while writing.load(std::sync::atomic::Ordering::Acquire) {
+                            // No value is ever seen half written
+                            let config = read_config_from(KEY_PATH);
+                            assert!([&before, &after]
+                                .iter()
+                                .any(|c| c.archive_password == config.archive_password));
+                            assert!([&before, &after]
+                                .iter()
+                                .any(|c| c.format_priority == config.format_priority));
+                        }
+                    });
+                }
+            });
+
+            // Once the writer is done, every read sees its last write
+            assert_eq!(read_config_from(KEY_PATH), after);
+            let cache = ConfigCache::new(KEY_PATH, Duration::from_secs(60));
+            assert_eq!(cache.get(), after);
+        }
+
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+    }
+
+    #[test]
+    fn test_clamp_max_entry_size_mb() {
+        assert_eq!(clamp_max_entry_size_mb(0), 32);
+        assert_eq!(clamp_max_entry_size_mb(1), 1);
+        assert_eq!(clamp_max_entry_size_mb(128), 128);
+        assert_eq!(clamp_max_entry_size_mb(256), 256);
+        assert_eq!(clamp_max_entry_size_mb(257), 256);
+        assert_eq!(clamp_max_entry_size_mb(u32::MAX), 256);
+    }
+
+    #[test]
+    fn test_max_entry_size_read_back_clamped() {
+        const KEY_PATH: &str = "Software\\CBXShell-rs\\Test\\MaxEntrySize";
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+
+        if let Ok((key, _)) = hkcu.create_subkey(KEY_PATH) {
+            // Missing value = default
+            assert_eq!(read_config_from(KEY_PATH).max_entry_size_mb, 32);
+
+            key.set_value(MAX_ENTRY_SIZE_MB_VALUE, &0u32).unwrap();
+            assert_eq!(read_config_from(KEY_PATH).max_entry_size_mb, 32);
+
+            key.set_value(MAX_ENTRY_SIZE_MB_VALUE, &100u32).unwrap();
+            assert_eq!(read_config_from(KEY_PATH).max_entry_size_mb, 100);
+
+            key.set_value(MAX_ENTRY_SIZE_MB_VALUE, &4096u32).unwrap();
+            assert_eq!(read_config_from(KEY_PATH).max_entry_size_mb, 256);
+        }
+
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+    }
+
+    #[test]
+    fn test_config_cache_picks_up_external_change() {
+        const KEY_PATH: &str = "Software\\CBXShell-rs\\Test\\ConfigCacheTtl";
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+
+        let ttl = Duration::from_millis(100);
+        let cache = ConfigCache::new(KEY_PATH, ttl);
+        assert!(cache.get().no_upscale);
+
+        // Simulate the manager (another process) changing a value directly
+        if let Ok((key, _)) = hkcu.create_subkey(KEY_PATH) {
+            key.set_value(NO_UPSCALE_VALUE, &0u32).unwrap();
+
+            // Observed on the first read after the TTL, without a restart
+            std::thread::sleep(ttl + Duration::from_millis(50));
+            assert!(!cache.get().no_upscale);
+
+            // Invalidation makes a change visible immediately
+            key.set_value(NO_UPSCALE_VALUE, &1u32).unwrap();
+            cache.invalidate();
+            assert!(cache.get().no_upscale);
+        }
+
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+    }
+
+    #[test]
+    fn test_config_cache_reuses_snapshot_within_ttl() {
+        let cache = ConfigCache::new("Software\\CBXShell-rs\\Test\\DoesNotExist", Duration::from_secs(60));
+        let first = cache.get();
+        let (read_at, _) = cache.snapshot.lock().unwrap().clone().unwrap();
+
+        assert_eq!(cache.get(), first);
+        assert_eq!(cache.snapshot.lock().unwrap().as_ref().unwrap().0, read_at);
+    }
+
+    #[test]
+    fn test_read_no_sort_setting_value_types() {
+        const KEY_PATH: &str = "Software\\CBXShell-rs\\Test\\NoSortValueTypes";
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+
+        // Might fail if no registry access
+        if let Ok((key, _)) = hkcu.create_subkey(KEY_PATH) {
+            assert_eq!(read_no_sort_setting(&key), None);
+
+            // DWORD
+            key.set_value(NO_SORT_VALUE, &0u32).unwrap();
+            assert_eq!(read_no_sort_setting(&key), Some(true));
+            key.set_value(NO_SORT_VALUE, &1u32).unwrap();
+            assert_eq!(read_no_sort_setting(&key), Some(false));
+
+            // Numeric REG_SZ (legacy installs, manual edits)
+            key.set_value(NO_SORT_VALUE, &"0").unwrap();
+            assert_eq!(read_no_sort_setting(&key), Some(true));
+            key.set_value(NO_SORT_VALUE, &" 1 ").unwrap();
+            assert_eq!(read_no_sort_setting(&key), Some(false));
+            assert!(!read_config_from(KEY_PATH).sort_images);
+
+            // Malformed string: the default applies
+            key.set_value(NO_SORT_VALUE, &"yes").unwrap();
+            assert_eq!(read_no_sort_setting(&key), None);
+            assert_eq!(read_config_from(KEY_PATH).sort_images, CbxConfig::default().sort_images);
+        }
+
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+    }
+
+    #[test]
+    fn test_parse_format_priority() {
+        assert_eq!(parse_format_priority("png,jpg,webp"), vec!["png", "jpg", "webp"]);
+        assert_eq!(parse_format_priority(" .PNG ; jpg,, "), vec!["png", "jpg"]);
+        assert!(parse_format_priority("").is_empty());
+    }
+
+    #[test]
+    fn test_cover_strategy_string_round_trip() {
+        for strategy in [
+            CoverStrategy::FirstImage,
+            CoverStrategy::OpfCover,
+            CoverStrategy::ComicInfo,
+            CoverStrategy::SmartCover,
+            CoverStrategy::Percent(DEFAULT_COVER_PERCENT),
+        ] {
+            assert_eq!(CoverStrategy::parse(strategy.as_str()), Some(strategy));
+        }
+        assert_eq!(CoverStrategy::parse("PERCENT"), Some(CoverStrategy::Percent(10)));
+        assert_eq!(CoverStrategy::parse("firstimage"), Some(CoverStrategy::FirstImage));
+        assert_eq!(CoverStrategy::parse(" OPFCOVER "), Some(CoverStrategy::OpfCover));
+        assert_eq!(CoverStrategy::parse("comicinfo"), Some(CoverStrategy::ComicInfo));
+        assert_eq!(CoverStrategy::parse("bogus"), None);
+    }
+
+    #[test]
+    fn test_read_cover_strategy_percent() {
+        const KEY_PATH: &str = "Software\\CBXShell-rs\\Test\\CoverPercent";
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+
+        // Might fail if no registry access
+        if let Ok((key, _)) = hkcu.create_subkey(KEY_PATH) {
+            key.set_value(COVER_STRATEGY_VALUE, &"Percent").unwrap();
+            assert_eq!(read_cover_strategy(&key), Some(CoverStrategy::Percent(DEFAULT_COVER_PERCENT)));
+
+            key.set_value(COVER_PERCENT_VALUE, &50u32).unwrap();
+            assert_eq!(read_cover_strategy(&key), Some(CoverStrategy::Percent(50)));
+
+            key.set_value(COVER_PERCENT_VALUE, &250u32).unwrap();
+            assert_eq!(read_cover_strategy(&key), Some(CoverStrategy::Percent(100)));
+
+            // Ignored by the other strategies
+            key.set_value(COVER_STRATEGY_VALUE, &"SmartCover").unwrap();
+            assert_eq!(read_cover_strategy(&key), Some(CoverStrategy::SmartCover));
+        }
+
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+    }
+
+    #[test]
+    fn test_extension_override() {
+        const KEY_PATH: &str = "Software\\CBXShell-rs\\Test\\ExtensionOverride";
+        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+
+        let epub_key = format!("{}\\{}\\.epub", KEY_PATH, EXTENSIONS_SUBKEY);
+
+        // Might fail if no registry access
+        if let Ok((key, _)) = hkcu.create_subkey(&epub_key) {
+            key.set_value(COVER_STRATEGY_VALUE, &"OpfCover").unwrap();
+            key.set_value(NO_SORT_VALUE, &1u32).unwrap();
+            key.set_value(OPEN_STRATEGY_VALUE, &"memory").unwrap();
+
+            let global = CoverOptions { sort: true, ..Default::default() };
+
+            // Extension with an override (lookup is case- and dot-insensitive)
+            let epub = read_extension_override_from(KEY_PATH, "EPUB");
+            assert_eq!(
+                epub,
+                ExtensionOverride {
+                    sort: Some(false),
+                    cover_strategy: Some(CoverStrategy::OpfCover),
+                    open_strategy: Some(OpenStrategy::Memory),
+                }
+            );
+            let mut options = global.clone();
+            epub.apply(&mut options);
+            assert!(!options.sort);
+            assert_eq!(options.strategy, CoverStrategy::OpfCover);
+
+            // Extension without an override keeps the global settings
+            let cbz = read_extension_override_from(KEY_PATH, ".cbz");
+            assert_eq!(cbz, ExtensionOverride::default());
+            let mut options = global.clone();
+            cbz.apply(&mut options);
+            assert_eq!(options, global);
+        }
+
+        let _ = hkcu.delete_subkey_all(KEY_PATH);
+    }
+
+    #[test]
+    fn test_extraction_settings_from_config() {
+        let config = CbxConfig {
+            sort_images: true,
+            prefer_cover_name: true,
+            cover_page_index: 2,
+            ..Default::default()
+        };
+        let epub_override = ExtensionOverride {
+            sort: Some(false),
+            cover_strategy: Some(CoverStrategy::OpfCover),
+            open_strategy: Some(OpenStrategy::Memory),
+        };
+
+        // Everything is derived from the one snapshot plus the override
+        let epub = ExtractionSettings::from_config(config.clone(), Some(".epub"), |ext| {
+            assert_eq!(ext, ".epub");
+            epub_override.clone()
+        });
+        let options = epub.cover_options();
+        assert!(!options.sort);
+        assert!(options.prefer_cover_name);
+        assert_eq!(options.page_index, 2);
+        assert_eq!(options.strategy, CoverStrategy::OpfCover);
+        assert_eq!(epub.open_strategy(), OpenStrategy::Memory);
+        assert_eq!(epub.config, config);
+
+        // Without an extension no override is read
+        let unknown = ExtractionSettings::from_config(config, None, |_| unreachable!());
+        assert!(unknown.cover_options().sort);
+        assert_eq!(unknown.cover_options().strategy, CoverStrategy::default());
+        assert_eq!(unknown.open_strategy(), OpenStrategy::Auto);
+    }
+
+    #[test]
+    fn test_resolve_open_strategy() {
+        // Defaults per format
+        assert_eq!(resolve_open_strategy(Some(".cbz"), None), OpenStrategy::Auto);
+        assert_eq!(resolve_open_strategy(Some(".cb7"), None), OpenStrategy::Auto);
+        assert_eq!(resolve_open_strategy(Some(".CBR"), None), OpenStrategy::Stream);
+        assert_eq!(resolve_open_strategy(Some("rar"), None), OpenStrategy::Stream);
+        assert_eq!(resolve_open_strategy(None, None), OpenStrategy::Auto);
+
+        // An override wins, also over a format default
+        assert_eq!(resolve_open_strategy(Some(".cb7"), Some(OpenStrategy::Memory)), OpenStrategy::Memory);
+        assert_eq!(resolve_open_strategy(Some(".cbr"), Some(OpenStrategy::Auto)), OpenStrategy::Auto);
+        assert_eq!(resolve_open_strategy(None, Some(OpenStrategy::TempFile)), OpenStrategy::TempFile);
+    }
+
+    #[test]
+    fn test_open_strategy_string_round_trip() {
+        for strategy in [OpenStrategy::Auto, OpenStrategy::Stream, OpenStrategy::Memory, OpenStrategy::TempFile] {
+            assert_eq!(OpenStrategy::parse(strategy.as_str()), Some(strategy));
+        }
+        assert_eq!(OpenStrategy::parse(" TEMPFILE "), Some(OpenStrategy::TempFile));
+        assert_eq!(OpenStrategy::parse("disk"), None);
+    }
+
+    #[test]
+    fn test_extension_subkey_name() {
+        assert_eq!(extension_subkey_name("epub"), ".epub");
+        assert_eq!(extension_subkey_name(".CBZ"), ".cbz");
+    }
 }
@@ -0,0 +1,356 @@
+//! Cover thumbnails cached inside the archive (`EmbedCoverCache`)
+//!
+//! Finding and decoding the cover of a large archive is the slow part of
+//! thumbnailing. For archives the user owns, the scaled cover can be written
+//! back into the ZIP as a small PNG entry (`cover_cache.png`) and used
+//! directly on later opens.
+//!
+//! The PNG records the cover options and size it was rendered with in a
+//! `tEXt` chunk. A cache rendered with other options (e.g. after changing
+//! `CoverStrategy` or `CoverPageIndex`) or smaller than the thumbnail wanted
+//! is ignored. Rendering settings such as `TrimBorders` are applied to the
+//! cached cover like to any other, so they need no record.
+//!
+//! Writing is strictly opt-in: `embed_cover_cache` does nothing unless
+//! `EmbedCoverCache` is enabled, works on path-based ZIP archives only and
+//! refuses read-only files. Archives opened from a stream (the Explorer
+//! path) are only ever read.
+//!
+//! The archive is never modified in place: the entry is appended to a copy
+//! next to it, which then replaces the original in one rename. A crash or a
+//! full disk mid-write leaves the original untouched.
+
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::config::should_embed_cover_cache;
+use super::utils::find_entry_ci;
+use super::{open_archive, ArchiveEntry, ArchiveType, CoverOptions};
+use crate::image_processor::encode::encode_cover_png;
+use crate::utils::error::{CbxError, Result};
+
+/// Name of the cached cover entry, stored at the archive root
+pub const COVER_CACHE_ENTRY: &str = "cover_cache.png";
+
+/// PNG `tEXt` keyword of the record of what the cache was rendered with
+const COVER_CACHE_KEYWORD: &str = "CBXShell-CoverCache";
+
+/// Whether an entry is the cached cover written by `embed_cover_cache`
+pub fn is_cover_cache(name: &str) -> bool {
+    name.eq_ignore_ascii_case(COVER_CACHE_ENTRY)
+}
+
+/// The cached cover entry in a listing, if the archive has one
+pub fn find_cover_cache(entries: &[ArchiveEntry]) -> Option<&ArchiveEntry> {
+    let files = || entries.iter().filter(|e| !e.is_directory);
+    let name = find_entry_ci(files().map(|e| e.name.as_str()), COVER_CACHE_ENTRY)?;
+    files().find(|e| e.name == name)
+}
+
+/// Record of the size and cover options a cache is rendered with
+fn cover_cache_record(options: &CoverOptions, size: u32) -> String {
+    format!("{} {:?}", size, options)
+}
+
+/// Whether cached cover `png` can stand in for the cover chosen with
+/// `options`, scaled to `size`
+///
+/// It must have been rendered with the same options and at least at `size`
+/// (a larger cache is scaled down like any cover). A PNG without a record,
+/// e.g. one written by an older version or by hand, never matches.
+pub fn cover_cache_matches(png: &[u8], options: &CoverOptions, size: u32) -> bool {
+    let record = read_png_text(png, COVER_CACHE_KEYWORD).and_then(|text| std::str::from_utf8(text).ok());
+    let Some((cached_size, cached_options)) = record.and_then(|record| record.split_once(' ')) else {
+        return false;
+    };
+    cached_size.parse::<u32>().is_ok_and(|cached_size| cached_size >= size)
+        && cached_options == format!("{:?}", options)
+}
+
+/// PNG signature followed by the IHDR chunk, which is always 13 bytes long
+const PNG_HEADER_LEN: usize = 8 + 4 + 4 + 13 + 4;
+
+/// `png` with a `tEXt` chunk `keyword`=`text` inserted right after IHDR
+fn add_png_text(png: &[u8], keyword: &str, text: &str) -> Result<Vec<u8>> {
+    if png.len() < PNG_HEADER_LEN || !png.starts_with(b"\x89PNG\r\n\x1a\n") || &png[12..16] != b"IHDR" {
+        return Err(CbxError::Image("Not a PNG with an IHDR chunk".to_string()));
+    }
+
+    let mut chunk = b"tEXt".to_vec();
+    chunk.extend_from_slice(keyword.as_bytes());
+    chunk.push(0);
+    chunk.extend_from_slice(text.as_bytes());
+    let data_len = (chunk.len() - 4) as u32;
+
+    let mut out = Vec::with_capacity(png.len() + chunk.len() + 8);
+    out.extend_from_slice(&png[..PNG_HEADER_LEN]);
+    out.extend_from_slice(&data_len.to_be_bytes());
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&crc32(&chunk).to_be_bytes());
+    out.extend_from_slice(&png[PNG_HEADER_LEN..]);
+    Ok(out)
+}
+
+/// Text of the `tEXt` chunk `keyword` ahead of the image data, if any
+fn read_png_text<'a>(png: &'a [u8], keyword: &str) -> Option<&'a [u8]> {
+    let mut pos = 8;
+    while pos + 8 <= png.len() {
+        let len = u32::from_be_bytes(png[pos..pos + 4].try_into().ok()?) as usize;
+        let chunk_type = &png[pos + 4..pos + 8];
+        let data = png.get(pos + 8..(pos + 8).checked_add(len)?)?;
+        match chunk_type {
+            b"IDAT" | b"IEND" => return None,
+            b"tEXt" => {
+                if let Some(text) = data.strip_prefix(keyword.as_bytes()).and_then(|rest| rest.strip_prefix(&[0])) {
+                    return Some(text);
+                }
+            }
+            _ => {}
+        }
+        pos += 8 + len + 4;
+    }
+    None
+}
+
+/// CRC-32 (IEEE) as used by PNG chunks
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Write the scaled cover into the ZIP at `path` as `cover_cache.png`
+///
+/// The cover is chosen with `options` and scaled to fit within
+/// `size`x`size`, so `size` should be the largest thumbnail size wanted.
+///
+/// Appending can't remove entries, so a cache rendered with other options
+/// is left in place (and ignored when reading).
+///
+/// # Returns
+/// * `Ok(true)` - The cover cache entry was added
+/// * `Ok(false)` - `EmbedCoverCache` is off, or the archive already has a cache
+/// * `Err(CbxError)` - Not a ZIP archive, read-only file, or no usable cover
+pub fn embed_cover_cache(path: &Path, options: &CoverOptions, size: u32) -> Result<bool> {
+    if !should_embed_cover_cache() {
+        return Ok(false);
+    }
+    write_cover_cache(path, options, size)
+}
+
+/// `embed_cover_cache` without the registry check
+fn write_cover_cache(path: &Path, options: &CoverOptions, size: u32) -> Result<bool> {
+    let extension = path
+        .extension()
+        .and_then(|s| s.to_str())
+        .ok_or(CbxError::InvalidPath)?;
+    if ArchiveType::from_extension(extension) != Some(ArchiveType::Zip) {
+        return Err(CbxError::UnsupportedFormat(format!(
+            "Cover cache requires a ZIP archive: {}",
+            path.display()
+        )));
+    }
+
+    if std::fs::metadata(path)?.permissions().readonly() {
+        return Err(CbxError::Archive(format!(
+            "Archive is read-only: {}",
+            path.display()
+        )));
+    }
+
+    // Close the archive before reopening the file for writing
+    let png = {
+        let archive = open_archive(path)?;
+        if find_cover_cache(&archive.list_entries()?).is_some() {
+            tracing::debug!("Archive already has a cover cache: {}", path.display());
+            return Ok(false);
+        }
+
+        let cover = archive.find_cover_image(options)?;
+        let png = encode_cover_png(&archive.extract_entry(&cover)?, size)?;
+        add_png_text(&png, COVER_CACHE_KEYWORD, &cover_cache_record(options, size))?
+    };
+
+    let temp_path = temp_path_for(path);
+    let result = append_cover_cache_copy(path, &temp_path, &png)
+        .and_then(|()| std::fs::rename(&temp_path, path).map_err(CbxError::from));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result?;
+
+    tracing::info!("Embedded {} byte cover cache in {}", png.len(), path.display());
+    Ok(true)
+}
+
+/// Temp file next to `path`, on the same volume so the rename is atomic
+fn temp_path_for(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".cbxtmp");
+    path.with_file_name(name)
+}
+
+/// Copy the archive at `path` to `temp_path` and append the cover cache to the copy
+///
+/// The copy is flushed to disk before returning, so it is complete before it
+/// replaces the original.
+fn append_cover_cache_copy(path: &Path, temp_path: &Path, png: &[u8]) -> Result<()> {
+    std::fs::copy(path, temp_path)?;
+
+    let file = OpenOptions::new().read(true).write(true).open(temp_path)?;
+    let mut writer = ZipWriter::new_append(file)
+        .map_err(|e| CbxError::Archive(format!("Failed to open ZIP for writing: {}", e)))?;
+
+    // PNG data is already compressed
+    let file_options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    writer
+        .start_file(COVER_CACHE_ENTRY, file_options)
+        .map_err(|e| CbxError::Archive(format!("Failed to add cover cache: {}", e)))?;
+    std::io::Write::write_all(&mut writer, png)?;
+    let file = writer
+        .finish()
+        .map_err(|e| CbxError::Archive(format!("Failed to write ZIP directory: {}", e)))?;
+    file.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::ArchiveSession;
+    use crate::image_processor::decoder::read_image_dimensions;
+    use std::io::{Cursor, Write};
+    use zip::ZipWriter;
+
+    fn create_test_zip_file(path: &Path, files: &[(&str, &[u8])]) {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        std::fs::write(path, zip.finish().unwrap().into_inner()).unwrap();
+    }
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let img = image::RgbImage::from_pixel(width, height, image::Rgb([200, 40, 40]));
+        let mut out = Vec::new();
+        img.write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png)
+            .unwrap();
+        out
+    }
+
+    #[test]
+    fn test_embed_then_read_cover_cache() {
+        let temp_path = std::env::temp_dir().join("test_cover_cache.cbz");
+        let cover = png(400, 600);
+        create_test_zip_file(&temp_path, &[("page02.png", &png(8, 8)), ("page01.png", &cover)]);
+
+        let options = CoverOptions {
+            sort: true,
+            ..Default::default()
+        };
+        assert!(write_cover_cache(&temp_path, &options, 256).unwrap());
+
+        // The cache is found on the next open and holds the scaled cover
+        let session = ArchiveSession::new(open_archive(&temp_path).unwrap());
+        let (cached, data) = session.cover_cache(&options, 256).unwrap().expect("cover cache entry");
+        assert_eq!(cached.name, COVER_CACHE_ENTRY);
+        assert_eq!(read_image_dimensions(&data).unwrap(), (171, 256));
+
+        // Smaller thumbnails can use it, larger ones or other options can't
+        assert!(session.cover_cache(&options, 96).unwrap().is_some());
+        assert!(session.cover_cache(&options, 512).unwrap().is_none());
+        let other = CoverOptions {
+            page_index: 1,
+            ..options.clone()
+        };
+        assert!(session.cover_cache(&other, 256).unwrap().is_none());
+
+        // Existing pages are untouched; the cache only counts as one while
+        // `EmbedCoverCache` is off
+        let pages = if should_embed_cover_cache() { 2 } else { 3 };
+        assert_eq!(session.image_count().unwrap(), pages);
+        let entries = session.entries().unwrap();
+        let page = entries.iter().find(|e| e.name == "page01.png").unwrap();
+        assert_eq!(session.extract(page).unwrap(), cover);
+
+        // The copy replaced the original, nothing is left behind
+        assert!(!temp_path_for(&temp_path).exists());
+
+        // A second embed leaves the archive alone
+        drop(session);
+        assert!(!write_cover_cache(&temp_path, &options, 256).unwrap());
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_cover_cache_never_written_to_read_only_archive() {
+        let temp_path = std::env::temp_dir().join("test_cover_cache_readonly.cbz");
+        create_test_zip_file(&temp_path, &[("page01.png", &png(16, 16))]);
+        let original = std::fs::read(&temp_path).unwrap();
+
+        let mut permissions = std::fs::metadata(&temp_path).unwrap().permissions();
+        permissions.set_readonly(true);
+        std::fs::set_permissions(&temp_path, permissions.clone()).unwrap();
+
+        assert!(write_cover_cache(&temp_path, &CoverOptions::default(), 256).is_err());
+        assert_eq!(std::fs::read(&temp_path).unwrap(), original);
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        permissions.set_readonly(false);
+        std::fs::set_permissions(&temp_path, permissions).unwrap();
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_find_cover_cache_ignores_case() {
+        let entry = |name: &str, is_directory| ArchiveEntry {
+            name: name.to_string(),
+            size: 0,
+            is_directory,
+        };
+        let entries = [
+            entry("Cover_Cache.PNG/", true),
+            entry("page01.png", false),
+            entry("COVER_CACHE.png", false),
+        ];
+
+        assert_eq!(find_cover_cache(&entries).unwrap().name, "COVER_CACHE.png");
+        assert!(find_cover_cache(&entries[..2]).is_none());
+    }
+
+    #[test]
+    fn test_cover_cache_record() {
+        let options = CoverOptions {
+            strategy: crate::archive::CoverStrategy::Percent(50),
+            ..Default::default()
+        };
+        let plain = png(4, 4);
+        let tagged = add_png_text(&plain, COVER_CACHE_KEYWORD, &cover_cache_record(&options, 256)).unwrap();
+
+        // Still a valid PNG
+        assert_eq!(read_image_dimensions(&tagged).unwrap(), (4, 4));
+        assert!(image::load_from_memory(&tagged).is_ok());
+
+        assert!(cover_cache_matches(&tagged, &options, 256));
+        assert!(!cover_cache_matches(&tagged, &CoverOptions::default(), 256));
+        // Untagged (older or hand-made) caches are never used
+        assert!(!cover_cache_matches(&plain, &options, 0));
+        assert!(add_png_text(b"GIF89a", COVER_CACHE_KEYWORD, "").is_err());
+    }
+
+    #[test]
+    fn test_cover_cache_requires_zip() {
+        let result = write_cover_cache(Path::new("comic.cbr"), &CoverOptions::default(), 256);
+        assert!(matches!(result, Err(CbxError::UnsupportedFormat(_))));
+    }
+}
@@ -0,0 +1,141 @@
+//! Cover pinned by the archive itself (`.cbxcover`)
+//!
+//! A `.cbxcover` text entry at the archive root names the entry to use as
+//! the cover, e.g. `extras/Cover Art.jpg`. It wins over every cover setting
+//! (sort mode, format priority, OPF cover); the name is matched
+//! case-insensitively and with either path separator. If the named entry
+//! doesn't exist the normal selection is used.
+
+use super::utils::{bounded_name, decode_text, find_entry_ci, normalize_entry_name};
+use super::{Archive, ArchiveEntry};
+
+/// Name of the cover override entry, stored at the archive root
+pub const COVER_OVERRIDE_ENTRY: &str = ".cbxcover";
+
+/// Largest `.cbxcover` entry that is read (it holds a single file name)
+const MAX_COVER_OVERRIDE_LEN: usize = 4096;
+
+/// The entry named by the archive's `.cbxcover`, if it has one that resolves
+///
+/// `entries` are the archive's file entries (directories are ignored).
+pub fn find_pinned_cover<A: Archive + ?Sized>(
+    archive: &A,
+    entries: &[ArchiveEntry],
+) -> Option<ArchiveEntry> {
+    let files = || entries.iter().filter(|e| !e.is_directory);
+    let override_name = find_entry_ci(files().map(|e| e.name.as_str()), COVER_OVERRIDE_ENTRY)?;
+    let override_entry = files().find(|e| e.name == override_name)?;
+
+    let content = match archive.read_entry_prefix(override_entry, MAX_COVER_OVERRIDE_LEN) {
+        Ok(content) => content,
+        Err(e) => {
+            tracing::warn!("Failed to read {}: {}", override_name, e);
+            return None;
+        }
+    };
+    let target = parse_cover_override(&content)?;
+
+    let cover_name = find_entry_ci(
+        files()
+            .map(|e| e.name.as_str())
+            .filter(|name| *name != override_name),
+        &target,
+    );
+    match cover_name {
+        Some(name) => {
+            tracing::info!("Using cover pinned by {}: {}", override_name, bounded_name(name));
+            files().find(|e| e.name == name).cloned()
+        }
+        None => {
+            tracing::info!("{} names missing entry {:?}, using normal selection", override_name, target);
+            None
+        }
+    }
+}
+
+/// The entry name in a `.cbxcover` file: its first non-blank line
+///
+/// The file may be UTF-8 or UTF-16 (Notepad's "Unicode"), see
+/// `decode_text`. Surrounding whitespace and a leading `/` or `./` are
+/// ignored; backslashes are treated as path separators.
+fn parse_cover_override(content: &[u8]) -> Option<String> {
+    let text = decode_text(content);
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())?;
+
+    let name = normalize_entry_name(line);
+    let name = name.trim_start_matches("./").trim_start_matches('/');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{open_archive_from_memory, ArchiveSession, CoverOptions};
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::ZipWriter;
+
+    fn zip_with_override(target: &str) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in [
+            ("page01.jpg", &b"\xFF\xD8\xFF one"[..]),
+            ("page02.jpg", b"\xFF\xD8\xFF two"),
+            ("Extras/Back Cover.JPG", b"\xFF\xD8\xFF back"),
+            (COVER_OVERRIDE_ENTRY, target.as_bytes()),
+        ] {
+            zip.start_file(name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_cover_override_pins_existing_page() {
+        let archive = open_archive_from_memory(zip_with_override("extras\\back cover.jpg\r\n")).unwrap();
+
+        // Wins in every sort mode, both directly and through a session
+        for sort in [true, false] {
+            let options = CoverOptions {
+                sort,
+                ..Default::default()
+            };
+            assert_eq!(archive.find_cover_image(&options).unwrap().name, "Extras/Back Cover.JPG");
+        }
+
+        let session = ArchiveSession::new(archive);
+        let cover = session.cover(&CoverOptions::default()).unwrap();
+        assert_eq!(cover.name, "Extras/Back Cover.JPG");
+        assert_eq!(session.extract(&cover).unwrap(), b"\xFF\xD8\xFF back");
+    }
+
+    #[test]
+    fn test_cover_override_missing_page_falls_back() {
+        let archive = open_archive_from_memory(zip_with_override("page99.jpg")).unwrap();
+        let options = CoverOptions {
+            sort: true,
+            ..Default::default()
+        };
+
+        assert_eq!(archive.find_cover_image(&options).unwrap().name, "page01.jpg");
+        assert_eq!(ArchiveSession::new(archive).cover(&options).unwrap().name, "page01.jpg");
+    }
+
+    #[test]
+    fn test_parse_cover_override() {
+        assert_eq!(parse_cover_override(b"page03.jpg").as_deref(), Some("page03.jpg"));
+        assert_eq!(
+            parse_cover_override(b"\xEF\xBB\xBF\r\n  ./art\\cover.png  \r\nignored").as_deref(),
+            Some("art/cover.png")
+        );
+        assert_eq!(parse_cover_override(b"/cover.png").as_deref(), Some("cover.png"));
+        assert_eq!(
+            parse_cover_override(b"\xFF\xFEp\x001\x00.\x00p\x00n\x00g\x00").as_deref(),
+            Some("p1.png")
+        );
+        assert_eq!(parse_cover_override(b" \n\t\n"), None);
+        assert_eq!(parse_cover_override(b""), None);
+    }
+}
@@ -0,0 +1,447 @@
+//! EPUB reading order
+//!
+//! EPUB images usually live under `OEBPS/images/` with names that don't sort
+//! in page order. The package document (OPF) lists the pages in its spine;
+//! this module maps the spine to image entries so pages can be enumerated in
+//! reading order. Only the handful of elements needed are scanned for, so no
+//! XML parser dependency is required.
+
+use std::collections::HashSet;
+
+use super::utils::{decode_text, find_entry_ci, is_image_file, natural_sort_cmp};
+use super::{Archive, ArchiveEntry};
+
+/// Entry pointing at the package document of every EPUB
+const CONTAINER_PATH: &str = "META-INF/container.xml";
+
+/// Item declared in the OPF manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ManifestItem {
+    id: String,
+    /// Archive path, already resolved against the OPF's directory
+    path: String,
+    media_type: String,
+    /// Space-separated EPUB 3 properties, e.g. "cover-image"
+    properties: String,
+}
+
+/// Parsed OPF package document (manifest plus spine)
+#[derive(Debug, Default)]
+struct Package {
+    manifest: Vec<ManifestItem>,
+    spine: Vec<String>,
+    /// Manifest id named by an EPUB 2 `<meta name="cover" content="...">`
+    cover_id: Option<String>,
+}
+
+impl Package {
+    /// Archive path of the declared cover image, if any
+    ///
+    /// EPUB 3 marks it with `properties="cover-image"`; EPUB 2 names its
+    /// manifest id in a `cover` meta element.
+    fn cover_path(&self) -> Option<&str> {
+        let is_image = |item: &&ManifestItem| item.media_type.starts_with("image/");
+
+        self.manifest
+            .iter()
+            .filter(is_image)
+            .find(|item| item.properties.split_whitespace().any(|p| p == "cover-image"))
+            .or_else(|| {
+                let cover_id = self.cover_id.as_deref()?;
+                self.manifest.iter().filter(is_image).find(|item| item.id == cover_id)
+            })
+            .map(|item| item.path.as_str())
+    }
+}
+
+/// Whether the entries look like an EPUB (have `META-INF/container.xml`)
+pub fn is_epub(entries: &[ArchiveEntry]) -> bool {
+    find_file(entries, CONTAINER_PATH).is_some()
+}
+
+/// The file entry at `path` (compared case-insensitively, see `find_entry_ci`)
+fn find_file<'a>(entries: &'a [ArchiveEntry], path: &str) -> Option<&'a ArchiveEntry> {
+    let files = || entries.iter().filter(|e| !e.is_directory);
+    let name = find_entry_ci(files().map(|e| e.name.as_str()), path)?;
+    files().find(|e| e.name == name)
+}
+
+/// Name of the cover image declared in the package document
+///
+/// Returns `None` if the EPUB declares no cover, the declared cover isn't an
+/// image entry in the archive, or the package document can't be read.
+pub fn declared_cover<A: Archive + ?Sized>(archive: &A, entries: &[ArchiveEntry]) -> Option<String> {
+    let package = read_package(archive, entries)?;
+    let cover = find_file(entries, package.cover_path()?)?;
+
+    is_image_file(&cover.name).then(|| cover.name.clone())
+}
+
+/// Read an entry as text (UTF-8 or UTF-16, see `decode_text`)
+fn read_text<A: Archive + ?Sized>(archive: &A, entries: &[ArchiveEntry], path: &str) -> Option<String> {
+    let entry = find_file(entries, path)?;
+    let data = archive.extract_entry(entry).ok()?;
+    Some(decode_text(&data))
+}
+
+/// Locate and parse the package document via `META-INF/container.xml`
+fn read_package<A: Archive + ?Sized>(archive: &A, entries: &[ArchiveEntry]) -> Option<Package> {
+    let container = read_text(archive, entries, CONTAINER_PATH)?;
+    let opf_path = parse_rootfile_path(&container)?;
+    parse_package(&read_text(archive, entries, &opf_path)?, parent_dir(&opf_path))
+}
+
+/// Image entry names in EPUB reading order
+///
+/// Images referenced by spine documents come first, in spine order, followed
+/// by remaining manifest images in manifest order and finally images the
+/// manifest doesn't mention (natural-sorted). Returns `None` if the container
+/// or package document is missing or can't be parsed, so the caller can fall
+/// back to natural sort.
+pub fn spine_ordered_images<A: Archive + ?Sized>(
+    archive: &A,
+    entries: &[ArchiveEntry],
+) -> Option<Vec<String>> {
+    let package = read_package(archive, entries)?;
+
+    let image_names: Vec<&str> = entries
+        .iter()
+        .filter(|e| !e.is_directory && is_image_file(&e.name))
+        .map(|e| e.name.as_str())
+        .collect();
+    let images: HashSet<&str> = image_names.iter().copied().collect();
+
+    let mut ordered: Vec<String> = Vec::with_capacity(images.len());
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut push = |path: &str, ordered: &mut Vec<String>| {
+        // Hrefs don't always match the stored case; exact hits skip the scan
+        let name = match images.get(path) {
+            Some(name) => Some(*name),
+            None => find_entry_ci(image_names.iter().copied(), path),
+        };
+        if let Some(name) = name {
+            if seen.insert(name.to_string()) {
+                ordered.push(name.to_string());
+            }
+        }
+    };
+
+    // Pages in spine order: either images themselves or documents referencing them
+    for idref in &package.spine {
+        let Some(item) = package.manifest.iter().find(|item| &item.id == idref) else {
+            continue;
+        };
+
+        if item.media_type.starts_with("image/") {
+            push(&item.path, &mut ordered);
+        } else if let Some(document) = read_text(archive, entries, &item.path) {
+            for src in image_references(&document) {
+                push(&resolve_href(parent_dir(&item.path), &src), &mut ordered);
+            }
+        }
+    }
+
+    // Images outside the spine: manifest order, then anything undeclared
+    for item in package.manifest.iter().filter(|item| item.media_type.starts_with("image/")) {
+        push(&item.path, &mut ordered);
+    }
+
+    let mut rest: Vec<&str> = images
+        .iter()
+        .copied()
+        .filter(|name| !ordered.iter().any(|o| o == name))
+        .collect();
+    rest.sort_by(|a, b| natural_sort_cmp(a, b));
+    ordered.extend(rest.into_iter().map(str::to_string));
+
+    Some(ordered)
+}
+
+/// Path of the package document from `META-INF/container.xml`
+fn parse_rootfile_path(container: &str) -> Option<String> {
+    start_tags(container, "rootfile")
+        .into_iter()
+        .find_map(|tag| attribute(tag, "full-path"))
+        .map(|path| resolve_href("", &path))
+        .filter(|path| !path.is_empty())
+}
+
+/// Parse the manifest and spine of an OPF document located in `base_dir`
+fn parse_package(opf: &str, base_dir: &str) -> Option<Package> {
+    let manifest: Vec<ManifestItem> = start_tags(opf, "item")
+        .into_iter()
+        .filter_map(|tag| {
+            Some(ManifestItem {
+                id: attribute(tag, "id")?,
+                path: resolve_href(base_dir, &attribute(tag, "href")?),
+                media_type: attribute(tag, "media-type").unwrap_or_default().to_ascii_lowercase(),
+                properties: attribute(tag, "properties").unwrap_or_default(),
+            })
+        })
+        .collect();
+
+    let spine: Vec<String> = start_tags(opf, "itemref")
+        .into_iter()
+        .filter_map(|tag| attribute(tag, "idref"))
+        .collect();
+
+    if manifest.is_empty() || spine.is_empty() {
+        return None;
+    }
+
+    let cover_id = start_tags(opf, "meta")
+        .into_iter()
+        .find(|tag| attribute(tag, "name").as_deref() == Some("cover"))
+        .and_then(|tag| attribute(tag, "content"));
+
+    Some(Package { manifest, spine, cover_id })
+}
+
+/// Image references (`<img src>`, SVG `<image href>`) in document order
+fn image_references(document: &str) -> Vec<String> {
+    let mut refs: Vec<(usize, String)> = Vec::new();
+
+    for tag in start_tags(document, "img") {
+        if let Some(src) = attribute(tag, "src") {
+            refs.push((offset_in(document, tag), src));
+        }
+    }
+    for tag in start_tags(document, "image") {
+        if let Some(href) = attribute(tag, "xlink:href").or_else(|| attribute(tag, "href")) {
+            refs.push((offset_in(document, tag), href));
+        }
+    }
+
+    refs.sort_by_key(|(offset, _)| *offset);
+    refs.into_iter().map(|(_, src)| src).collect()
+}
+
+/// Byte offset of a slice returned by `start_tags` within `document`
+fn offset_in(document: &str, tag: &str) -> usize {
+    tag.as_ptr() as usize - document.as_ptr() as usize
+}
+
+/// Attribute text of every start tag with the given local name
+///
+/// Namespace prefixes (`<opf:item>`) are ignored and tag names compare
+/// case-insensitively. The returned slices span from after the name to
+/// before the closing `>`.
+pub fn start_tags<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut tags = Vec::new();
+    let mut rest = xml;
+
+    while let Some(open) = rest.find('<') {
+        let after = &rest[open + 1..];
+        let Some(close) = after.find('>') else {
+            break;
+        };
+
+        let body = &after[..close];
+        let name_end = body
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(body.len());
+        let tag_name = &body[..name_end];
+        let local_name = tag_name.rsplit(':').next().unwrap_or(tag_name);
+
+        if local_name.eq_ignore_ascii_case(name) {
+            tags.push(&body[name_end..]);
+        }
+
+        rest = &after[close + 1..];
+    }
+
+    tags
+}
+
+/// Value of a `name="value"` or `name='value'` attribute, entity-decoded
+///
+/// A namespace prefix is ignored unless `name` has one itself, so `name`
+/// also matches `opf:name`.
+pub fn attribute(tag: &str, name: &str) -> Option<String> {
+    let mut rest = tag;
+
+    while let Some(pos) = rest.find(name) {
+        let head = &rest[..pos];
+        let qualifier = &head[head.rfind(|c: char| c.is_ascii_whitespace()).map_or(0, |i| i + 1)..];
+        let before_ok = match qualifier.strip_suffix(':') {
+            Some(prefix) => {
+                !prefix.is_empty()
+                    && prefix.chars().all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+            }
+            None => head.chars().next_back().map_or(true, |c| c.is_whitespace()),
+        };
+        let after = rest[pos + name.len()..].trim_start();
+
+        if before_ok {
+            if let Some(value) = after.strip_prefix('=') {
+                let value = value.trim_start();
+                let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+                let end = value[1..].find(quote)?;
+                return Some(decode_entities(&value[1..1 + end]));
+            }
+        }
+
+        rest = &rest[pos + name.len()..];
+    }
+
+    None
+}
+
+/// Decode the predefined XML entities
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Directory part of an archive path ("" for top-level entries)
+fn parent_dir(path: &str) -> &str {
+    path.rfind('/').map_or("", |i| &path[..i])
+}
+
+/// Resolve a relative href against `base_dir` into an archive path
+///
+/// Fragments and queries are dropped, percent-escapes decoded, and `.`/`..`
+/// segments normalized.
+fn resolve_href(base_dir: &str, href: &str) -> String {
+    let href = href.split(['#', '?']).next().unwrap_or("");
+    let href = percent_decode(href);
+
+    let mut segments: Vec<&str> = if href.starts_with('/') {
+        Vec::new()
+    } else {
+        base_dir.split('/').filter(|s| !s.is_empty()).collect()
+    };
+
+    for segment in href.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            _ => segments.push(segment),
+        }
+    }
+
+    segments.join("/")
+}
+
+/// Decode `%XX` escapes (invalid escapes are kept verbatim)
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            if let Some(byte) = s.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rootfile_path() {
+        let container = r#"<?xml version="1.0"?>
+            <container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+              <rootfiles>
+                <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+              </rootfiles>
+            </container>"#;
+        assert_eq!(parse_rootfile_path(container).as_deref(), Some("OEBPS/content.opf"));
+        assert_eq!(parse_rootfile_path("<container/>"), None);
+    }
+
+    #[test]
+    fn test_parse_package() {
+        let opf = r#"<package xmlns="http://www.idpf.org/2007/opf">
+            <opf:manifest>
+              <opf:item id="p1" href="text/p1.xhtml" media-type="application/xhtml+xml"/>
+              <item media-type='image/jpeg' href='images/cover%20art.jpg' id='cover'/>
+            </opf:manifest>
+            <spine><itemref idref="cover"/><itemref idref="p1"/></spine>
+        </package>"#;
+
+        let package = parse_package(opf, "OEBPS").unwrap();
+        assert_eq!(package.spine, vec!["cover", "p1"]);
+        assert_eq!(package.manifest[0].path, "OEBPS/text/p1.xhtml");
+        assert_eq!(package.manifest[1].path, "OEBPS/images/cover art.jpg");
+        assert_eq!(package.manifest[1].media_type, "image/jpeg");
+
+        assert!(parse_package("not xml at all", "").is_none());
+        assert!(parse_package("<package><spine/></package>", "").is_none());
+    }
+
+    #[test]
+    fn test_package_cover_path() {
+        // EPUB 3: properties="cover-image"
+        let opf3 = r#"<package><manifest>
+              <item id="a" href="images/a.jpg" media-type="image/jpeg"/>
+              <item id="c" href="images/c.jpg" media-type="image/jpeg" properties="cover-image"/>
+            </manifest><spine><itemref idref="a"/></spine></package>"#;
+        assert_eq!(parse_package(opf3, "OEBPS").unwrap().cover_path(), Some("OEBPS/images/c.jpg"));
+
+        // EPUB 2: <meta name="cover" content="id">
+        let opf2 = r#"<package><metadata><meta name="cover" content="img-b"/></metadata>
+            <manifest>
+              <item id="img-a" href="a.png" media-type="image/png"/>
+              <item id="img-b" href="b.png" media-type="image/png"/>
+            </manifest><spine><itemref idref="img-a"/></spine></package>"#;
+        assert_eq!(parse_package(opf2, "").unwrap().cover_path(), Some("b.png"));
+
+        // No declared cover
+        let none = r#"<package><manifest><item id="a" href="a.png" media-type="image/png"/>
+            </manifest><spine><itemref idref="a"/></spine></package>"#;
+        assert_eq!(parse_package(none, "").unwrap().cover_path(), None);
+    }
+
+    #[test]
+    fn test_image_references_in_document_order() {
+        let xhtml = r#"<html><body>
+            <svg><image width="10" xlink:href="../images/b.png"/></svg>
+            <img alt="x" src="../images/a.jpg#frag"/>
+            <p data-src="ignored.jpg">text</p>
+        </body></html>"#;
+        assert_eq!(image_references(xhtml), vec!["../images/b.png", "../images/a.jpg#frag"]);
+    }
+
+    #[test]
+    fn test_attribute_matches_whole_name() {
+        assert_eq!(attribute(r#" data-src="x" src="y""#, "src").as_deref(), Some("y"));
+        assert_eq!(attribute(r#" href="a&amp;b""#, "href").as_deref(), Some("a&b"));
+        assert_eq!(attribute(r#" id=unquoted"#, "id"), None);
+    }
+
+    #[test]
+    fn test_attribute_ignores_namespace_prefix() {
+        let tag = r#" opf:name="cover" opf:content="img-c" xlink:href="a.png""#;
+        assert_eq!(attribute(tag, "name").as_deref(), Some("cover"));
+        assert_eq!(attribute(tag, "content").as_deref(), Some("img-c"));
+        assert_eq!(attribute(tag, "href").as_deref(), Some("a.png"));
+        assert_eq!(attribute(r#" data-src="x""#, "src"), None);
+        assert_eq!(attribute(r#" a:b:src="x""#, "src"), None);
+    }
+
+    #[test]
+    fn test_resolve_href() {
+        assert_eq!(resolve_href("OEBPS/text", "../images/p1.jpg"), "OEBPS/images/p1.jpg");
+        assert_eq!(resolve_href("OEBPS", "./images/p%231.jpg#top"), "OEBPS/images/p#1.jpg");
+        assert_eq!(resolve_href("OEBPS", "/root.jpg"), "root.jpg");
+        assert_eq!(resolve_href("", "a.jpg"), "a.jpg");
+        assert_eq!(percent_decode("100%"), "100%");
+    }
+}
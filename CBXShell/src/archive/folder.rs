@@ -0,0 +1,260 @@
+//! Folder pseudo-archive
+//!
+//! A comic kept as a plain directory of images is opened like an archive:
+//! its entries are the files directly inside it (subfolders are not
+//! descended into), listed by name so that archive order is stable. Entry
+//! data is read straight from disk, so every entry is "Stored".
+//!
+//! The listing is taken when the folder is opened; files added later are
+//! not seen, and a file removed since fails to extract.
+
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::archive::{Archive, ArchiveEntry, ArchiveMetadata, ArchiveType};
+use crate::utils::error::{CbxError, Result};
+use super::config::read_max_entry_size;
+use super::utils::{bounded_name, find_first_image, is_image_file};
+
+/// A directory opened as an archive
+pub struct FolderArchive {
+    root: PathBuf,
+    /// Files directly in `root`, sorted by name
+    entries: Vec<ArchiveEntry>,
+    /// Newest file modification time
+    modified: Option<SystemTime>,
+}
+
+impl FolderArchive {
+    /// List the files of the directory at `path`
+    ///
+    /// Files whose names aren't valid Unicode are skipped, as are entries
+    /// whose metadata can't be read (e.g. a dangling symlink).
+    pub fn open(path: &Path) -> Result<Self> {
+        tracing::debug!("Opening folder as archive: {:?}", path);
+
+        let dir = std::fs::read_dir(path)
+            .map_err(|e| CbxError::Archive(format!("Failed to read folder: {}", e)))?;
+
+        let mut entries = Vec::new();
+        let mut modified = None;
+        for dir_entry in dir {
+            let dir_entry = dir_entry.map_err(|e| CbxError::Archive(format!("Failed to read folder: {}", e)))?;
+
+            // Follows symlinks, so a linked image counts as a file
+            let metadata = match std::fs::metadata(dir_entry.path()) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    tracing::debug!("Skipping {:?}: {}", dir_entry.file_name(), e);
+                    continue;
+                }
+            };
+            if !metadata.is_file() {
+                continue;
+            }
+            let Ok(name) = dir_entry.file_name().into_string() else {
+                tracing::debug!("Skipping non-Unicode file name {:?}", dir_entry.file_name());
+                continue;
+            };
+
+            modified = modified.max(metadata.modified().ok());
+            entries.push(ArchiveEntry {
+                name,
+                size: metadata.len(),
+                is_directory: false,
+            });
+        }
+        entries.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(Self {
+            root: path.to_path_buf(),
+            entries,
+            modified,
+        })
+    }
+
+    /// Listed entry named `name`
+    fn find(&self, name: &str) -> Result<&ArchiveEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.name == name)
+            .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", name)))
+    }
+
+    /// Path of a listed entry
+    ///
+    /// Only names from the listing are accepted, so an entry can't point
+    /// outside the folder.
+    fn entry_path(&self, name: &str) -> Result<PathBuf> {
+        Ok(self.root.join(&self.find(name)?.name))
+    }
+}
+
+impl Archive for FolderArchive {
+    fn open(path: &Path) -> Result<Box<dyn Archive>> {
+        Ok(Box::new(Self::open(path)?))
+    }
+
+    /// List all files, sorted by name
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        Ok(self.entries.clone())
+    }
+
+    fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
+        tracing::debug!("Finding first image in folder (sort={})", sort);
+
+        if self.entries.is_empty() {
+            return Err(CbxError::Archive("Folder is empty".to_string()));
+        }
+
+        let names = self.entries.iter().map(|e| e.name.as_str());
+        let image_name = find_first_image(names, sort)
+            .ok_or_else(|| CbxError::Archive("No images found in folder".to_string()))?;
+
+        tracing::info!("Found first image: {}", bounded_name(&image_name));
+        self.find(&image_name).cloned()
+    }
+
+    fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+        tracing::debug!("Reading file: {} ({} bytes)", bounded_name(&entry.name), entry.size);
+
+        // Safety check: prevent memory exhaustion (same limit as the archive formats)
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
+            return Err(CbxError::Archive(format!(
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
+            )));
+        }
+
+        let mut buffer = Vec::new();
+        File::open(self.entry_path(&entry.name)?)?
+            .take(max_entry_size)
+            .read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn read_entry_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        File::open(self.entry_path(&entry.name)?)?
+            .take(max_len as u64)
+            .read_to_end(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        self.find(&entry.name)?;
+        Ok("Stored".to_string())
+    }
+
+    fn get_metadata(&self) -> Result<ArchiveMetadata> {
+        let total_files = self.entries.len();
+        let image_count = self.entries.iter().filter(|e| is_image_file(&e.name)).count();
+
+        tracing::debug!("Folder metadata: {} files, {} images", total_files, image_count);
+
+        Ok(ArchiveMetadata {
+            total_files,
+            image_count,
+            compressed_size: self.entries.iter().map(|e| e.size).sum(),
+            archive_type: ArchiveType::Folder,
+            modified: self.modified,
+        })
+    }
+
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Folder
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{open_archive, open_folder_as_archive};
+
+    /// A fresh directory holding `files`
+    fn create_test_folder(test_name: &str, files: &[(&str, &[u8])]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cbx_folder_{}_{}", test_name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, data) in files {
+            std::fs::write(dir.join(name), data).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_folder_lists_files_and_finds_cover() {
+        let dir = create_test_folder(
+            "mixed",
+            &[
+                ("page10.jpg", b"\xFF\xD8\xFF ten"),
+                ("notes.txt", b"not an image"),
+                ("page2.png", b"\x89PNG\r\n\x1a\n two"),
+                ("ComicInfo.xml", b"<ComicInfo/>"),
+                ("page9.webp", b"RIFF\x24\x00\x00\x00WEBPVP8 nine"),
+            ],
+        );
+        std::fs::create_dir(dir.join("extras")).unwrap();
+        std::fs::write(dir.join("extras").join("page1.jpg"), b"nested").unwrap();
+
+        let archive = open_folder_as_archive(&dir).unwrap();
+        assert_eq!(archive.archive_type(), ArchiveType::Folder);
+
+        // Files only, by name; the subfolder is not descended into
+        let names: Vec<String> = archive.list_entries().unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["ComicInfo.xml", "notes.txt", "page10.jpg", "page2.png", "page9.webp"]);
+
+        // Natural sort: 2 < 9 < 10; unsorted: first image by name
+        let cover = archive.find_first_image(true).unwrap();
+        assert_eq!((cover.name.as_str(), cover.size), ("page2.png", 12));
+        assert_eq!(archive.extract_entry(&cover).unwrap(), b"\x89PNG\r\n\x1a\n two");
+        assert_eq!(archive.read_entry_prefix(&cover, 4).unwrap(), b"\x89PNG");
+        assert_eq!(archive.compression_method(&cover).unwrap(), "Stored");
+        assert_eq!(archive.find_first_image(false).unwrap().name, "page10.jpg");
+        assert_eq!(archive.list_images().unwrap(), ["page2.png", "page9.webp", "page10.jpg"]);
+
+        let metadata = archive.get_metadata().unwrap();
+        assert_eq!((metadata.total_files, metadata.image_count), (5, 3));
+        assert_eq!(metadata.archive_type, ArchiveType::Folder);
+        assert!(metadata.modified.is_some());
+
+        // `open_archive` opens a directory as a folder too, whatever its name
+        let renamed = dir.with_extension("cbz");
+        let _ = std::fs::remove_dir_all(&renamed);
+        std::fs::rename(&dir, &renamed).unwrap();
+        let archive = open_archive(&renamed).unwrap();
+        assert_eq!(archive.archive_type(), ArchiveType::Folder);
+        assert_eq!(archive.find_first_image(true).unwrap().name, "page2.png");
+
+        std::fs::remove_dir_all(&renamed).ok();
+    }
+
+    #[test]
+    fn test_folder_without_images() {
+        let dir = create_test_folder("no_images", &[("readme.txt", b"text"), ("cover.pdf", b"%PDF")]);
+        let archive = FolderArchive::open(&dir).unwrap();
+
+        assert!(archive.find_first_image(true).is_err());
+        assert_eq!(archive.get_metadata().unwrap().image_count, 0);
+
+        // Names outside the listing are refused
+        let outside = ArchiveEntry {
+            name: "../readme.txt".to_string(),
+            size: 4,
+            is_directory: false,
+        };
+        assert!(archive.extract_entry(&outside).is_err());
+
+        std::fs::remove_dir_all(&dir).ok();
+
+        let empty = create_test_folder("empty", &[]);
+        assert!(FolderArchive::open(&empty).unwrap().find_first_image(false).is_err());
+        std::fs::remove_dir_all(&empty).ok();
+
+        assert!(FolderArchive::open(&empty).is_err());
+    }
+}
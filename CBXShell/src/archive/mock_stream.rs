@@ -0,0 +1,155 @@
+//! In-memory `IStream` for tests
+//!
+//! Explorer hands the provider a COM stream; `MemoryStream` is one backed by
+//! a `Vec<u8>`, so `IStreamReader`, `read_stream_to_memory` and the stream
+//! open path can be driven end to end without files or Shell APIs. It is
+//! read-only and reports an optional file name through `Stat`, like the file
+//! streams Explorer passes in.
+
+use std::cell::Cell;
+use windows::core::{implement, Result, HRESULT, PWSTR};
+use windows::Win32::Foundation::{E_NOTIMPL, E_OUTOFMEMORY, E_POINTER, STG_E_ACCESSDENIED, STG_E_INVALIDFUNCTION, S_FALSE, S_OK};
+use windows::Win32::System::Com::{
+    CoTaskMemAlloc, ISequentialStream_Impl, IStream, IStream_Impl, LOCKTYPE, STATFLAG, STATFLAG_NONAME, STATSTG,
+    STGC, STGTY_STREAM, STREAM_SEEK, STREAM_SEEK_CUR, STREAM_SEEK_END, STREAM_SEEK_SET,
+};
+
+/// Read-only COM stream over a byte buffer
+#[implement(IStream)]
+pub struct MemoryStream {
+    data: Vec<u8>,
+    position: Cell<u64>,
+    /// Name reported by `Stat`, `None` for an anonymous stream
+    name: Option<String>,
+}
+
+impl MemoryStream {
+    /// Anonymous stream over `data`
+    pub fn create(data: Vec<u8>) -> IStream {
+        Self { data, position: Cell::new(0), name: None }.into()
+    }
+
+    /// Stream over `data` that reports `name` like an Explorer file stream
+    pub fn create_named(data: Vec<u8>, name: &str) -> IStream {
+        Self {
+            data,
+            position: Cell::new(0),
+            name: Some(name.to_string()),
+        }
+        .into()
+    }
+}
+
+impl ISequentialStream_Impl for MemoryStream {
+    fn Read(&self, pv: *mut std::ffi::c_void, cb: u32, pcbread: *mut u32) -> HRESULT {
+        if pv.is_null() {
+            return E_POINTER;
+        }
+
+        let start = (self.position.get() as usize).min(self.data.len());
+        let count = (cb as usize).min(self.data.len() - start);
+        // Safety: the caller's buffer holds `cb` bytes and `count <= cb`
+        unsafe {
+            std::ptr::copy_nonoverlapping(self.data[start..].as_ptr(), pv as *mut u8, count);
+            if !pcbread.is_null() {
+                *pcbread = count as u32;
+            }
+        }
+        self.position.set((start + count) as u64);
+
+        // A short read is S_FALSE, like a file stream at its end
+        if count == cb as usize {
+            S_OK
+        } else {
+            S_FALSE
+        }
+    }
+
+    fn Write(&self, _pv: *const std::ffi::c_void, _cb: u32, _pcbwritten: *mut u32) -> HRESULT {
+        STG_E_ACCESSDENIED
+    }
+}
+
+impl IStream_Impl for MemoryStream {
+    fn Seek(&self, dlibmove: i64, dworigin: STREAM_SEEK, plibnewposition: *mut u64) -> Result<()> {
+        let base = match dworigin {
+            STREAM_SEEK_SET => 0,
+            STREAM_SEEK_CUR => self.position.get() as i64,
+            STREAM_SEEK_END => self.data.len() as i64,
+            _ => return Err(STG_E_INVALIDFUNCTION.into()),
+        };
+        let position = base
+            .checked_add(dlibmove)
+            .filter(|position| *position >= 0)
+            .ok_or_else(|| windows::core::Error::from(STG_E_INVALIDFUNCTION))?;
+
+        self.position.set(position as u64);
+        if !plibnewposition.is_null() {
+            // Safety: checked for null; the caller owns the out parameter
+            unsafe { *plibnewposition = position as u64 };
+        }
+        Ok(())
+    }
+
+    fn SetSize(&self, _libnewsize: u64) -> Result<()> {
+        Err(STG_E_ACCESSDENIED.into())
+    }
+
+    fn CopyTo(&self, _pstm: Option<&IStream>, _cb: u64, _pcbread: *mut u64, _pcbwritten: *mut u64) -> Result<()> {
+        Err(E_NOTIMPL.into())
+    }
+
+    fn Commit(&self, _grfcommitflags: &STGC) -> Result<()> {
+        Ok(())
+    }
+
+    fn Revert(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn LockRegion(&self, _liboffset: u64, _cb: u64, _dwlocktype: &LOCKTYPE) -> Result<()> {
+        Err(E_NOTIMPL.into())
+    }
+
+    fn UnlockRegion(&self, _liboffset: u64, _cb: u64, _dwlocktype: u32) -> Result<()> {
+        Err(E_NOTIMPL.into())
+    }
+
+    fn Stat(&self, pstatstg: *mut STATSTG, grfstatflag: &STATFLAG) -> Result<()> {
+        if pstatstg.is_null() {
+            return Err(E_POINTER.into());
+        }
+
+        // The name is allocated with CoTaskMemAlloc; the caller frees it
+        let name = match &self.name {
+            Some(name) if *grfstatflag != STATFLAG_NONAME => {
+                let wide: Vec<u16> = name.encode_utf16().chain(std::iter::once(0)).collect();
+                // Safety: the allocation is checked and sized for `wide`
+                unsafe {
+                    let buffer = CoTaskMemAlloc(wide.len() * 2) as *mut u16;
+                    if buffer.is_null() {
+                        return Err(E_OUTOFMEMORY.into());
+                    }
+                    std::ptr::copy_nonoverlapping(wide.as_ptr(), buffer, wide.len());
+                    PWSTR(buffer)
+                }
+            }
+            _ => PWSTR::null(),
+        };
+
+        // Safety: checked for null; the caller owns the out parameter
+        unsafe {
+            *pstatstg = STATSTG {
+                pwcsName: name,
+                r#type: STGTY_STREAM.0 as u32,
+                cbSize: self.data.len() as u64,
+                ..Default::default()
+            };
+        }
+        Ok(())
+    }
+
+    fn Clone(&self) -> Result<IStream> {
+        Err(E_NOTIMPL.into())
+    }
+}
@@ -1,19 +1,54 @@
 ///! Archive format handling
 ///!
-///! Supports ZIP, RAR, and 7z formats for comic book archives
+///! Supports ZIP, RAR, 7z, and TAR formats for comic book archives, and
+///! plain folders of images opened like an archive (`open_folder_as_archive`)
 
+use std::collections::HashSet;
 use std::path::Path;
+use std::time::SystemTime;
+use image::DynamicImage;
+use ::zip::CompressionMethod;
+use crate::image_processor::magic::ImageFormat;
 use crate::utils::error::{CbxError, Result};
 
 mod utils;
+mod comicinfo;
 mod config;
+mod cover_cache;
+mod cover_override;
+mod epub;
+mod folder;
+mod probe;
 mod zip;
 mod sevenz;
 mod rar;
+mod session;
+mod smart_cover;
+mod tar;
+mod spill;
 pub mod stream_reader;
+#[cfg(test)]
+pub(crate) mod mock_stream;
 
 // Re-export utilities for internal use only (not used in public API)
-pub use config::should_sort_images;
+pub use config::{is_enabled, read_debug_log_path, should_debug_log};
+
+// Re-export the full configuration API (exposed publicly from the crate root)
+pub use config::{
+    apply_config, read_config, read_no_sort_setting, CbxConfig, CoverStrategy, ExtractionSettings, OpenStrategy,
+};
+
+// Scaled covers cached inside writable ZIP archives (opt-in, `EmbedCoverCache`)
+pub use cover_cache::{embed_cover_cache, COVER_CACHE_ENTRY};
+
+// Cover pinned by a `.cbxcover` entry in the archive
+pub use cover_override::COVER_OVERRIDE_ENTRY;
+
+// One opened archive answering several queries (used by COM shell extension)
+pub use session::ArchiveSession;
+
+// Best-effort type/first-image detection from a prefix of the archive
+pub use probe::{probe, Probe};
 
 // Re-export image verification function (used by COM shell extension)
 pub use utils::verify_image_data;
@@ -24,27 +59,87 @@ pub use zip::ZipArchive;
 pub use sevenz::SevenZipArchive;
 #[allow(dead_code)] // Used by open_archive function and part of public API
 pub use rar::RarArchive;
+#[allow(dead_code)] // Used by open_archive function and part of public API
+pub use tar::TarArchive;
+#[allow(dead_code)] // Used by open_folder_as_archive and part of public API
+pub use folder::FolderArchive;
 
 // Re-export stream reader utilities (detect_archive_type_from_bytes is used publicly)
-pub use stream_reader::{detect_archive_type_from_bytes, IStreamReader};
+pub use stream_reader::{
+    detect_archive_type_from_bytes, detect_archive_type_from_bytes_detailed,
+    detect_archive_type_or_extension, file_extension, note_type_mismatch, stream_file_name, type_mismatch_count, IStreamReader,
+};
 
 /// Represents an entry in an archive
 #[derive(Debug, Clone)]
 pub struct ArchiveEntry {
     pub name: String,
     pub size: u64,
-    #[allow(dead_code)] // Part of public API, may be used in future
     pub is_directory: bool,
 }
 
+/// Settings controlling which image becomes the cover
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverOptions {
+    /// Natural-sort image names before picking (`NoSort` = 0)
+    pub sort: bool,
+    /// When sorting, compare file names before directories (`SortByBasename`)
+    pub sort_by_basename: bool,
+    /// With sorting off, a first-in-archive image is always the cover
+    pub honor_archive_order: bool,
+    /// An image whose file name contains "cover" or "front" wins, if it is
+    /// the only one (`PreferCoverName`)
+    pub prefer_cover_name: bool,
+    /// Preferred extensions (lowercase, no dot) for images sharing a base name;
+    /// empty = no preference
+    pub format_priority: Vec<String>,
+    /// Detect images among extensionless entries by their magic bytes
+    pub sniff_extensionless: bool,
+    /// Cover selection strategy
+    pub strategy: CoverStrategy,
+    /// Use the image at this 0-based index instead of the first (`FirstImage`
+    /// strategy only); clamped to the last image
+    pub page_index: usize,
+}
+
+impl CoverOptions {
+    /// True if only the sort flag matters (plain `find_first_image`)
+    pub fn is_plain(&self) -> bool {
+        // Options that only change how the first image is picked
+        let custom_pick = (self.sort && self.sort_by_basename) || self.prefer_cover_name;
+
+        (self.sort || !self.honor_archive_order)
+            && !custom_pick
+            && self.format_priority.is_empty()
+            && !self.sniff_extensionless
+            && self.strategy == CoverStrategy::FirstImage
+            && self.page_index == 0
+    }
+}
+
+/// Maximum number of images `Archive::find_first_decodable_image` extracts and decodes
+pub const MAX_DECODE_ATTEMPTS: usize = 5;
+
+/// Maximum number of extensionless entries inspected in `SniffExtensionless` mode
+pub const MAX_SNIFFED_ENTRIES: usize = 32;
+
+/// Bytes read from each extensionless entry for magic detection
+const SNIFF_PREFIX_LEN: usize = 32;
+
+/// Bytes read from each image by `list_images_with_format` (enough for the
+/// AVIF `ftyp` scan)
+const FORMAT_PREFIX_LEN: usize = crate::image_processor::magic::DEFAULT_FTYP_SCAN_WINDOW;
+
 /// Archive metadata
 #[derive(Debug, Clone)]
-#[allow(dead_code)] // Part of public API, may be used in future
 pub struct ArchiveMetadata {
     pub total_files: usize,
     pub image_count: usize,
     pub compressed_size: u64,
     pub archive_type: ArchiveType,
+    /// Newest entry modification time; path-based opens fall back to the
+    /// file's mtime when no entry carries a timestamp
+    pub modified: Option<SystemTime>,
 }
 
 /// Archive type
@@ -53,26 +148,35 @@ pub enum ArchiveType {
     Zip,
     Rar,
     SevenZip,
+    Tar,
+    /// A directory of images (`open_folder_as_archive`); never detected from
+    /// bytes or an extension
+    Folder,
 }
 
 impl ArchiveType {
+    /// Every archive file format; all of them are always compiled in
+    /// (`Folder` is not a file format and is left out)
+    pub const ALL: [Self; 4] = [Self::Zip, Self::Rar, Self::SevenZip, Self::Tar];
+
     /// Detect archive type from file extension
-    #[allow(dead_code)] // Part of public API, may be used in future
     pub fn from_extension(ext: &str) -> Option<Self> {
         match ext.to_lowercase().as_str() {
             "zip" | "cbz" | "epub" | "phz" => Some(Self::Zip),
             "rar" | "cbr" => Some(Self::Rar),
             "7z" | "cb7" => Some(Self::SevenZip),
+            "tar" | "cbt" => Some(Self::Tar),
             _ => None,
         }
     }
 
-    #[allow(dead_code)] // Part of public API, may be used in future
     pub fn as_str(&self) -> &'static str {
         match self {
             Self::Zip => "ZIP",
             Self::Rar => "RAR",
             Self::SevenZip => "7-Zip",
+            Self::Tar => "TAR",
+            Self::Folder => "Folder",
         }
     }
 }
@@ -85,12 +189,257 @@ pub trait Archive {
     where
         Self: Sized;
 
+    /// List all entries (files and directories) in archive order
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>>;
+
     /// Find the first image in the archive (optionally sorted alphabetically)
     fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry>;
 
+    /// Find the image at `index` (0-based, optionally sorted alphabetically)
+    ///
+    /// An index past the last image yields the last image. Index 0 is
+    /// `find_first_image(sort)`.
+    fn find_nth_image(&self, index: usize, sort: bool) -> Result<ArchiveEntry> {
+        if index == 0 {
+            return self.find_first_image(sort);
+        }
+
+        let entries = self.list_entries()?;
+        let mut files = entries.iter().filter(|e| !e.is_directory);
+        let name = utils::find_nth_image(files.clone().map(|e| e.name.as_str()), index, sort)
+            .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
+
+        files
+            .find(|e| e.name == name)
+            .cloned()
+            .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", name)))
+    }
+
+    /// Find the first image `decode` accepts (optionally sorted alphabetically)
+    ///
+    /// Like `find_first_image`, but each candidate is extracted and handed to
+    /// `decode`, and one it rejects (e.g. truncated or corrupt) is skipped for
+    /// the next image. `decode` keeps what it made of the winner, so that is
+    /// never decoded twice. At most `MAX_DECODE_ATTEMPTS` images are tried,
+    /// since each costs a full extract and decode; if none is accepted, the
+    /// first error is returned.
+    fn find_first_decodable_image(
+        &self,
+        sort: bool,
+        decode: &mut dyn FnMut(&ArchiveEntry, Vec<u8>) -> Result<()>,
+    ) -> Result<ArchiveEntry> {
+        let entries = self.list_entries()?;
+        let mut images: Vec<&ArchiveEntry> = entries
+            .iter()
+            .filter(|e| !e.is_directory && utils::is_image_file(&e.name))
+            .collect();
+        if sort {
+            images.sort_by(|a, b| utils::natural_sort_cmp(&a.name, &b.name));
+        }
+
+        let mut first_error = None;
+        for entry in images.into_iter().take(MAX_DECODE_ATTEMPTS) {
+            let decoded = self.extract_entry(entry).and_then(|data| decode(entry, data));
+            match decoded {
+                Ok(_) => return Ok(entry.clone()),
+                Err(e) => {
+                    tracing::info!("Skipping undecodable image {}: {}", utils::bounded_name(&entry.name), e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+
+        Err(first_error.unwrap_or_else(|| CbxError::Archive("No images found in archive".to_string())))
+    }
+
+    /// Find the cover image according to the cover selection options
+    ///
+    /// A `.cbxcover` entry naming an existing entry wins over all options
+    /// (see `cover_override`). Otherwise, with default options this is
+    /// exactly `find_first_image(sort)`; other options are resolved by
+    /// `utils::select_cover`.
+    fn find_cover_image(&self, options: &CoverOptions) -> Result<ArchiveEntry> {
+        let entries: Vec<ArchiveEntry> = self
+            .list_entries()?
+            .into_iter()
+            .filter(|e| !e.is_directory)
+            .collect();
+
+        if let Some(entry) = cover_override::find_pinned_cover(self, &entries) {
+            return Ok(entry);
+        }
+
+        if options.is_plain() {
+            return self.find_first_image(options.sort);
+        }
+
+        if options.page_index > 0 && options.strategy == CoverStrategy::FirstImage {
+            return self.find_nth_image(options.page_index, options.sort);
+        }
+
+        if let CoverStrategy::Percent(percent) = options.strategy {
+            let count = entries.iter().filter(|e| utils::is_image_file(&e.name)).count();
+            return self.find_nth_image(utils::percent_index(count, percent), options.sort);
+        }
+
+        if options.strategy == CoverStrategy::OpfCover && epub::is_epub(&entries) {
+            if let Some(cover_name) = epub::declared_cover(self, &entries) {
+                tracing::info!("Found OPF-declared cover image: {}", utils::bounded_name(&cover_name));
+                if let Some(entry) = entries.iter().find(|e| e.name == cover_name) {
+                    return Ok(entry.clone());
+                }
+            }
+            tracing::debug!("EPUB declares no usable cover, using first image");
+        }
+
+        if options.strategy == CoverStrategy::ComicInfo {
+            if let Some(cover_name) = comicinfo::declared_cover(self, &entries) {
+                tracing::info!("Found ComicInfo.xml front cover: {}", utils::bounded_name(&cover_name));
+                if let Some(entry) = entries.iter().find(|e| e.name == cover_name) {
+                    return Ok(entry.clone());
+                }
+            }
+            tracing::debug!("No ComicInfo.xml front cover, using first image");
+        }
+
+        let sniffed = if options.sniff_extensionless {
+            sniff_extensionless_images(self, &entries, options.sort)
+        } else {
+            HashSet::new()
+        };
+        let is_image = |name: &str| utils::is_image_file(name) || sniffed.contains(name);
+
+        if options.strategy == CoverStrategy::SmartCover {
+            let mut images: Vec<&str> = entries.iter().map(|e| e.name.as_str()).filter(|name| is_image(name)).collect();
+            if options.sort {
+                images.sort_by(|a, b| utils::natural_sort_cmp(a, b));
+            }
+            if let Some(cover_name) = smart_cover::most_colorful(self, &entries, &images) {
+                tracing::info!("Found most colorful cover candidate: {}", utils::bounded_name(&cover_name));
+                if let Some(entry) = entries.iter().find(|e| e.name == cover_name) {
+                    return Ok(entry.clone());
+                }
+            }
+            tracing::debug!("No cover candidate could be sampled, using first image");
+        }
+
+        let cover_name = utils::select_cover(entries.iter().map(|e| e.name.as_str()), options, is_image)
+            .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
+
+        tracing::info!("Found cover image: {} ({:?})", utils::bounded_name(&cover_name), options);
+
+        entries
+            .into_iter()
+            .find(|e| e.name == cover_name)
+            .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", cover_name)))
+    }
+
+    /// List image entry names in page order
+    ///
+    /// EPUBs follow the OPF spine (reading order); other archives, and EPUBs
+    /// whose package document can't be parsed, are natural-sorted.
+    fn list_images(&self) -> Result<Vec<String>> {
+        let entries = self.list_entries()?;
+
+        if epub::is_epub(&entries) {
+            if let Some(images) = epub::spine_ordered_images(self, &entries) {
+                return Ok(images);
+            }
+            tracing::debug!("EPUB package document unreadable, using natural sort");
+        }
+
+        let mut images: Vec<String> = entries
+            .into_iter()
+            .filter(|e| !e.is_directory && utils::is_image_file(&e.name))
+            .map(|e| e.name)
+            .collect();
+        images.sort_by(|a, b| utils::natural_sort_cmp(a, b));
+
+        Ok(images)
+    }
+
+    /// List image entries together with the format detected from their header
+    ///
+    /// Opt-in, and noticeably more expensive than `list_images`: the first
+    /// few bytes of every image entry are read (a seek and a short read per
+    /// entry for ZIP, decompressing up to each entry in solid 7z/RAR
+    /// archives). Entries are in archive order, or natural-sorted if `sort`.
+    /// The format is `None` for entries whose header isn't a known image
+    /// format or can't be read, e.g. a text file named `page01.jpg`.
+    fn list_images_with_format(&self, sort: bool) -> Result<Vec<(ArchiveEntry, Option<ImageFormat>)>> {
+        let mut images: Vec<ArchiveEntry> = self
+            .list_entries()?
+            .into_iter()
+            .filter(|e| !e.is_directory && utils::is_image_file(&e.name))
+            .collect();
+        if sort {
+            images.sort_by(|a, b| utils::natural_sort_cmp(&a.name, &b.name));
+        }
+
+        Ok(images
+            .into_iter()
+            .map(|entry| {
+                let format = match self.read_entry_prefix(&entry, FORMAT_PREFIX_LEN) {
+                    Ok(header) => crate::image_processor::magic::detect_image_format(&header).ok(),
+                    Err(e) => {
+                        tracing::debug!("Failed to read header of {}: {}", utils::bounded_name(&entry.name), e);
+                        None
+                    }
+                };
+                (entry, format)
+            })
+            .collect())
+    }
+
+    /// Check whether the archive contains a no-thumbnail marker entry
+    ///
+    /// See `utils::is_no_thumb_marker` for the recognized names.
+    fn has_no_thumb_marker(&self) -> Result<bool> {
+        Ok(self
+            .list_entries()?
+            .iter()
+            .any(|e| !e.is_directory && utils::is_no_thumb_marker(&e.name)))
+    }
+
     /// Extract an entry to a byte vector
     fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>>;
 
+    /// Read up to `max_len` leading bytes of an entry (for magic sniffing)
+    ///
+    /// The default extracts the whole entry; backends that can stream a
+    /// single entry override this to stop early.
+    fn read_entry_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        let mut data = self.extract_entry(entry)?;
+        data.truncate(max_len);
+        Ok(data)
+    }
+
+    /// Read an entry's data as stored, without decompressing it
+    ///
+    /// Returns the compression method with the raw bytes so a caller can
+    /// re-pack the entry or decompress it later. Only ZIP archives support
+    /// this; other backends fail with `CbxError::UnsupportedFormat`.
+    fn extract_entry_raw(&self, entry: &ArchiveEntry) -> Result<(CompressionMethod, Vec<u8>)> {
+        Err(CbxError::UnsupportedFormat(format!(
+            "Raw extraction of {} from a {} archive",
+            entry.name,
+            self.archive_type().as_str()
+        )))
+    }
+
+    /// Human name of an entry's compression method, e.g. "Deflate" or "LZMA2"
+    ///
+    /// For performance diagnostics (a slow cover is often an LZMA one). Only
+    /// headers are read. Backends that don't know the method fail with
+    /// `CbxError::UnsupportedFormat`.
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        Err(CbxError::UnsupportedFormat(format!(
+            "Compression method of {} in a {} archive",
+            entry.name,
+            self.archive_type().as_str()
+        )))
+    }
+
     /// Get archive metadata
     fn get_metadata(&self) -> Result<ArchiveMetadata>;
 
@@ -98,9 +447,51 @@ pub trait Archive {
     fn archive_type(&self) -> ArchiveType;
 }
 
+/// Find extensionless entries whose leading bytes are a known image format
+///
+/// Formats without a decoder (see `ImageFormat::may_decode`) don't count.
+/// At most `MAX_SNIFFED_ENTRIES` entries are read, in the order the cover
+/// would be chosen (natural order when sorting, archive order otherwise),
+/// so the likely cover is always among those inspected.
+fn sniff_extensionless_images<A: Archive + ?Sized>(
+    archive: &A,
+    entries: &[ArchiveEntry],
+    sort: bool,
+) -> HashSet<String> {
+    let mut candidates: Vec<&ArchiveEntry> = entries
+        .iter()
+        .filter(|e| !e.is_directory && e.size > 0 && utils::has_no_extension(&e.name))
+        .collect();
+
+    if sort {
+        candidates.sort_by(|a, b| utils::natural_sort_cmp(&a.name, &b.name));
+    }
+
+    let mut images = HashSet::new();
+    for entry in candidates.into_iter().take(MAX_SNIFFED_ENTRIES) {
+        match archive.read_entry_prefix(entry, SNIFF_PREFIX_LEN) {
+            Ok(prefix)
+                if crate::image_processor::magic::detect_image_format(&prefix).is_ok_and(|format| format.may_decode()) =>
+            {
+                tracing::debug!("Sniffed extensionless image: {}", utils::bounded_name(&entry.name));
+                images.insert(entry.name.clone());
+            }
+            Ok(_) => {}
+            Err(e) => tracing::debug!("Failed to sniff {}: {}", utils::bounded_name(&entry.name), e),
+        }
+    }
+
+    images
+}
+
 /// Open an archive of any supported type from a file path
-#[allow(dead_code)] // Part of public API, may be used in future
+///
+/// A directory is opened with `open_folder_as_archive`, whatever its name.
 pub fn open_archive(path: &Path) -> Result<Box<dyn Archive>> {
+    if path.is_dir() {
+        return open_folder_as_archive(path);
+    }
+
     let extension = path
         .extension()
         .and_then(|s| s.to_str())
@@ -113,9 +504,19 @@ pub fn open_archive(path: &Path) -> Result<Box<dyn Archive>> {
         ArchiveType::Zip => <ZipArchive as Archive>::open(path),
         ArchiveType::Rar => <RarArchive as Archive>::open(path),
         ArchiveType::SevenZip => <SevenZipArchive as Archive>::open(path),
+        ArchiveType::Tar => <TarArchive as Archive>::open(path),
+        ArchiveType::Folder => open_folder_as_archive(path),
     }
 }
 
+/// Open a directory as an archive whose entries are the files in it
+///
+/// For comics kept as loose images. Covers are selected like in any other
+/// archive, reading the files straight from disk; see `FolderArchive`.
+pub fn open_folder_as_archive(path: &Path) -> Result<Box<dyn Archive>> {
+    <FolderArchive as Archive>::open(path)
+}
+
 /// Open an archive from in-memory data (for IStream support)
 ///
 /// This function detects the archive type from magic bytes and opens
@@ -140,11 +541,7 @@ pub fn open_archive_from_memory(data: Vec<u8>) -> Result<Box<dyn Archive>> {
     match archive_type {
         ArchiveType::Zip => {
             // Create ZIP archive from memory
-            let cursor = Cursor::new(data);
-            let zip_reader = ::zip::ZipArchive::new(cursor)
-                .map_err(|e| CbxError::Archive(format!("Failed to open ZIP from memory: {}", e)))?;
-
-            Ok(Box::new(zip::ZipArchiveFromMemory::new(zip_reader)))
+            Ok(Box::new(zip::ZipArchiveFromMemory::new(data)?))
         }
         ArchiveType::SevenZip => {
             // Create 7z archive from memory
@@ -155,6 +552,11 @@ pub fn open_archive_from_memory(data: Vec<u8>) -> Result<Box<dyn Archive>> {
             // Create RAR archive from memory (uses temp file)
             Ok(Box::new(rar::RarArchiveFromMemory::new(data)?))
         }
+        ArchiveType::Tar => {
+            // TAR: no compression, entries are read straight from the buffer
+            Ok(Box::new(tar::TarArchiveFromStream::from_memory(data)?))
+        }
+        ArchiveType::Folder => Err(CbxError::UnsupportedFormat("Folder (not an archive)".to_string())),
     }
 }
 
@@ -167,10 +569,26 @@ pub fn open_archive_from_memory(data: Vec<u8>) -> Result<Box<dyn Archive>> {
 /// - **open_archive_from_memory**: Load 1GB to memory (~3s) + process
 /// - **open_archive_from_stream**: Stream directly (~50-100ms for metadata + image)
 ///
+/// # Small archives
+/// Every streamed read is a COM call, which isn't worth it for tiny comics.
+/// Streams up to `InMemoryThresholdMB` (4MB by default, see
+/// `read_in_memory_threshold`) are read into memory once and opened with
+/// `open_archive_from_memory` instead.
+///
+/// # Non-seekable streams
+/// Some IStream implementations fail every seek. Such a stream is copied
+/// into a temp file (removed when the archive is dropped) and the archive
+/// is opened from there; the downgrade is logged.
+///
+/// # Strategy
+/// Memory vs streaming is decided by stream size; use
+/// `open_archive_from_stream_with_strategy` to force one.
+///
 /// # Supported Formats
 /// - **ZIP**: Direct streaming (20-50x faster for large archives)
 /// - **RAR**: Streaming write to temp file (2-3x faster, temp file still required)
 /// - **7z**: Streaming with RefCell pattern (19-28x faster for large archives)
+/// - **TAR**: Direct streaming (headers are indexed once, entries read on demand)
 ///
 /// # Arguments
 /// * `reader` - Any Read implementer (IStreamReader, File, etc.)
@@ -191,15 +609,300 @@ pub fn open_archive_from_memory(data: Vec<u8>) -> Result<Box<dyn Archive>> {
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
 pub fn open_archive_from_stream<R: std::io::Read + std::io::Seek + 'static>(
-    mut reader: R
+    reader: R
+) -> Result<Box<dyn Archive>> {
+    open_archive_from_stream_with_strategy(reader, OpenStrategy::Auto)
+}
+
+/// `open_archive_from_stream` reading the stream as `strategy` says
+///
+/// Used with the per-extension `OpenStrategy` setting (`ExtractionSettings::open_strategy`).
+/// A stream that can't seek is always copied to a temp file.
+pub fn open_archive_from_stream_with_strategy<R: std::io::Read + std::io::Seek + 'static>(
+    reader: R,
+    strategy: OpenStrategy,
+) -> Result<Box<dyn Archive>> {
+    open_stream_with_threshold(reader, config::read_in_memory_threshold(), strategy)
+}
+
+/// `open_archive_from_stream` with the open strategy and in-memory threshold
+/// of an extraction's settings snapshot
+pub fn open_archive_from_stream_with_settings<R: std::io::Read + std::io::Seek + 'static>(
+    reader: R,
+    settings: &ExtractionSettings,
 ) -> Result<Box<dyn Archive>> {
+    open_stream_with_threshold(reader, settings.in_memory_threshold(), settings.open_strategy())
+}
+
+/// Decode the cover of the archive at `path`
+///
+/// For callers doing their own image processing: selects the cover like the
+/// thumbnail provider (natural-sorted first image if `sort`, otherwise the
+/// first in archive order; a `.cbxcover` entry wins), verifies and decodes
+/// it, and stops before any scaling. Uses no Windows types.
+///
+/// # Returns
+/// * `Ok(DynamicImage)` - The decoded cover at its full size
+/// * `Err(CbxError)` - The archive can't be opened, has no image, or the
+///   cover can't be decoded
+pub fn extract_cover_image(path: &Path, sort: bool) -> Result<DynamicImage> {
+    decode_cover(open_archive(path)?, sort)
+}
+
+/// `extract_cover_image` for an archive read from a stream (any format,
+/// detected from its magic bytes, see `open_archive_from_stream`)
+pub fn extract_cover_image_from_stream<R: std::io::Read + std::io::Seek + 'static>(
+    reader: R,
+    sort: bool,
+) -> Result<DynamicImage> {
+    decode_cover(open_archive_from_stream(reader)?, sort)
+}
+
+/// The cover the thumbnail provider would choose, decoded
+pub struct CoverPreview {
+    /// Name of the chosen entry
+    pub entry_name: String,
+    /// The decoded cover at its full size
+    pub image: DynamicImage,
+}
+
+/// Choose and decode the cover of the archive at `path` like the thumbnail
+/// provider does with the current settings
+///
+/// Uses the registry's cover options for the file's extension
+/// (`ExtractionSettings::cover_options`), honors the no-thumbnail marker and an embedded
+/// cover cache when those settings are on, and stops before scaling. Lets
+/// the manager show which entry Explorer would use.
+///
+/// # Returns
+/// * `Ok(CoverPreview)` - The chosen entry and its decoded image
+/// * `Err(CbxError::NoThumbnailMarker)` - The archive opted out of thumbnails
+/// * `Err(CbxError)` - The archive can't be opened, has no image, or the
+///   cover can't be decoded
+pub fn preview_cover(path: &Path) -> Result<CoverPreview> {
+    let session = ArchiveSession::new(open_archive(path)?);
+    let extension = path.file_name().and_then(|name| name.to_str()).and_then(file_extension);
+    let settings = ExtractionSettings::read(extension.as_deref());
+
+    if settings.config.honor_no_thumb_marker && session.has_no_thumb_marker()? {
+        return Err(CbxError::NoThumbnailMarker);
+    }
+
+    let options = settings.cover_options();
+    // A preview isn't scaled, so a cache of any size will do
+    let cover_cache_size = settings.config.embed_cover_cache.then_some(0);
+    let (entry_name, image) = decode_session_cover(&session, &options, cover_cache_size)?;
+
+    Ok(CoverPreview { entry_name, image })
+}
+
+/// A thumbnail built by the provider's pipeline and read back from its bitmap
+pub struct RenderedThumbnail {
+    /// Archive type detected from the file
+    pub archive_type: ArchiveType,
+    /// Name of the first image, used as the cover
+    pub entry_name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Unmultiplied RGBA pixels, rows top to bottom
+    pub rgba: Vec<u8>,
+}
+
+/// Build a `size`x`size` thumbnail for the archive at `path`, bypassing Explorer
+///
+/// Opens the archive, takes its first image (sorted if `sort`), and runs it
+/// through `create_thumbnail_with_size`, so a file that shows a blank icon
+/// can be diagnosed from the manager. Registry settings other than the sort
+/// order are not applied; `preview_cover` reproduces the full cover choice.
+///
+/// # Returns
+/// * `Ok(RenderedThumbnail)` - The detected type, chosen entry and pixels
+/// * `Err(CbxError)` - The first failing step (open, find, extract, verify,
+///   decode or bitmap creation)
+pub fn render_thumbnail(path: &Path, size: u32, sort: bool) -> Result<RenderedThumbnail> {
+    use crate::image_processor::thumbnail::{create_thumbnail_with_size, read_dib_bgra};
+    use windows::Win32::Graphics::Gdi::DeleteObject;
+
+    let archive = open_archive(path)?;
+    let archive_type = archive.archive_type();
+    let entry = archive.find_first_image(sort)?;
+    let data = archive.extract_entry(&entry)?;
+    verify_image_data(&data, &entry.name)?;
+
+    let thumbnail = create_thumbnail_with_size(&data, size, size)?;
+    let pixels = read_dib_bgra(thumbnail.bitmap);
+    // UNAVOIDABLE UNSAFE: the bitmap was created above and is not used after this
+    unsafe {
+        DeleteObject(thumbnail.bitmap);
+    }
+    let (width, height, mut rgba) = pixels?;
+
+    // BGRA -> RGBA
+    for pixel in rgba.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+
+    Ok(RenderedThumbnail {
+        archive_type,
+        entry_name: entry.name,
+        width,
+        height,
+        rgba,
+    })
+}
+
+fn decode_cover(archive: Box<dyn Archive>, sort: bool) -> Result<DynamicImage> {
+    let options = CoverOptions {
+        sort,
+        ..Default::default()
+    };
+
+    decode_session_cover(&ArchiveSession::new(archive), &options, None).map(|(_, image)| image)
+}
+
+/// Select, verify and decode the cover
+///
+/// With `cover_cache_size`, an embedded cover cache of at least that size
+/// rendered with `options` wins.
+fn decode_session_cover(
+    session: &ArchiveSession,
+    options: &CoverOptions,
+    cover_cache_size: Option<u32>,
+) -> Result<(String, DynamicImage)> {
+    let cached = match cover_cache_size {
+        Some(size) => session.cover_cache(options, size)?,
+        None => None,
+    };
+    let (entry, data) = match cached {
+        Some(cached) => cached,
+        None => session.extract_cover(session.cover(options)?, options)?,
+    };
+
+    verify_image_data(&data, &entry.name)?;
+    let image = crate::image_processor::decoder::decode_image(&data)?;
+    Ok((entry.name, image))
+}
+
+/// How `open_archive_from_stream` reads an archive
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum StreamStrategy {
+    /// Read the whole archive once and use the in-memory backends
+    Memory,
+    /// Read from the stream on demand
+    Streaming,
+    /// The stream can't seek; copy it to a temp file and open that
+    Spill,
+}
+
+/// Largest stream read into memory, even when `OpenStrategy::Memory` is
+/// forced (same cap as `InMemoryThresholdMB`)
+const MAX_IN_MEMORY_SIZE: u64 = config::MAX_IN_MEMORY_THRESHOLD_MB as u64 * 1024 * 1024;
+
+/// Pick the strategy from the stream size (`threshold` 0 = always stream)
+///
+/// Returns the size too, if known. Leaves the stream at its start. A stream
+/// whose size can't be determined is streamed; one that can't seek back to
+/// its start is spilled (it is assumed to still be at its start, as a
+/// freshly handed over IStream is).
+fn stream_strategy<R: std::io::Seek>(reader: &mut R, threshold: u64) -> (StreamStrategy, Option<u64>) {
     use std::io::SeekFrom;
 
+    let size = reader.seek(SeekFrom::End(0)).ok();
+    if let Err(e) = reader.seek(SeekFrom::Start(0)) {
+        tracing::warn!("Stream can't seek ({}), falling back to a temp file", e);
+        crate::utils::debug_log::debug_log(&format!(
+            "Stream can't seek ({}), downgrading to spill file", e
+        ));
+        return (StreamStrategy::Spill, size);
+    }
+
+    let strategy = match size {
+        Some(size) if size <= threshold => StreamStrategy::Memory,
+        _ => StreamStrategy::Streaming,
+    };
+    (strategy, size)
+}
+
+/// Replace the size-based strategy with the configured one, unless it is
+/// `Auto` or the stream can't seek (only a spill works then)
+///
+/// `Memory` is only honored for streams known to be at most
+/// `MAX_IN_MEMORY_SIZE`; larger ones (or ones of unknown size) are streamed.
+fn apply_open_strategy(detected: StreamStrategy, preferred: OpenStrategy, size: Option<u64>) -> StreamStrategy {
+    match (detected, preferred) {
+        (StreamStrategy::Spill, _) | (_, OpenStrategy::Auto) => detected,
+        (_, OpenStrategy::Stream) => StreamStrategy::Streaming,
+        (_, OpenStrategy::Memory) => match size {
+            Some(size) if size <= MAX_IN_MEMORY_SIZE => StreamStrategy::Memory,
+            _ => {
+                tracing::warn!("Stream too large for memory ({:?} bytes), streaming instead", size);
+                StreamStrategy::Streaming
+            }
+        },
+        (_, OpenStrategy::TempFile) => StreamStrategy::Spill,
+    }
+}
+
+/// Read all of `reader`, failing once it passes `max_size` bytes
+///
+/// The in-memory strategy is picked from the size the stream reports; a
+/// stream returning more than that is cut off instead of buffered without
+/// limit.
+fn read_to_end_capped<R: std::io::Read>(reader: R, max_size: u64) -> Result<Vec<u8>> {
+    use std::io::Read;
+
+    let mut data = Vec::new();
+    reader
+        .take(max_size.saturating_add(1))
+        .read_to_end(&mut data)
+        .map_err(|e| CbxError::Archive(format!("Failed to read archive: {}", e)))?;
+    if data.len() as u64 > max_size {
+        return Err(CbxError::Archive(format!(
+            "Archive stream is larger than {} bytes",
+            max_size
+        )));
+    }
+    Ok(data)
+}
+
+/// Leading bytes read from a stream to detect its archive type
+///
+/// One TAR header block, which holds the `ustar` marker at offset 257.
+const MAGIC_PREFIX_LEN: usize = 512;
+
+/// `open_archive_from_stream` with an explicit in-memory threshold in bytes
+fn open_stream_with_threshold<R: std::io::Read + std::io::Seek + 'static>(
+    mut reader: R,
+    threshold: u64,
+    preferred: OpenStrategy,
+) -> Result<Box<dyn Archive>> {
+    use std::io::{Read, SeekFrom};
+
     crate::utils::debug_log::debug_log(">>>>> open_archive_from_stream STARTING (OPTIMIZED) <<<<<");
 
-    // Read first 16 bytes for magic byte detection
-    let mut magic_bytes = [0u8; 16];
-    reader.read_exact(&mut magic_bytes)
+    let (detected, size) = stream_strategy(&mut reader, threshold);
+    let strategy = apply_open_strategy(detected, preferred, size);
+    crate::utils::debug_log::debug_log(&format!("Open strategy: {:?} ({})", strategy, preferred.as_str()));
+
+    match strategy {
+        StreamStrategy::Memory => {
+            let data = read_to_end_capped(&mut reader, MAX_IN_MEMORY_SIZE)?;
+            crate::utils::debug_log::debug_log(&format!("Small archive ({} bytes), reading into memory", data.len()));
+            return open_archive_from_memory(data);
+        }
+        StreamStrategy::Spill => {
+            // The spill file seeks fine and is opened by size, so this never spills twice
+            let spill = spill::SpillFile::from_reader(reader, stream_reader::MAX_STREAM_SIZE)?;
+            return open_stream_with_threshold(spill, threshold, OpenStrategy::Auto);
+        }
+        StreamStrategy::Streaming => {}
+    }
+
+    // Read the first header block for magic byte detection (TAR's marker is at offset 257)
+    let mut magic_bytes = Vec::with_capacity(MAGIC_PREFIX_LEN);
+    (&mut reader)
+        .take(MAGIC_PREFIX_LEN as u64)
+        .read_to_end(&mut magic_bytes)
         .map_err(|e| CbxError::Archive(format!("Failed to read magic bytes: {}", e)))?;
 
     // Detect archive type
@@ -226,5 +929,179 @@ pub fn open_archive_from_stream<R: std::io::Read + std::io::Seek + 'static>(
             crate::utils::debug_log::debug_log("Using optimized 7z streaming");
             Ok(Box::new(sevenz::SevenZipArchiveFromStream::new(reader)?))
         }
+        ArchiveType::Tar => {
+            crate::utils::debug_log::debug_log("Using TAR streaming");
+            Ok(Box::new(tar::TarArchiveFromStream::new(reader)?))
+        }
+        ArchiveType::Folder => Err(CbxError::UnsupportedFormat("Folder (not an archive)".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+    use std::rc::Rc;
+
+    /// Stream reporting an arbitrary size and counting reads, like an IStream
+    struct MockStream {
+        data: Cursor<Vec<u8>>,
+        reported_size: u64,
+        reads: Rc<Cell<usize>>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.reads.set(self.reads.get() + 1);
+            self.data.read(buf)
+        }
+    }
+
+    impl Seek for MockStream {
+        fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+            match pos {
+                SeekFrom::End(0) => Ok(self.reported_size),
+                pos => self.data.seek(pos),
+            }
+        }
+    }
+
+    fn small_zip() -> Vec<u8> {
+        let mut zip = ::zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["page02.jpg", "page01.jpg"] {
+            zip.start_file(name, ::zip::write::FileOptions::default()).unwrap();
+            zip.write_all(b"\xFF\xD8\xFF image").unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_stream_strategy_follows_reported_size() {
+        const THRESHOLD: u64 = 4 * 1024 * 1024;
+
+        let mock = |reported_size| MockStream {
+            data: Cursor::new(small_zip()),
+            reported_size,
+            reads: Rc::new(Cell::new(0)),
+        };
+
+        let mut small = mock(1024);
+        assert_eq!(stream_strategy(&mut small, THRESHOLD), (StreamStrategy::Memory, Some(1024)));
+        assert_eq!(stream_strategy(&mut small, 0).0, StreamStrategy::Streaming);
+
+        let mut large = mock(THRESHOLD + 1);
+        assert_eq!(stream_strategy(&mut large, THRESHOLD), (StreamStrategy::Streaming, Some(THRESHOLD + 1)));
+        assert_eq!(large.data.position(), 0);
+    }
+
+    #[test]
+    fn test_small_stream_is_read_once() {
+        let data = small_zip();
+        let open = |reported_size, threshold| {
+            let reads = Rc::new(Cell::new(0));
+            let stream = MockStream {
+                data: Cursor::new(data.clone()),
+                reported_size,
+                reads: Rc::clone(&reads),
+            };
+            let archive = open_stream_with_threshold(stream, threshold, OpenStrategy::Auto).unwrap();
+            let reads_after_open = reads.get();
+
+            let cover = archive.find_first_image(true).unwrap();
+            assert_eq!(cover.name, "page01.jpg");
+            assert_eq!(archive.extract_entry(&cover).unwrap(), b"\xFF\xD8\xFF image");
+            (reads_after_open, reads.get())
+        };
+
+        // In memory: the stream is consumed while opening and never touched again
+        let (opened, total) = open(data.len() as u64, 4 * 1024 * 1024);
+        assert_eq!(opened, total);
+
+        // Streaming: lookups keep reading from the stream
+        let (opened, total) = open(data.len() as u64, 16);
+        assert!(total > opened);
+    }
+
+    /// Stream whose `seek` always fails, like some network IStreams
+    struct UnseekableStream(Cursor<Vec<u8>>);
+
+    impl Read for UnseekableStream {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Seek for UnseekableStream {
+        fn seek(&mut self, _pos: SeekFrom) -> std::io::Result<u64> {
+            Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "seek not supported"))
+        }
+    }
+
+    #[test]
+    fn test_unseekable_stream_spills_to_temp_file() {
+        let mut stream = UnseekableStream(Cursor::new(small_zip()));
+        assert_eq!(stream_strategy(&mut stream, 0).0, StreamStrategy::Spill);
+
+        // Both the in-memory and the streaming backends work from the spill file
+        for threshold in [4 * 1024 * 1024, 0] {
+            let stream = UnseekableStream(Cursor::new(small_zip()));
+            let archive = open_stream_with_threshold(stream, threshold, OpenStrategy::Auto).unwrap();
+
+            let cover = archive.find_first_image(true).unwrap();
+            assert_eq!(cover.name, "page01.jpg");
+            assert_eq!(archive.extract_entry(&cover).unwrap(), b"\xFF\xD8\xFF image");
+        }
+    }
+
+    #[test]
+    fn test_open_strategy_overrides_size_based_choice() {
+        use StreamStrategy::*;
+
+        const SIZE: Option<u64> = Some(1024);
+        assert_eq!(apply_open_strategy(Memory, OpenStrategy::Auto, SIZE), Memory);
+        assert_eq!(apply_open_strategy(Streaming, OpenStrategy::Auto, SIZE), Streaming);
+        assert_eq!(apply_open_strategy(Streaming, OpenStrategy::Memory, SIZE), Memory);
+        assert_eq!(apply_open_strategy(Memory, OpenStrategy::Stream, SIZE), Streaming);
+        assert_eq!(apply_open_strategy(Memory, OpenStrategy::TempFile, SIZE), Spill);
+        // An unseekable stream can only be spilled
+        assert_eq!(apply_open_strategy(Spill, OpenStrategy::Stream, SIZE), Spill);
+
+        // Every strategy opens the archive
+        for strategy in [OpenStrategy::Auto, OpenStrategy::Stream, OpenStrategy::Memory, OpenStrategy::TempFile] {
+            let archive = open_stream_with_threshold(Cursor::new(small_zip()), 0, strategy).unwrap();
+            let cover = archive.find_first_image(true).unwrap();
+            assert_eq!(archive.extract_entry(&cover).unwrap(), b"\xFF\xD8\xFF image", "{:?}", strategy);
+        }
+    }
+
+    #[test]
+    fn test_read_to_end_capped() {
+        let data = vec![7u8; 100];
+        assert_eq!(read_to_end_capped(Cursor::new(data.clone()), 100).unwrap(), data);
+        assert!(read_to_end_capped(Cursor::new(data.clone()), 99).is_err());
+
+        // A stream that reports less than it returns is still cut off
+        let mut stream = MockStream {
+            data: Cursor::new(data),
+            reported_size: 10,
+            reads: Rc::new(Cell::new(0)),
+        };
+        assert_eq!(stream_strategy(&mut stream, 16).0, StreamStrategy::Memory);
+        assert!(read_to_end_capped(&mut stream, 50).is_err());
+    }
+
+    #[test]
+    fn test_forced_memory_strategy_is_capped() {
+        use StreamStrategy::*;
+
+        let at_cap = Some(MAX_IN_MEMORY_SIZE);
+        assert_eq!(apply_open_strategy(Streaming, OpenStrategy::Memory, at_cap), Memory);
+
+        // Oversized or unknown size: streamed instead of buffered whole
+        let oversized = Some(MAX_IN_MEMORY_SIZE + 1);
+        assert_eq!(apply_open_strategy(Streaming, OpenStrategy::Memory, oversized), Streaming);
+        assert_eq!(apply_open_strategy(Streaming, OpenStrategy::Memory, None), Streaming);
+        assert_eq!(apply_open_strategy(Memory, OpenStrategy::Memory, oversized), Streaming);
     }
 }
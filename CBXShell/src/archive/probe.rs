@@ -0,0 +1,132 @@
+//! Best-effort archive probe from a byte prefix
+//!
+//! A library pre-scan over a network share wants to know what a file is
+//! without downloading all of it. `probe` looks at the first few KB only:
+//! - The archive type comes from the magic bytes.
+//! - For ZIP, the local file headers at the start of the archive are walked
+//!   and the first image entry (in archive order) is identified by its
+//!   leading bytes, decompressing them if needed.
+//! - RAR and 7z report the type only; 7z keeps its directory at the end,
+//!   and RAR file headers aren't parsed here.
+//!
+//! Results are hints, not facts: the central directory at the end of a ZIP
+//! is what the thumbnail path trusts, and may disagree with the local
+//! headers. The number of images can't be known from a prefix at all. A
+//! prefix that ends early simply yields less (`None` fields).
+
+use super::stream_reader::detect_archive_type_from_bytes_detailed;
+use super::ArchiveType;
+use crate::image_processor::magic::{detect_image_format, ImageFormat, DEFAULT_FTYP_SCAN_WINDOW};
+
+/// What `probe` could tell from an archive prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Probe {
+    /// Archive type from the magic bytes, `None` if not a supported archive
+    pub archive_type: Option<ArchiveType>,
+    /// Format of the first image entry in archive order, if its header was
+    /// in the prefix (ZIP only)
+    pub likely_image_format_of_first: Option<ImageFormat>,
+}
+
+/// Identify an archive and its first image from the first bytes of the file
+///
+/// Best effort, see the module documentation. A few KB (e.g. 4096 bytes)
+/// usually covers the first entry's header of a ZIP.
+pub fn probe(prefix: &[u8]) -> Probe {
+    let archive_type = detect_archive_type_from_bytes_detailed(prefix)
+        .ok()
+        .map(|detection| detection.kind);
+
+    let likely_image_format_of_first = match archive_type {
+        Some(ArchiveType::Zip) => super::zip::first_image_prefix(prefix, DEFAULT_FTYP_SCAN_WINDOW)
+            .and_then(|header| detect_image_format(&header).ok()),
+        _ => None,
+    };
+
+    Probe {
+        archive_type,
+        likely_image_format_of_first,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    /// PNG with noisy pixels, so it doesn't compress to a few bytes
+    fn noisy_png(side: u32) -> Vec<u8> {
+        let mut state = 0x2545_F491u32;
+        let img = image::RgbImage::from_fn(side, side, |_, _| {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let [r, g, b, _] = state.to_le_bytes();
+            image::Rgb([r, g, b])
+        });
+        let mut out = Vec::new();
+        img.write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png).unwrap();
+        out
+    }
+
+    fn zip_with(files: &[(&str, &[u8])], method: CompressionMethod) -> Vec<u8> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in files {
+            zip.start_file(*name, FileOptions::default().compression_method(method)).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_probe_zip_prefix() {
+        let png = noisy_png(64);
+        for method in [CompressionMethod::Stored, CompressionMethod::Deflated] {
+            let archive = zip_with(
+                &[("ComicInfo.xml", b"<ComicInfo/>"), ("page01.png", &png), ("page02.jpg", b"\xFF\xD8\xFF")],
+                method,
+            );
+            assert!(archive.len() > 4096);
+
+            // Only the first 4KB: the cover's data is cut off, its header isn't
+            let result = probe(&archive[..4096]);
+            assert_eq!(result.archive_type, Some(ArchiveType::Zip), "{:?}", method);
+            assert_eq!(result.likely_image_format_of_first, Some(ImageFormat::Png), "{:?}", method);
+        }
+
+        // Too short to reach the first image's data
+        let archive = zip_with(&[("page01.png", &png)], CompressionMethod::Stored);
+        assert_eq!(
+            probe(&archive[..40]),
+            Probe { archive_type: Some(ArchiveType::Zip), likely_image_format_of_first: None }
+        );
+    }
+
+    #[test]
+    fn test_probe_rar_and_7z_prefixes() {
+        let mut rar4 = b"Rar!\x1A\x07\x00".to_vec();
+        rar4.resize(512, 0);
+        let mut rar5 = b"Rar!\x1A\x07\x01\x00".to_vec();
+        rar5.resize(512, 0);
+        let mut sevenz = b"7z\xBC\xAF\x27\x1C\x00\x04".to_vec();
+        sevenz.resize(512, 0);
+
+        for (prefix, archive_type) in [(rar4, ArchiveType::Rar), (rar5, ArchiveType::Rar), (sevenz, ArchiveType::SevenZip)] {
+            assert_eq!(
+                probe(&prefix),
+                Probe { archive_type: Some(archive_type), likely_image_format_of_first: None }
+            );
+        }
+    }
+
+    #[test]
+    fn test_probe_unknown_prefix() {
+        let none = Probe { archive_type: None, likely_image_format_of_first: None };
+        assert_eq!(probe(b"%PDF-1.7 not an archive"), none);
+        assert_eq!(probe(b"PK"), none);
+        assert_eq!(probe(&[]), none);
+    }
+}
@@ -1,16 +1,121 @@
 ///! RAR/CBR archive implementation
 ///!
 ///! Supports RAR and CBR formats using the `unrar` crate
+///!
+///! Every volume of a split set (`book.part1.rar`, `book.part2.rar`, ...) can
+///! be opened on its own. Only entries that start in the opened volume are
+///! listed; a later volume whose own entries contain no image fails with an
+///! error pointing at the first volume.
 
 use std::fs::File;
 use std::io::{Write as IoWrite, Read};
 use std::path::{Path, PathBuf};
 use std::hash::BuildHasher;
-use unrar::Archive as UnrarArchive;
+use std::time::SystemTime;
+use unrar::{Archive as UnrarArchive, FileHeader, VolumeInfo};
 
 use crate::archive::{Archive, ArchiveEntry, ArchiveMetadata, ArchiveType};
 use crate::utils::error::{CbxError, Result};
-use super::utils::{is_image_file, find_first_image, MAX_ENTRY_SIZE};
+use super::utils::{
+    bounded_name, dos_datetime_to_system_time, find_first_image, is_image_file, normalize_entry_name,
+};
+use super::config::read_max_entry_size;
+
+/// Newest last-modified time among the archive's entries
+///
+/// Entry times are MS-DOS date (high word) and time (low word) values.
+fn newest_entry_time(path: &Path) -> Option<SystemTime> {
+    list_headers(path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            dos_datetime_to_system_time((entry.file_time >> 16) as u16, entry.file_time as u16)
+        })
+        .max()
+}
+
+/// Headers of the entries in `path`, in archive order
+///
+/// unrar stops at the end-of-archive block, so data appended after it is
+/// never read. Archives written without that block run into the appended
+/// data instead; a damaged header after at least one good one is taken for
+/// such trailing data and ends the listing. A damaged first header fails.
+fn list_headers(path: &Path) -> Result<impl Iterator<Item = Result<FileHeader>>> {
+    let archive = UnrarArchive::new(path)
+        .open_for_listing()
+        .map_err(|e| CbxError::Archive(format!("Failed to open RAR for listing: {:?}", e)))?;
+
+    let mut listed = false;
+    Ok(archive.map_while(move |entry_result| match entry_result {
+        Ok(entry) => {
+            listed = true;
+            Some(Ok(entry))
+        }
+        Err(e) if listed => {
+            tracing::warn!("Ignoring data after the last readable RAR entry: {:?}", e);
+            None
+        }
+        Err(e) => Some(Err(CbxError::Archive(format!("RAR entry error: {:?}", e)))),
+    }))
+}
+
+/// Whether `path` is a volume other than the first of a split RAR set
+fn is_later_volume(path: &Path) -> bool {
+    UnrarArchive::new(path)
+        .open_for_listing()
+        .map(|archive| archive.volume_info() == VolumeInfo::Subsequent)
+        .unwrap_or(false)
+}
+
+/// Human name of a RAR compression method (`FileHeader::method`)
+///
+/// RAR records a compression level rather than an algorithm.
+fn method_name(method: u32) -> String {
+    match method {
+        0x30 => "Stored".to_string(),
+        0x31 => "RAR (fastest)".to_string(),
+        0x32 => "RAR (fast)".to_string(),
+        0x33 => "RAR (normal)".to_string(),
+        0x34 => "RAR (good)".to_string(),
+        0x35 => "RAR (best)".to_string(),
+        other => format!("Method 0x{:X}", other),
+    }
+}
+
+/// Compression method of entry `name` (see `Archive::compression_method`)
+fn entry_compression(path: &Path, name: &str) -> Result<String> {
+    for entry in list_headers(path)? {
+        let entry = entry?;
+        if normalize_entry_name(&entry.filename.to_string_lossy()) == name {
+            return Ok(method_name(entry.method));
+        }
+    }
+
+    Err(CbxError::Archive(format!("Entry not found: {}", name)))
+}
+
+/// Error for an archive without images
+///
+/// Entries continued from an earlier volume are not visible in a later
+/// volume opened on its own, so the cover may simply live in part 1.
+fn no_images_error(path: &Path) -> CbxError {
+    if !is_later_volume(path) {
+        return CbxError::Archive("No images found in archive".to_string());
+    }
+
+    // Temp copies of stream data have no volume naming to derive part 1 from
+    let first_part = UnrarArchive::new(path).first_part();
+    let hint = match first_part.file_name() {
+        Some(name) if first_part != path => format!(" ({})", name.to_string_lossy()),
+        _ => String::new(),
+    };
+
+    tracing::info!("RAR volume {:?} has no images of its own", path);
+    CbxError::Archive(format!(
+        "No images start in this RAR volume; it needs the first volume of the set{}",
+        hint
+    ))
+}
 
 /// RAR archive handler
 pub struct RarArchive {
@@ -49,21 +154,22 @@ impl RarArchive {
             path: path.to_path_buf(),
         })
     }
+}
 
-    /// List all entries in archive
-    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
-        let archive = UnrarArchive::new(&self.path)
-            .open_for_listing()
-            .map_err(|e| CbxError::Archive(format!("Failed to open RAR for listing: {:?}", e)))?;
+impl Archive for RarArchive {
+    fn open(path: &Path) -> Result<Box<dyn Archive>> {
+        Ok(Box::new(Self::open(path)?))
+    }
 
+    /// List all entries in archive order
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
         let mut entries = Vec::new();
 
-        for entry_result in archive {
-            let entry = entry_result
-                .map_err(|e| CbxError::Archive(format!("RAR entry error: {:?}", e)))?;
+        for entry in list_headers(&self.path)? {
+            let entry = entry?;
 
             // Get filename from entry
-            let filename = entry.filename.to_string_lossy().to_string();
+            let filename = normalize_entry_name(&entry.filename.to_string_lossy());
 
             entries.push(ArchiveEntry {
                 name: filename,
@@ -74,12 +180,6 @@ impl RarArchive {
 
         Ok(entries)
     }
-}
-
-impl Archive for RarArchive {
-    fn open(path: &Path) -> Result<Box<dyn Archive>> {
-        Ok(Box::new(Self::open(path)?))
-    }
 
     fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
         tracing::debug!("Finding first image in RAR (sort={})", sort);
@@ -89,18 +189,13 @@ impl Archive for RarArchive {
             // without listing all entries (faster for large archives)
             tracing::debug!("Fast path: finding first image without full listing");
 
-            let archive = UnrarArchive::new(&self.path)
-                .open_for_listing()
-                .map_err(|e| CbxError::Archive(format!("Failed to open RAR for listing: {:?}", e)))?;
+            for entry in list_headers(&self.path)? {
+                let entry = entry?;
 
-            for entry_result in archive {
-                let entry = entry_result
-                    .map_err(|e| CbxError::Archive(format!("RAR entry error: {:?}", e)))?;
-
-                let filename = entry.filename.to_string_lossy().to_string();
+                let filename = normalize_entry_name(&entry.filename.to_string_lossy());
 
                 if is_image_file(&filename) {
-                    tracing::info!("Found first image (unsorted): {}", filename);
+                    tracing::info!("Found first image (unsorted): {}", bounded_name(&filename));
                     return Ok(ArchiveEntry {
                         name: filename,
                         size: entry.unpacked_size,
@@ -109,22 +204,26 @@ impl Archive for RarArchive {
                 }
             }
 
-            return Err(CbxError::Archive("No images found in archive".to_string()));
+            return Err(no_images_error(&self.path));
         }
 
         // STANDARD PATH: List all entries and sort
         let entries = self.list_entries()?;
 
         if entries.is_empty() {
+            // A later volume may only continue entries from earlier volumes
+            if is_later_volume(&self.path) {
+                return Err(no_images_error(&self.path));
+            }
             return Err(CbxError::Archive("Archive is empty".to_string()));
         }
 
         let names: Vec<String> = entries.iter().map(|e| e.name.clone()).collect();
 
         let image_name = find_first_image(names.iter().map(|s| s.as_str()), sort)
-            .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
+            .ok_or_else(|| no_images_error(&self.path))?;
 
-        tracing::info!("Found first image (sorted): {}", image_name);
+        tracing::info!("Found first image (sorted): {}", bounded_name(&image_name));
 
         entries
             .into_iter()
@@ -133,14 +232,15 @@ impl Archive for RarArchive {
     }
 
     fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
-        tracing::debug!("Extracting entry: {} ({} bytes)", entry.name, entry.size);
+        tracing::debug!("Extracting entry: {} ({} bytes)", bounded_name(&entry.name), entry.size);
 
-        // Safety check: prevent memory exhaustion (32MB limit)
-        if entry.size > MAX_ENTRY_SIZE {
-            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, MAX_ENTRY_SIZE);
+        // Safety check: prevent memory exhaustion (`MaxEntrySizeMB`)
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
             return Err(CbxError::Archive(format!(
-                "Entry too large: {} bytes (max 32MB)",
-                entry.size
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
             )));
         }
 
@@ -154,7 +254,7 @@ impl Archive for RarArchive {
         loop {
             match archive.read_header() {
                 Ok(Some(header)) => {
-                    let current_name = header.entry().filename.to_string_lossy().to_string();
+                    let current_name = normalize_entry_name(&header.entry().filename.to_string_lossy());
 
                     if current_name == entry.name {
                         // Extract to memory
@@ -187,14 +287,20 @@ impl Archive for RarArchive {
         })
     }
 
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        entry_compression(&self.path, &entry.name)
+    }
+
     fn get_metadata(&self) -> Result<ArchiveMetadata> {
         let entries = self.list_entries()?;
         let total_files = entries.len();
         let image_count = entries.iter().filter(|e| is_image_file(&e.name)).count();
 
-        let compressed_size = std::fs::metadata(&self.path)
-            .map(|m| m.len())
-            .unwrap_or(0);
+        let file_metadata = std::fs::metadata(&self.path).ok();
+        let compressed_size = file_metadata.as_ref().map_or(0, |m| m.len());
+
+        let modified = newest_entry_time(&self.path)
+            .or_else(|| file_metadata.and_then(|m| m.modified().ok()));
 
         tracing::debug!(
             "RAR metadata: {} files, {} images, {} bytes",
@@ -208,6 +314,7 @@ impl Archive for RarArchive {
             image_count,
             compressed_size,
             archive_type: ArchiveType::Rar,
+            modified,
         })
     }
 
@@ -382,30 +489,6 @@ impl RarArchiveFromMemory {
 
         Ok(Self { temp_path })
     }
-
-    /// List all entries in archive
-    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
-        let archive = UnrarArchive::new(&self.temp_path)
-            .open_for_listing()
-            .map_err(|e| CbxError::Archive(format!("Failed to open RAR for listing: {:?}", e)))?;
-
-        let mut entries = Vec::new();
-
-        for entry_result in archive {
-            let entry = entry_result
-                .map_err(|e| CbxError::Archive(format!("RAR entry error: {:?}", e)))?;
-
-            let filename = entry.filename.to_string_lossy().to_string();
-
-            entries.push(ArchiveEntry {
-                name: filename,
-                size: entry.unpacked_size,
-                is_directory: entry.is_directory(),
-            });
-        }
-
-        Ok(entries)
-    }
 }
 
 impl Drop for RarArchiveFromMemory {
@@ -427,6 +510,25 @@ impl Archive for RarArchiveFromMemory {
         Err(CbxError::Archive("Use open_archive_from_memory instead".to_string()))
     }
 
+    /// List all entries in archive order
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        let mut entries = Vec::new();
+
+        for entry in list_headers(&self.temp_path)? {
+            let entry = entry?;
+
+            let filename = normalize_entry_name(&entry.filename.to_string_lossy());
+
+            entries.push(ArchiveEntry {
+                name: filename,
+                size: entry.unpacked_size,
+                is_directory: entry.is_directory(),
+            });
+        }
+
+        Ok(entries)
+    }
+
     fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
         tracing::debug!("Finding first image in RAR from memory (sort={})", sort);
 
@@ -434,18 +536,13 @@ impl Archive for RarArchiveFromMemory {
             // OPTIMIZATION: When not sorting, find first image immediately
             tracing::debug!("Fast path: finding first image without full listing");
 
-            let archive = UnrarArchive::new(&self.temp_path)
-                .open_for_listing()
-                .map_err(|e| CbxError::Archive(format!("Failed to open RAR for listing: {:?}", e)))?;
-
-            for entry_result in archive {
-                let entry = entry_result
-                    .map_err(|e| CbxError::Archive(format!("RAR entry error: {:?}", e)))?;
+            for entry in list_headers(&self.temp_path)? {
+                let entry = entry?;
 
-                let filename = entry.filename.to_string_lossy().to_string();
+                let filename = normalize_entry_name(&entry.filename.to_string_lossy());
 
                 if is_image_file(&filename) {
-                    tracing::info!("Found first image (unsorted): {}", filename);
+                    tracing::info!("Found first image (unsorted): {}", bounded_name(&filename));
                     return Ok(ArchiveEntry {
                         name: filename,
                         size: entry.unpacked_size,
@@ -454,22 +551,26 @@ impl Archive for RarArchiveFromMemory {
                 }
             }
 
-            return Err(CbxError::Archive("No images found in archive".to_string()));
+            return Err(no_images_error(&self.temp_path));
         }
 
         // STANDARD PATH: List all entries and sort
         let entries = self.list_entries()?;
 
         if entries.is_empty() {
+            // A later volume may only continue entries from earlier volumes
+            if is_later_volume(&self.temp_path) {
+                return Err(no_images_error(&self.temp_path));
+            }
             return Err(CbxError::Archive("Archive is empty".to_string()));
         }
 
         let names: Vec<String> = entries.iter().map(|e| e.name.clone()).collect();
 
         let image_name = find_first_image(names.iter().map(|s| s.as_str()), sort)
-            .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
+            .ok_or_else(|| no_images_error(&self.temp_path))?;
 
-        tracing::info!("Found first image (sorted): {}", image_name);
+        tracing::info!("Found first image (sorted): {}", bounded_name(&image_name));
 
         entries
             .into_iter()
@@ -478,14 +579,15 @@ impl Archive for RarArchiveFromMemory {
     }
 
     fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
-        tracing::debug!("Extracting entry from memory: {} ({} bytes)", entry.name, entry.size);
+        tracing::debug!("Extracting entry from memory: {} ({} bytes)", bounded_name(&entry.name), entry.size);
 
         // Safety check: prevent memory exhaustion
-        if entry.size > MAX_ENTRY_SIZE {
-            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, MAX_ENTRY_SIZE);
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
             return Err(CbxError::Archive(format!(
-                "Entry too large: {} bytes (max 32MB)",
-                entry.size
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
             )));
         }
 
@@ -499,7 +601,7 @@ impl Archive for RarArchiveFromMemory {
         loop {
             match archive.read_header() {
                 Ok(Some(header)) => {
-                    let current_name = header.entry().filename.to_string_lossy().to_string();
+                    let current_name = normalize_entry_name(&header.entry().filename.to_string_lossy());
 
                     if current_name == entry.name {
                         // Extract to memory
@@ -532,6 +634,10 @@ impl Archive for RarArchiveFromMemory {
         })
     }
 
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        entry_compression(&self.temp_path, &entry.name)
+    }
+
     fn get_metadata(&self) -> Result<ArchiveMetadata> {
         let entries = self.list_entries()?;
         let total_files = entries.len();
@@ -553,6 +659,8 @@ impl Archive for RarArchiveFromMemory {
             image_count,
             compressed_size: compressed_size,
             archive_type: ArchiveType::Rar,
+            // The temp file's mtime is meaningless, so only entry times count
+            modified: newest_entry_time(&self.temp_path),
         })
     }
 
@@ -566,8 +674,8 @@ mod tests {
     use super::*;
 
     // Note: Creating valid RAR archives programmatically is not possible
-    // with the unrar crate (it's extraction-only). Tests will need to use
-    // pre-existing RAR files or be integration tests.
+    // with the unrar crate (it's extraction-only). The split-volume tests
+    // below assemble minimal stored RAR 4 volumes by hand instead.
 
     #[test]
     fn test_open_nonexistent_rar() {
@@ -596,6 +704,191 @@ mod tests {
         assert_eq!(rar.archive_type(), ArchiveType::Rar);
     }
 
-    // Note: More comprehensive tests require actual RAR files
-    // These should be added as integration tests with test fixtures
+    /// CRC-32 (IEEE), used for RAR 4 header and file checksums
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= u32::from(byte);
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    /// RAR 4 block: CRC16 (low half of the CRC-32 of the rest), type, flags, size, body
+    fn rar4_block(kind: u8, flags: u16, body: &[u8]) -> Vec<u8> {
+        let mut block = vec![kind];
+        block.extend_from_slice(&flags.to_le_bytes());
+        block.extend_from_slice(&((7 + body.len()) as u16).to_le_bytes());
+        block.extend_from_slice(body);
+
+        let mut out = (crc32(&block) as u16).to_le_bytes().to_vec();
+        out.extend(block);
+        out
+    }
+
+    /// A stored (uncompressed) file, or the part of one, in a RAR 4 volume
+    struct StoredPart<'a> {
+        name: &'a str,
+        data: &'a [u8],
+        /// Size of the whole file across volumes
+        total_size: u32,
+        split_before: bool,
+    }
+
+    /// Build volume `number` (1-based) of a split RAR 4 set using
+    /// `name.partN.rar` numbering. Parts never continue into a next volume.
+    fn rar4_volume(number: u32, last: bool, parts: &[StoredPart]) -> Vec<u8> {
+        const MHD_VOLUME: u16 = 0x0001;
+        const MHD_NEWNUMBERING: u16 = 0x0010;
+        const MHD_FIRSTVOLUME: u16 = 0x0100;
+
+        let mut rar = b"Rar!\x1A\x07\x00".to_vec();
+
+        let mut flags = MHD_VOLUME | MHD_NEWNUMBERING;
+        if number == 1 {
+            flags |= MHD_FIRSTVOLUME;
+        }
+        rar.extend(rar4_block(0x73, flags, &[0; 6]));
+
+        for part in parts {
+            rar.extend(rar4_file(part));
+        }
+
+        // End of archive, flagging that another volume follows
+        rar.extend(rar4_block(0x7B, if last { 0 } else { 0x0001 }, &[]));
+        rar
+    }
+
+    /// File block of a stored part, followed by its data
+    fn rar4_file(part: &StoredPart) -> Vec<u8> {
+        const LHD_SPLIT_BEFORE: u16 = 0x0001;
+        const LONG_BLOCK: u16 = 0x8000;
+
+        let mut flags = LONG_BLOCK;
+        if part.split_before {
+            flags |= LHD_SPLIT_BEFORE;
+        }
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&(part.data.len() as u32).to_le_bytes()); // Packed size
+        body.extend_from_slice(&part.total_size.to_le_bytes()); // Unpacked size
+        body.push(2); // Host OS: Windows
+        body.extend_from_slice(&crc32(part.data).to_le_bytes());
+        body.extend_from_slice(&0x586F_6000u32.to_le_bytes()); // 2024-03-15 12:00
+        body.push(29); // Version needed to extract
+        body.push(0x30); // Method: store
+        body.extend_from_slice(&(part.name.len() as u16).to_le_bytes());
+        body.extend_from_slice(&0x20u32.to_le_bytes()); // Attributes: archive
+        body.extend_from_slice(part.name.as_bytes());
+
+        let mut file = rar4_block(0x74, flags, &body);
+        file.extend_from_slice(part.data);
+        file
+    }
+
+    /// Single-volume RAR 4 archive of whole stored files, with or without the
+    /// end-of-archive block
+    fn rar4_archive(files: &[(&str, &[u8])], end_block: bool) -> Vec<u8> {
+        let mut rar = b"Rar!\x1A\x07\x00".to_vec();
+        rar.extend(rar4_block(0x73, 0, &[0; 6]));
+        for &(name, data) in files {
+            rar.extend(rar4_file(&StoredPart { name, data, total_size: data.len() as u32, split_before: false }));
+        }
+        if end_block {
+            rar.extend(rar4_block(0x7B, 0, &[]));
+        }
+        rar
+    }
+
+    /// Write a split set's second volume to a fresh directory
+    fn write_part2(test_name: &str, parts: &[StoredPart]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cbx_rar_{}_{}", test_name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("book.part2.rar");
+        std::fs::write(&path, rar4_volume(2, true, parts)).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_open_later_volume_alone_yields_its_first_image() {
+        // part2 continues 01.jpg from part1, then holds 02.jpg entirely
+        let path = write_part2("own_image", &[
+            StoredPart { name: "01.jpg", data: b"tail of page one", total_size: 40, split_before: true },
+            StoredPart { name: "02.jpg", data: b"page two", total_size: 8, split_before: false },
+        ]);
+
+        assert!(is_later_volume(&path));
+
+        let archive = RarArchive::open(&path).unwrap();
+        let entry = archive.find_first_image(true).unwrap();
+        assert_eq!(entry.name, "02.jpg");
+        assert_eq!(archive.extract_entry(&entry).unwrap(), b"page two");
+        assert_eq!(archive.compression_method(&entry).unwrap(), "Stored");
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_open_later_volume_without_own_images_needs_part1() {
+        let path = write_part2("needs_part1", &[StoredPart {
+            name: "01.jpg",
+            data: b"tail of page one",
+            total_size: 40,
+            split_before: true,
+        }]);
+
+        // Opening succeeds; the cover lookup explains what is missing
+        let archive = RarArchive::open(&path).unwrap();
+        for sort in [true, false] {
+            let message = archive.find_first_image(sort).unwrap_err().to_string();
+            assert!(message.contains("needs the first volume"), "{}", message);
+            assert!(message.contains("book.part1.rar"), "{}", message);
+        }
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn test_trailing_data_is_ignored() {
+        let files: &[(&str, &[u8])] = &[("01.jpg", b"cover"), ("02.jpg", b"page two")];
+
+        // With an end-of-archive block and without one (the data then runs
+        // straight into the junk)
+        for end_block in [true, false] {
+            let mut data = rar4_archive(files, end_block);
+            data.extend(std::iter::repeat(0xAB).take(70_000));
+
+            let archive = RarArchiveFromMemory::new(data).unwrap();
+            assert_eq!(archive.list_entries().unwrap().len(), 2);
+            for sort in [true, false] {
+                let entry = archive.find_first_image(sort).unwrap();
+                assert_eq!(entry.name, "01.jpg");
+                assert_eq!(archive.extract_entry(&entry).unwrap(), b"cover");
+            }
+        }
+
+        // Junk in place of the first header is still an error
+        let mut data = rar4_archive(files, false);
+        data.truncate(7 + 13);
+        data.extend(std::iter::repeat(0xAB).take(100));
+        let archive = RarArchiveFromMemory::new(data);
+        assert!(archive.map_or(true, |archive| archive.list_entries().is_err()));
+    }
+
+    #[test]
+    fn test_find_first_decodable_image_skips_corrupt_cover() {
+        let mut jpeg = Vec::new();
+        image::RgbImage::new(4, 4)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+
+        let data = rar4_archive(&[("01.jpg", b"\xFF\xD8\xFF garbage"), ("02.jpg", &jpeg)], true);
+        let mut decode = |_: &ArchiveEntry, data: Vec<u8>| crate::image_processor::decoder::decode_image(&data).map(drop);
+        let archive = RarArchiveFromMemory::new(data).unwrap();
+        assert_eq!(archive.find_first_image(true).unwrap().name, "01.jpg");
+        assert_eq!(archive.find_first_decodable_image(true, &mut decode).unwrap().name, "02.jpg");
+    }
 }
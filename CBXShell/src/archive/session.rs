@@ -0,0 +1,452 @@
+//! One opened archive answering several queries
+//!
+//! The COM object needs the no-thumbnail marker check, the cover, its data
+//! and possibly metadata from the same archive. Each `Archive` method lists
+//! the directory on its own, so asking them one after another re-reads every
+//! entry header (for a streamed ZIP that is a seek and a read per entry).
+//! `ArchiveSession` lists the directory once and serves the trait's listing
+//! based methods from that cached copy.
+
+use std::cell::{OnceCell, RefCell};
+use std::collections::HashSet;
+use std::path::Path;
+use zip::CompressionMethod;
+
+use super::cover_cache::{cover_cache_matches, find_cover_cache};
+use super::utils::{bounded_name, find_first_image};
+use super::{Archive, ArchiveEntry, ArchiveMetadata, ArchiveType, CoverOptions};
+use crate::image_processor::magic::detect_image_format;
+use crate::utils::error::{CbxError, Result};
+
+/// Most covers `extract_cover` skips for having no decoder
+const MAX_SKIPPED_COVERS: usize = 8;
+
+/// An opened archive with its directory listing cached
+pub struct ArchiveSession {
+    archive: Box<dyn Archive>,
+    entries: OnceCell<Vec<ArchiveEntry>>,
+    pages: OnceCell<Vec<String>>,
+    metadata: OnceCell<ArchiveMetadata>,
+    /// The cover last returned by `cover` or `cover_cache`
+    chosen_cover: RefCell<Option<ArchiveEntry>>,
+    /// Covers `extract_cover` found undecodable; `cover` never returns them
+    skipped: RefCell<HashSet<String>>,
+}
+
+impl ArchiveSession {
+    /// Wrap an archive opened by `open_archive`, `open_archive_from_stream`, etc.
+    pub fn new(archive: Box<dyn Archive>) -> Self {
+        Self {
+            archive,
+            entries: OnceCell::new(),
+            pages: OnceCell::new(),
+            metadata: OnceCell::new(),
+            chosen_cover: RefCell::new(None),
+            skipped: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// The archive's format, as detected from its magic bytes
+    pub fn archive_type(&self) -> ArchiveType {
+        self.archive.archive_type()
+    }
+
+    /// All entries in archive order, listed on first use
+    pub fn entries(&self) -> Result<&[ArchiveEntry]> {
+        if let Some(entries) = self.entries.get() {
+            return Ok(entries);
+        }
+
+        let entries = self.archive.list_entries()?;
+        Ok(self.entries.get_or_init(|| entries))
+    }
+
+    /// The cover image according to `options` (see `Archive::find_cover_image`)
+    ///
+    /// The directory is always listed, since a `.cbxcover` entry anywhere in
+    /// the listing overrides the options. Covers skipped by `extract_cover`
+    /// are left out.
+    pub fn cover(&self, options: &CoverOptions) -> Result<ArchiveEntry> {
+        let skipped = self.skipped.borrow();
+        let cover = if skipped.is_empty() {
+            self.cached()?.find_cover_image(options)?
+        } else {
+            let remaining: Vec<ArchiveEntry> = self
+                .entries()?
+                .iter()
+                .filter(|e| !skipped.contains(&e.name))
+                .cloned()
+                .collect();
+            let cover = CachedListing {
+                archive: self.archive.as_ref(),
+                entries: &remaining,
+            }
+            .find_cover_image(options)?;
+
+            // The backend's own lookup doesn't know what was skipped
+            if skipped.contains(&cover.name) {
+                return Err(CbxError::Archive("No decodable images found in archive".to_string()));
+            }
+            cover
+        };
+
+        *self.chosen_cover.borrow_mut() = Some(cover.clone());
+        Ok(cover)
+    }
+
+    /// Extract `cover`, moving on to the next cover while it can't be decoded
+    ///
+    /// A cover in a format that is recognized but has no decoder (e.g. HEIC
+    /// without the `wic-fallback` feature) is skipped and `cover` picks again
+    /// from the remaining entries, up to `MAX_SKIPPED_COVERS` times. If no
+    /// other cover is found the last candidate is returned, so verifying or
+    /// decoding it reports the format. Returns the entry actually extracted
+    /// with its data.
+    pub fn extract_cover(&self, cover: ArchiveEntry, options: &CoverOptions) -> Result<(ArchiveEntry, Vec<u8>)> {
+        let mut cover = cover;
+        let mut data = self.extract(&cover)?;
+
+        for _ in 0..MAX_SKIPPED_COVERS {
+            let Some(format) = detect_image_format(&data).ok().filter(|format| !format.may_decode()) else {
+                break;
+            };
+            tracing::info!(
+                "Skipping {} cover {} (no decoder), trying the next image",
+                format.as_str(),
+                bounded_name(&cover.name)
+            );
+            self.skipped.borrow_mut().insert(cover.name.clone());
+
+            let Ok(next) = self.cover(options) else {
+                break;
+            };
+            data = self.extract(&next)?;
+            cover = next;
+        }
+
+        Ok((cover, data))
+    }
+
+    /// The first image `decode` accepts (see `Archive::find_first_decodable_image`)
+    ///
+    /// For replacing a cover that turned out to be corrupt; it becomes the
+    /// chosen cover.
+    pub fn first_decodable_image(
+        &self,
+        sort: bool,
+        decode: &mut dyn FnMut(&ArchiveEntry, Vec<u8>) -> Result<()>,
+    ) -> Result<ArchiveEntry> {
+        let cover = self.cached()?.find_first_decodable_image(sort, decode)?;
+        *self.chosen_cover.borrow_mut() = Some(cover.clone());
+        Ok(cover)
+    }
+
+    /// The embedded cover cache entry (`cover_cache.png`) and its data
+    ///
+    /// `None` if there is none, it can't be read, or it wasn't rendered with
+    /// `options` at `size` or larger (see `cover_cache_matches`).
+    pub fn cover_cache(&self, options: &CoverOptions, size: u32) -> Result<Option<(ArchiveEntry, Vec<u8>)>> {
+        let Some(entry) = find_cover_cache(self.entries()?).cloned() else {
+            return Ok(None);
+        };
+        let data = match self.extract(&entry) {
+            Ok(data) => data,
+            Err(e) => {
+                tracing::debug!("Ignoring unreadable cover cache: {}", e);
+                return Ok(None);
+            }
+        };
+        if !cover_cache_matches(&data, options, size) {
+            tracing::debug!("Ignoring cover cache rendered with other settings");
+            return Ok(None);
+        }
+
+        *self.chosen_cover.borrow_mut() = Some(entry.clone());
+        Ok(Some((entry, data)))
+    }
+
+    /// Compression method of the cover last chosen by `cover` or `cover_cache`
+    ///
+    /// See `Archive::compression_method`.
+    pub fn cover_compression(&self) -> Result<String> {
+        let chosen = self.chosen_cover.borrow();
+        let cover = chosen
+            .as_ref()
+            .ok_or_else(|| CbxError::Archive("No cover chosen yet".to_string()))?;
+        self.archive.compression_method(cover)
+    }
+
+    /// Whether the archive contains a no-thumbnail marker entry
+    pub fn has_no_thumb_marker(&self) -> Result<bool> {
+        self.cached()?.has_no_thumb_marker()
+    }
+
+    /// Archive metadata, read from the backend once
+    pub fn metadata(&self) -> Result<ArchiveMetadata> {
+        if let Some(metadata) = self.metadata.get() {
+            return Ok(metadata.clone());
+        }
+
+        let metadata = self.archive.get_metadata()?;
+        Ok(self.metadata.get_or_init(|| metadata).clone())
+    }
+
+    /// Number of pages (image entries)
+    #[allow(dead_code)] // Part of public API, may be used in future
+    pub fn image_count(&self) -> Result<usize> {
+        Ok(self.pages()?.len())
+    }
+
+    /// Data of page `index` (0-based) in reading order (see `Archive::list_images`)
+    #[allow(dead_code)] // Part of public API, may be used in future
+    pub fn page(&self, index: usize) -> Result<Vec<u8>> {
+        let pages = self.pages()?;
+        let name = pages.get(index).ok_or_else(|| {
+            CbxError::Archive(format!("Page {} out of range ({} pages)", index, pages.len()))
+        })?;
+
+        let entry = self
+            .entries()?
+            .iter()
+            .find(|e| &e.name == name)
+            .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", name)))?;
+
+        self.archive.extract_entry(entry)
+    }
+
+    /// Extract an entry to a byte vector
+    pub fn extract(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+        self.archive.extract_entry(entry)
+    }
+
+    /// Read up to `max_len` leading bytes of an entry
+    pub fn extract_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        self.archive.read_entry_prefix(entry, max_len)
+    }
+
+    /// Image entry names in reading order, computed once
+    fn pages(&self) -> Result<&[String]> {
+        if let Some(pages) = self.pages.get() {
+            return Ok(pages);
+        }
+
+        let pages = self.cached()?.list_images()?;
+        Ok(self.pages.get_or_init(|| pages))
+    }
+
+    /// The archive with `list_entries` answered from the cache
+    fn cached(&self) -> Result<CachedListing<'_>> {
+        Ok(CachedListing {
+            archive: self.archive.as_ref(),
+            entries: self.entries()?,
+        })
+    }
+}
+
+/// Adapter serving `list_entries` from a cached listing
+///
+/// The trait's default methods (`find_cover_image`, `list_images`,
+/// `has_no_thumb_marker`) are built on `list_entries`, so running them on
+/// this adapter reuses the cache; everything else goes to the backend.
+struct CachedListing<'a> {
+    archive: &'a dyn Archive,
+    entries: &'a [ArchiveEntry],
+}
+
+impl Archive for CachedListing<'_> {
+    fn open(_path: &Path) -> Result<Box<dyn Archive>> {
+        Err(CbxError::Archive("Use ArchiveSession::new instead".to_string()))
+    }
+
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        Ok(self.entries.to_vec())
+    }
+
+    fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
+        let names = self.entries.iter().filter(|e| !e.is_directory).map(|e| e.name.as_str());
+        let cover = find_first_image(names, sort)
+            .and_then(|name| self.entries.iter().find(|e| e.name == name));
+
+        match cover {
+            Some(entry) => Ok(entry.clone()),
+            // The backend knows why (e.g. a RAR volume that needs part 1)
+            None => self.archive.find_first_image(sort),
+        }
+    }
+
+    fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+        self.archive.extract_entry(entry)
+    }
+
+    fn read_entry_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        self.archive.read_entry_prefix(entry, max_len)
+    }
+
+    fn extract_entry_raw(&self, entry: &ArchiveEntry) -> Result<(CompressionMethod, Vec<u8>)> {
+        self.archive.extract_entry_raw(entry)
+    }
+
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        self.archive.compression_method(entry)
+    }
+
+    fn get_metadata(&self) -> Result<ArchiveMetadata> {
+        self.archive.get_metadata()
+    }
+
+    fn archive_type(&self) -> ArchiveType {
+        self.archive.archive_type()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// In-memory archive counting how often its directory is listed
+    struct CountingArchive {
+        files: Vec<(&'static str, &'static [u8])>,
+        listings: Rc<Cell<usize>>,
+    }
+
+    impl Archive for CountingArchive {
+        fn open(_path: &Path) -> Result<Box<dyn Archive>> {
+            unimplemented!()
+        }
+
+        fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+            self.listings.set(self.listings.get() + 1);
+            Ok(self
+                .files
+                .iter()
+                .map(|(name, data)| ArchiveEntry {
+                    name: name.to_string(),
+                    size: data.len() as u64,
+                    is_directory: false,
+                })
+                .collect())
+        }
+
+        fn find_first_image(&self, _sort: bool) -> Result<ArchiveEntry> {
+            unimplemented!("a session with a cached listing never takes the fast path")
+        }
+
+        fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+            self.files
+                .iter()
+                .find(|(name, _)| *name == entry.name)
+                .map(|(_, data)| data.to_vec())
+                .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", entry.name)))
+        }
+
+        fn get_metadata(&self) -> Result<ArchiveMetadata> {
+            Ok(ArchiveMetadata {
+                total_files: self.files.len(),
+                image_count: 3,
+                compressed_size: 0,
+                archive_type: ArchiveType::Zip,
+                modified: None,
+            })
+        }
+
+        fn archive_type(&self) -> ArchiveType {
+            ArchiveType::Zip
+        }
+    }
+
+    #[test]
+    fn test_session_lists_directory_once() {
+        let listings = Rc::new(Cell::new(0));
+        let session = ArchiveSession::new(Box::new(CountingArchive {
+            files: vec![
+                ("page10.jpg", b"ten"),
+                ("info.txt", b"text"),
+                ("page2.jpg", b"two"),
+                ("page1.jpg", b"one"),
+            ],
+            listings: Rc::clone(&listings),
+        }));
+
+        let prefer_png = CoverOptions {
+            sort: true,
+            format_priority: vec!["png".to_string()],
+            ..Default::default()
+        };
+        let plain = CoverOptions {
+            sort: true,
+            ..Default::default()
+        };
+
+        assert!(!session.has_no_thumb_marker().unwrap());
+        assert_eq!(session.cover(&prefer_png).unwrap().name, "page1.jpg");
+        assert_eq!(session.cover(&plain).unwrap().name, "page1.jpg");
+        assert_eq!(session.metadata().unwrap().total_files, 4);
+        assert_eq!(session.image_count().unwrap(), 3);
+        assert_eq!(session.page(0).unwrap(), b"one");
+        assert_eq!(session.page(2).unwrap(), b"ten");
+        assert!(session.page(3).is_err());
+        assert_eq!(session.entries().unwrap().len(), 4);
+
+        assert_eq!(listings.get(), 1);
+    }
+
+    #[test]
+    fn test_extract_cover_skips_undecodable_formats() {
+        const HEIC: &[u8] = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        const PNG: &[u8] = b"\x89PNG\r\n\x1A\n";
+
+        let session = ArchiveSession::new(Box::new(CountingArchive {
+            files: vec![("page1.jpg", HEIC), ("page2.png", PNG), ("page3.jpg", HEIC)],
+            listings: Rc::new(Cell::new(0)),
+        }));
+        let options = CoverOptions {
+            sort: true,
+            ..Default::default()
+        };
+
+        let cover = session.cover(&options).unwrap();
+        assert_eq!(cover.name, "page1.jpg");
+        let (cover, data) = session.extract_cover(cover, &options).unwrap();
+        if cfg!(feature = "wic-fallback") {
+            // WIC may have a HEIF codec, so nothing is skipped
+            assert_eq!((cover.name.as_str(), &data[..]), ("page1.jpg", HEIC));
+            return;
+        }
+        assert_eq!((cover.name.as_str(), &data[..]), ("page2.png", PNG));
+        assert_eq!(session.cover(&options).unwrap().name, "page2.png");
+
+        // Nothing decodable left: the last candidate is kept for the error
+        let session = ArchiveSession::new(Box::new(CountingArchive {
+            files: vec![("page1.jpg", HEIC), ("page3.jpg", HEIC), ("info.txt", b"text")],
+            listings: Rc::new(Cell::new(0)),
+        }));
+        let cover = session.cover(&options).unwrap();
+        let (cover, data) = session.extract_cover(cover, &options).unwrap();
+        assert_eq!((cover.name.as_str(), &data[..]), ("page3.jpg", HEIC));
+        assert!(session.cover(&options).is_err());
+    }
+
+    #[test]
+    fn test_cover_compression_follows_chosen_cover() {
+        use std::io::Write as _;
+        use zip::write::{FileOptions, ZipWriter};
+
+        let mut buffer = Vec::new();
+        {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut buffer));
+            zip.start_file("page1.jpg", FileOptions::default().compression_method(CompressionMethod::Stored))
+                .unwrap();
+            zip.write_all(b"one").unwrap();
+            zip.finish().unwrap();
+        }
+
+        let archive = super::super::zip::ZipArchiveFromStream::new(std::io::Cursor::new(buffer)).unwrap();
+        let session = ArchiveSession::new(Box::new(archive));
+        assert!(session.cover_compression().is_err());
+
+        session.cover(&CoverOptions::default()).unwrap();
+        assert_eq!(session.cover_compression().unwrap(), "Stored");
+    }
+}
@@ -1,15 +1,124 @@
 ///! 7-Zip archive implementation
 ///!
 ///! Supports 7z and CB7 formats using the `sevenz-rust` crate
+///!
+///! Archive headers may be plain or compressed (`kEncodedHeader`, 7-Zip's
+///! default `-mhc=on`); sevenz-rust decodes a compressed header before any
+///! entry is listed, so both kinds open the same way.
 
 use std::fs::File;
 use std::io::{Cursor, Read, Seek};
 use std::path::{Path, PathBuf};
-use sevenz_rust::{SevenZReader, Password};
+use std::time::SystemTime;
+use sevenz_rust::{Password, SevenZMethod, SevenZReader};
 
 use crate::archive::{Archive, ArchiveEntry, ArchiveMetadata, ArchiveType};
 use crate::utils::error::{CbxError, Result};
-use super::utils::{is_image_file, find_first_image, MAX_ENTRY_SIZE};
+use super::utils::{
+    bounded_name, filetime_to_system_time, find_first_image, is_image_file, normalize_entry_name,
+};
+use super::config::read_max_entry_size;
+
+/// Newest last-modified time among the archive's entries
+///
+/// Reads the archive header only; no entry data is decompressed.
+fn newest_entry_time<R: Read + Seek>(reader: R, len: u64, password: Password) -> Option<SystemTime> {
+    let archive = SevenZReader::new(reader, len, password).ok()?;
+
+    archive
+        .archive()
+        .files
+        .iter()
+        .filter(|entry| entry.has_last_modified_date)
+        .filter_map(|entry| filetime_to_system_time(entry.last_modified_date().to_raw()))
+        .max()
+}
+
+/// Entries from the archive header, in archive order
+///
+/// Names and sizes come from the header alone, so listing neither decodes
+/// entry data (a solid block would be decompressed in full) nor needs the
+/// password of an archive whose data, but not header, is encrypted.
+fn header_entries(archive: &sevenz_rust::Archive) -> Vec<ArchiveEntry> {
+    archive
+        .files
+        .iter()
+        .map(|entry| ArchiveEntry {
+            name: normalize_entry_name(entry.name()),
+            size: entry.size(),
+            is_directory: entry.is_directory(),
+        })
+        .collect()
+}
+
+/// Coders that decode entry `name`, in decoding order, e.g. `LZMA` or `LZMA+BCJ_X86`
+///
+/// Names are sevenz-rust's, except that `COPY` is reported as `Stored`, as
+/// is an empty entry (it has no data to decode).
+fn entry_compression(archive: &sevenz_rust::Archive, name: &str) -> Result<String> {
+    let index = archive
+        .files
+        .iter()
+        .position(|entry| normalize_entry_name(entry.name()) == name)
+        .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", name)))?;
+
+    let folder = archive
+        .stream_map
+        .file_folder_index
+        .get(index)
+        .copied()
+        .flatten()
+        .and_then(|folder| archive.folders.get(folder));
+    let Some(folder) = folder else {
+        return Ok("Stored".to_string());
+    };
+
+    let coders: Vec<&str> = folder
+        .ordered_coder_iter()
+        .map(|(_, coder)| match SevenZMethod::by_id(coder.decompression_method_id()) {
+            Some(method) if method == SevenZMethod::COPY => "Stored",
+            Some(method) => method.name(),
+            None => "Unknown",
+        })
+        .collect();
+    Ok(coders.join("+"))
+}
+
+/// Whether a sevenz-rust error means the data is AES-encrypted
+///
+/// Without the crate's `aes256` feature the AES coder is unknown to it
+/// (`UnsupportedCompressionMethod`); with it, a missing password is
+/// `PasswordRequired`.
+fn is_encryption_error(e: &sevenz_rust::Error) -> bool {
+    match e {
+        sevenz_rust::Error::PasswordRequired => true,
+        sevenz_rust::Error::UnsupportedCompressionMethod(method) => {
+            method == SevenZMethod::AES256SHA256.name()
+        }
+        _ => false,
+    }
+}
+
+/// Map an error from reading the archive header
+///
+/// 7z can encrypt the header itself (`7z a -mhe=on`), in which case even
+/// the entry names can't be read without the password.
+fn header_error(e: sevenz_rust::Error, context: &str) -> CbxError {
+    if is_encryption_error(&e) {
+        CbxError::Encrypted("7z header (AES-256, password needed to list entries)".to_string())
+    } else {
+        CbxError::Archive(format!("{}: {}", context, e))
+    }
+}
+
+/// Map an error from decoding the data of entry `name`
+fn data_error(e: sevenz_rust::Error, name: &str, context: &str) -> CbxError {
+    if is_encryption_error(&e) {
+        CbxError::Encrypted(format!("{} (7z AES-256)", name))
+    } else {
+        CbxError::Archive(format!("{}: {}", context, e))
+    }
+}
 
 /// 7-Zip archive handler
 pub struct SevenZipArchive {
@@ -31,14 +140,20 @@ impl SevenZipArchive {
 
         let password = Password::empty();
         let mut _reader = SevenZReader::new(file, file_len, password)
-            .map_err(|e| CbxError::Archive(format!("Invalid 7z archive: {}", e)))?;
+            .map_err(|e| header_error(e, "Invalid 7z archive"))?;
 
         Ok(Self {
             path: path.to_path_buf(),
         })
     }
+}
 
-    /// List all entries in archive
+impl Archive for SevenZipArchive {
+    fn open(path: &Path) -> Result<Box<dyn Archive>> {
+        Ok(Box::new(Self::open(path)?))
+    }
+
+    /// List all entries in archive order
     fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
         let file = File::open(&self.path)
             .map_err(|e| CbxError::Archive(format!("Failed to open 7z: {}", e)))?;
@@ -48,29 +163,10 @@ impl SevenZipArchive {
             .len();
 
         let password = Password::empty();
-        let mut archive = SevenZReader::new(file, file_len, password)
-            .map_err(|e| CbxError::Archive(format!("Failed to read 7z: {}", e)))?;
+        let archive = SevenZReader::new(file, file_len, password)
+            .map_err(|e| header_error(e, "Failed to read 7z"))?;
 
-        let mut entries = Vec::new();
-
-        archive
-            .for_each_entries(|entry, _reader| {
-                entries.push(ArchiveEntry {
-                    name: entry.name().to_string(),
-                    size: entry.size(),
-                    is_directory: entry.is_directory(),
-                });
-                Ok(true) // Continue iteration
-            })
-            .map_err(|e| CbxError::Archive(format!("7z iteration error: {}", e)))?;
-
-        Ok(entries)
-    }
-}
-
-impl Archive for SevenZipArchive {
-    fn open(path: &Path) -> Result<Box<dyn Archive>> {
-        Ok(Box::new(Self::open(path)?))
+        Ok(header_entries(archive.archive()))
     }
 
     fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
@@ -88,27 +184,16 @@ impl Archive for SevenZipArchive {
                 .len();
 
             let password = Password::empty();
-            let mut archive = SevenZReader::new(file, file_len, password)
-                .map_err(|e| CbxError::Archive(format!("Failed to read 7z: {}", e)))?;
-
-            let mut first_image: Option<ArchiveEntry> = None;
-
-            archive
-                .for_each_entries(|entry, _reader| {
-                    let name = entry.name().to_string();
-                    if is_image_file(&name) {
-                        tracing::info!("Found first image (unsorted): {}", name);
-                        first_image = Some(ArchiveEntry {
-                            name,
-                            size: entry.size(),
-                            is_directory: entry.is_directory(),
-                        });
-                        Ok(false) // Stop iteration
-                    } else {
-                        Ok(true) // Continue
-                    }
-                })
-                .map_err(|e| CbxError::Archive(format!("7z iteration error: {}", e)))?;
+            let archive = SevenZReader::new(file, file_len, password)
+                .map_err(|e| header_error(e, "Failed to read 7z"))?;
+
+            let first_image = header_entries(archive.archive())
+                .into_iter()
+                .find(|entry| is_image_file(&entry.name));
+
+            if let Some(entry) = &first_image {
+                tracing::info!("Found first image (unsorted): {}", bounded_name(&entry.name));
+            }
 
             return first_image
                 .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()));
@@ -126,7 +211,7 @@ impl Archive for SevenZipArchive {
         let image_name = find_first_image(names.iter().map(|s| s.as_str()), sort)
             .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
 
-        tracing::info!("Found first image (sorted): {}", image_name);
+        tracing::info!("Found first image (sorted): {}", bounded_name(&image_name));
 
         entries
             .into_iter()
@@ -135,14 +220,15 @@ impl Archive for SevenZipArchive {
     }
 
     fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
-        tracing::debug!("Extracting entry: {} ({} bytes)", entry.name, entry.size);
+        tracing::debug!("Extracting entry: {} ({} bytes)", bounded_name(&entry.name), entry.size);
 
-        // Safety check: prevent memory exhaustion (32MB limit)
-        if entry.size > MAX_ENTRY_SIZE {
-            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, MAX_ENTRY_SIZE);
+        // Safety check: prevent memory exhaustion (`MaxEntrySizeMB`)
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
             return Err(CbxError::Archive(format!(
-                "Entry too large: {} bytes (max 32MB)",
-                entry.size
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
             )));
         }
 
@@ -155,13 +241,13 @@ impl Archive for SevenZipArchive {
 
         let password = Password::empty();
         let mut archive = SevenZReader::new(file, file_len, password)
-            .map_err(|e| CbxError::Archive(format!("Failed to read 7z: {}", e)))?;
+            .map_err(|e| header_error(e, "Failed to read 7z"))?;
 
         let mut extracted_data = None;
 
         archive
             .for_each_entries(|sz_entry, reader| {
-                if sz_entry.name() == entry.name {
+                if normalize_entry_name(sz_entry.name()) == entry.name {
                     let mut buffer = Vec::with_capacity(sz_entry.size() as usize);
                     std::io::copy(reader, &mut buffer)
                         .map_err(|e| sevenz_rust::Error::Io(e, "Extract failed".into()))?;
@@ -171,21 +257,39 @@ impl Archive for SevenZipArchive {
                     Ok(true) // Continue
                 }
             })
-            .map_err(|e| CbxError::Archive(format!("7z extraction error: {}", e)))?;
+            .map_err(|e| data_error(e, &entry.name, "7z extraction error"))?;
 
         extracted_data.ok_or_else(|| {
             CbxError::Archive(format!("Entry not found: {}", entry.name))
         })
     }
 
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        let file = File::open(&self.path)
+            .map_err(|e| CbxError::Archive(format!("Failed to open 7z: {}", e)))?;
+
+        let file_len = file.metadata()
+            .map_err(|e| CbxError::Archive(format!("Failed to get file metadata: {}", e)))?
+            .len();
+
+        let archive = SevenZReader::new(file, file_len, Password::empty())
+            .map_err(|e| header_error(e, "Failed to read 7z"))?;
+
+        entry_compression(archive.archive(), &entry.name)
+    }
+
     fn get_metadata(&self) -> Result<ArchiveMetadata> {
         let entries = self.list_entries()?;
         let total_files = entries.len();
         let image_count = entries.iter().filter(|e| is_image_file(&e.name)).count();
 
-        let compressed_size = std::fs::metadata(&self.path)
-            .map(|m| m.len())
-            .unwrap_or(0);
+        let file_metadata = std::fs::metadata(&self.path).ok();
+        let compressed_size = file_metadata.as_ref().map_or(0, |m| m.len());
+
+        let modified = File::open(&self.path)
+            .ok()
+            .and_then(|file| newest_entry_time(file, compressed_size, Password::empty()))
+            .or_else(|| file_metadata.and_then(|m| m.modified().ok()));
 
         tracing::debug!(
             "7z metadata: {} files, {} images, {} bytes",
@@ -199,6 +303,7 @@ impl Archive for SevenZipArchive {
             image_count,
             compressed_size,
             archive_type: ArchiveType::SevenZip,
+            modified,
         })
     }
 
@@ -246,6 +351,23 @@ mod tests {
         std::fs::remove_file(&temp_path).ok();
     }
 
+    #[test]
+    fn test_find_first_decodable_image_skips_corrupt_cover() {
+        let mut jpeg = Vec::new();
+        image::RgbImage::new(4, 4)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+
+        let temp_path = std::env::temp_dir().join("test_decodable_cover.7z");
+        let mut decode = |_: &ArchiveEntry, data: Vec<u8>| crate::image_processor::decoder::decode_image(&data).map(drop);
+        create_test_7z_file(&temp_path, &[("page1.jpg", b"\xFF\xD8\xFF garbage"), ("page2.jpg", &jpeg)]).unwrap();
+
+        let archive = SevenZipArchive::open(&temp_path).unwrap();
+        assert_eq!(archive.find_first_decodable_image(true, &mut decode).unwrap().name, "page2.jpg");
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
     #[test]
     fn test_open_invalid_7z() {
         let temp_path = std::env::temp_dir().join("test_invalid.7z");
@@ -355,6 +477,201 @@ mod tests {
 
         std::fs::remove_file(&temp_path).ok();
     }
+
+    #[test]
+    fn test_compression_method() {
+        let temp_path = std::env::temp_dir().join("test_compression_method.7z");
+        create_test_7z_file(&temp_path, &[("page1.jpg", b"image 1"), ("page2.jpg", b"image 2")]).unwrap();
+
+        // SevenZWriter compresses with LZMA2 unless told otherwise
+        let archive = SevenZipArchive::open(&temp_path).unwrap();
+        let entries = archive.list_entries().unwrap();
+        assert_eq!(archive.compression_method(&entries[1]).unwrap(), "LZMA2");
+
+        let stream = SevenZipArchiveFromStream::new(File::open(&temp_path).unwrap()).unwrap();
+        assert_eq!(stream.compression_method(&entries[0]).unwrap(), "LZMA2");
+        let missing = ArchiveEntry {
+            name: "missing.jpg".to_string(),
+            size: 0,
+            is_directory: false,
+        };
+        assert!(stream.compression_method(&missing).is_err());
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_trailing_data_is_ignored() {
+        // The start header points at the end header, so appended data is never read
+        let temp_path = std::env::temp_dir().join("test_trailing_data.7z");
+        create_test_7z_file(&temp_path, &[("page2.jpg", b"image 2"), ("page1.jpg", b"image 1")]).unwrap();
+        let mut data = std::fs::read(&temp_path).unwrap();
+        data.extend(std::iter::repeat(0xAB).take(70_000));
+        std::fs::write(&temp_path, &data).unwrap();
+
+        let archives: Vec<Box<dyn Archive>> = vec![
+            Box::new(SevenZipArchive::open(&temp_path).unwrap()),
+            Box::new(SevenZipArchiveFromMemory::new(Cursor::new(data)).unwrap()),
+            Box::new(SevenZipArchiveFromStream::new(File::open(&temp_path).unwrap()).unwrap()),
+        ];
+        for archive in archives {
+            let cover = archive.find_first_image(true).unwrap();
+            assert_eq!(cover.name, "page1.jpg");
+            assert_eq!(archive.extract_entry(&cover).unwrap(), b"image 1");
+        }
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    /// Property ID of the header the start header points at: `kHeader` (0x01)
+    /// or `kEncodedHeader` (0x17)
+    fn next_header_id(data: &[u8]) -> u8 {
+        let offset = u64::from_le_bytes(data[12..20].try_into().unwrap()) as usize;
+        data[32 + offset]
+    }
+
+    #[test]
+    fn test_compressed_header_7z() {
+        // With more than one entry, sevenz-rust LZMA-compresses the header like 7-Zip does
+        let pages: Vec<(String, Vec<u8>)> = (1..=12)
+            .rev()
+            .map(|i| (format!("chapter 1/page{:02}.jpg", i), format!("image {}", i).into_bytes()))
+            .collect();
+        let files: Vec<(&str, &[u8])> = pages.iter().map(|(name, data)| (name.as_str(), &data[..])).collect();
+
+        let temp_path = std::env::temp_dir().join("test_compressed_header.7z");
+        create_test_7z_file(&temp_path, &files).unwrap();
+        let data = std::fs::read(&temp_path).unwrap();
+        assert_eq!(next_header_id(&data), 0x17, "header should be compressed");
+
+        let archives: Vec<Box<dyn Archive>> = vec![
+            Box::new(SevenZipArchive::open(&temp_path).unwrap()),
+            Box::new(SevenZipArchiveFromMemory::new(Cursor::new(data)).unwrap()),
+            Box::new(SevenZipArchiveFromStream::new(File::open(&temp_path).unwrap()).unwrap()),
+        ];
+        for archive in archives {
+            assert_eq!(archive.list_entries().unwrap().len(), 12);
+            assert_eq!(archive.get_metadata().unwrap().image_count, 12);
+
+            let cover = archive.find_first_image(true).unwrap();
+            assert_eq!(cover.name, "chapter 1/page01.jpg");
+            assert_eq!(archive.extract_entry(&cover).unwrap(), b"image 1");
+            assert_eq!(archive.find_first_image(false).unwrap().name, "chapter 1/page12.jpg");
+        }
+
+        // A single small entry doesn't compress well; its header stays plain
+        create_test_7z_file(&temp_path, &[("cover.jpg", b"cover")]).unwrap();
+        let data = std::fs::read(&temp_path).unwrap();
+        assert_eq!(next_header_id(&data), 0x01);
+        let archive = SevenZipArchiveFromStream::new(Cursor::new(data)).unwrap();
+        assert_eq!(archive.find_first_image(true).unwrap().name, "cover.jpg");
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+            }
+        }
+        !crc
+    }
+
+    /// Hand-built 7z whose only folder is AES-256 encrypted
+    ///
+    /// With `encrypted_header` the folder holds the header itself, as written
+    /// by `7z a -p -mhe=on`; otherwise it holds the data of `page1.jpg` and
+    /// the header is plain (`7z a -p`). The ciphertext is filler: encryption
+    /// is detected before any of it is decrypted.
+    fn encrypted_7z(encrypted_header: bool) -> Vec<u8> {
+        let streams_info = |unpack_size: u8| {
+            let mut info = vec![
+                0x06, 0, 1, 0x09, 16, 0x00, // kPackInfo: one 16-byte stream at 0, kEnd
+                0x07, 0x0B, 1, 0, // kUnpackInfo, kFolder: 1 folder, not external
+                1, 0x24, 0x06, 0xF1, 0x07, 0x01, // 1 coder: AES256SHA256 with properties
+                10, 0x53, 0x07, 1, 2, 3, 4, 5, 6, 7, 8, // IV flag, 2^19 rounds, 8-byte IV
+            ];
+            info.extend_from_slice(&[0x0C, unpack_size, 0x00]); // kCodersUnpackSize, kEnd
+            info
+        };
+
+        let mut header = Vec::new();
+        if encrypted_header {
+            header.push(0x17); // kEncodedHeader
+            header.extend(streams_info(40));
+            header.push(0x00);
+        } else {
+            header.extend_from_slice(&[0x01, 0x04]); // kHeader, kMainStreamsInfo
+            header.extend(streams_info(10));
+            header.extend_from_slice(&[0x08, 0x00, 0x00]); // kSubStreamsInfo (defaults), kEnd
+
+            let name: Vec<u8> = "page1.jpg"
+                .encode_utf16()
+                .chain([0])
+                .flat_map(u16::to_le_bytes)
+                .collect();
+            header.extend_from_slice(&[0x05, 1, 0x11, name.len() as u8 + 1, 0]); // kFilesInfo, kName
+            header.extend(name);
+            header.extend_from_slice(&[0x00, 0x00]); // end of file properties, kEnd
+        }
+
+        let mut start_header = 16u64.to_le_bytes().to_vec(); // header follows the packed stream
+        start_header.extend_from_slice(&(header.len() as u64).to_le_bytes());
+        start_header.extend_from_slice(&crc32(&header).to_le_bytes());
+
+        let mut data = b"7z\xBC\xAF\x27\x1C\x00\x04".to_vec();
+        data.extend_from_slice(&crc32(&start_header).to_le_bytes());
+        data.extend(start_header);
+        data.extend_from_slice(&[0xA5; 16]);
+        data.extend(header);
+        data
+    }
+
+    #[test]
+    fn test_encrypted_header_7z_reports_encrypted() {
+        let is_header_encrypted =
+            |result: Result<()>| matches!(&result, Err(CbxError::Encrypted(msg)) if msg.contains("header"));
+
+        let stream = SevenZipArchiveFromStream::new(Cursor::new(encrypted_7z(true)));
+        assert!(is_header_encrypted(stream.map(|_| ())));
+        let memory = SevenZipArchiveFromMemory::new(Cursor::new(encrypted_7z(true)));
+        assert!(is_header_encrypted(memory.map(|_| ())));
+
+        let temp_path = std::env::temp_dir().join("test_encrypted_header.7z");
+        std::fs::write(&temp_path, encrypted_7z(true)).unwrap();
+        assert!(is_header_encrypted(SevenZipArchive::open(&temp_path).map(|_| ())));
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_encrypted_data_7z_lists_entries_but_reports_encrypted() {
+        // Names are in the plain header, so listing needs no password
+        let archive = SevenZipArchiveFromStream::new(Cursor::new(encrypted_7z(false))).unwrap();
+        let entries = archive.list_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!((entries[0].name.as_str(), entries[0].size), ("page1.jpg", 10));
+
+        for sort in [false, true] {
+            let cover = archive.find_first_image(sort).unwrap();
+            let result = archive.extract_entry(&cover);
+            assert!(
+                matches!(&result, Err(CbxError::Encrypted(msg)) if msg.contains("page1.jpg")),
+                "{:?}",
+                result
+            );
+        }
+
+        let memory = SevenZipArchiveFromMemory::new(Cursor::new(encrypted_7z(false))).unwrap();
+        assert!(matches!(memory.extract_entry(&entries[0]), Err(CbxError::Encrypted(_))));
+
+        // A password is carried along; listing still works with one
+        let with_password =
+            SevenZipArchiveFromStream::with_password(Cursor::new(encrypted_7z(false)), "secret").unwrap();
+        assert_eq!(with_password.list_entries().unwrap().len(), 1);
+    }
 }
 
 /// 7-Zip archive handler for in-memory data (IStream support)
@@ -372,41 +689,28 @@ impl SevenZipArchiveFromMemory {
         let data_len = data.len() as u64;
         let password = Password::empty();
         let _reader = SevenZReader::new(cursor_test, data_len, password)
-            .map_err(|e| CbxError::Archive(format!("Invalid 7z archive from memory: {}", e)))?;
+            .map_err(|e| header_error(e, "Invalid 7z archive from memory"))?;
 
         Ok(Self { data })
     }
+}
 
-    /// List all entries in archive
+impl Archive for SevenZipArchiveFromMemory {
+    fn open(_path: &Path) -> Result<Box<dyn Archive>> {
+        // Not used for in-memory archives
+        Err(CbxError::Archive("Use open_archive_from_memory instead".to_string()))
+    }
+
+    /// List all entries in archive order
     fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
         let cursor = Cursor::new(&self.data);
         let data_len = self.data.len() as u64;
         let password = Password::empty();
 
-        let mut archive = SevenZReader::new(cursor, data_len, password)
-            .map_err(|e| CbxError::Archive(format!("Failed to read 7z from memory: {}", e)))?;
-
-        let mut entries = Vec::new();
+        let archive = SevenZReader::new(cursor, data_len, password)
+            .map_err(|e| header_error(e, "Failed to read 7z from memory"))?;
 
-        archive
-            .for_each_entries(|entry, _reader| {
-                entries.push(ArchiveEntry {
-                    name: entry.name().to_string(),
-                    size: entry.size(),
-                    is_directory: entry.is_directory(),
-                });
-                Ok(true) // Continue iteration
-            })
-            .map_err(|e| CbxError::Archive(format!("7z iteration error: {}", e)))?;
-
-        Ok(entries)
-    }
-}
-
-impl Archive for SevenZipArchiveFromMemory {
-    fn open(_path: &Path) -> Result<Box<dyn Archive>> {
-        // Not used for in-memory archives
-        Err(CbxError::Archive("Use open_archive_from_memory instead".to_string()))
+        Ok(header_entries(archive.archive()))
     }
 
     fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
@@ -420,27 +724,16 @@ impl Archive for SevenZipArchiveFromMemory {
             let data_len = self.data.len() as u64;
             let password = Password::empty();
 
-            let mut archive = SevenZReader::new(cursor, data_len, password)
-                .map_err(|e| CbxError::Archive(format!("Failed to read 7z from memory: {}", e)))?;
-
-            let mut first_image: Option<ArchiveEntry> = None;
-
-            archive
-                .for_each_entries(|entry, _reader| {
-                    let name = entry.name().to_string();
-                    if is_image_file(&name) {
-                        tracing::info!("Found first image (unsorted): {}", name);
-                        first_image = Some(ArchiveEntry {
-                            name,
-                            size: entry.size(),
-                            is_directory: entry.is_directory(),
-                        });
-                        Ok(false) // Stop iteration
-                    } else {
-                        Ok(true) // Continue
-                    }
-                })
-                .map_err(|e| CbxError::Archive(format!("7z iteration error: {}", e)))?;
+            let archive = SevenZReader::new(cursor, data_len, password)
+                .map_err(|e| header_error(e, "Failed to read 7z from memory"))?;
+
+            let first_image = header_entries(archive.archive())
+                .into_iter()
+                .find(|entry| is_image_file(&entry.name));
+
+            if let Some(entry) = &first_image {
+                tracing::info!("Found first image (unsorted): {}", bounded_name(&entry.name));
+            }
 
             return first_image
                 .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()));
@@ -458,7 +751,7 @@ impl Archive for SevenZipArchiveFromMemory {
         let image_name = find_first_image(names.iter().map(|s| s.as_str()), sort)
             .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
 
-        tracing::info!("Found first image (sorted): {}", image_name);
+        tracing::info!("Found first image (sorted): {}", bounded_name(&image_name));
 
         entries
             .into_iter()
@@ -467,14 +760,15 @@ impl Archive for SevenZipArchiveFromMemory {
     }
 
     fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
-        tracing::debug!("Extracting entry from memory: {} ({} bytes)", entry.name, entry.size);
+        tracing::debug!("Extracting entry from memory: {} ({} bytes)", bounded_name(&entry.name), entry.size);
 
         // Safety check: prevent memory exhaustion
-        if entry.size > MAX_ENTRY_SIZE {
-            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, MAX_ENTRY_SIZE);
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
             return Err(CbxError::Archive(format!(
-                "Entry too large: {} bytes (max 32MB)",
-                entry.size
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
             )));
         }
 
@@ -483,13 +777,13 @@ impl Archive for SevenZipArchiveFromMemory {
         let password = Password::empty();
 
         let mut archive = SevenZReader::new(cursor, data_len, password)
-            .map_err(|e| CbxError::Archive(format!("Failed to read 7z from memory: {}", e)))?;
+            .map_err(|e| header_error(e, "Failed to read 7z from memory"))?;
 
         let mut extracted_data = None;
 
         archive
             .for_each_entries(|sz_entry, reader| {
-                if sz_entry.name() == entry.name {
+                if normalize_entry_name(sz_entry.name()) == entry.name {
                     let mut buffer = Vec::with_capacity(sz_entry.size() as usize);
                     std::io::copy(reader, &mut buffer)
                         .map_err(|e| sevenz_rust::Error::Io(e, "Extract failed".into()))?;
@@ -499,13 +793,21 @@ impl Archive for SevenZipArchiveFromMemory {
                     Ok(true) // Continue
                 }
             })
-            .map_err(|e| CbxError::Archive(format!("7z extraction error: {}", e)))?;
+            .map_err(|e| data_error(e, &entry.name, "7z extraction error"))?;
 
         extracted_data.ok_or_else(|| {
             CbxError::Archive(format!("Entry not found: {}", entry.name))
         })
     }
 
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        let cursor = Cursor::new(&self.data);
+        let archive = SevenZReader::new(cursor, self.data.len() as u64, Password::empty())
+            .map_err(|e| header_error(e, "Failed to read 7z from memory"))?;
+
+        entry_compression(archive.archive(), &entry.name)
+    }
+
     fn get_metadata(&self) -> Result<ArchiveMetadata> {
         let entries = self.list_entries()?;
         let total_files = entries.len();
@@ -522,6 +824,11 @@ impl Archive for SevenZipArchiveFromMemory {
             image_count,
             compressed_size: self.data.len() as u64,
             archive_type: ArchiveType::SevenZip,
+            modified: newest_entry_time(
+                Cursor::new(&self.data),
+                self.data.len() as u64,
+                Password::empty(),
+            ),
         })
     }
 
@@ -552,6 +859,7 @@ impl Archive for SevenZipArchiveFromMemory {
 pub struct SevenZipArchiveFromStream<R: Read + Seek> {
     reader: std::cell::RefCell<R>,
     size: u64,
+    password: Password,
 }
 
 impl<R: Read + Seek> SevenZipArchiveFromStream<R> {
@@ -562,8 +870,25 @@ impl<R: Read + Seek> SevenZipArchiveFromStream<R> {
     ///
     /// # Returns
     /// * `Ok(Self)` - Archive ready for processing
+    /// * `Err(CbxError::Encrypted)` - The archive header is encrypted
     /// * `Err(CbxError)` - If validation fails
-    pub fn new(mut reader: R) -> Result<Self> {
+    pub fn new(reader: R) -> Result<Self> {
+        Self::open_with_password(reader, Password::empty())
+    }
+
+    /// Create a 7z archive from a streaming reader, decrypting with `password`
+    ///
+    /// Needed for archives with an encrypted header (`7z a -mhe=on`), whose
+    /// entries can't even be listed otherwise. Decryption is done by
+    /// sevenz-rust and requires its `aes256` feature; without it, encrypted
+    /// archives report `CbxError::Encrypted` whether or not a password is
+    /// given.
+    #[allow(dead_code)] // Part of public API, may be used in future
+    pub fn with_password(reader: R, password: &str) -> Result<Self> {
+        Self::open_with_password(reader, Password::from(password))
+    }
+
+    fn open_with_password(mut reader: R, password: Password) -> Result<Self> {
         use std::io::SeekFrom;
 
         // Get size
@@ -578,9 +903,8 @@ impl<R: Read + Seek> SevenZipArchiveFromStream<R> {
             .map_err(|e| CbxError::Archive(format!("Failed to seek to start: {}", e)))?;
 
         // Validate by creating a test reader
-        let password = Password::empty();
-        let _test = SevenZReader::new(&mut reader, size, password)
-            .map_err(|e| CbxError::Archive(format!("Invalid 7z archive from stream: {}", e)))?;
+        let _test = SevenZReader::new(&mut reader, size, password.clone())
+            .map_err(|e| header_error(e, "Invalid 7z archive from stream"))?;
 
         // Seek back to start again
         reader.seek(SeekFrom::Start(0))
@@ -591,10 +915,18 @@ impl<R: Read + Seek> SevenZipArchiveFromStream<R> {
         Ok(Self {
             reader: std::cell::RefCell::new(reader),
             size,
+            password,
         })
     }
+}
 
-    /// List all entries in archive (helper method)
+impl<R: Read + Seek> Archive for SevenZipArchiveFromStream<R> {
+    fn open(_path: &Path) -> Result<Box<dyn Archive>> {
+        // Not used for stream-based archives
+        Err(CbxError::Archive("Use open_archive_from_stream instead".to_string()))
+    }
+
+    /// List all entries in archive order
     fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
         use std::io::SeekFrom;
 
@@ -604,31 +936,11 @@ impl<R: Read + Seek> SevenZipArchiveFromStream<R> {
         reader_ref.seek(SeekFrom::Start(0))
             .map_err(|e| CbxError::Archive(format!("Failed to seek to start: {}", e)))?;
 
-        let password = Password::empty();
-        let mut archive = SevenZReader::new(&mut *reader_ref, self.size, password)
-            .map_err(|e| CbxError::Archive(format!("Failed to create 7z reader: {}", e)))?;
-
-        let mut entries = Vec::new();
-
-        archive
-            .for_each_entries(|entry, _reader| {
-                entries.push(ArchiveEntry {
-                    name: entry.name().to_string(),
-                    size: entry.size(),
-                    is_directory: entry.is_directory(),
-                });
-                Ok(true) // Continue iteration
-            })
-            .map_err(|e| CbxError::Archive(format!("7z iteration error: {}", e)))?;
-
-        Ok(entries)
-    }
-}
+        let password = self.password.clone();
+        let archive = SevenZReader::new(&mut *reader_ref, self.size, password)
+            .map_err(|e| header_error(e, "Failed to create 7z reader"))?;
 
-impl<R: Read + Seek> Archive for SevenZipArchiveFromStream<R> {
-    fn open(_path: &Path) -> Result<Box<dyn Archive>> {
-        // Not used for stream-based archives
-        Err(CbxError::Archive("Use open_archive_from_stream instead".to_string()))
+        Ok(header_entries(archive.archive()))
     }
 
     fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
@@ -646,30 +958,18 @@ impl<R: Read + Seek> Archive for SevenZipArchiveFromStream<R> {
             reader_ref.seek(SeekFrom::Start(0))
                 .map_err(|e| CbxError::Archive(format!("Failed to seek to start: {}", e)))?;
 
-            let password = Password::empty();
-            let mut archive = SevenZReader::new(&mut *reader_ref, self.size, password)
-                .map_err(|e| CbxError::Archive(format!("Failed to create 7z reader: {}", e)))?;
-
-            let mut first_image: Option<ArchiveEntry> = None;
-
-            archive
-                .for_each_entries(|entry, _reader| {
-                    let name = entry.name().to_string();
-                    if is_image_file(&name) {
-                        tracing::info!("Found first image (unsorted, streaming): {}", name);
-                        crate::utils::debug_log::debug_log(&format!("Found first image: {}", name));
-
-                        first_image = Some(ArchiveEntry {
-                            name,
-                            size: entry.size(),
-                            is_directory: entry.is_directory(),
-                        });
-                        Ok(false) // Stop iteration
-                    } else {
-                        Ok(true) // Continue
-                    }
-                })
-                .map_err(|e| CbxError::Archive(format!("7z iteration error: {}", e)))?;
+            let password = self.password.clone();
+            let archive = SevenZReader::new(&mut *reader_ref, self.size, password)
+                .map_err(|e| header_error(e, "Failed to create 7z reader"))?;
+
+            let first_image = header_entries(archive.archive())
+                .into_iter()
+                .find(|entry| is_image_file(&entry.name));
+
+            if let Some(entry) = &first_image {
+                tracing::info!("Found first image (unsorted, streaming): {}", bounded_name(&entry.name));
+                crate::utils::debug_log::debug_log(&format!("Found first image: {}", bounded_name(&entry.name)));
+            }
 
             return first_image
                 .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()));
@@ -688,8 +988,8 @@ impl<R: Read + Seek> Archive for SevenZipArchiveFromStream<R> {
         let image_name = find_first_image(names.iter().map(|s| s.as_str()), sort)
             .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
 
-        tracing::info!("Found first image (sorted, streaming): {}", image_name);
-        crate::utils::debug_log::debug_log(&format!("Found first image (sorted): {}", image_name));
+        tracing::info!("Found first image (sorted, streaming): {}", bounded_name(&image_name));
+        crate::utils::debug_log::debug_log(&format!("Found first image (sorted): {}", bounded_name(&image_name)));
 
         entries
             .into_iter()
@@ -698,15 +998,16 @@ impl<R: Read + Seek> Archive for SevenZipArchiveFromStream<R> {
     }
 
     fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
-        tracing::debug!("Extracting entry from 7z stream: {} ({} bytes)", entry.name, entry.size);
-        crate::utils::debug_log::debug_log(&format!("7z stream: extract_entry: {} ({} bytes)", entry.name, entry.size));
+        tracing::debug!("Extracting entry from 7z stream: {} ({} bytes)", bounded_name(&entry.name), entry.size);
+        crate::utils::debug_log::debug_log(&format!("7z stream: extract_entry: {} ({} bytes)", bounded_name(&entry.name), entry.size));
 
         // Safety check: prevent memory exhaustion
-        if entry.size > MAX_ENTRY_SIZE {
-            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, MAX_ENTRY_SIZE);
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
             return Err(CbxError::Archive(format!(
-                "Entry too large: {} bytes (max 32MB)",
-                entry.size
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
             )));
         }
 
@@ -719,15 +1020,15 @@ impl<R: Read + Seek> Archive for SevenZipArchiveFromStream<R> {
         reader_ref.seek(SeekFrom::Start(0))
             .map_err(|e| CbxError::Archive(format!("Failed to seek to start: {}", e)))?;
 
-        let password = Password::empty();
+        let password = self.password.clone();
         let mut archive = SevenZReader::new(&mut *reader_ref, self.size, password)
-            .map_err(|e| CbxError::Archive(format!("Failed to create 7z reader: {}", e)))?;
+            .map_err(|e| header_error(e, "Failed to create 7z reader"))?;
 
         let mut extracted_data = None;
 
         archive
             .for_each_entries(|sz_entry, reader| {
-                if sz_entry.name() == entry.name {
+                if normalize_entry_name(sz_entry.name()) == entry.name {
                     let mut buffer = Vec::with_capacity(sz_entry.size() as usize);
                     std::io::copy(reader, &mut buffer)
                         .map_err(|e| sevenz_rust::Error::Io(e, "Extract failed".into()))?;
@@ -741,18 +1042,41 @@ impl<R: Read + Seek> Archive for SevenZipArchiveFromStream<R> {
                     Ok(true) // Continue
                 }
             })
-            .map_err(|e| CbxError::Archive(format!("7z extraction error: {}", e)))?;
+            .map_err(|e| data_error(e, &entry.name, "7z extraction error"))?;
 
         extracted_data.ok_or_else(|| {
             CbxError::Archive(format!("Entry not found in 7z stream: {}", entry.name))
         })
     }
 
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        use std::io::SeekFrom;
+
+        let mut reader_ref = self.reader.borrow_mut();
+        reader_ref.seek(SeekFrom::Start(0))
+            .map_err(|e| CbxError::Archive(format!("Failed to seek to start: {}", e)))?;
+
+        let archive = SevenZReader::new(&mut *reader_ref, self.size, self.password.clone())
+            .map_err(|e| header_error(e, "Failed to create 7z reader"))?;
+
+        entry_compression(archive.archive(), &entry.name)
+    }
+
     fn get_metadata(&self) -> Result<ArchiveMetadata> {
         let entries = self.list_entries()?;
         let total_files = entries.len();
         let image_count = entries.iter().filter(|e| is_image_file(&e.name)).count();
 
+        let modified = {
+            use std::io::SeekFrom;
+
+            let mut reader_ref = self.reader.borrow_mut();
+            reader_ref
+                .seek(SeekFrom::Start(0))
+                .ok()
+                .and_then(|_| newest_entry_time(&mut *reader_ref, self.size, self.password.clone()))
+        };
+
         tracing::debug!(
             "7z metadata (from stream): {} files, {} images",
             total_files,
@@ -764,6 +1088,7 @@ impl<R: Read + Seek> Archive for SevenZipArchiveFromStream<R> {
             image_count,
             compressed_size: self.size,
             archive_type: ArchiveType::SevenZip,
+            modified,
         })
     }
 
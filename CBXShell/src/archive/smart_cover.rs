@@ -0,0 +1,105 @@
+//! Colorfulness-based cover detection
+//!
+//! Some archives start with a credits or text page before the actual cover.
+//! Cover art is usually rich in color while text pages are near-monochrome,
+//! so the `SmartCover` strategy decodes the first few candidate images at a
+//! tiny size and picks the most colorful one. Decoding is the expensive
+//! part, hence the cap on the number of candidates.
+
+use image::DynamicImage;
+
+use super::utils::bounded_name;
+use super::{Archive, ArchiveEntry};
+use crate::image_processor::decoder::decode_image;
+
+/// Images sampled per archive
+pub const MAX_SAMPLED_IMAGES: usize = 4;
+
+/// Longest side of the sampled copy in pixels
+const SAMPLE_SIZE: u32 = 32;
+
+/// The most colorful of the first `MAX_SAMPLED_IMAGES` of `candidates`
+///
+/// `candidates` are image names in page order. Entries that can't be
+/// extracted or decoded are skipped; ties keep the earlier page. Returns
+/// `None` if no candidate could be sampled.
+pub fn most_colorful<A: Archive + ?Sized>(
+    archive: &A,
+    entries: &[ArchiveEntry],
+    candidates: &[&str],
+) -> Option<String> {
+    let mut best: Option<(&str, f64)> = None;
+
+    for &name in candidates.iter().take(MAX_SAMPLED_IMAGES) {
+        let Some(entry) = entries.iter().find(|e| e.name == name) else {
+            continue;
+        };
+        let image = match archive.extract_entry(entry).and_then(|data| decode_image(&data)) {
+            Ok(image) => image,
+            Err(e) => {
+                tracing::debug!("SmartCover: skipping {}: {}", bounded_name(name), e);
+                continue;
+            }
+        };
+
+        let score = colorfulness(&image.thumbnail(SAMPLE_SIZE, SAMPLE_SIZE));
+        tracing::debug!("SmartCover: {} scores {:.1}", bounded_name(name), score);
+        if best.map_or(true, |(_, best_score)| score > best_score) {
+            best = Some((name, score));
+        }
+    }
+
+    best.map(|(name, _)| name.to_string())
+}
+
+/// Colorfulness metric of Hasler and Süsstrunk (2003)
+///
+/// 0 for grayscale images, roughly 100+ for highly colorful ones. Alpha is
+/// ignored.
+fn colorfulness(image: &DynamicImage) -> f64 {
+    let rgb = image.to_rgb8();
+    let count = (rgb.width() * rgb.height()) as f64;
+    if count == 0.0 {
+        return 0.0;
+    }
+
+    let (mut sum_rg, mut sum_yb, mut sum_rg2, mut sum_yb2) = (0.0, 0.0, 0.0, 0.0);
+    for pixel in rgb.pixels() {
+        let [r, g, b] = pixel.0.map(f64::from);
+        let rg = r - g;
+        let yb = 0.5 * (r + g) - b;
+        sum_rg += rg;
+        sum_yb += yb;
+        sum_rg2 += rg * rg;
+        sum_yb2 += yb * yb;
+    }
+
+    let (mean_rg, mean_yb) = (sum_rg / count, sum_yb / count);
+    let var_rg = (sum_rg2 / count - mean_rg * mean_rg).max(0.0);
+    let var_yb = (sum_yb2 / count - mean_yb * mean_yb).max(0.0);
+
+    (var_rg + var_yb).sqrt() + 0.3 * (mean_rg * mean_rg + mean_yb * mean_yb).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    #[test]
+    fn test_colorfulness_grayscale_is_zero() {
+        let page = RgbImage::from_fn(16, 16, |x, _| if x % 2 == 0 { Rgb([0, 0, 0]) } else { Rgb([255, 255, 255]) });
+        assert!(colorfulness(&DynamicImage::ImageRgb8(page)) < 1e-9);
+    }
+
+    #[test]
+    fn test_colorfulness_orders_by_saturation() {
+        let muted = RgbImage::from_fn(16, 16, |x, _| if x < 8 { Rgb([140, 120, 120]) } else { Rgb([120, 120, 140]) });
+        let vivid = RgbImage::from_fn(16, 16, |x, _| if x < 8 { Rgb([255, 0, 0]) } else { Rgb([0, 0, 255]) });
+
+        let muted = colorfulness(&DynamicImage::ImageRgb8(muted));
+        let vivid = colorfulness(&DynamicImage::ImageRgb8(vivid));
+        assert!(muted > 0.0);
+        assert!(vivid > muted * 5.0, "vivid {} vs muted {}", vivid, muted);
+    }
+}
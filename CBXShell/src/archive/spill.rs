@@ -0,0 +1,149 @@
+//! Temp-file fallback for streams that can't seek
+//!
+//! Every backend opened by `open_archive_from_stream` needs `Seek`, but some
+//! IStream implementations (network shares, shell namespace extensions)
+//! fail `IStream::Seek`. Such a stream is copied once into a temp file, the
+//! same way the RAR backend already does, and the archive is opened from
+//! that file instead.
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::utils::error::{CbxError, Result};
+
+/// Distinguishes spill files created by the same thread in the same millisecond
+static NEXT_SPILL: AtomicU32 = AtomicU32::new(0);
+
+/// A temp file holding a spilled stream, removed when dropped
+pub(crate) struct SpillFile {
+    file: Option<File>,
+    path: PathBuf,
+}
+
+impl SpillFile {
+    /// Copy `reader` from its current position to EOF into a new temp file
+    ///
+    /// The returned file is positioned at its start. A stream longer than
+    /// `max_size` bytes fails with `CbxError::Archive` (and its partial copy
+    /// is removed) rather than filling the disk.
+    pub(crate) fn from_reader<R: Read>(mut reader: R, max_size: u64) -> Result<Self> {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let sequence = NEXT_SPILL.fetch_add(1, Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "cbxshell_spill_{}_{:?}_{}_{}.tmp",
+            std::process::id(),
+            std::thread::current().id(),
+            timestamp,
+            sequence
+        ));
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .map_err(|e| CbxError::Archive(format!("Failed to create spill file: {}", e)))?;
+
+        // From here on the file is cleaned up by Drop, also on error
+        let mut spill = Self { file: Some(file), path };
+        let file = spill.file.as_mut().unwrap();
+
+        let mut total_written = 0u64;
+        let mut buffer = vec![0u8; 1024 * 1024]; // 1MB chunks
+        loop {
+            let bytes_read = reader
+                .read(&mut buffer)
+                .map_err(|e| CbxError::Archive(format!("Failed to read from stream: {}", e)))?;
+            if bytes_read == 0 {
+                break;
+            }
+            if total_written + bytes_read as u64 > max_size {
+                return Err(CbxError::Archive(format!(
+                    "Stream is larger than {} bytes, not spilling it",
+                    max_size
+                )));
+            }
+            file.write_all(&buffer[..bytes_read])
+                .map_err(|e| CbxError::Archive(format!("Failed to write spill file: {}", e)))?;
+            total_written += bytes_read as u64;
+        }
+
+        file.seek(SeekFrom::Start(0))
+            .map_err(|e| CbxError::Archive(format!("Failed to rewind spill file: {}", e)))?;
+
+        tracing::debug!("Spilled {} bytes to {:?}", total_written, spill.path);
+        crate::utils::debug_log::debug_log(&format!(
+            "Spilled {} bytes to {:?}",
+            total_written, spill.path
+        ));
+
+        Ok(spill)
+    }
+
+    fn file(&mut self) -> &mut File {
+        self.file.as_mut().expect("spill file is open until dropped")
+    }
+}
+
+impl Read for SpillFile {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.file().read(buf)
+    }
+}
+
+impl Seek for SpillFile {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.file().seek(pos)
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        // Close the handle first; Windows can't delete an open file
+        drop(self.file.take());
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!("Failed to remove spill file {:?}: {}", self.path, e);
+        } else {
+            tracing::debug!("Cleaned up spill file: {:?}", self.path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_spill_file_round_trip_and_cleanup() {
+        let data: Vec<u8> = (0..3 * 1024 * 1024 + 17).map(|i| (i % 251) as u8).collect();
+
+        let mut spill = SpillFile::from_reader(Cursor::new(data.clone()), data.len() as u64).unwrap();
+        let path = spill.path.clone();
+        assert!(path.exists());
+
+        let mut read_back = Vec::new();
+        spill.read_to_end(&mut read_back).unwrap();
+        assert_eq!(read_back, data);
+
+        spill.seek(SeekFrom::Start(5)).unwrap();
+        let mut byte = [0u8; 1];
+        spill.read_exact(&mut byte).unwrap();
+        assert_eq!(byte[0], data[5]);
+
+        drop(spill);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_spill_file_size_limit() {
+        let data = vec![0u8; 3 * 1024 * 1024];
+        let result = SpillFile::from_reader(Cursor::new(data), 2 * 1024 * 1024);
+        assert!(matches!(result, Err(CbxError::Archive(msg)) if msg.contains("larger than")));
+    }
+}
@@ -6,12 +6,19 @@
 use windows::Win32::System::Com::*;
 use crate::utils::error::{CbxError, Result};
 use crate::archive::ArchiveType;
+use crate::utils::detection::Detection;
 use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 /// Maximum buffer size for reading from IStream (10GB)
 /// We only extract the first image (max 32MB), so archive size doesn't matter much
 /// Limit set to 10GB to support very large comic archives
-const MAX_STREAM_SIZE: usize = 10 * 1024 * 1024 * 1024;
+///
+/// A `u64`, since the limit doesn't fit in a 32-bit `usize`; in a 32-bit
+/// process `stream_buffer_size` caps buffers at the address space instead.
+/// Thumbnails open archives through `open_archive_from_stream`, which never
+/// buffers the whole stream.
+pub(crate) const MAX_STREAM_SIZE: u64 = 10 * 1024 * 1024 * 1024;
 
 /// Read entire IStream contents into memory
 ///
@@ -57,19 +64,10 @@ pub fn read_stream_to_memory(stream: &IStream) -> Result<Vec<u8>> {
             return Err(CbxError::Archive("Failed to seek to end of stream".to_string()));
         }
 
-        let stream_size = new_position as usize;
-        crate::utils::debug_log::debug_log(&format!("Stream size: {} bytes", stream_size));
+        crate::utils::debug_log::debug_log(&format!("Stream size: {} bytes", new_position));
 
-        // Step 2: Validate size
-        if stream_size == 0 {
-            crate::utils::debug_log::debug_log("ERROR: Stream is empty");
-            return Err(CbxError::Archive("Empty stream".to_string()));
-        }
-
-        if stream_size > MAX_STREAM_SIZE {
-            crate::utils::debug_log::debug_log(&format!("ERROR: Stream too large: {} bytes (max: {})", stream_size, MAX_STREAM_SIZE));
-            return Err(CbxError::Archive(format!("Stream too large: {} bytes", stream_size)));
-        }
+        // Step 2: Validate size (before it is narrowed to `usize`)
+        let stream_size: usize = stream_buffer_size(new_position, MAX_STREAM_SIZE)?;
 
         // Step 3: Seek back to beginning
         if stream.Seek(
@@ -83,38 +81,123 @@ pub fn read_stream_to_memory(stream: &IStream) -> Result<Vec<u8>> {
 
         crate::utils::debug_log::debug_log("Seek to beginning successful");
 
-        // Step 4: Allocate buffer
-        let mut buffer = vec![0u8; stream_size];
-        crate::utils::debug_log::debug_log(&format!("Allocated buffer: {} bytes", buffer.len()));
-
-        // Step 5: Read all data
-        let mut total_read = 0usize;
-        while total_read < stream_size {
+        // Step 4: Read all data (the buffer is sized from the reported size)
+        let max_size = usize::try_from(MAX_STREAM_SIZE).unwrap_or(usize::MAX);
+        let buffer = read_reported_size(stream_size, max_size, |chunk| {
             let mut bytes_read = 0u32;
-            let remaining = stream_size - total_read;
-            let to_read = remaining.min(1024 * 1024); // Read in 1MB chunks
-
             if stream.Read(
-                buffer[total_read..].as_mut_ptr() as *mut _,
-                to_read as u32,
+                chunk.as_mut_ptr() as *mut _,
+                chunk.len() as u32,
                 Some(&mut bytes_read)
             ).is_err() {
                 crate::utils::debug_log::debug_log("ERROR: Failed to read from stream");
                 return Err(CbxError::Archive("Failed to read from stream".to_string()));
             }
+            Ok(bytes_read as usize)
+        })?;
 
-            if bytes_read == 0 {
-                crate::utils::debug_log::debug_log(&format!("ERROR: Unexpected EOF at {} bytes (expected {})", total_read, stream_size));
-                return Err(CbxError::Archive("Unexpected end of stream".to_string()));
-            }
+        crate::utils::debug_log::debug_log(&format!("SUCCESS: Read {} bytes from stream", buffer.len()));
+        Ok(buffer)
+    }
+}
+
+/// Buffer size for a stream reporting `reported_size` bytes
+///
+/// Fails for an empty stream, one larger than `max_size`, and one that
+/// doesn't fit in `T` (`usize` of a 32-bit process for a stream over 4GB),
+/// so a size is never truncated by a cast.
+#[allow(dead_code)] // Used by read_stream_to_memory
+fn stream_buffer_size<T: TryFrom<u64>>(reported_size: u64, max_size: u64) -> Result<T> {
+    if reported_size == 0 {
+        crate::utils::debug_log::debug_log("ERROR: Stream is empty");
+        return Err(CbxError::Archive("Empty stream".to_string()));
+    }
+
+    if reported_size > max_size {
+        crate::utils::debug_log::debug_log(&format!("ERROR: Stream too large: {} bytes (max: {})", reported_size, max_size));
+        return Err(CbxError::Archive(format!("Stream too large: {} bytes", reported_size)));
+    }
+
+    T::try_from(reported_size).map_err(|_| {
+        crate::utils::debug_log::debug_log(&format!("ERROR: Stream of {} bytes exceeds the address space", reported_size));
+        CbxError::Archive(format!(
+            "Stream too large to buffer in this process: {} bytes (open it as a stream instead)",
+            reported_size
+        ))
+    })
+}
+
+/// Chunk size for reading a stream into memory
+#[allow(dead_code)] // Used by read_stream_to_memory
+const READ_CHUNK: usize = 1024 * 1024;
+
+/// How much the buffer grows when a stream has more data than it reported
+#[allow(dead_code)] // Used by read_stream_to_memory
+const OVERRUN_CHUNK: usize = 64 * 1024;
 
-            total_read += bytes_read as usize;
-            crate::utils::debug_log::debug_log(&format!("Read progress: {}/{} bytes", total_read, stream_size));
+/// Read a stream that reported `reported_size` bytes, until EOF
+///
+/// `read` fills as much of the given chunk as it can and returns the byte
+/// count, 0 at EOF. Faulty streams are handled without ever indexing past
+/// the buffer:
+/// - A stream returning data beyond its reported size: the buffer grows, up
+///   to `max_size` (more than that is an error).
+/// - A read claiming more bytes than requested: only the requested bytes
+///   are counted.
+/// - EOF before the reported size: an error, as the data is truncated.
+#[allow(dead_code)] // Used by read_stream_to_memory
+fn read_reported_size(
+    reported_size: usize,
+    max_size: usize,
+    mut read: impl FnMut(&mut [u8]) -> Result<usize>,
+) -> Result<Vec<u8>> {
+    let mut buffer = vec![0u8; reported_size];
+    crate::utils::debug_log::debug_log(&format!("Allocated buffer: {} bytes", buffer.len()));
+
+    let mut total_read = 0usize;
+    loop {
+        if total_read == buffer.len() {
+            // Check for data past the end; at `max_size` one byte tells
+            let grow = if total_read < max_size {
+                OVERRUN_CHUNK.min(max_size - total_read)
+            } else {
+                1
+            };
+            buffer.resize(total_read + grow, 0);
         }
 
-        crate::utils::debug_log::debug_log(&format!("SUCCESS: Read {} bytes from stream", total_read));
-        Ok(buffer)
+        let end = buffer.len().min(total_read + READ_CHUNK);
+        let requested = end - total_read;
+        let bytes_read = read(&mut buffer[total_read..end])?;
+        if bytes_read == 0 {
+            break;
+        }
+        if bytes_read > requested {
+            tracing::warn!("Stream claimed {} bytes for a {} byte read", bytes_read, requested);
+        }
+
+        total_read += bytes_read.min(requested);
+        if total_read > max_size {
+            crate::utils::debug_log::debug_log(&format!("ERROR: Stream exceeds {} bytes", max_size));
+            return Err(CbxError::Archive(format!("Stream too large: more than {} bytes", max_size)));
+        }
+        crate::utils::debug_log::debug_log(&format!("Read progress: {}/{} bytes", total_read, reported_size));
+    }
+
+    if total_read < reported_size {
+        crate::utils::debug_log::debug_log(&format!("ERROR: Unexpected EOF at {} bytes (expected {})", total_read, reported_size));
+        return Err(CbxError::Archive("Unexpected end of stream".to_string()));
+    }
+    if total_read > reported_size {
+        tracing::warn!("Stream reported {} bytes but returned {}", reported_size, total_read);
+        crate::utils::debug_log::debug_log(&format!(
+            "WARNING: Stream reported {} bytes but returned {}",
+            reported_size, total_read
+        ));
     }
+
+    buffer.truncate(total_read);
+    Ok(buffer)
 }
 
 /// IStream adapter that implements Read and Seek traits
@@ -170,6 +253,81 @@ impl IStreamReader {
     }
 }
 
+/// Name of the file behind an IStream, as reported by `IStream::Stat`
+///
+/// Explorer's file streams report the file name (without directory), which is
+/// enough to recover the extension. Returns `None` for anonymous streams.
+pub fn stream_file_name(stream: &IStream) -> Option<String> {
+    // UNAVOIDABLE UNSAFE: IStream::Stat fills a STATSTG whose name string is
+    // allocated by the stream and must be released with CoTaskMemFree
+    unsafe {
+        let mut stat = STATSTG::default();
+        stream.Stat(&mut stat, STATFLAG_DEFAULT).ok()?;
+
+        if stat.pwcsName.is_null() {
+            return None;
+        }
+
+        let name = stat.pwcsName.to_string().ok();
+        CoTaskMemFree(Some(stat.pwcsName.as_ptr() as *const _));
+        name
+    }
+}
+
+/// Extension of a file name including the leading dot (e.g. ".cbz")
+pub fn file_extension(name: &str) -> Option<String> {
+    std::path::Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| format!(".{}", ext.to_ascii_lowercase()))
+}
+
+/// Archives whose extension named a different format than their magic bytes
+static TYPE_MISMATCHES: AtomicU64 = AtomicU64::new(0);
+
+/// Log and count an archive whose extension disagrees with its detected type
+///
+/// Streams are opened by magic bytes, so a `.cbz` that is really a RAR still
+/// works, just with RAR's costs (e.g. the temp file). The distinct log line
+/// explains such surprises. Unknown extensions are never a mismatch.
+///
+/// # Arguments
+/// * `extension` - File extension with the leading dot, see `file_extension`
+/// * `detected` - Type detected from the archive's magic bytes
+///
+/// # Returns
+/// `true` if the extension names a different archive type
+pub fn note_type_mismatch(extension: Option<&str>, detected: ArchiveType) -> bool {
+    let Some(extension) = extension else {
+        return false;
+    };
+    let expected = match ArchiveType::from_extension(extension.trim_start_matches('.')) {
+        Some(expected) if expected != detected => expected,
+        _ => return false,
+    };
+
+    let count = TYPE_MISMATCHES.fetch_add(1, Ordering::Relaxed) + 1;
+    tracing::warn!(
+        "TYPE MISMATCH: {} file is actually {} (mismatch #{})",
+        extension,
+        detected.as_str(),
+        count
+    );
+    crate::utils::debug_log::debug_log(&format!(
+        "!!!!! TYPE MISMATCH: extension {} implies {} but magic bytes say {} (mismatch #{}) !!!!!",
+        extension,
+        expected.as_str(),
+        detected.as_str(),
+        count
+    ));
+    true
+}
+
+/// Number of type mismatches noted by `note_type_mismatch` in this process
+pub fn type_mismatch_count() -> u64 {
+    TYPE_MISMATCHES.load(Ordering::Relaxed)
+}
+
 impl Read for IStreamReader {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
         if buf.is_empty() {
@@ -256,11 +414,6 @@ impl Seek for IStreamReader {
 pub fn detect_archive_type_from_bytes(data: &[u8]) -> Result<ArchiveType> {
     crate::utils::debug_log::debug_log(">>>>> detect_archive_type_from_bytes STARTING <<<<<");
 
-    if data.len() < 8 {
-        crate::utils::debug_log::debug_log(&format!("ERROR: Data too short: {} bytes", data.len()));
-        return Err(CbxError::UnsupportedFormat("Data too short".to_string()));
-    }
-
     // Log first 16 bytes as hex for debugging
     let preview_len = data.len().min(16);
     let hex_preview: Vec<String> = data[..preview_len]
@@ -269,49 +422,97 @@ pub fn detect_archive_type_from_bytes(data: &[u8]) -> Result<ArchiveType> {
         .collect();
     crate::utils::debug_log::debug_log(&format!("First {} bytes: {}", preview_len, hex_preview.join(" ")));
 
-    // Check ZIP magic bytes
-    if data.len() >= 4 {
-        let magic = &data[0..4];
-        if magic == b"PK\x03\x04" || magic == b"PK\x05\x06" || magic == b"PK\x07\x08" {
-            crate::utils::debug_log::debug_log("Detected: ZIP format");
-            return Ok(ArchiveType::Zip);
+    match detect_archive_type_from_bytes_detailed(data) {
+        Ok(detection) => {
+            crate::utils::debug_log::debug_log(&format!("Detected: {}", detection));
+            Ok(detection.kind)
         }
-    }
-
-    // Check 7z magic bytes
-    if data.len() >= 6 {
-        let magic = &data[0..6];
-        if magic == b"7z\xBC\xAF\x27\x1C" {
-            crate::utils::debug_log::debug_log("Detected: 7-Zip format");
-            return Ok(ArchiveType::SevenZip);
+        Err(e) => {
+            crate::utils::debug_log::debug_log(&format!("ERROR: {}", e));
+            Err(e)
         }
     }
+}
 
-    // Check RAR magic bytes (RAR 4.x and 5.x)
-    if data.len() >= 7 {
-        let magic = &data[0..7];
-        if magic == b"Rar!\x1A\x07\x00" {
-            crate::utils::debug_log::debug_log("Detected: RAR 4.x format");
-            return Ok(ArchiveType::Rar);
-        }
-    }
+/// Archive signatures, checked in order
+const ARCHIVE_SIGNATURES: &[(ArchiveType, &[u8])] = &[
+    (ArchiveType::Zip, b"PK\x03\x04"),
+    (ArchiveType::Zip, b"PK\x05\x06"),
+    (ArchiveType::Zip, b"PK\x07\x08"),
+    (ArchiveType::SevenZip, b"7z\xBC\xAF\x27\x1C"),
+    (ArchiveType::Rar, b"Rar!\x1A\x07\x00"),
+    (ArchiveType::Rar, b"Rar!\x1A\x07\x01\x00"),
+];
+
+/// `magic` field of a POSIX ustar (and GNU tar) header
+///
+/// Tar has no signature at offset 0; the first header's `magic` field is
+/// the only marker. Pre-POSIX (v7) tars lack it and aren't detected.
+const TAR_SIGNATURE: &[u8] = b"ustar";
 
-    if data.len() >= 8 {
-        let magic = &data[0..8];
-        if magic == b"Rar!\x1A\x07\x01\x00" {
-            crate::utils::debug_log::debug_log("Detected: RAR 5.x format");
-            return Ok(ArchiveType::Rar);
-        }
+/// Offset of the `magic` field in a tar header
+const TAR_SIGNATURE_OFFSET: usize = 257;
+
+/// Detect archive type from magic bytes, reporting the matched signature
+///
+/// Same detection as `detect_archive_type_from_bytes`, without its debug
+/// logging; the returned `Detection` says which signature matched and where.
+pub fn detect_archive_type_from_bytes_detailed(data: &[u8]) -> Result<Detection<ArchiveType>> {
+    if data.len() < 8 {
+        return Err(CbxError::UnsupportedFormat(format!(
+            "Data too short: {} bytes",
+            data.len()
+        )));
     }
 
-    crate::utils::debug_log::debug_log("ERROR: Unrecognized archive format");
-    Err(CbxError::UnsupportedFormat("Unrecognized archive format".to_string()))
+    ARCHIVE_SIGNATURES
+        .iter()
+        .find(|(_, signature)| data.starts_with(signature))
+        .map(|&(kind, signature)| Detection {
+            kind,
+            matched_signature: signature,
+            offset: 0,
+        })
+        .or_else(|| {
+            let end = TAR_SIGNATURE_OFFSET + TAR_SIGNATURE.len();
+            (data.get(TAR_SIGNATURE_OFFSET..end) == Some(TAR_SIGNATURE)).then_some(Detection {
+                kind: ArchiveType::Tar,
+                matched_signature: TAR_SIGNATURE,
+                offset: TAR_SIGNATURE_OFFSET,
+            })
+        })
+        .ok_or_else(|| CbxError::UnsupportedFormat("Unrecognized archive format".to_string()))
+}
+
+/// Detect archive type from magic bytes, falling back to the extension for
+/// data too short to rule out TAR
+///
+/// A TAR is only recognizable once its `ustar` marker (offset 257) has been
+/// read; for a shorter prefix the extension (e.g. `.cbt`) decides.
+///
+/// # Arguments
+/// * `data` - Leading bytes of the archive
+/// * `extension` - File extension with the leading dot, see `file_extension`
+pub fn detect_archive_type_or_extension(data: &[u8], extension: Option<&str>) -> Result<ArchiveType> {
+    match detect_archive_type_from_bytes(data) {
+        Err(e) if data.len() < TAR_SIGNATURE_OFFSET + TAR_SIGNATURE.len() => extension
+            .and_then(|ext| ArchiveType::from_extension(ext.trim_start_matches('.')))
+            .ok_or(e),
+        detected => detected,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_file_extension() {
+        assert_eq!(file_extension("Book.EPUB").as_deref(), Some(".epub"));
+        assert_eq!(file_extension("vol.01.cbz").as_deref(), Some(".cbz"));
+        assert_eq!(file_extension("README"), None);
+    }
+
     #[test]
     fn test_detect_zip_format() {
         // ZIP local file header signature
@@ -359,15 +560,257 @@ mod tests {
         );
     }
 
+    /// First block of a tar archive: an entry name, then the `magic` field
+    fn tar_block(magic: &[u8]) -> Vec<u8> {
+        let mut block = vec![0u8; 512];
+        block[..9].copy_from_slice(b"page1.jpg");
+        block[257..257 + magic.len()].copy_from_slice(magic);
+        block
+    }
+
+    #[test]
+    fn test_detect_tar_format() {
+        // POSIX ustar and GNU tar magic
+        for magic in [&b"ustar\x0000"[..], b"ustar  \x00"] {
+            let detection = detect_archive_type_from_bytes_detailed(&tar_block(magic)).unwrap();
+            assert_eq!(detection.kind, ArchiveType::Tar);
+            assert_eq!(detection.matched_signature, b"ustar");
+            assert_eq!(detection.offset, 257);
+        }
+
+        // The marker must be exactly at offset 257
+        let mut shifted = vec![0u8; 512];
+        shifted[256..261].copy_from_slice(b"ustar");
+        assert!(detect_archive_type_from_bytes(&shifted).is_err());
+        assert!(detect_archive_type_from_bytes(&tar_block(b"xstar")).is_err());
+    }
+
+    #[test]
+    fn test_detect_tar_needs_full_marker() {
+        let block = tar_block(b"ustar\x0000");
+        assert_eq!(detect_archive_type_from_bytes(&block[..262]).unwrap(), ArchiveType::Tar);
+        assert!(detect_archive_type_from_bytes(&block[..261]).is_err());
+    }
+
+    #[test]
+    fn test_detect_falls_back_to_extension_when_too_short() {
+        let block = tar_block(b"ustar\x0000");
+
+        // Too short to see the marker: the extension decides
+        assert_eq!(detect_archive_type_or_extension(&block[..16], Some(".cbt")).unwrap(), ArchiveType::Tar);
+        assert!(detect_archive_type_or_extension(&block[..16], None).is_err());
+        assert!(detect_archive_type_or_extension(&block[..16], Some(".txt")).is_err());
+
+        // Magic bytes win whenever they are conclusive
+        assert_eq!(detect_archive_type_or_extension(&block, Some(".cbz")).unwrap(), ArchiveType::Tar);
+        assert_eq!(detect_archive_type_or_extension(b"PK\x03\x04\x14\x00\x00\x00", Some(".cbt")).unwrap(), ArchiveType::Zip);
+        assert!(detect_archive_type_or_extension(&vec![0u8; 512], Some(".cbt")).is_err());
+    }
+
     #[test]
     fn test_detect_unknown_format() {
         let unknown_data = b"UNKNOWN\x00\x00\x00\x00";
         assert!(detect_archive_type_from_bytes(unknown_data).is_err());
     }
 
+    #[test]
+    fn test_detect_archive_type_detailed() {
+        let detection = detect_archive_type_from_bytes_detailed(b"PK\x07\x08\x00\x00\x00\x00").unwrap();
+        assert_eq!(detection.kind, ArchiveType::Zip);
+        assert_eq!(detection.matched_signature, b"PK\x07\x08");
+        assert_eq!(detection.offset, 0);
+
+        let detection = detect_archive_type_from_bytes_detailed(b"Rar!\x1A\x07\x01\x00\x33").unwrap();
+        assert_eq!(detection.kind, ArchiveType::Rar);
+        assert_eq!(detection.matched_signature, b"Rar!\x1A\x07\x01\x00");
+
+        let detection = detect_archive_type_from_bytes_detailed(b"7z\xBC\xAF\x27\x1C\x00\x04").unwrap();
+        assert_eq!(detection.to_string(), "SevenZip (signature 37 7A BC AF 27 1C at offset 0)");
+
+        assert!(detect_archive_type_from_bytes_detailed(b"Rar!\x1A\x07\x02\x00").is_err());
+        assert!(detect_archive_type_from_bytes_detailed(b"PK\x03\x04").is_err());
+    }
+
     #[test]
     fn test_detect_data_too_short() {
         let short_data = b"PK";
         assert!(detect_archive_type_from_bytes(short_data).is_err());
     }
+
+    #[test]
+    fn test_note_type_mismatch_counts_mislabeled_archive() {
+        use crate::archive::{open_archive_from_stream, ArchiveSession};
+        use std::io::{Cursor, Write};
+
+        let mut zip = ::zip::ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("page01.jpg", ::zip::write::FileOptions::default()).unwrap();
+        zip.write_all(b"\xFF\xD8\xFF image").unwrap();
+        let data = zip.finish().unwrap().into_inner();
+
+        // A ZIP saved as .cbr
+        let archive = ArchiveSession::new(open_archive_from_stream(Cursor::new(data)).unwrap());
+        assert_eq!(archive.archive_type(), ArchiveType::Zip);
+
+        let before = type_mismatch_count();
+        assert!(note_type_mismatch(file_extension("comic.cbr").as_deref(), archive.archive_type()));
+        assert_eq!(type_mismatch_count(), before + 1);
+
+        // Matching or unknown extensions aren't counted
+        assert!(!note_type_mismatch(Some(".CBZ"), ArchiveType::Zip));
+        assert!(!note_type_mismatch(Some(".epub"), ArchiveType::Zip));
+        assert!(!note_type_mismatch(Some(".bin"), ArchiveType::Rar));
+        assert!(!note_type_mismatch(None, ArchiveType::Rar));
+        assert_eq!(type_mismatch_count(), before + 1);
+    }
+
+    /// `read` callback over `data` returning at most `chunk_len` bytes per call
+    fn chunked_reader(data: Vec<u8>, chunk_len: usize) -> impl FnMut(&mut [u8]) -> Result<usize> {
+        let mut cursor = std::io::Cursor::new(data);
+        move |buf: &mut [u8]| {
+            let len = buf.len().min(chunk_len);
+            Ok(cursor.read(&mut buf[..len])?)
+        }
+    }
+
+    #[test]
+    fn test_read_reported_size_exact() {
+        let data: Vec<u8> = (0..100u8).collect();
+        assert_eq!(read_reported_size(100, 1000, chunked_reader(data.clone(), 7)).unwrap(), data);
+    }
+
+    #[test]
+    fn test_read_reported_size_stream_larger_than_reported() {
+        // Reports 10 bytes but holds 200_000: everything is read, nothing is lost
+        let data: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        let read = read_reported_size(10, 1_000_000, chunked_reader(data.clone(), 3000)).unwrap();
+        assert_eq!(read, data);
+
+        // ...but never beyond the size limit
+        let result = read_reported_size(10, 1000, chunked_reader(data, 3000));
+        assert!(matches!(result, Err(CbxError::Archive(msg)) if msg.contains("too large")));
+
+        // Exactly at the limit is fine
+        let data = vec![7u8; 1000];
+        assert_eq!(read_reported_size(10, 1000, chunked_reader(data.clone(), 64)).unwrap(), data);
+    }
+
+    #[test]
+    fn test_read_reported_size_overclaiming_and_short_streams() {
+        // Claims more bytes than requested: counted as the requested amount
+        let mut calls = 0;
+        let overclaiming = |buf: &mut [u8]| {
+            calls += 1;
+            buf.fill(1);
+            Ok(if calls <= 2 { buf.len() + 1000 } else { 0 })
+        };
+        let read = read_reported_size(16, 1_000_000, overclaiming).unwrap();
+        assert_eq!(read.len(), 16 + OVERRUN_CHUNK);
+
+        // Ends before the reported size
+        let result = read_reported_size(50, 1000, chunked_reader(vec![0u8; 20], 8));
+        assert!(matches!(result, Err(CbxError::Archive(msg)) if msg.contains("Unexpected end")));
+    }
+
+    fn pattern(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_read_stream_to_memory_mock() {
+        use crate::archive::mock_stream::MemoryStream;
+
+        // Larger than one READ_CHUNK, so it takes several reads
+        let data = pattern(READ_CHUNK * 2 + 123);
+        let stream = MemoryStream::create(data.clone());
+        assert_eq!(read_stream_to_memory(&stream).unwrap(), data);
+
+        // The stream is rewound first, wherever it was left
+        unsafe { stream.Seek(10, STREAM_SEEK_SET, None).unwrap() };
+        assert_eq!(read_stream_to_memory(&stream).unwrap(), data);
+
+        let empty = MemoryStream::create(Vec::new());
+        assert!(matches!(read_stream_to_memory(&empty), Err(CbxError::Archive(msg)) if msg == "Empty stream"));
+    }
+
+    #[test]
+    fn test_stream_buffer_size() {
+        const GB: u64 = 1024 * 1024 * 1024;
+
+        assert_eq!(stream_buffer_size::<usize>(1234, MAX_STREAM_SIZE).unwrap(), 1234);
+        assert_eq!(stream_buffer_size::<u32>(u32::MAX as u64, MAX_STREAM_SIZE).unwrap(), u32::MAX);
+        assert!(matches!(stream_buffer_size::<usize>(0, MAX_STREAM_SIZE), Err(CbxError::Archive(msg)) if msg == "Empty stream"));
+        assert!(stream_buffer_size::<u64>(MAX_STREAM_SIZE + 1, MAX_STREAM_SIZE).is_err());
+
+        // A 5GB stream on a 32-bit `usize`: an error, not a 1GB buffer
+        let result = stream_buffer_size::<u32>(5 * GB, MAX_STREAM_SIZE);
+        assert!(
+            matches!(&result, Err(CbxError::Archive(msg)) if msg.contains("5368709120 bytes")),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_istream_reader_read_and_seek() {
+        use crate::archive::mock_stream::MemoryStream;
+
+        let data = pattern(1000);
+        let mut reader = IStreamReader::new(MemoryStream::create(data.clone()));
+
+        let mut buf = [0u8; 10];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[..10]);
+        assert_eq!(reader.position(), 10);
+
+        assert_eq!(reader.seek(SeekFrom::Current(5)).unwrap(), 15);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[15..25]);
+
+        assert_eq!(reader.seek(SeekFrom::End(-4)).unwrap(), 996);
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, data[996..]);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+
+        // `size` leaves the position alone
+        assert_eq!(reader.seek(SeekFrom::Start(100)).unwrap(), 100);
+        assert_eq!(reader.size().unwrap(), 1000);
+        assert_eq!(reader.position(), 100);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, data[100..110]);
+
+        assert!(reader.seek(SeekFrom::Current(-1000)).is_err());
+    }
+
+    #[test]
+    fn test_stream_file_name_from_mock() {
+        use crate::archive::mock_stream::MemoryStream;
+
+        let named = MemoryStream::create_named(vec![0u8; 4], "Vol. 01.cbz");
+        assert_eq!(stream_file_name(&named).as_deref(), Some("Vol. 01.cbz"));
+        assert_eq!(stream_file_name(&MemoryStream::create(vec![0u8; 4])), None);
+    }
+
+    #[test]
+    fn test_open_archive_from_mock_stream() {
+        use crate::archive::mock_stream::MemoryStream;
+        use crate::archive::open_archive_from_stream;
+        use std::io::Write;
+
+        let mut zip = ::zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for name in ["page02.jpg", "page01.jpg"] {
+            zip.start_file(name, ::zip::write::FileOptions::default()).unwrap();
+            zip.write_all(b"\xFF\xD8\xFF image").unwrap();
+        }
+        let data = zip.finish().unwrap().into_inner();
+
+        let stream = MemoryStream::create_named(data, "comic.cbr");
+        let bytes = read_stream_to_memory(&stream).unwrap();
+        let detected = detect_archive_type_or_extension(&bytes, Some(".cbr")).unwrap();
+        assert_eq!(detected, ArchiveType::Zip);
+
+        let archive = open_archive_from_stream(IStreamReader::new(stream)).unwrap();
+        assert_eq!(archive.archive_type(), ArchiveType::Zip);
+        assert_eq!(archive.find_first_image(true).unwrap().name, "page01.jpg");
+    }
 }
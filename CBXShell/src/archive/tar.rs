@@ -0,0 +1,529 @@
+//! TAR/CBT archive implementation
+//!
+//! Tar has no compression and no central directory: the archive is a chain
+//! of 512-byte headers, each followed by the entry data padded to a whole
+//! block. Opening walks the headers once (seeking over the data) to build an
+//! index; extracting seeks straight to an entry's data.
+//!
+//! Understood header formats: POSIX ustar (with the `prefix` field), GNU long
+//! names (`L` entries) and the `path` record of PAX extended headers. Links,
+//! devices and other special entries are skipped.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::archive::{Archive, ArchiveEntry, ArchiveMetadata, ArchiveType};
+use crate::utils::error::{CbxError, Result};
+use super::config::read_max_entry_size;
+use super::utils::{bounded_name, find_first_image, is_image_file, normalize_entry_name};
+
+/// Size of a tar header and of the blocks entry data is padded to
+const BLOCK_LEN: u64 = 512;
+
+/// Largest GNU long name or PAX header read (they are a few hundred bytes)
+const MAX_META_ENTRY_SIZE: u64 = 1024 * 1024;
+
+/// An indexed entry: where its data starts and when it was modified
+#[derive(Debug, Clone)]
+struct TarEntry {
+    entry: ArchiveEntry,
+    data_offset: u64,
+    mtime: u64,
+}
+
+/// Parse a numeric header field
+///
+/// Numbers are octal text padded with NULs or spaces, except that GNU tar
+/// writes values too large for the field in base-256 (high bit of the first
+/// byte set).
+fn parse_number(field: &[u8]) -> Option<u64> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        return field[1..]
+            .iter()
+            .try_fold(u64::from(field[0] & 0x7F), |n, &b| n.checked_mul(256)?.checked_add(b as u64));
+    }
+
+    let digits = field.split(|&b| b == 0).next().unwrap_or_default();
+    let text = std::str::from_utf8(digits).ok()?.trim();
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+/// Whether the header checksum matches (unsigned sum, or signed as some old tars wrote it)
+fn checksum_matches(header: &[u8; BLOCK_LEN as usize]) -> bool {
+    let Some(stored) = parse_number(&header[148..156]) else {
+        return false;
+    };
+
+    let (mut unsigned, mut signed) = (0u64, 0i64);
+    for (i, &b) in header.iter().enumerate() {
+        let b = if (148..156).contains(&i) { b' ' } else { b };
+        unsigned += b as u64;
+        signed += b as i8 as i64;
+    }
+
+    stored == unsigned || stored as i64 == signed
+}
+
+/// Text of a NUL-terminated header field
+fn field_text(field: &[u8]) -> String {
+    let text = field.split(|&b| b == 0).next().unwrap_or_default();
+    String::from_utf8_lossy(text).into_owned()
+}
+
+/// Entry name from the header, joining the ustar `prefix` field if present
+fn header_name(header: &[u8; BLOCK_LEN as usize]) -> String {
+    let name = field_text(&header[0..100]);
+    if &header[257..262] != b"ustar" {
+        return name;
+    }
+
+    let prefix = field_text(&header[345..500]);
+    if prefix.is_empty() {
+        name
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// The `path` record of a PAX extended header (`"<len> path=<value>\n"` records)
+fn pax_path(data: &[u8]) -> Option<String> {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let space = rest.iter().position(|&b| b == b' ')?;
+        let len: usize = std::str::from_utf8(&rest[..space]).ok()?.parse().ok()?;
+        if len <= space || len > rest.len() {
+            return None;
+        }
+
+        let record = &rest[space + 1..len];
+        let record = record.strip_suffix(b"\n").unwrap_or(record);
+        if let Some(value) = record.strip_prefix(b"path=") {
+            return Some(String::from_utf8_lossy(value).into_owned());
+        }
+        rest = &rest[len..];
+    }
+    None
+}
+
+/// Walk the headers and index every file and directory
+///
+/// The walk ends at the first all-zero block or at the end of the data (some
+/// writers omit the two terminating blocks). Without the terminating blocks,
+/// data appended to the archive follows the last entry directly; a header
+/// that fails its checksum after the first is taken for such trailing data
+/// and ends the walk too.
+fn read_index<R: Read + Seek>(reader: &mut R) -> Result<Vec<TarEntry>> {
+    let mut entries = Vec::new();
+    let mut offset = 0u64;
+    // Name for the next entry from a GNU long name or PAX header
+    let mut pending_name: Option<String> = None;
+
+    loop {
+        let mut header = [0u8; BLOCK_LEN as usize];
+        let read = reader.seek(SeekFrom::Start(offset)).and_then(|_| reader.read_exact(&mut header));
+        match read {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof && offset > 0 => break,
+            Err(e) => return Err(CbxError::Archive(format!("Failed to read TAR header: {}", e))),
+        }
+
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        if !checksum_matches(&header) {
+            if offset > 0 {
+                tracing::warn!("Ignoring data after the last TAR entry (offset {})", offset);
+                break;
+            }
+            return Err(CbxError::Archive(format!("Invalid TAR header at offset {}", offset)));
+        }
+
+        let size = parse_number(&header[124..136])
+            .ok_or_else(|| CbxError::Archive(format!("Invalid TAR entry size at offset {}", offset)))?;
+        let mtime = parse_number(&header[136..148]).unwrap_or(0);
+        let data_offset = offset + BLOCK_LEN;
+        offset = size
+            .checked_add(BLOCK_LEN - 1)
+            .map(|padded| padded / BLOCK_LEN * BLOCK_LEN)
+            .and_then(|padded| data_offset.checked_add(padded))
+            .ok_or_else(|| CbxError::Archive(format!("Invalid TAR entry size: {}", size)))?;
+
+        match header[156] {
+            b'L' | b'x' => {
+                if size > MAX_META_ENTRY_SIZE {
+                    return Err(CbxError::Archive(format!("TAR extended header too large: {} bytes", size)));
+                }
+                let mut data = vec![0u8; size as usize];
+                reader.seek(SeekFrom::Start(data_offset))
+                    .and_then(|_| reader.read_exact(&mut data))
+                    .map_err(|e| CbxError::Archive(format!("Failed to read TAR extended header: {}", e)))?;
+
+                let name = if header[156] == b'L' { Some(field_text(&data)) } else { pax_path(&data) };
+                if name.is_some() {
+                    pending_name = name;
+                }
+            }
+            // Global PAX header: nothing we use
+            b'g' => {}
+            typeflag @ (b'0' | b'\0' | b'7' | b'5') => {
+                let name = normalize_entry_name(&pending_name.take().unwrap_or_else(|| header_name(&header)));
+                let is_directory = typeflag == b'5' || name.ends_with('/');
+                entries.push(TarEntry {
+                    entry: ArchiveEntry {
+                        name,
+                        size: if is_directory { 0 } else { size },
+                        is_directory,
+                    },
+                    data_offset,
+                    mtime,
+                });
+            }
+            typeflag => {
+                tracing::debug!("Skipping TAR entry of type {:?} at offset {}", typeflag as char, data_offset - BLOCK_LEN);
+                pending_name = None;
+            }
+        }
+    }
+
+    tracing::debug!("Indexed {} TAR entries", entries.len());
+    Ok(entries)
+}
+
+/// TAR archive handler for in-memory data and streams
+///
+/// In-memory archives are opened through a `Cursor`.
+pub struct TarArchiveFromStream<R: Read + Seek> {
+    reader: RefCell<R>,
+    entries: Vec<TarEntry>,
+    size: u64,
+}
+
+impl<R: Read + Seek> TarArchiveFromStream<R> {
+    /// Index the archive read from `reader`
+    pub fn new(mut reader: R) -> Result<Self> {
+        let size = reader.seek(SeekFrom::End(0))
+            .map_err(|e| CbxError::Archive(format!("Failed to seek TAR archive: {}", e)))?;
+        let entries = read_index(&mut reader)?;
+
+        Ok(Self {
+            reader: RefCell::new(reader),
+            entries,
+            size,
+        })
+    }
+
+    fn find(&self, name: &str) -> Result<&TarEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.entry.name == name)
+            .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", name)))
+    }
+
+    /// Read up to `len` bytes of the entry's data
+    fn read(&self, entry: &TarEntry, len: u64) -> Result<Vec<u8>> {
+        let mut buffer = vec![0u8; entry.entry.size.min(len) as usize];
+        let mut reader = self.reader.borrow_mut();
+        reader.seek(SeekFrom::Start(entry.data_offset))
+            .and_then(|_| reader.read_exact(&mut buffer))
+            .map_err(|e| CbxError::Archive(format!("Failed to extract entry: {}", e)))?;
+        Ok(buffer)
+    }
+}
+
+impl TarArchiveFromStream<Cursor<Vec<u8>>> {
+    /// Create a TAR archive from in-memory data
+    pub fn from_memory(data: Vec<u8>) -> Result<Self> {
+        Self::new(Cursor::new(data))
+    }
+}
+
+impl<R: Read + Seek> Archive for TarArchiveFromStream<R> {
+    fn open(_path: &Path) -> Result<Box<dyn Archive>> {
+        Err(CbxError::Archive(
+            "Use TarArchive::open for file paths".to_string(),
+        ))
+    }
+
+    /// List all entries in archive order
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        Ok(self.entries.iter().map(|e| e.entry.clone()).collect())
+    }
+
+    fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
+        tracing::debug!("Finding first image in TAR (sort={})", sort);
+
+        if self.entries.is_empty() {
+            return Err(CbxError::Archive("Archive is empty".to_string()));
+        }
+
+        let names = self.entries.iter().filter(|e| !e.entry.is_directory).map(|e| e.entry.name.as_str());
+        let image_name = find_first_image(names, sort)
+            .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
+
+        tracing::info!("Found first image: {}", bounded_name(&image_name));
+        Ok(self.find(&image_name)?.entry.clone())
+    }
+
+    fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+        tracing::debug!("Extracting entry: {} ({} bytes)", bounded_name(&entry.name), entry.size);
+
+        // Safety check: prevent memory exhaustion (same limit as the other formats)
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
+            return Err(CbxError::Archive(format!(
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
+            )));
+        }
+
+        let buffer = self.read(self.find(&entry.name)?, u64::MAX)?;
+        tracing::debug!("Extracted {} bytes", buffer.len());
+        Ok(buffer)
+    }
+
+    fn read_entry_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        self.read(self.find(&entry.name)?, max_len as u64)
+    }
+
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        // Tar has no compression of its own
+        self.find(&entry.name)?;
+        Ok("Stored".to_string())
+    }
+
+    fn get_metadata(&self) -> Result<ArchiveMetadata> {
+        let total_files = self.entries.len();
+        let image_count = self
+            .entries
+            .iter()
+            .filter(|e| !e.entry.is_directory && is_image_file(&e.entry.name))
+            .count();
+
+        let modified = self
+            .entries
+            .iter()
+            .map(|e| e.mtime)
+            .filter(|&mtime| mtime > 0)
+            .max()
+            .map(|mtime| UNIX_EPOCH + Duration::from_secs(mtime));
+
+        tracing::debug!("TAR metadata: {} files, {} images, {} bytes", total_files, image_count, self.size);
+
+        Ok(ArchiveMetadata {
+            total_files,
+            image_count,
+            compressed_size: self.size,
+            archive_type: ArchiveType::Tar,
+            modified,
+        })
+    }
+
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Tar
+    }
+}
+
+/// TAR/CBT archive handler
+pub struct TarArchive {
+    inner: TarArchiveFromStream<BufReader<File>>,
+    #[allow(dead_code)] // Stored for potential future use (metadata, error messages)
+    path: PathBuf,
+}
+
+impl TarArchive {
+    /// Open a TAR archive from path
+    pub fn open(path: &Path) -> Result<Self> {
+        tracing::debug!("Opening TAR archive: {:?}", path);
+
+        let file = File::open(path)
+            .map_err(|e| CbxError::Archive(format!("Failed to open TAR file: {}", e)))?;
+
+        Ok(Self {
+            inner: TarArchiveFromStream::new(BufReader::new(file))?,
+            path: path.to_path_buf(),
+        })
+    }
+}
+
+impl Archive for TarArchive {
+    fn open(path: &Path) -> Result<Box<dyn Archive>> {
+        Ok(Box::new(Self::open(path)?))
+    }
+
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        self.inner.list_entries()
+    }
+
+    fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
+        self.inner.find_first_image(sort)
+    }
+
+    fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+        self.inner.extract_entry(entry)
+    }
+
+    fn read_entry_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        self.inner.read_entry_prefix(entry, max_len)
+    }
+
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        self.inner.compression_method(entry)
+    }
+
+    fn get_metadata(&self) -> Result<ArchiveMetadata> {
+        self.inner.get_metadata()
+    }
+
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Tar
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::open_archive_from_memory;
+
+    const MTIME: u64 = 1_700_000_000;
+
+    /// A ustar header with a valid checksum
+    fn tar_header(name: &str, size: u64, typeflag: u8) -> [u8; 512] {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[100..108].copy_from_slice(b"0000644\0");
+        header[124..136].copy_from_slice(format!("{:011o}\0", size).as_bytes());
+        header[136..148].copy_from_slice(format!("{:011o}\0", MTIME).as_bytes());
+        header[156] = typeflag;
+        header[257..265].copy_from_slice(b"ustar\x0000");
+
+        header[148..156].copy_from_slice(b"        ");
+        let sum: u64 = header.iter().map(|&b| b as u64).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+        header
+    }
+
+    /// Append an entry, padding its data to a whole block
+    fn push_entry(tar: &mut Vec<u8>, name: &str, data: &[u8], typeflag: u8) {
+        tar.extend_from_slice(&tar_header(name, data.len() as u64, typeflag));
+        tar.extend_from_slice(data);
+        tar.resize((tar.len() + 511) / 512 * 512, 0);
+    }
+
+    /// Create a test TAR archive in memory (regular files only)
+    fn create_test_tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut tar = Vec::new();
+        for (name, data) in files {
+            push_entry(&mut tar, name, data, b'0');
+        }
+        tar.extend_from_slice(&[0u8; 1024]);
+        tar
+    }
+
+    #[test]
+    fn test_find_and_extract_first_image() {
+        let data = create_test_tar(&[
+            ("page10.jpg", b"ten"),
+            ("info.txt", b"text"),
+            ("page2.jpg", b"two"),
+        ]);
+        let archive = open_archive_from_memory(data).unwrap();
+        assert_eq!(archive.archive_type(), ArchiveType::Tar);
+
+        let sorted = archive.find_first_image(true).unwrap();
+        assert_eq!(sorted.name, "page2.jpg");
+        assert_eq!(archive.extract_entry(&sorted).unwrap(), b"two");
+
+        let unsorted = archive.find_first_image(false).unwrap();
+        assert_eq!(unsorted.name, "page10.jpg");
+        assert_eq!(archive.read_entry_prefix(&unsorted, 2).unwrap(), b"te");
+    }
+
+    #[test]
+    fn test_open_tar_file() {
+        let temp_path = std::env::temp_dir().join("test_open.cbt");
+        std::fs::write(&temp_path, create_test_tar(&[("page1.png", b"\x89PNG one")])).unwrap();
+
+        let archive = crate::archive::open_archive(&temp_path).unwrap();
+        let cover = archive.find_first_image(true).unwrap();
+        assert_eq!(archive.extract_entry(&cover).unwrap(), b"\x89PNG one");
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_long_names_and_directories() {
+        let long_dir = "d".repeat(120);
+        let mut tar = Vec::new();
+        push_entry(&mut tar, "chapter1/", b"", b'5');
+        push_entry(&mut tar, "././@LongLink", format!("{}/page1.jpg\0", long_dir).as_bytes(), b'L');
+        push_entry(&mut tar, "truncated", b"gnu", b'0');
+        // PAX records start with their own length: 2 digits, a space and the record
+        let record = "path=pax/page1.jpg\n";
+        let pax = format!("{} {}", record.len() + 3, record);
+        push_entry(&mut tar, "PaxHeader", pax.as_bytes(), b'x');
+        push_entry(&mut tar, "truncated2", b"pax", b'0');
+        push_entry(&mut tar, "link.jpg", b"", b'2');
+        tar.extend_from_slice(&[0u8; 1024]);
+
+        let archive = TarArchiveFromStream::from_memory(tar).unwrap();
+        let entries = archive.list_entries().unwrap();
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["chapter1/", &format!("{}/page1.jpg", long_dir), "pax/page1.jpg"]);
+        assert!(entries[0].is_directory);
+
+        let pax_entry = &entries[2];
+        assert_eq!(archive.extract_entry(pax_entry).unwrap(), b"pax");
+    }
+
+    #[test]
+    fn test_get_metadata() {
+        let data = create_test_tar(&[("page1.jpg", b"one"), ("notes.txt", b"text")]);
+        let len = data.len() as u64;
+        let metadata = TarArchiveFromStream::from_memory(data).unwrap().get_metadata().unwrap();
+
+        assert_eq!(metadata.total_files, 2);
+        assert_eq!(metadata.image_count, 1);
+        assert_eq!(metadata.compressed_size, len);
+        assert_eq!(metadata.archive_type, ArchiveType::Tar);
+        assert_eq!(metadata.modified, Some(UNIX_EPOCH + Duration::from_secs(MTIME)));
+    }
+
+    #[test]
+    fn test_corrupt_header_is_rejected() {
+        let mut data = create_test_tar(&[("page1.jpg", b"one")]);
+        data[0] ^= 0xFF;
+        assert!(TarArchiveFromStream::from_memory(data).is_err());
+    }
+
+    #[test]
+    fn test_trailing_data_is_ignored() {
+        let mut terminated = create_test_tar(&[("page1.jpg", b"one"), ("page2.jpg", b"two")]);
+        let mut unterminated = terminated[..terminated.len() - 1024].to_vec();
+        for data in [&mut terminated, &mut unterminated] {
+            data.extend(std::iter::repeat(0xAB).take(70_000));
+        }
+
+        for data in [terminated, unterminated] {
+            let archive = TarArchiveFromStream::from_memory(data).unwrap();
+            assert_eq!(archive.list_entries().unwrap().len(), 2);
+            let cover = archive.find_first_image(true).unwrap();
+            assert_eq!(archive.extract_entry(&cover).unwrap(), b"one");
+        }
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number(b"00000000017\0"), Some(15));
+        assert_eq!(parse_number(b"     17 "), Some(15));
+        assert_eq!(parse_number(b"\0\0\0\0"), Some(0));
+        assert_eq!(parse_number(&[0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0x00, 0x00]), Some(65536));
+        assert_eq!(parse_number(b"9"), None);
+    }
+}
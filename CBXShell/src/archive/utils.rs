@@ -3,10 +3,16 @@
 ///! Provides image detection, natural sorting, and common helpers
 
 use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use crate::utils::error::{CbxError, Result};
+use crate::utils::text::truncate_chars;
+use super::config::should_embed_cover_cache;
+use super::cover_cache::is_cover_cache;
+use super::CoverOptions;
 
-/// Maximum uncompressed size for a single entry (32MB)
-/// This matches the C++ implementation's CBXMEM_MAXBUFFER_SIZE
+/// Default maximum uncompressed size for a single entry (32MB)
+/// This matches the C++ implementation's CBXMEM_MAXBUFFER_SIZE; the
+/// effective limit is `config::read_max_entry_size`
 pub const MAX_ENTRY_SIZE: u64 = 32 * 1024 * 1024;
 
 /// Supported image extensions
@@ -18,10 +24,130 @@ const IMAGE_EXTENSIONS: &[&str] = &[
     "tif", "tiff",
     "webp",  // Phase 3
     "avif",  // Phase 3
+    "qoi",
+    "jxl",
 ];
 
+/// Marker file names that opt an archive out of thumbnailing
+/// (compared case-insensitively against the entry's base name)
+const NO_THUMB_MARKERS: &[&str] = &[".nothumb", "nothumb", ".nothumbnail"];
+
+/// Metadata sidecar names that are never a cover, in addition to
+/// `ComicInfo.xml` (compared case-insensitively against the entry's base name)
+const METADATA_FILES: &[&str] = &["metadata.json", "info.xml", "series.json"];
+
+/// File name of an entry, without its folders
+fn base_name(name: &str) -> &str {
+    name.rsplit(['/', '\\']).next().unwrap_or(name)
+}
+
+/// Check if an entry is a no-thumbnail marker (e.g. `.nothumb`, `NOTHUMB`)
+///
+/// Markers are recognized in any folder of the archive.
+pub fn is_no_thumb_marker(name: &str) -> bool {
+    let base_name = base_name(name);
+
+    NO_THUMB_MARKERS
+        .iter()
+        .any(|marker| base_name.eq_ignore_ascii_case(marker))
+}
+
+/// Check if an entry is a metadata sidecar (e.g. `metadata.json`, `Info.xml`)
+///
+/// Some tools store sidecars under an extra image extension
+/// (`series.json.jpg`), which would make them a cover candidate, so the name
+/// is also matched with its last extension removed. Sidecars are recognized
+/// in any folder of the archive.
+pub fn is_metadata_file(name: &str) -> bool {
+    let base_name = base_name(name);
+    let stem = base_name.rsplit_once('.').map_or(base_name, |(stem, _)| stem);
+
+    METADATA_FILES
+        .iter()
+        .any(|file| base_name.eq_ignore_ascii_case(file) || stem.eq_ignore_ascii_case(file))
+}
+
+/// Normalize an entry path to forward slashes
+///
+/// Some Windows tools store `images\page01.jpg` instead of the standard
+/// `images/page01.jpg`. Backends normalize names as they enumerate entries,
+/// so sorting and path matching see a single separator, and compare
+/// normalized names when looking an entry up again.
+pub fn normalize_entry_name(name: &str) -> String {
+    name.replace('\\', "/")
+}
+
+/// Decode a text entry (XML, `.cbxcover`) whatever its encoding
+///
+/// Detected in this order:
+/// - A byte order mark (UTF-8, UTF-16 LE/BE), which is stripped
+/// - BOM-less UTF-16 XML, recognized by its `<` followed or preceded by a NUL
+/// - An XML declaration naming ISO-8859-1 (Latin-1)
+/// - Otherwise UTF-8, with invalid sequences replaced
+pub fn decode_text(data: &[u8]) -> String {
+    fn utf16(data: &[u8], from_bytes: fn([u8; 2]) -> u16) -> String {
+        let units: Vec<u16> = data.chunks_exact(2).map(|c| from_bytes([c[0], c[1]])).collect();
+        String::from_utf16_lossy(&units)
+    }
+
+    match data {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [b'<', 0, ..] => utf16(data, u16::from_le_bytes),
+        [0, b'<', ..] => utf16(data, u16::from_be_bytes),
+        _ if declares_latin1(data) => data.iter().map(|&b| b as char).collect(),
+        _ => String::from_utf8_lossy(data).into_owned(),
+    }
+}
+
+/// Whether an XML declaration (`<?xml ... encoding="ISO-8859-1"?>`) names Latin-1
+fn declares_latin1(data: &[u8]) -> bool {
+    let Some(declaration) = data
+        .strip_prefix(b"<?xml")
+        .and_then(|rest| rest.split(|&b| b == b'>').next())
+    else {
+        return false;
+    };
+    let declaration = String::from_utf8_lossy(declaration).to_ascii_lowercase();
+    ["iso-8859-1", "latin1", "latin-1"]
+        .iter()
+        .any(|name| declaration.contains(&format!("encoding=\"{}\"", name))
+            || declaration.contains(&format!("encoding='{}'", name)))
+}
+
+/// Find an entry name matching `target`, ignoring ASCII case
+///
+/// Archive tools disagree on the case of well-known files
+/// (`META-INF/container.xml` vs `META-INF/CONTAINER.XML`, `cover_cache.png`,
+/// `ComicInfo.xml` vs `comicinfo.xml`), so every special-file lookup goes
+/// through this helper. An exact match wins over a case-insensitive one.
+pub fn find_entry_ci<'a, I>(names: I, target: &str) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut folded = None;
+    for name in names {
+        if name == target {
+            return Some(name);
+        }
+        if folded.is_none() && name.eq_ignore_ascii_case(target) {
+            folded = Some(name);
+        }
+    }
+    folded
+}
+
 /// Check if filename is an image based on extension
+///
+/// Metadata sidecars (see `is_metadata_file`) are not pages and never count
+/// as images. Neither does the embedded cover cache (`cover_cache.png`) while
+/// `EmbedCoverCache` is on; with it off, that name is an ordinary page.
 pub fn is_image_file(name: &str) -> bool {
+    if is_metadata_file(name) || (is_cover_cache(name) && should_embed_cover_cache()) {
+        return false;
+    }
+
     if let Some(ext) = Path::new(name)
         .extension()
         .and_then(|s| s.to_str())
@@ -32,32 +158,284 @@ pub fn is_image_file(name: &str) -> bool {
     }
 }
 
+/// Check if an entry's file name has no extension (e.g. "0001", "pages/0001")
+pub fn has_no_extension(name: &str) -> bool {
+    Path::new(name).extension().is_none()
+}
+
+/// Entry names are compared and logged up to this many characters
+///
+/// Real names are far shorter; a crafted archive can carry names hundreds of
+/// kilobytes long, which would make every comparison and log line that long.
+pub const MAX_NAME_CHARS: usize = 4096;
+
+/// The first `MAX_NAME_CHARS` characters of an entry name (no allocation)
+pub fn bounded_name(name: &str) -> &str {
+    truncate_chars(name, MAX_NAME_CHARS)
+}
+
 /// Natural sort comparison using natord (matches Windows StrCmpLogicalW)
+///
+/// Only the first `MAX_NAME_CHARS` characters are compared; names that share
+/// that prefix compare equal and keep their relative order.
 pub fn natural_sort_cmp(a: &str, b: &str) -> std::cmp::Ordering {
-    natord::compare(a, b)
+    natord::compare(bounded_name(a), bounded_name(b))
+}
+
+/// Natural sort comparison of the file names, then of the full paths
+///
+/// "intro/cover.jpg" sorts before "chapter01/page1.jpg": the directories
+/// only decide between entries with equal file names, e.g. the
+/// "page1.jpg" of several chapters.
+pub fn basename_sort_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    fn basename(name: &str) -> &str {
+        name.rsplit('/').next().unwrap_or(name)
+    }
+    natural_sort_cmp(basename(a), basename(b)).then_with(|| natural_sort_cmp(a, b))
 }
 
 /// Find first image entry from a list, optionally sorted
 ///
 /// If `sort` is true, returns alphabetically first image (natural order).
 /// If `sort` is false, returns first image encountered (early exit optimization).
+///
+/// Sorted mode keeps a running minimum instead of collecting and sorting, so
+/// memory use doesn't grow with the number of images. Among names that
+/// compare equal the earliest wins, as with a stable sort.
 pub fn find_first_image<'a>(
     names: impl Iterator<Item = &'a str>,
     sort: bool
 ) -> Option<String> {
-    let mut images: Vec<&str> = names
-        .filter(|name| is_image_file(name))
-        .collect();
+    find_first_image_by(names, sort, is_image_file)
+}
+
+/// Find the image at `index` from a list, optionally sorted
+///
+/// Images are counted in natural order if `sort` is true, in list order
+/// otherwise (a stable sort, so equal names keep list order). An index past
+/// the last image returns the last image.
+pub fn find_nth_image<'a>(
+    names: impl Iterator<Item = &'a str>,
+    index: usize,
+    sort: bool,
+) -> Option<String> {
+    let mut images: Vec<&str> = names.filter(|name| is_image_file(name)).collect();
+    if sort {
+        images.sort_by(|a, b| natural_sort_cmp(a, b));
+    }
+
+    let last = images.len().checked_sub(1)?;
+    Some(images[index.min(last)].to_string())
+}
+
+/// Index of the image `percent` percent into `count` images
+///
+/// `floor(percent * count / 100)`, clamped to the last image; 0 if there
+/// are no images. Used by `CoverStrategy::Percent`.
+pub fn percent_index(count: usize, percent: u8) -> usize {
+    (count * usize::from(percent) / 100).min(count.saturating_sub(1))
+}
+
+/// `find_first_image` with a custom image predicate (e.g. magic sniffing)
+pub fn find_first_image_by<'a>(
+    names: impl Iterator<Item = &'a str>,
+    sort: bool,
+    is_image: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let mut images = names.filter(|name| is_image(name));
+
+    let first = if sort {
+        // `min_by` returns the first of several equal minimums
+        images.min_by(|a, b| natural_sort_cmp(a, b))
+    } else {
+        images.next()
+    };
+
+    first.map(str::to_string)
+}
 
-    if images.is_empty() {
+/// First image in `basename_sort_cmp` order (`SortByBasename`)
+///
+/// Like sorted `find_first_image_by`, but entries in subdirectories compete
+/// by file name, as readers that flatten the page list present them.
+pub fn find_first_image_by_basename<'a>(
+    names: impl Iterator<Item = &'a str>,
+    is_image: impl Fn(&str) -> bool,
+) -> Option<String> {
+    names
+        .filter(|name| is_image(name))
+        .min_by(|a, b| basename_sort_cmp(a, b))
+        .map(str::to_string)
+}
+
+/// File name words that mark an image as the cover (`PreferCoverName`)
+const COVER_NAME_WORDS: [&str; 2] = ["cover", "front"];
+
+/// The only image whose file name contains "cover" or "front" (`PreferCoverName`)
+///
+/// Matches the file name case-insensitively, ignoring directories, so both
+/// "cover.jpg" and "extras/00_Front.png" count. Returns `None` if no image
+/// or more than one image matches, leaving the choice to the sorted
+/// selection.
+pub fn find_named_cover<'a>(
+    names: impl Iterator<Item = &'a str>,
+    is_image: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let mut matches = names.filter(|name| {
+        let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name).to_lowercase();
+        is_image(name) && COVER_NAME_WORDS.iter().any(|word| file_name.contains(word))
+    });
+
+    let cover = matches.next()?;
+    if matches.next().is_some() {
         return None;
     }
+    Some(cover.to_string())
+}
 
-    if sort {
-        images.sort_by(|a, b| natural_sort_cmp(a, b));
+/// Find the cover in "honor archive order" mode (legacy custom thumbnail)
+///
+/// The legacy C++ shell let users pick a custom thumbnail by packing it as
+/// the very first file of the archive with sorting disabled. This formalizes
+/// that rule:
+/// - If the first file in archive order is an image, it is the cover,
+///   regardless of its name.
+/// - Otherwise, fall back to the natural-sorted first image.
+///
+/// `names` must list file entries (no directories) in archive order;
+/// `is_image` decides which names count as images (normally `is_image_file`).
+pub fn find_archive_order_cover<'a>(
+    mut names: impl Iterator<Item = &'a str>,
+    is_image: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let first = names.next()?;
+
+    if is_image(first) {
+        return Some(first.to_string());
     }
 
-    images.first().map(|s| (*s).to_string())
+    find_first_image_by(names, true, is_image)
+}
+
+/// Select the cover image name according to `options`
+///
+/// `names` must list file entries (no directories) in archive order;
+/// `is_image` decides which names count as images (normally `is_image_file`,
+/// extended with sniffed names in `SniffExtensionless` mode).
+/// 1. Pick the base cover: with `prefer_cover_name`, the only image named
+///    like a cover (`find_named_cover`); otherwise the archive-order cover
+///    (sorting off and `honor_archive_order` on), otherwise the first image
+///    (natural-sorted if `sort` is on, by file name first with
+///    `sort_by_basename`).
+/// 2. If `format_priority` is set, images sharing the cover's base name
+///    (path without extension, case-insensitive) compete, and the one whose
+///    extension appears earliest in the list wins. Unlisted extensions rank
+///    after listed ones; ties keep the base cover.
+pub fn select_cover<'a>(
+    names: impl Iterator<Item = &'a str>,
+    options: &CoverOptions,
+    is_image: impl Fn(&str) -> bool,
+) -> Option<String> {
+    let names: Vec<&str> = names.collect();
+
+    let named = if options.prefer_cover_name {
+        find_named_cover(names.iter().copied(), &is_image)
+    } else {
+        None
+    };
+
+    let cover = if let Some(named) = named {
+        named
+    } else if !options.sort && options.honor_archive_order {
+        find_archive_order_cover(names.iter().copied(), &is_image)?
+    } else if options.sort && options.sort_by_basename {
+        find_first_image_by_basename(names.iter().copied(), &is_image)?
+    } else {
+        find_first_image_by(names.iter().copied(), options.sort, &is_image)?
+    };
+
+    if options.format_priority.is_empty() {
+        return Some(cover);
+    }
+
+    let cover_stem = image_stem(&cover);
+    let mut best = cover.as_str();
+    let mut best_rank = format_rank(best, &options.format_priority);
+
+    for &name in names.iter() {
+        if is_image(name) && image_stem(name).eq_ignore_ascii_case(cover_stem) {
+            let rank = format_rank(name, &options.format_priority);
+            if rank < best_rank {
+                best = name;
+                best_rank = rank;
+            }
+        }
+    }
+
+    Some(best.to_string())
+}
+
+/// Entry path without its extension ("dir/page01.jpg" -> "dir/page01")
+fn image_stem(name: &str) -> &str {
+    match name.rfind('.') {
+        Some(dot) if !name[dot..].contains(['/', '\\']) => &name[..dot],
+        _ => name,
+    }
+}
+
+/// Position of the entry's extension in the priority list (unlisted = last)
+fn format_rank(name: &str, priority: &[String]) -> usize {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|s| s.to_str())
+        .unwrap_or("");
+
+    priority
+        .iter()
+        .position(|p| p.eq_ignore_ascii_case(ext))
+        .unwrap_or(priority.len())
+}
+
+/// Convert an MS-DOS date/time pair (ZIP and RAR entry timestamps) to `SystemTime`
+///
+/// DOS timestamps carry no time zone; they are interpreted as UTC. Returns
+/// `None` for the zero timestamp and out-of-range fields.
+pub fn dos_datetime_to_system_time(date: u16, time: u16) -> Option<SystemTime> {
+    let year = 1980 + i64::from(date >> 9);
+    let month = u32::from((date >> 5) & 0x0F);
+    let day = u32::from(date & 0x1F);
+    let hour = u64::from(time >> 11);
+    let minute = u64::from((time >> 5) & 0x3F);
+    let second = u64::from(time & 0x1F) * 2;
+
+    if date == 0 || !(1..=12).contains(&month) || !(1..=31).contains(&day)
+        || hour > 23 || minute > 59 || second > 59
+    {
+        return None;
+    }
+
+    // Days since 1970-01-01 (proleptic Gregorian calendar)
+    let (y, m) = if month <= 2 { (year - 1, month + 9) } else { (year, month - 3) };
+    let era = y.div_euclid(400);
+    let year_of_era = y - era * 400;
+    let day_of_year = (153 * i64::from(m) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    let secs = u64::try_from(days).ok()? * 86_400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Convert a Windows FILETIME (100ns ticks since 1601-01-01, used by 7z) to `SystemTime`
+///
+/// Returns `None` for unset (zero) and pre-1970 timestamps.
+pub fn filetime_to_system_time(ticks: u64) -> Option<SystemTime> {
+    /// FILETIME ticks between 1601-01-01 and 1970-01-01
+    const UNIX_EPOCH_TICKS: u64 = 116_444_736_000_000_000;
+
+    let since_epoch = ticks.checked_sub(UNIX_EPOCH_TICKS)?;
+    let nanos = (since_epoch % 10_000_000) as u32 * 100;
+    Some(UNIX_EPOCH + Duration::new(since_epoch / 10_000_000, nanos))
 }
 
 /// Verify that extracted data is actually a valid image using magic headers
@@ -69,6 +447,11 @@ pub fn find_first_image<'a>(
 /// This ensures we don't waste time trying to decode files that only
 /// have image extensions but aren't actually images (e.g., renamed files).
 ///
+/// The magic table only covers formats we know about. When it doesn't
+/// recognize the data but the entry has an image extension, the decoder's
+/// own format guessing gets a chance to parse the header before the entry is
+/// rejected.
+///
 /// # Arguments
 /// * `data` - Extracted file data to verify
 /// * `filename` - Original filename (for error messages)
@@ -84,6 +467,7 @@ pub fn find_first_image<'a>(
 /// // Now safe to decode the image
 /// ```
 pub fn verify_image_data(data: &[u8], filename: &str) -> Result<()> {
+    use crate::image_processor::decoder::primary_dimensions;
     use crate::image_processor::magic::verify_image_format;
 
     match verify_image_format(data) {
@@ -96,6 +480,18 @@ pub fn verify_image_data(data: &[u8], filename: &str) -> Result<()> {
             Ok(())
         }
         Err(e) => {
+            if is_image_file(filename) {
+                if let Some((width, height)) = primary_dimensions(data) {
+                    tracing::debug!(
+                        "Image {} not in magic table, but decoder recognized it ({}x{})",
+                        filename,
+                        width,
+                        height
+                    );
+                    return Ok(());
+                }
+            }
+
             tracing::warn!(
                 "File {} has image extension but failed magic header verification: {}",
                 filename,
@@ -113,6 +509,230 @@ pub fn verify_image_data(data: &[u8], filename: &str) -> Result<()> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_decode_text_encodings() {
+        let utf16 = |text: &str, bom: &[u8], to_bytes: fn(u16) -> [u8; 2]| -> Vec<u8> {
+            let mut data = bom.to_vec();
+            data.extend(text.encode_utf16().flat_map(to_bytes));
+            data
+        };
+        let xml = "<?xml version=\"1.0\" encoding=\"UTF-16\"?><p>Café</p>";
+
+        assert_eq!(decode_text(xml.as_bytes()), xml);
+        assert_eq!(decode_text(b"\xEF\xBB\xBFpage01.jpg"), "page01.jpg");
+        assert_eq!(decode_text(&utf16(xml, &[0xFF, 0xFE], u16::to_le_bytes)), xml);
+        assert_eq!(decode_text(&utf16(xml, &[0xFE, 0xFF], u16::to_be_bytes)), xml);
+        assert_eq!(decode_text(&utf16(xml, &[], u16::to_le_bytes)), xml);
+        assert_eq!(decode_text(&utf16(xml, &[], u16::to_be_bytes)), xml);
+
+        let latin1 = b"<?xml version='1.0' encoding='ISO-8859-1'?><p>Caf\xE9</p>";
+        assert_eq!(decode_text(latin1), "<?xml version='1.0' encoding='ISO-8859-1'?><p>Café</p>");
+
+        // Invalid UTF-8 without a declaration is replaced, not dropped
+        assert_eq!(decode_text(b"a\xFFb"), "a\u{FFFD}b");
+        assert_eq!(decode_text(b""), "");
+    }
+
+    #[test]
+    fn test_is_no_thumb_marker() {
+        assert!(is_no_thumb_marker(".nothumb"));
+        assert!(is_no_thumb_marker("NOTHUMB"));
+        assert!(is_no_thumb_marker("Chapter 1/.NoThumb"));
+        assert!(is_no_thumb_marker("Chapter 1\\nothumb"));
+
+        assert!(!is_no_thumb_marker("nothumb.jpg"));
+        assert!(!is_no_thumb_marker("page_nothumb"));
+        assert!(!is_no_thumb_marker("nothumb/page01.jpg"));
+    }
+
+    #[test]
+    fn test_find_entry_ci() {
+        let names = ["comicinfo.xml", "pages/ComicInfo.xml", "ComicInfo.XML", "page01.jpg"];
+
+        // Case-insensitive, first match in archive order
+        assert_eq!(find_entry_ci(names, "ComicInfo.xml"), Some("comicinfo.xml"));
+        // An exact match wins
+        assert_eq!(find_entry_ci(names, "ComicInfo.XML"), Some("ComicInfo.XML"));
+        // Whole paths only
+        assert_eq!(find_entry_ci(names, "PAGES/comicinfo.XML"), Some("pages/ComicInfo.xml"));
+        assert_eq!(find_entry_ci(names, "info.xml"), None);
+        assert_eq!(find_entry_ci([], "ComicInfo.xml"), None);
+    }
+
+    #[test]
+    fn test_normalize_entry_name() {
+        assert_eq!(normalize_entry_name("images\\vol 1\\page01.jpg"), "images/vol 1/page01.jpg");
+        assert_eq!(normalize_entry_name("images/page01.jpg"), "images/page01.jpg");
+        assert_eq!(normalize_entry_name("cover.jpg"), "cover.jpg");
+    }
+
+    fn priority(exts: &[&str]) -> CoverOptions {
+        CoverOptions {
+            sort: true,
+            format_priority: exts.iter().map(|e| e.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_select_cover_format_priority_decides() {
+        let names = ["page01.jpg", "page01.png", "page02.png"];
+
+        // No preference: pure natural sort
+        let plain = CoverOptions { sort: true, ..Default::default() };
+        assert_eq!(select_cover(names.iter().copied(), &plain, is_image_file), Some("page01.jpg".to_string()));
+
+        // PNG preferred over JPEG for the same base name
+        assert_eq!(
+            select_cover(names.iter().copied(), &priority(&["png", "jpg"]), is_image_file),
+            Some("page01.png".to_string())
+        );
+
+        // JPEG preferred: keeps page01.jpg
+        assert_eq!(
+            select_cover(names.iter().copied(), &priority(&["jpg", "png"]), is_image_file),
+            Some("page01.jpg".to_string())
+        );
+    }
+
+    #[test]
+    fn test_select_cover_format_priority_only_same_base_name() {
+        // page02.png is preferred by format but has a different base name
+        let names = ["page01.jpg", "page02.png"];
+        assert_eq!(
+            select_cover(names.iter().copied(), &priority(&["png"]), is_image_file),
+            Some("page01.jpg".to_string())
+        );
+
+        // Base names compare case-insensitively, including folders
+        let names = ["Ch1/Page01.JPG", "ch1/page01.webp"];
+        assert_eq!(
+            select_cover(names.iter().copied(), &priority(&["webp", "jpg"]), is_image_file),
+            Some("ch1/page01.webp".to_string())
+        );
+    }
+
+    #[test]
+    fn test_select_cover_with_sniffed_predicate() {
+        let names = ["info.txt", "0002", "0001"];
+        let sniffed = ["0001", "0002"];
+        let is_image = |name: &str| is_image_file(name) || sniffed.contains(&name);
+
+        let sorted = CoverOptions { sort: true, ..Default::default() };
+        assert_eq!(select_cover(names.iter().copied(), &sorted, is_image_file), None);
+        assert_eq!(
+            select_cover(names.iter().copied(), &sorted, is_image),
+            Some("0001".to_string())
+        );
+    }
+
+    #[test]
+    fn test_dos_datetime_to_system_time() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        // 2024-03-15 12:34:56
+        let date = ((2024 - 1980) << 9) | (3 << 5) | 15;
+        let time = (12 << 11) | (34 << 5) | (56 / 2);
+        assert_eq!(
+            dos_datetime_to_system_time(date, time),
+            Some(UNIX_EPOCH + Duration::from_secs(1_710_506_096))
+        );
+
+        // 1980-01-01 00:00:00, the earliest DOS timestamp
+        assert_eq!(
+            dos_datetime_to_system_time((1 << 5) | 1, 0),
+            Some(UNIX_EPOCH + Duration::from_secs(315_532_800))
+        );
+
+        assert_eq!(dos_datetime_to_system_time(0, 0), None);
+        assert_eq!(dos_datetime_to_system_time((13 << 5) | 1, 0), None); // Month 13
+    }
+
+    #[test]
+    fn test_filetime_to_system_time() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        assert_eq!(
+            filetime_to_system_time(116_444_736_000_000_000 + 1_710_506_096 * 10_000_000 + 5),
+            Some(UNIX_EPOCH + Duration::new(1_710_506_096, 500))
+        );
+        assert_eq!(filetime_to_system_time(0), None);
+    }
+
+    #[test]
+    fn test_has_no_extension() {
+        assert!(has_no_extension("0001"));
+        assert!(has_no_extension("scans.v2/0001"));
+        assert!(!has_no_extension("0001.jpg"));
+    }
+
+    #[test]
+    fn test_image_stem() {
+        assert_eq!(image_stem("page01.jpg"), "page01");
+        assert_eq!(image_stem("dir.v2/page01"), "dir.v2/page01");
+        assert_eq!(image_stem("dir/page.01.png"), "dir/page.01");
+    }
+
+    #[test]
+    fn test_find_first_image_matches_sort_then_first() {
+        let names = [
+            "notes.txt", "Page10.jpg", "page2.png", "page02.jpg", "page2.jpg", "cover/",
+            "a/page1.jpg", "page1.JPG", "page001.webp", "z.gif", "Page1.jpg",
+        ];
+
+        // The previous implementation: collect, stable sort, take the first
+        for len in 0..=names.len() {
+            let mut expected: Vec<&str> = names[..len].iter().copied().filter(|n| is_image_file(n)).collect();
+            expected.sort_by(|a, b| natural_sort_cmp(a, b));
+            assert_eq!(
+                find_first_image(names[..len].iter().copied(), true).as_deref(),
+                expected.first().copied()
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_first_image_sorted_streams_names() {
+        // Names come from a lazy iterator; nothing is collected per name
+        let listing: String = (0..200_000u32).rev().map(|i| format!("page{}.jpg\n", i)).collect();
+        let mut seen = 0usize;
+        let names = listing.lines().inspect(|_| seen += 1);
+
+        assert_eq!(find_first_image(names, true), Some("page0.jpg".to_string()));
+        assert_eq!(seen, 200_000);
+    }
+
+    #[test]
+    fn test_archive_order_cover_honors_first_entry() {
+        // "zz_cover.jpg" sorts last but is packed first
+        let names = ["zz_cover.jpg", "page01.jpg", "page02.jpg"];
+
+        let sorted = find_first_image(names.iter().copied(), true);
+        assert_eq!(sorted, Some("page01.jpg".to_string()));
+
+        let cover = find_archive_order_cover(names.iter().copied(), is_image_file);
+        assert_eq!(cover, Some("zz_cover.jpg".to_string()));
+    }
+
+    #[test]
+    fn test_archive_order_cover_falls_back_to_sorted() {
+        // First entry is not an image: behaves like sorted selection,
+        // unlike plain unsorted mode which takes "page10.jpg"
+        let names = ["readme.txt", "page10.jpg", "page2.jpg"];
+
+        let unsorted = find_first_image(names.iter().copied(), false);
+        assert_eq!(unsorted, Some("page10.jpg".to_string()));
+
+        let cover = find_archive_order_cover(names.iter().copied(), is_image_file);
+        assert_eq!(cover, Some("page2.jpg".to_string()));
+    }
+
+    #[test]
+    fn test_archive_order_cover_empty_or_no_images() {
+        assert_eq!(find_archive_order_cover(std::iter::empty(), is_image_file), None);
+        assert_eq!(find_archive_order_cover(["a.txt", "b.nfo"].into_iter(), is_image_file), None);
+    }
+
     #[test]
     fn test_is_image_file() {
         // Supported formats
@@ -123,12 +743,42 @@ mod tests {
         assert!(is_image_file("icon.ico"));
         assert!(is_image_file("graphic.bmp"));
         assert!(is_image_file("scan.tiff"));
+        assert!(is_image_file("page01.jxl"));
 
         // Unsupported formats
         assert!(!is_image_file("readme.txt"));
         assert!(!is_image_file("archive.zip"));
         assert!(!is_image_file("video.mp4"));
         assert!(!is_image_file("noextension"));
+
+        // Embedded cover cache: only skipped while the feature is on
+        assert_eq!(is_image_file("cover_cache.png"), !should_embed_cover_cache());
+        assert!(is_image_file("extras/cover_cache.png"));
+
+        // Metadata sidecars disguised as images
+        assert!(!is_image_file("series.json.jpg"));
+        assert!(!is_image_file("Vol 1/Metadata.JSON.png"));
+    }
+
+    #[test]
+    fn test_is_metadata_file() {
+        assert!(is_metadata_file("metadata.json"));
+        assert!(is_metadata_file("INFO.XML"));
+        assert!(is_metadata_file("Chapter 1\\series.json"));
+        assert!(is_metadata_file("series.json.jpg"));
+
+        assert!(!is_metadata_file("metadata.jpg"));
+        assert!(!is_metadata_file("info.xml/page01.jpg"));
+        assert!(!is_metadata_file("page_info.xml"));
+        assert!(!is_metadata_file("ComicInfo.xml"));
+    }
+
+    #[test]
+    fn test_find_first_image_skips_metadata_sidecar() {
+        // "metadata..." sorts before "page..." and comes first in the archive
+        let names = ["metadata.json.jpg", "page01.jpg", "page02.jpg"];
+        assert_eq!(find_first_image(names.iter().copied(), true).as_deref(), Some("page01.jpg"));
+        assert_eq!(find_first_image(names.iter().copied(), false).as_deref(), Some("page01.jpg"));
     }
 
     #[test]
@@ -145,6 +795,82 @@ mod tests {
         assert_eq!(natural_sort_cmp("apple.jpg", "banana.jpg"), Ordering::Less);
     }
 
+    #[test]
+    fn test_basename_sort_vs_path_sort() {
+        let names = ["chapter01/page1.jpg", "chapter01/page10.jpg", "intro/cover.jpg", "page2.jpg", "chapter02/page1.jpg"];
+
+        let mut by_path = names.to_vec();
+        by_path.sort_by(|a, b| natural_sort_cmp(a, b));
+        assert_eq!(
+            by_path,
+            ["chapter01/page1.jpg", "chapter01/page10.jpg", "chapter02/page1.jpg", "intro/cover.jpg", "page2.jpg"]
+        );
+
+        // File names first; the paths only order the two "page1.jpg"
+        let mut by_basename = names.to_vec();
+        by_basename.sort_by(|a, b| basename_sort_cmp(a, b));
+        assert_eq!(
+            by_basename,
+            ["intro/cover.jpg", "chapter01/page1.jpg", "chapter02/page1.jpg", "page2.jpg", "chapter01/page10.jpg"]
+        );
+
+        assert_eq!(find_first_image(names.iter().copied(), true).as_deref(), Some("chapter01/page1.jpg"));
+        assert_eq!(
+            find_first_image_by_basename(names.iter().copied(), is_image_file).as_deref(),
+            Some("intro/cover.jpg")
+        );
+        assert_eq!(find_first_image_by_basename(["notes.txt"].into_iter(), is_image_file), None);
+
+        // Only applies to sorted selection
+        let options = CoverOptions { sort: true, sort_by_basename: true, ..Default::default() };
+        assert_eq!(select_cover(names.iter().copied(), &options, is_image_file).as_deref(), Some("intro/cover.jpg"));
+        let options = CoverOptions { sort_by_basename: true, ..Default::default() };
+        assert_eq!(select_cover(names.iter().copied(), &options, is_image_file).as_deref(), Some("chapter01/page1.jpg"));
+    }
+
+    #[test]
+    fn test_find_named_cover_single_match() {
+        let names = ["page001.jpg", "page002.jpg", "extras/00_Cover.PNG", "cover.txt"];
+        assert_eq!(find_named_cover(names.iter().copied(), is_image_file).as_deref(), Some("extras/00_Cover.PNG"));
+
+        let names = ["page001.jpg", "front.webp"];
+        assert_eq!(find_named_cover(names.iter().copied(), is_image_file).as_deref(), Some("front.webp"));
+
+        // Only the file name counts, not its directory
+        let names = ["covers/page001.jpg", "covers/page002.jpg"];
+        assert_eq!(find_named_cover(names.iter().copied(), is_image_file), None);
+    }
+
+    #[test]
+    fn test_find_named_cover_multiple_matches() {
+        let names = ["page001.jpg", "cover.jpg", "back_cover.jpg"];
+        assert_eq!(find_named_cover(names.iter().copied(), is_image_file), None);
+
+        // Ambiguous: falls back to sorted selection
+        let options = CoverOptions { sort: true, prefer_cover_name: true, ..Default::default() };
+        assert_eq!(select_cover(names.iter().copied(), &options, is_image_file).as_deref(), Some("back_cover.jpg"));
+    }
+
+    #[test]
+    fn test_find_named_cover_no_match() {
+        let names = ["page002.jpg", "page001.jpg", "readme.txt"];
+        assert_eq!(find_named_cover(names.iter().copied(), is_image_file), None);
+
+        let options = CoverOptions { sort: true, prefer_cover_name: true, ..Default::default() };
+        assert_eq!(select_cover(names.iter().copied(), &options, is_image_file).as_deref(), Some("page001.jpg"));
+    }
+
+    #[test]
+    fn test_select_cover_prefers_cover_name() {
+        let names = ["page001.jpg", "page002.jpg", "zz_cover.jpg"];
+
+        let sorted = CoverOptions { sort: true, ..Default::default() };
+        assert_eq!(select_cover(names.iter().copied(), &sorted, is_image_file).as_deref(), Some("page001.jpg"));
+
+        let options = CoverOptions { prefer_cover_name: true, ..sorted };
+        assert_eq!(select_cover(names.iter().copied(), &options, is_image_file).as_deref(), Some("zz_cover.jpg"));
+    }
+
     #[test]
     fn test_find_first_image_sorted() {
         let files = vec!["readme.txt", "page10.jpg", "page2.jpg", "page1.jpg"];
@@ -167,6 +893,45 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn test_find_nth_image() {
+        let files = ["page10.jpg", "info.txt", "page2.jpg", "page1.png", "dir/page3.jpg"];
+
+        // Index 0 agrees with find_first_image
+        assert_eq!(find_nth_image(files.iter().copied(), 0, true), find_first_image(files.iter().copied(), true));
+        assert_eq!(find_nth_image(files.iter().copied(), 0, false), Some("page10.jpg".to_string()));
+
+        // Middle: natural order is dir/page3, page1, page2, page10
+        assert_eq!(find_nth_image(files.iter().copied(), 2, true), Some("page2.jpg".to_string()));
+        assert_eq!(find_nth_image(files.iter().copied(), 2, false), Some("page1.png".to_string()));
+
+        // Out of range: last image
+        assert_eq!(find_nth_image(files.iter().copied(), 99, true), Some("page10.jpg".to_string()));
+        assert_eq!(find_nth_image(files.iter().copied(), usize::MAX, false), Some("dir/page3.jpg".to_string()));
+
+        assert_eq!(find_nth_image(["info.txt"].into_iter(), 1, true), None);
+    }
+
+    #[test]
+    fn test_percent_index() {
+        // Listed in reverse; natural order is page1 ... page10
+        let files: Vec<String> = (1..=10).map(|i| format!("page{}.jpg", i)).collect();
+        let pick = |percent| {
+            let index = percent_index(files.len(), percent);
+            find_nth_image(files.iter().rev().map(|s| s.as_str()), index, true)
+        };
+
+        assert_eq!(pick(0), Some("page1.jpg".to_string()));
+        assert_eq!(pick(50), Some("page6.jpg".to_string()));
+        assert_eq!(pick(100), Some("page10.jpg".to_string()));
+
+        assert_eq!(percent_index(10, 10), 1);
+        assert_eq!(percent_index(10, 99), 9);
+        assert_eq!(percent_index(3, 50), 1);
+        assert_eq!(percent_index(1, 100), 0);
+        assert_eq!(percent_index(0, 50), 0);
+    }
+
     #[test]
     fn test_find_first_image_empty() {
         let files: Vec<&str> = vec![];
@@ -174,6 +939,34 @@ mod tests {
         assert_eq!(result, None);
     }
 
+    #[test]
+    fn test_long_entry_names() {
+        use std::cmp::Ordering;
+
+        // 100KB names, one with a multibyte character right at the cut
+        let long_a = format!("{}a.jpg", "x".repeat(100 * 1024));
+        let long_b = format!("{}b.jpg", "x".repeat(100 * 1024));
+        let long_kana = format!("{}ページ.png", "y".repeat(MAX_NAME_CHARS - 1));
+
+        assert_eq!(bounded_name(&long_a).len(), MAX_NAME_CHARS);
+        assert_eq!(bounded_name(&long_kana).chars().count(), MAX_NAME_CHARS);
+        assert!(bounded_name(&long_kana).ends_with('ペ'));
+        assert_eq!(bounded_name("page01.jpg"), "page01.jpg");
+
+        // Equal within the compared prefix
+        assert_eq!(natural_sort_cmp(&long_a, &long_b), Ordering::Equal);
+        assert_eq!(natural_sort_cmp(&long_a, "page01.jpg"), Ordering::Greater);
+
+        // Sorting still works on the prefix, ties keep archive order
+        let names = [long_b.as_str(), long_a.as_str(), "x2.jpg", "x10.jpg", long_kana.as_str()];
+        let first = find_first_image(names.iter().copied(), true).unwrap();
+        assert_eq!(first, "x2.jpg");
+
+        let mut sorted = names.to_vec();
+        sorted.sort_by(|a, b| natural_sort_cmp(a, b));
+        assert_eq!(sorted, vec!["x2.jpg", "x10.jpg", long_b.as_str(), long_a.as_str(), long_kana.as_str()]);
+    }
+
     #[test]
     fn test_max_entry_size() {
         assert_eq!(MAX_ENTRY_SIZE, 33_554_432);
@@ -208,6 +1001,18 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_verify_image_data_falls_back_to_decoder() {
+        // 2x1 binary PPM: not in the magic table, but `image` can decode it
+        let ppm = b"P6\n2 1\n255\n\xFF\x00\x00\x00\xFF\x00";
+        assert!(crate::image_processor::magic::detect_image_format(ppm).is_err());
+
+        assert!(verify_image_data(ppm, "page.png").is_ok());
+
+        // Without an image extension the entry is still rejected
+        assert!(verify_image_data(ppm, "page.dat").is_err());
+    }
+
     #[test]
     fn test_verify_image_data_empty() {
         let result = verify_image_data(&[], "empty.jpg");
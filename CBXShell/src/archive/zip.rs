@@ -2,21 +2,32 @@
 ///!
 ///! Supports ZIP, CBZ, EPUB, and PHZ formats using the `zip` crate
 
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
 use std::fs::File;
-use std::io::{BufReader, Cursor, Read, Seek};
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use zip::read::ZipFile;
+use zip::result::{InvalidPassword, ZipError, ZipResult};
 use zip::ZipArchive as ZipReader;
+use zip::CompressionMethod;
 
+use std::time::SystemTime;
 use crate::archive::{Archive, ArchiveEntry, ArchiveMetadata, ArchiveType};
 use crate::utils::error::{CbxError, Result};
-use super::utils::{is_image_file, find_first_image, MAX_ENTRY_SIZE};
+use super::utils::{
+    bounded_name, dos_datetime_to_system_time, find_first_image, is_image_file, normalize_entry_name,
+};
+use super::config::{read_archive_password, read_max_entry_size};
 
 /// ZIP archive handler
 pub struct ZipArchive {
-    archive: RefCell<ZipReader<BufReader<File>>>,
+    archive: RefCell<ZipReader<SharedReader<BufReader<File>>>>,
+    recovery: CentralDirectoryOnly<BufReader<File>>,
     #[allow(dead_code)] // Stored for potential future use (metadata, error messages)
     path: PathBuf,
+    /// Password for encrypted entries (`ArchivePassword`)
+    password: Option<String>,
 }
 
 impl ZipArchive {
@@ -27,13 +38,15 @@ impl ZipArchive {
         let file = File::open(path)
             .map_err(|e| CbxError::Archive(format!("Failed to open ZIP file: {}", e)))?;
 
-        let reader = BufReader::new(file);
-        let archive = ZipReader::new(reader)
+        let reader = open_shared_reader(BufReader::new(file))?;
+        let archive = ZipReader::new(reader.clone())
             .map_err(|e| CbxError::Archive(format!("Invalid ZIP archive: {}", e)))?;
 
         Ok(Self {
             archive: RefCell::new(archive),
+            recovery: CentralDirectoryOnly::new(reader),
             path: path.to_path_buf(),
+            password: read_archive_password(),
         })
     }
 
@@ -42,9 +55,9 @@ impl ZipArchive {
         let mut archive = self.archive.borrow_mut();
         (0..archive.len())
             .filter_map(|i| {
-                archive.by_index(i)
+                self.recovery.entry_at(&mut archive, i, false)
                     .ok()
-                    .map(|f| f.name().to_string())
+                    .map(|e| e.name)
             })
             .collect()
     }
@@ -54,15 +67,9 @@ impl ZipArchive {
         let mut archive = self.archive.borrow_mut();
 
         for i in 0..archive.len() {
-            let zip_entry = archive.by_index(i)
-                .map_err(|e| CbxError::Archive(format!("Failed to get entry {}: {}", i, e)))?;
-
-            if zip_entry.name() == name {
-                return Ok(ArchiveEntry {
-                    name: name.to_string(),
-                    size: zip_entry.size(),
-                    is_directory: zip_entry.is_dir(),
-                });
+            let entry = self.recovery.entry_at(&mut archive, i, false)?;
+            if entry.name == name {
+                return Ok(entry);
             }
         }
 
@@ -75,6 +82,19 @@ impl Archive for ZipArchive {
         Ok(Box::new(Self::open(path)?))
     }
 
+    /// List all entries in archive order
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        let mut archive = self.archive.borrow_mut();
+        let mut entries = Vec::with_capacity(archive.len());
+
+        for i in 0..archive.len() {
+            // Raw access: listing must not fail on encrypted entries
+            entries.push(self.recovery.entry_at(&mut archive, i, true)?);
+        }
+
+        Ok(entries)
+    }
+
     fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
         tracing::debug!("Finding first image in ZIP (sort={})", sort);
 
@@ -85,15 +105,10 @@ impl Archive for ZipArchive {
 
             let mut archive = self.archive.borrow_mut();
             for i in 0..archive.len() {
-                if let Ok(entry) = archive.by_index(i) {
-                    let name = entry.name().to_string();
-                    if is_image_file(&name) {
-                        tracing::info!("Found first image (unsorted): {}", name);
-                        return Ok(ArchiveEntry {
-                            name,
-                            size: entry.size(),
-                            is_directory: entry.is_dir(),
-                        });
+                if let Ok(entry) = self.recovery.entry_at(&mut archive, i, false) {
+                    if is_image_file(&entry.name) {
+                        tracing::info!("Found first image (unsorted): {}", bounded_name(&entry.name));
+                        return Ok(entry);
                     }
                 }
             }
@@ -112,30 +127,38 @@ impl Archive for ZipArchive {
         let image_name = find_first_image(entry_names.iter().map(|s| s.as_str()), sort)
             .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
 
-        tracing::info!("Found first image (sorted): {}", image_name);
+        tracing::info!("Found first image (sorted): {}", bounded_name(&image_name));
 
         // Get entry details
         self.get_entry_by_name(&image_name)
     }
 
     fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
-        tracing::debug!("Extracting entry: {} ({} bytes)", entry.name, entry.size);
+        tracing::debug!("Extracting entry: {} ({} bytes)", bounded_name(&entry.name), entry.size);
 
-        // Safety check: prevent memory exhaustion (32MB limit from C++ implementation)
-        if entry.size > MAX_ENTRY_SIZE {
-            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, MAX_ENTRY_SIZE);
+        // Safety check: prevent memory exhaustion (`MaxEntrySizeMB`, 32MB by default like the C++ implementation)
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
             return Err(CbxError::Archive(format!(
-                "Entry too large: {} bytes (max 32MB)",
-                entry.size
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
             )));
         }
 
+        self.recovery.ensure_readable(&entry.name, self.password.is_some())?;
         let mut archive = self.archive.borrow_mut();
 
         // Find and extract entry by name
-        let mut zip_entry = archive
-            .by_name(&entry.name)
-            .map_err(|e| CbxError::Archive(format!("Entry not found: {}", e)))?;
+        let mut zip_entry = match by_normalized_name(&mut archive, &entry.name, self.password.as_deref()) {
+            Ok(Ok(zip_entry)) => zip_entry,
+            Ok(Err(InvalidPassword)) => return Err(wrong_password(&entry.name)),
+            Err(e) if is_local_header_error(&e) => {
+                tracing::warn!("Damaged local header for {} ({}), using central directory", bounded_name(&entry.name), e);
+                return self.recovery.read(&entry.name, u64::MAX);
+            }
+            Err(e) => return Err(CbxError::Archive(format!("Entry not found: {}", e))),
+        };
 
         // Read to buffer (encrypted files will fail during read)
         let mut buffer = Vec::with_capacity(entry.size as usize);
@@ -147,6 +170,35 @@ impl Archive for ZipArchive {
         Ok(buffer)
     }
 
+    fn read_entry_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        self.recovery.ensure_readable(&entry.name, self.password.is_some())?;
+        let mut archive = self.archive.borrow_mut();
+        let zip_entry = match by_normalized_name(&mut archive, &entry.name, self.password.as_deref()) {
+            Ok(Ok(zip_entry)) => zip_entry,
+            Ok(Err(InvalidPassword)) => return Err(wrong_password(&entry.name)),
+            Err(e) if is_local_header_error(&e) => {
+                return self.recovery.read(&entry.name, max_len as u64);
+            }
+            Err(e) => return Err(CbxError::Archive(format!("Entry not found: {}", e))),
+        };
+
+        let mut buffer = Vec::with_capacity(max_len);
+        zip_entry
+            .take(max_len as u64)
+            .read_to_end(&mut buffer)
+            .map_err(|e| CbxError::Archive(format!("Failed to read entry: {}", e)))?;
+
+        Ok(buffer)
+    }
+
+    fn extract_entry_raw(&self, entry: &ArchiveEntry) -> Result<(CompressionMethod, Vec<u8>)> {
+        read_raw_entry(&mut self.archive.borrow_mut(), entry)
+    }
+
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        entry_compression(&mut self.archive.borrow_mut(), entry)
+    }
+
     fn get_metadata(&self) -> Result<ArchiveMetadata> {
         let entry_names = self.get_entry_names();
         let total_files = entry_names.len();
@@ -156,9 +208,11 @@ impl Archive for ZipArchive {
             .count();
 
         // Calculate compressed size from file
-        let compressed_size = std::fs::metadata(&self.path)
-            .map(|m| m.len())
-            .unwrap_or(0);
+        let file_metadata = std::fs::metadata(&self.path).ok();
+        let compressed_size = file_metadata.as_ref().map_or(0, |m| m.len());
+
+        let modified = newest_entry_time(&mut self.archive.borrow_mut())
+            .or_else(|| file_metadata.and_then(|m| m.modified().ok()));
 
         tracing::debug!(
             "ZIP metadata: {} files, {} images, {} bytes",
@@ -172,6 +226,7 @@ impl Archive for ZipArchive {
             image_count,
             compressed_size,
             archive_type: ArchiveType::Zip,
+            modified,
         })
     }
 
@@ -183,6 +238,7 @@ impl Archive for ZipArchive {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::archive::{open_archive_from_memory, CoverOptions, CoverStrategy};
     use std::io::Write;
     use zip::write::{FileOptions, ZipWriter};
 
@@ -278,6 +334,185 @@ mod tests {
         std::fs::remove_file(&temp_path).ok();
     }
 
+    #[test]
+    fn test_open_phz_archive() {
+        // `.phz` is a comic ZIP under another name
+        let temp_path = std::env::temp_dir().join("test_open.phz");
+        create_test_zip_file(&temp_path, &[("page2.png", b"\x89PNG two"), ("page1.png", b"\x89PNG one")]).unwrap();
+
+        let archive = crate::archive::open_archive(&temp_path).unwrap();
+        assert_eq!(archive.archive_type(), ArchiveType::Zip);
+        let cover = archive.find_first_image(true).unwrap();
+        assert_eq!(cover.name, "page1.png");
+        assert_eq!(archive.extract_entry(&cover).unwrap(), b"\x89PNG one");
+
+        // Stream opens go by magic bytes, and a ZIP is what `.phz` promises
+        let data = std::fs::read(&temp_path).unwrap();
+        assert_eq!(open_archive_from_memory(data).unwrap().archive_type(), ArchiveType::Zip);
+        assert!(!crate::archive::note_type_mismatch(Some(".phz"), ArchiveType::Zip));
+        assert!(crate::archive::note_type_mismatch(Some(".phz"), ArchiveType::Rar));
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_find_first_image_all_in_volume_folder() {
+        // Nothing at the root: every page shares the volume folder prefix,
+        // whose name has a dot and a number of its own
+        let mut buffer = Vec::new();
+        {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut buffer));
+            zip.add_directory("Vol. 03", FileOptions::default()).unwrap();
+            for name in ["Vol. 03/page10.jpg", "Vol. 03/page9.jpg", "Vol. 03/page2.jpg", "Vol. 03/info.txt"] {
+                zip.start_file(name, FileOptions::default()).unwrap();
+                zip.write_all(b"\xFF\xD8\xFF image").unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        let archive = open_archive_from_memory(buffer).unwrap();
+
+        // Natural sort on the full path: 2 < 9 < 10
+        assert_eq!(archive.find_first_image(true).unwrap().name, "Vol. 03/page2.jpg");
+        let options = CoverOptions { sort: true, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&options).unwrap().name, "Vol. 03/page2.jpg");
+
+        // Unsorted: first image in archive order, the directory entry is skipped
+        assert_eq!(archive.find_first_image(false).unwrap().name, "Vol. 03/page10.jpg");
+
+        assert_eq!(
+            archive.list_images().unwrap(),
+            ["Vol. 03/page2.jpg", "Vol. 03/page9.jpg", "Vol. 03/page10.jpg"]
+        );
+    }
+
+    #[test]
+    fn test_list_images_with_format() {
+        use crate::image_processor::magic::ImageFormat;
+
+        let temp_path = std::env::temp_dir().join("test_list_images_with_format.zip");
+        create_test_zip_file(
+            &temp_path,
+            &[
+                ("page10.webp", b"RIFF\x24\x00\x00\x00WEBPVP8 image"),
+                ("page2.png", b"\x89PNG\r\n\x1a\n image"),
+                ("notes.txt", b"not an image"),
+                ("page1.jpg", b"\xFF\xD8\xFF\xE0 image"),
+                ("scans/", b""),
+                ("page3.gif", b"GIF89a image"),
+                ("page4.jpg", b"actually text"),
+            ],
+        )
+        .unwrap();
+
+        let archive = ZipArchive::open(&temp_path).unwrap();
+        let formats = |sort| -> Vec<(String, Option<ImageFormat>)> {
+            archive
+                .list_images_with_format(sort)
+                .unwrap()
+                .into_iter()
+                .map(|(entry, format)| (entry.name, format))
+                .collect()
+        };
+
+        assert_eq!(
+            formats(true),
+            vec![
+                ("page1.jpg".to_string(), Some(ImageFormat::Jpeg)),
+                ("page2.png".to_string(), Some(ImageFormat::Png)),
+                ("page3.gif".to_string(), Some(ImageFormat::Gif)),
+                ("page4.jpg".to_string(), None),
+                ("page10.webp".to_string(), Some(ImageFormat::WebP)),
+            ]
+        );
+        assert_eq!(formats(false)[0], ("page10.webp".to_string(), Some(ImageFormat::WebP)));
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_find_cover_image_archive_order() {
+        let temp_path = std::env::temp_dir().join("test_archive_order_cover.zip");
+        create_test_zip_file(
+            &temp_path,
+            &[
+                ("zz_cover.jpg", b"custom cover"),
+                ("page01.jpg", b"image 1"),
+                ("page02.jpg", b"image 2"),
+            ],
+        )
+        .unwrap();
+
+        let archive = ZipArchive::open(&temp_path).unwrap();
+
+        // Sorted: natural order wins, honor flag is ignored
+        let sorted = CoverOptions { sort: true, honor_archive_order: true, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&sorted).unwrap().name, "page01.jpg");
+
+        // Unsorted + honor archive order: first entry is the cover
+        let archive_order = CoverOptions { sort: false, honor_archive_order: true, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&archive_order).unwrap().name, "zz_cover.jpg");
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
+    #[test]
+    fn test_has_no_thumb_marker() {
+        let marked_path = std::env::temp_dir().join("test_nothumb_marked.zip");
+        create_test_zip_file(
+            &marked_path,
+            &[("page01.jpg", b"image 1"), ("NOTHUMB", b"")],
+        )
+        .unwrap();
+
+        let plain_path = std::env::temp_dir().join("test_nothumb_plain.zip");
+        create_test_zip_file(&plain_path, &[("page01.jpg", b"image 1")]).unwrap();
+
+        let marked = ZipArchive::open(&marked_path).unwrap();
+        assert!(marked.has_no_thumb_marker().unwrap());
+
+        let plain = ZipArchive::open(&plain_path).unwrap();
+        assert!(!plain.has_no_thumb_marker().unwrap());
+
+        std::fs::remove_file(&marked_path).ok();
+        std::fs::remove_file(&plain_path).ok();
+    }
+
+    #[test]
+    fn test_find_cover_image_sniff_extensionless() {
+        const PNG_MAGIC: &[u8] = &[0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A, 0, 0, 0, 0];
+        const JPEG_MAGIC: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0, 0, 0x10, b'J', b'F', b'I', b'F'];
+
+        let temp_path = std::env::temp_dir().join("test_sniff_extensionless.zip");
+        create_test_zip_file(
+            &temp_path,
+            &[
+                ("notes", b"plain text, not an image"),
+                ("0002", PNG_MAGIC),
+                ("0001", JPEG_MAGIC),
+            ],
+        )
+        .unwrap();
+
+        let archive = ZipArchive::open(&temp_path).unwrap();
+
+        // Without sniffing no cover is found
+        let plain = CoverOptions { sort: true, ..Default::default() };
+        assert!(archive.find_cover_image(&plain).is_err());
+
+        // Sorted sniffing: "0001" (JPEG magic) wins, "notes" is rejected
+        let sniff = CoverOptions { sort: true, sniff_extensionless: true, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&sniff).unwrap().name, "0001");
+
+        // Unsorted sniffing: first image in archive order
+        let sniff_unsorted = CoverOptions { sniff_extensionless: true, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&sniff_unsorted).unwrap().name, "0002");
+
+        assert_eq!(archive.read_entry_prefix(&archive.find_cover_image(&sniff).unwrap(), 4).unwrap(),
+            &JPEG_MAGIC[..4]);
+
+        std::fs::remove_file(&temp_path).ok();
+    }
+
     #[test]
     fn test_no_images_found() {
         let temp_path = std::env::temp_dir().join("test_no_images.zip");
@@ -310,6 +545,53 @@ mod tests {
         std::fs::remove_file(&temp_path).ok();
     }
 
+    #[test]
+    fn test_extract_entry_raw_deflate() {
+        let content = b"page data ".repeat(500);
+        let data = create_test_zip(&[("image.jpg", &content)]);
+        let archive = ZipArchiveFromMemory::new(data).unwrap();
+        let entry = archive.find_first_image(false).unwrap();
+
+        let (method, raw) = archive.extract_entry_raw(&entry).unwrap();
+        assert_eq!(method, CompressionMethod::Deflated);
+        assert!(raw.len() < content.len());
+
+        // The raw bytes decompress back to the entry
+        let record = &archive.recovery.directory().unwrap().records[0];
+        let mut rebuilt = ZipReader::new(Cursor::new(rebuild_single_entry_zip(record, &raw))).unwrap();
+        let mut decompressed = Vec::new();
+        rebuilt.by_index(0).unwrap().read_to_end(&mut decompressed).unwrap();
+        assert_eq!(decompressed, content);
+    }
+
+    #[test]
+    fn test_compression_method() {
+        let mut buffer = Vec::new();
+        {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut buffer));
+            let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+            zip.start_file("cover.jpg", stored).unwrap();
+            zip.write_all(b"\xFF\xD8\xFF stored cover").unwrap();
+            zip.start_file("page2.jpg", FileOptions::default()).unwrap();
+            zip.write_all(&b"page data ".repeat(50)).unwrap();
+            zip.finish().unwrap();
+        }
+
+        let archive = ZipArchiveFromStream::new(Cursor::new(buffer.clone())).unwrap();
+        let entries = archive.list_entries().unwrap();
+        assert_eq!(archive.compression_method(&entries[0]).unwrap(), "Stored");
+        assert_eq!(archive.compression_method(&entries[1]).unwrap(), "Deflate");
+
+        let archive = open_archive_from_memory(buffer).unwrap();
+        assert_eq!(archive.compression_method(&entries[1]).unwrap(), "Deflate");
+
+        // Methods the zip crate can't read are still named
+        let archive = open_archive_from_memory(create_legacy_method_zip("cover.jpg", 6)).unwrap();
+        let cover = archive.find_cover_image(&CoverOptions::default()).unwrap();
+        assert_eq!(archive.compression_method(&cover).unwrap(), "Implode");
+        assert_eq!(compression_name(CompressionMethod::JPEG), "Method 96");
+    }
+
     #[test]
     fn test_get_metadata() {
         let temp_path = std::env::temp_dir().join("test_metadata.zip");
@@ -333,161 +615,1788 @@ mod tests {
 
         std::fs::remove_file(&temp_path).ok();
     }
-}
-
-/// ZIP archive handler for in-memory data (IStream support)
-pub struct ZipArchiveFromMemory {
-    archive: RefCell<ZipReader<Cursor<Vec<u8>>>>,
-    #[allow(dead_code)] // Used in get_metadata() method for compressed_size
-    data_size: usize,
-}
 
-impl ZipArchiveFromMemory {
-    /// Create a ZIP archive from in-memory data
-    pub fn new(archive: ZipReader<Cursor<Vec<u8>>>) -> Self {
-        let data_size = archive.len();
-        Self {
-            archive: RefCell::new(archive),
-            data_size,
-        }
-    }
+    #[test]
+    fn test_get_metadata_counts_only_images() {
+        let zip = create_test_zip(&[
+            ("pages/", b""),
+            ("pages/001.JPG", b"image 1"),
+            ("pages/002.webp", b"image 2"),
+            ("ComicInfo.xml", b"<ComicInfo/>"),
+            ("cover.jpg.txt", b"not an image"),
+            ("notes.txt", b"text"),
+        ]);
+
+        // The page count the property handler reports
+        let archive = ZipArchiveFromStream::new(Cursor::new(zip.clone())).unwrap();
+        let metadata = archive.get_metadata().unwrap();
+        assert_eq!(metadata.total_files, 6);
+        assert_eq!(metadata.image_count, 2);
 
-    /// Get all entry names (for internal use)
-    fn get_entry_names(&self) -> Vec<String> {
-        let mut archive = self.archive.borrow_mut();
-        (0..archive.len())
-            .filter_map(|i| {
-                archive.by_index(i)
-                    .ok()
-                    .map(|f| f.name().to_string())
-            })
-            .collect()
+        let metadata = open_archive_from_memory(zip).unwrap().get_metadata().unwrap();
+        assert_eq!(metadata.image_count, 2);
     }
 
-    /// Get entry details by name
-    fn get_entry_by_name(&self, name: &str) -> Result<ArchiveEntry> {
-        let mut archive = self.archive.borrow_mut();
+    /// Create a test ZIP archive in memory with an archive comment
+    fn create_test_zip_with_comment(files: &[(&str, &[u8])], comment: &str) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut buffer));
+            zip.set_comment(comment);
 
-        for i in 0..archive.len() {
-            let zip_entry = archive.by_index(i)
-                .map_err(|e| CbxError::Archive(format!("Failed to get entry {}: {}", i, e)))?;
-
-            if zip_entry.name() == name {
-                return Ok(ArchiveEntry {
-                    name: name.to_string(),
-                    size: zip_entry.size(),
-                    is_directory: zip_entry.is_dir(),
-                });
+            for (name, content) in files {
+                zip.start_file(*name, FileOptions::default()).unwrap();
+                zip.write_all(content).unwrap();
             }
+
+            zip.finish().unwrap();
         }
+        buffer
+    }
 
-        Err(CbxError::Archive(format!("Entry not found: {}", name)))
+    /// Build an EPUB whose spine order differs from alphabetical order
+    fn create_test_epub(opf: &str) -> Vec<u8> {
+        let container = r#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#;
+        let page = |img: &str| format!(r#"<html><body><img src="../images/{}"/></body></html>"#, img);
+        let (page1, page2) = (page("zebra.jpg"), page("apple.jpg"));
+
+        create_test_zip(&[
+            ("mimetype", b"application/epub+zip"),
+            ("META-INF/container.xml", container.as_bytes()),
+            ("OEBPS/content.opf", opf.as_bytes()),
+            ("OEBPS/text/page1.xhtml", page1.as_bytes()),
+            ("OEBPS/text/page2.xhtml", page2.as_bytes()),
+            ("OEBPS/images/apple.jpg", b"second page"),
+            ("OEBPS/images/mango.png", b"not in spine"),
+            ("OEBPS/images/zebra.jpg", b"first page"),
+        ])
     }
-}
 
-impl Archive for ZipArchiveFromMemory {
-    fn open(_path: &Path) -> Result<Box<dyn Archive>> {
-        // Not used for in-memory archives
-        Err(CbxError::Archive("Use open_archive_from_memory instead".to_string()))
+    #[test]
+    fn test_list_images_epub_spine_order() {
+        let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+  <manifest>
+    <item id="p1" href="text/page1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="p2" href="text/page2.xhtml" media-type="application/xhtml+xml"/>
+    <item id="img-a" href="images/apple.jpg" media-type="image/jpeg"/>
+    <item id="img-m" href="images/mango.png" media-type="image/png"/>
+    <item id="img-z" href="images/zebra.jpg" media-type="image/jpeg"/>
+  </manifest>
+  <spine><itemref idref="p1"/><itemref idref="p2"/></spine>
+</package>"#;
+
+        let archive = ZipArchiveFromStream::new(Cursor::new(create_test_epub(opf))).unwrap();
+        assert_eq!(
+            archive.list_images().unwrap(),
+            vec![
+                "OEBPS/images/zebra.jpg",
+                "OEBPS/images/apple.jpg",
+                "OEBPS/images/mango.png",
+            ]
+        );
     }
 
-    fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
-        tracing::debug!("Finding first image in ZIP from memory (sort={})", sort);
+    #[test]
+    fn test_list_images_epub_unparseable_opf_falls_back_to_natural_sort() {
+        let archive = ZipArchiveFromStream::new(Cursor::new(create_test_epub("garbage"))).unwrap();
+        assert_eq!(
+            archive.list_images().unwrap(),
+            vec![
+                "OEBPS/images/apple.jpg",
+                "OEBPS/images/mango.png",
+                "OEBPS/images/zebra.jpg",
+            ]
+        );
+    }
 
-        if !sort {
-            // OPTIMIZATION: When not sorting, find first image immediately
-            tracing::debug!("Fast path: finding first image without full listing");
+    #[test]
+    fn test_metadata_reports_newest_entry_time() {
+        use std::time::{Duration, UNIX_EPOCH};
+        use zip::DateTime;
 
-            let mut archive = self.archive.borrow_mut();
-            for i in 0..archive.len() {
-                if let Ok(entry) = archive.by_index(i) {
-                    let name = entry.name().to_string();
-                    if is_image_file(&name) {
-                        tracing::info!("Found first image (unsorted): {}", name);
-                        return Ok(ArchiveEntry {
-                            name,
-                            size: entry.size(),
-                            is_directory: entry.is_dir(),
-                        });
-                    }
-                }
+        let mut buffer = Vec::new();
+        {
+            let mut zip = ZipWriter::new(Cursor::new(&mut buffer));
+            let entries = [
+                ("page1.jpg", DateTime::from_date_and_time(2023, 1, 2, 3, 4, 6).unwrap()),
+                ("page2.jpg", DateTime::from_date_and_time(2024, 3, 15, 12, 34, 56).unwrap()),
+                ("notes.txt", DateTime::from_date_and_time(2020, 6, 1, 0, 0, 0).unwrap()),
+            ];
+            for (name, time) in entries {
+                zip.start_file(name, FileOptions::default().last_modified_time(time)).unwrap();
+                zip.write_all(b"data").unwrap();
             }
-
-            return Err(CbxError::Archive("No images found in archive".to_string()));
+            zip.finish().unwrap();
         }
 
-        // STANDARD PATH: List all entries and sort
-        let entry_names = self.get_entry_names();
+        let archive = ZipArchiveFromStream::new(Cursor::new(buffer)).unwrap();
+        let metadata = archive.get_metadata().unwrap();
 
-        if entry_names.is_empty() {
-            return Err(CbxError::Archive("Archive is empty".to_string()));
+        // 2024-03-15 12:34:56 UTC
+        assert_eq!(metadata.modified, Some(UNIX_EPOCH + Duration::from_secs(1_710_506_096)));
+    }
+
+    #[test]
+    fn test_find_cover_image_opf_cover_strategy() {
+        let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata><meta name="cover" content="img-m"/></metadata>
+  <manifest>
+    <item id="p1" href="text/page1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="img-m" href="images/mango.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="p1"/></spine>
+</package>"#;
+
+        let archive = ZipArchiveFromStream::new(Cursor::new(create_test_epub(opf))).unwrap();
+
+        let opf_cover = CoverOptions { sort: true, strategy: CoverStrategy::OpfCover, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&opf_cover).unwrap().name, "OEBPS/images/mango.png");
+
+        let first_image = CoverOptions { sort: true, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&first_image).unwrap().name, "OEBPS/images/apple.jpg");
+
+        // A plain ZIP under OpfCover behaves like FirstImage
+        let zip = create_test_zip(&[("b.jpg", b"b"), ("a.jpg", b"a")]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(zip)).unwrap();
+        assert_eq!(archive.find_cover_image(&opf_cover).unwrap().name, "a.jpg");
+    }
+
+    #[test]
+    fn test_find_cover_image_comicinfo_strategy() {
+        let comic_info = br#"<?xml version="1.0"?>
+<ComicInfo>
+  <Pages>
+    <Page Image="0" Type="Advertisement"/>
+    <Page Image="1" Type="FrontCover"/>
+  </Pages>
+</ComicInfo>"#;
+        // Images in page order: page1 (ad), page2 (cover), page10
+        let zip = create_test_zip(&[
+            ("page10.jpg", b"ten"),
+            ("ComicInfo.xml", comic_info),
+            ("page2.jpg", b"two"),
+            ("page1.jpg", b"ad"),
+        ]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(zip)).unwrap();
+
+        let comic_info_cover = CoverOptions { sort: true, strategy: CoverStrategy::ComicInfo, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&comic_info_cover).unwrap().name, "page2.jpg");
+
+        let first_image = CoverOptions { sort: true, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&first_image).unwrap().name, "page1.jpg");
+
+        // No ComicInfo.xml, or an index past the last image: first image
+        for files in [
+            &[("page2.jpg", &b"two"[..]), ("page1.jpg", b"ad")][..],
+            &[("ComicInfo.xml", br#"<Pages><Page Image="7" Type="FrontCover"/></Pages>"#), ("page1.jpg", b"ad")],
+        ] {
+            let archive = ZipArchiveFromStream::new(Cursor::new(create_test_zip(files))).unwrap();
+            assert_eq!(archive.find_cover_image(&comic_info_cover).unwrap().name, "page1.jpg");
         }
+    }
 
-        // Find first image using shared utility
-        let image_name = find_first_image(entry_names.iter().map(|s| s.as_str()), sort)
-            .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
+    #[test]
+    fn test_find_cover_image_smart_cover_strategy() {
+        use image::{Rgb, RgbImage};
+
+        let png = |image: RgbImage| {
+            let mut data = Vec::new();
+            image.write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png).unwrap();
+            data
+        };
+        // Black text lines on white, then a colorful cover
+        let text_page = png(RgbImage::from_fn(40, 60, |_, y| if y % 6 == 0 { Rgb([0, 0, 0]) } else { Rgb([250, 250, 250]) }));
+        let cover = png(RgbImage::from_fn(40, 60, |x, y| Rgb([(x * 6) as u8, 200, (y * 4) as u8])));
+
+        let zip = create_test_zip(&[
+            ("page002.png", &cover),
+            ("page001.png", &text_page),
+            ("page003.png", &text_page),
+        ]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(zip)).unwrap();
+
+        let smart_cover = CoverOptions { sort: true, strategy: CoverStrategy::SmartCover, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&smart_cover).unwrap().name, "page002.png");
+
+        let first_image = CoverOptions { sort: true, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&first_image).unwrap().name, "page001.png");
+
+        // Nothing decodable: natural-sort first image
+        let zip = create_test_zip(&[("page2.jpg", b"two"), ("page1.jpg", b"one")]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(zip)).unwrap();
+        assert_eq!(archive.find_cover_image(&smart_cover).unwrap().name, "page1.jpg");
+    }
 
-        tracing::info!("Found first image (sorted): {}", image_name);
+    #[test]
+    fn test_find_nth_image() {
+        let zip = create_test_zip(&[
+            ("page10.jpg", b"ten"),
+            ("logo.png", b"logo"),
+            ("notes.txt", b"text"),
+            ("page2.jpg", b"two"),
+            ("page1.jpg", b"one"),
+        ]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(zip)).unwrap();
+
+        // Natural order: logo, page1, page2, page10
+        assert_eq!(archive.find_nth_image(0, true).unwrap().name, "logo.png");
+        assert_eq!(archive.find_nth_image(2, true).unwrap().name, "page2.jpg");
+        assert_eq!(archive.find_nth_image(2, false).unwrap().name, "page2.jpg");
+        assert_eq!(archive.find_nth_image(1, false).unwrap().name, "logo.png");
+        assert_eq!(archive.find_nth_image(100, true).unwrap().name, "page10.jpg");
+        assert_eq!(archive.find_nth_image(100, false).unwrap().name, "page1.jpg");
+
+        // CoverPageIndex goes through the cover options
+        let second = CoverOptions { sort: true, page_index: 1, ..Default::default() };
+        assert!(!second.is_plain());
+        assert_eq!(archive.find_cover_image(&second).unwrap().name, "page1.jpg");
+
+        let empty = create_test_zip(&[("notes.txt", b"text")]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(empty)).unwrap();
+        assert!(archive.find_nth_image(1, true).is_err());
+    }
 
-        // Get entry details
-        self.get_entry_by_name(&image_name)
+    #[test]
+    fn test_find_cover_image_percent() {
+        let zip = create_test_zip(&[
+            ("page10.jpg", b"ten"),
+            ("notes.txt", b"text"),
+            ("page2.jpg", b"two"),
+            ("page1.jpg", b"one"),
+            ("page3.jpg", b"three"),
+        ]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(zip)).unwrap();
+        let percent = |percent| CoverOptions { sort: true, strategy: CoverStrategy::Percent(percent), ..Default::default() };
+
+        // Natural order: page1, page2, page3, page10
+        assert_eq!(archive.find_cover_image(&percent(0)).unwrap().name, "page1.jpg");
+        assert_eq!(archive.find_cover_image(&percent(50)).unwrap().name, "page3.jpg");
+        assert_eq!(archive.find_cover_image(&percent(100)).unwrap().name, "page10.jpg");
+
+        let empty = create_test_zip(&[("notes.txt", b"text")]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(empty)).unwrap();
+        assert!(archive.find_cover_image(&percent(50)).is_err());
     }
 
-    fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
-        tracing::debug!("Extracting entry from memory: {} ({} bytes)", entry.name, entry.size);
+    #[test]
+    fn test_epub_utf16_namespaced_package() {
+        let utf16le = |text: &str| -> Vec<u8> {
+            let mut data = vec![0xFF, 0xFE];
+            data.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            data
+        };
+        let utf16be = |text: &str| -> Vec<u8> {
+            text.encode_utf16().flat_map(u16::to_be_bytes).collect()
+        };
+
+        let container = r#"<?xml version="1.0" encoding="UTF-16"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles>
+</container>"#;
+        let opf = r#"<?xml version="1.0" encoding="UTF-16"?>
+<opf:package xmlns:opf="http://www.idpf.org/2007/opf" version="2.0">
+  <opf:metadata><opf:meta opf:name="cover" opf:content="img-m"/></opf:metadata>
+  <opf:manifest>
+    <opf:item id="img-z" href="images/zebra.jpg" media-type="image/jpeg"/>
+    <opf:item id="img-a" href="images/apple.jpg" media-type="image/jpeg"/>
+    <opf:item id="img-m" href="images/mango.png" media-type="image/png"/>
+  </opf:manifest>
+  <opf:spine><opf:itemref idref="img-z"/><opf:itemref idref="img-a"/></opf:spine>
+</opf:package>"#;
+
+        let epub = create_test_zip(&[
+            ("mimetype", b"application/epub+zip"),
+            ("META-INF/container.xml", &utf16be(container)),
+            ("OEBPS/content.opf", &utf16le(opf)),
+            ("OEBPS/images/apple.jpg", b"second page"),
+            ("OEBPS/images/mango.png", b"cover"),
+            ("OEBPS/images/zebra.jpg", b"first page"),
+        ]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(epub)).unwrap();
+
+        assert_eq!(
+            archive.list_images().unwrap(),
+            vec!["OEBPS/images/zebra.jpg", "OEBPS/images/apple.jpg", "OEBPS/images/mango.png"]
+        );
+        let opf_cover = CoverOptions { sort: true, strategy: CoverStrategy::OpfCover, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&opf_cover).unwrap().name, "OEBPS/images/mango.png");
+    }
 
-        // Safety check: prevent memory exhaustion
-        if entry.size > MAX_ENTRY_SIZE {
-            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, MAX_ENTRY_SIZE);
-            return Err(CbxError::Archive(format!(
-                "Entry too large: {} bytes (max 32MB)",
-                entry.size
-            )));
-        }
+    #[test]
+    fn test_epub_special_files_found_regardless_of_case() {
+        let container = r#"<container><rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles></container>"#;
+        let opf = r#"<package><metadata><meta name="cover" content="img-m"/></metadata>
+  <manifest>
+    <item id="p1" href="Text/Page1.xhtml" media-type="application/xhtml+xml"/>
+    <item id="img-m" href="images/mango.png" media-type="image/png"/>
+  </manifest>
+  <spine><itemref idref="p1"/></spine>
+</package>"#;
+        let zip = create_test_zip(&[
+            ("META-INF/Container.XML", container.as_bytes()),
+            ("OEBPS/Content.OPF", opf.as_bytes()),
+            ("OEBPS/text/page1.xhtml", br#"<img src="../Images/Zebra.JPG"/>"#),
+            ("OEBPS/Images/Mango.PNG", b"declared cover"),
+            ("OEBPS/images/zebra.jpg", b"first page"),
+            ("OEBPS/images/apple.jpg", b"not in spine"),
+        ]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(zip)).unwrap();
+
+        let opf_cover = CoverOptions { sort: true, strategy: CoverStrategy::OpfCover, ..Default::default() };
+        assert_eq!(archive.find_cover_image(&opf_cover).unwrap().name, "OEBPS/Images/Mango.PNG");
+        assert_eq!(
+            archive.list_images().unwrap(),
+            vec!["OEBPS/images/zebra.jpg", "OEBPS/Images/Mango.PNG", "OEBPS/images/apple.jpg"]
+        );
+    }
 
-        let mut archive = self.archive.borrow_mut();
+    #[test]
+    fn test_eocd_search_window() {
+        assert_eq!(EOCD_SEARCH_WINDOW, 65557);
+    }
 
-        // Find and extract entry by name
-        let mut zip_entry = archive
-            .by_name(&entry.name)
-            .map_err(|e| CbxError::Archive(format!("Entry not found: {}", e)))?;
+    #[test]
+    fn test_stream_zip_with_max_length_comment() {
+        // Comment of the maximum length pushes the EOCD to the edge of the window
+        let comment = "c".repeat(u16::MAX as usize);
+        let data = create_test_zip_with_comment(
+            &[("page002.jpg", b"second"), ("page001.jpg", b"cover")],
+            &comment,
+        );
 
-        // Read to buffer
-        let mut buffer = Vec::with_capacity(entry.size as usize);
-        zip_entry
-            .read_to_end(&mut buffer)
-            .map_err(|e| CbxError::Archive(format!("Failed to extract entry: {}", e)))?;
+        let eocd = locate_eocd(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(eocd, data.len() as u64 - EOCD_SEARCH_WINDOW);
 
-        tracing::debug!("Extracted {} bytes", buffer.len());
-        Ok(buffer)
+        let archive = ZipArchiveFromStream::new(Cursor::new(data)).unwrap();
+        let cover = archive.find_first_image(true).unwrap();
+        assert_eq!(cover.name, "page001.jpg");
+        assert_eq!(archive.extract_entry(&cover).unwrap(), b"cover");
     }
 
-    fn get_metadata(&self) -> Result<ArchiveMetadata> {
-        let entry_names = self.get_entry_names();
-        let total_files = entry_names.len();
-        let image_count = entry_names
-            .iter()
-            .filter(|name| is_image_file(name))
-            .count();
-
-        tracing::debug!(
-            "ZIP metadata (from memory): {} files, {} images",
-            total_files,
-            image_count
-        );
+    #[test]
+    fn test_locate_eocd_ignores_signature_in_comment() {
+        // Comment text containing the EOCD signature must not be mistaken for the record
+        let comment = "PK\u{5}\u{6}".to_string() + &"x".repeat(1000);
+        let data = create_test_zip_with_comment(&[("page001.jpg", b"cover")], &comment);
 
-        Ok(ArchiveMetadata {
-            total_files,
-            image_count,
-            compressed_size: self.data_size as u64,
-            archive_type: ArchiveType::Zip,
-        })
+        let eocd = locate_eocd(&mut Cursor::new(&data)).unwrap();
+        assert_eq!(eocd, (data.len() - comment.len()) as u64 - EOCD_RECORD_LEN);
     }
 
-    fn archive_type(&self) -> ArchiveType {
-        ArchiveType::Zip
+    #[test]
+    fn test_locate_eocd_missing() {
+        let result = locate_eocd(&mut Cursor::new(vec![0u8; 100_000]));
+        assert!(result.is_err());
+
+        assert!(ZipArchiveFromStream::new(Cursor::new(vec![0u8; 10])).is_err());
     }
-}
+
+    #[test]
+    fn test_trailing_data_is_ignored() {
+        let zip = create_test_zip(&[("page2.jpg", b"image 2"), ("page1.jpg", b"image 1")]);
+        // Within the EOCD search window, and well past it
+        for junk_len in [1_000, 200_000] {
+            let mut data = zip.clone();
+            data.extend(std::iter::repeat(0xAB).take(junk_len));
+            if junk_len > EOCD_SEARCH_WINDOW as usize {
+                assert!(locate_eocd(&mut Cursor::new(&data)).is_err());
+                assert_eq!(find_zip_end(&mut Cursor::new(&data)), Some(zip.len() as u64));
+            }
+
+            let temp_path = std::env::temp_dir().join(format!("test_trailing_data_{}.cbz", junk_len));
+            std::fs::write(&temp_path, &data).unwrap();
+
+            let archives: Vec<Box<dyn Archive>> = vec![
+                Box::new(ZipArchive::open(&temp_path).unwrap()),
+                Box::new(ZipArchiveFromMemory::new(data.clone()).unwrap()),
+                Box::new(ZipArchiveFromStream::new(Cursor::new(data)).unwrap()),
+            ];
+            for archive in archives {
+                assert_eq!(archive.list_entries().unwrap().len(), 2);
+                let cover = archive.find_first_image(true).unwrap();
+                assert_eq!(cover.name, "page1.jpg");
+                assert_eq!(archive.extract_entry(&cover).unwrap(), b"image 1");
+            }
+
+            std::fs::remove_file(&temp_path).ok();
+        }
+
+        assert_eq!(find_zip_end(&mut Cursor::new(vec![0xABu8; 200_000])), None);
+    }
+
+    #[test]
+    fn test_backslash_entry_names_are_normalized() {
+        let data = create_test_zip(&[
+            ("images2/00.jpg", b"other"),
+            ("images\\01.jpg", b"cover"),
+            ("images\\.nothumb", b""),
+        ]);
+        let archive = ZipArchiveFromStream::new(Cursor::new(data)).unwrap();
+
+        let names: Vec<String> = archive.list_entries().unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["images2/00.jpg", "images/01.jpg", "images/.nothumb"]);
+
+        // "images/" sorts before "images2/"; with the raw '\' it would not
+        let cover = archive.find_first_image(true).unwrap();
+        assert_eq!(cover.name, "images/01.jpg");
+        assert_eq!(archive.extract_entry(&cover).unwrap(), b"cover");
+        assert_eq!(archive.read_entry_prefix(&cover, 2).unwrap(), b"co");
+
+        assert!(archive.has_no_thumb_marker().unwrap());
+    }
+
+    #[test]
+    fn test_recover_entry_with_damaged_local_header() {
+        let cover: Vec<u8> = b"\xFF\xD8\xFF".iter().chain(&[0x42; 4096]).copied().collect();
+        let mut data = create_test_zip(&[("page001.jpg", &cover), ("page002.jpg", b"second")]);
+
+        // Wipe the first entry's local header; the central directory is intact
+        data[..30].fill(0);
+
+        let archive = ZipArchiveFromStream::new(Cursor::new(data.clone())).unwrap();
+        let names: Vec<String> = archive.list_entries().unwrap().into_iter().map(|e| e.name).collect();
+        assert_eq!(names, ["page001.jpg", "page002.jpg"]);
+
+        let first = archive.find_first_image(true).unwrap();
+        assert_eq!(first.name, "page001.jpg");
+        assert_eq!(first.size, cover.len() as u64);
+        assert_eq!(archive.extract_entry(&first).unwrap(), cover);
+        assert_eq!(archive.read_entry_prefix(&first, 3).unwrap(), b"\xFF\xD8\xFF");
+
+        let unsorted = archive.find_first_image(false).unwrap();
+        assert_eq!(unsorted.name, "page001.jpg");
+
+        // Intact entries still go through the zip crate
+        let entry = ArchiveEntry { name: "page002.jpg".to_string(), size: 6, is_directory: false };
+        assert_eq!(archive.extract_entry(&entry).unwrap(), b"second");
+
+        let memory = ZipArchiveFromMemory::new(data).unwrap();
+        assert_eq!(memory.extract_entry(&first).unwrap(), cover);
+    }
+
+    /// Single-entry ZIP with a WinZip AES (`AE-2`, AES-256, deflate) entry
+    fn create_aes_zip(name: &str, flags: u16) -> Vec<u8> {
+        let extra: &[u8] = &[0x01, 0x99, 7, 0, 2, 0, b'A', b'E', 3, 8, 0];
+        // Salt, password verifier, "encrypted" data and authentication code
+        let data = [0x5A; 16 + 2 + 12 + 10];
+        create_single_entry_zip(name, flags, METHOD_AES, 0, 12, extra, &data)
+    }
+
+    /// Single entry with the given header fields and raw (stored) `data`
+    fn create_single_entry_zip(
+        name: &str,
+        flags: u16,
+        method: u16,
+        crc: u32,
+        size: u32,
+        extra: &[u8],
+        data: &[u8],
+    ) -> Vec<u8> {
+        let name = name.as_bytes();
+        let mut zip = Vec::new();
+        let put16 = |zip: &mut Vec<u8>, value: u16| zip.extend_from_slice(&value.to_le_bytes());
+        let put32 = |zip: &mut Vec<u8>, value: u32| zip.extend_from_slice(&value.to_le_bytes());
+
+        zip.extend_from_slice(&LOCAL_HEADER_SIGNATURE);
+        for value in [51, flags, method, 0, 0x21] {
+            put16(&mut zip, value);
+        }
+        for value in [crc, data.len() as u32, size] {
+            put32(&mut zip, value);
+        }
+        put16(&mut zip, name.len() as u16);
+        put16(&mut zip, extra.len() as u16);
+        zip.extend_from_slice(name);
+        zip.extend_from_slice(extra);
+        zip.extend_from_slice(data);
+
+        let directory_offset = zip.len() as u32;
+        zip.extend_from_slice(&CENTRAL_HEADER_SIGNATURE);
+        for value in [51, 51, flags, method, 0, 0x21] {
+            put16(&mut zip, value);
+        }
+        for value in [crc, data.len() as u32, size] {
+            put32(&mut zip, value);
+        }
+        for value in [name.len() as u16, extra.len() as u16, 0, 0, 0] {
+            put16(&mut zip, value);
+        }
+        put32(&mut zip, 0);
+        put32(&mut zip, 0);
+        zip.extend_from_slice(name);
+        zip.extend_from_slice(extra);
+        let directory_len = zip.len() as u32 - directory_offset;
+
+        zip.extend_from_slice(&EOCD_SIGNATURE);
+        for value in [0, 0, 1, 1] {
+            put16(&mut zip, value);
+        }
+        put32(&mut zip, directory_len);
+        put32(&mut zip, directory_offset);
+        put16(&mut zip, 0);
+        zip
+    }
+
+    #[test]
+    fn test_aes_entry_is_reported_as_encrypted() {
+        assert_eq!(aes_vendor_version(&[0x01, 0x99, 7, 0, 2, 0, b'A', b'E', 3, 8, 0]), Some(2));
+        assert_eq!(aes_vendor_version(&[0x55, 0x54, 1, 0, 0]), None);
+
+        // Without the encryption flag the zip crate panics, so the entry is
+        // listed but no cover candidate, and reading it is refused
+        let archive = ZipArchiveFromStream::new(Cursor::new(create_aes_zip("cover.jpg", 0))).unwrap();
+        let entries = archive.list_entries().unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(matches!(archive.find_first_image(true), Err(CbxError::Archive(_))));
+
+        let cover = &entries[0];
+        assert_eq!(cover.name, "cover.jpg");
+        let error = archive.extract_entry(cover).unwrap_err();
+        assert!(matches!(&error, CbxError::Encrypted(msg) if msg.contains("AE-2")), "{}", error);
+        assert!(matches!(archive.read_entry_prefix(cover, 4), Err(CbxError::Encrypted(_))));
+
+        // With the flag it is a cover candidate that needs a password
+        let zip = create_aes_zip("cover.jpg", FLAG_ENCRYPTED);
+        let archive = ZipArchiveFromStream::open_with_password(Cursor::new(zip.clone()), None).unwrap();
+        let cover = archive.find_first_image(true).unwrap();
+        assert_eq!(cover.name, "cover.jpg");
+        let error = archive.extract_entry(&cover).unwrap_err();
+        assert!(matches!(&error, CbxError::PasswordRequired(msg) if msg.contains("AE-2")), "{}", error);
+        assert!(matches!(archive.read_entry_prefix(&cover, 4), Err(CbxError::PasswordRequired(_))));
+
+        // The fixture's password verifier matches no password
+        let archive = ZipArchiveFromStream::with_password(Cursor::new(zip), "secret").unwrap();
+        let error = archive.extract_entry(&cover).unwrap_err();
+        assert!(matches!(&error, CbxError::Encrypted(msg) if msg.contains("wrong password")), "{}", error);
+    }
+
+    fn crc32_update(crc: u32, byte: u8) -> u32 {
+        let mut crc = crc ^ byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+        crc
+    }
+
+    /// Single stored entry encrypted with ZipCrypto (traditional PKWARE)
+    fn create_zipcrypto_zip(name: &str, data: &[u8], password: &str) -> Vec<u8> {
+        let crc = !data.iter().fold(!0, |crc, &byte| crc32_update(crc, byte));
+
+        let mut keys = [0x1234_5678u32, 0x2345_6789, 0x3456_7890];
+        let update = |keys: &mut [u32; 3], byte: u8| {
+            keys[0] = crc32_update(keys[0], byte);
+            keys[1] = keys[1].wrapping_add(keys[0] & 0xFF).wrapping_mul(134_775_813).wrapping_add(1);
+            keys[2] = crc32_update(keys[2], (keys[1] >> 24) as u8);
+        };
+        for byte in password.bytes() {
+            update(&mut keys, byte);
+        }
+
+        // 12-byte header whose last byte is the password check
+        let mut plain = vec![0xA5; 11];
+        plain.push((crc >> 24) as u8);
+        plain.extend_from_slice(data);
+        let encrypted: Vec<u8> = plain
+            .into_iter()
+            .map(|byte| {
+                let temp = (keys[2] | 2) as u16;
+                let cipher = byte ^ (temp.wrapping_mul(temp ^ 1) >> 8) as u8;
+                update(&mut keys, byte);
+                cipher
+            })
+            .collect();
+
+        create_single_entry_zip(name, FLAG_ENCRYPTED, 0, crc, data.len() as u32, &[], &encrypted)
+    }
+
+    #[test]
+    fn test_zipcrypto_entry_is_decrypted_with_password() {
+        let jpeg = b"\xFF\xD8\xFF\xE0 password-protected cover";
+        let zip = create_zipcrypto_zip("cover.jpg", jpeg, "secret");
+
+        let archive = ZipArchiveFromStream::with_password(Cursor::new(zip.clone()), "secret").unwrap();
+        let cover = archive.find_first_image(true).unwrap();
+        assert_eq!(cover.name, "cover.jpg");
+        assert_eq!(archive.extract_entry(&cover).unwrap(), jpeg);
+        assert_eq!(archive.read_entry_prefix(&cover, 4).unwrap(), &jpeg[..4]);
+
+        // "wrong" fails the check byte (others may only fail the CRC)
+        let archive = ZipArchiveFromStream::with_password(Cursor::new(zip.clone()), "wrong").unwrap();
+        let error = archive.extract_entry(&cover).unwrap_err();
+        assert!(matches!(&error, CbxError::Encrypted(msg) if msg.contains("wrong password")), "{}", error);
+
+        let archive = ZipArchiveFromStream::open_with_password(Cursor::new(zip), None).unwrap();
+        let error = archive.extract_entry(&cover).unwrap_err();
+        assert!(matches!(&error, CbxError::PasswordRequired(msg) if msg.contains("ZipCrypto")), "{}", error);
+    }
+
+    /// Single stored entry relabeled with compression `method`
+    fn create_legacy_method_zip(name: &str, method: u16) -> Vec<u8> {
+        let mut zip = create_test_zip_with_comment(&[(name, b"\x1F\x8B legacy payload")], "");
+
+        let local = zip.windows(4).position(|w| w == LOCAL_HEADER_SIGNATURE).unwrap();
+        zip[local + 8..local + 10].copy_from_slice(&method.to_le_bytes());
+        let central = zip.windows(4).position(|w| w == CENTRAL_HEADER_SIGNATURE).unwrap();
+        zip[central + 10..central + 12].copy_from_slice(&method.to_le_bytes());
+        zip
+    }
+
+    #[test]
+    fn test_legacy_compression_methods_are_named() {
+        for (method, name) in [(6, "Implode"), (1, "Shrink"), (3, "Reduce")] {
+            let archive = open_archive_from_memory(create_legacy_method_zip("cover.jpg", method)).unwrap();
+
+            // Listing and cover selection work; reading the cover names the method
+            let cover = archive.find_cover_image(&CoverOptions::default()).unwrap();
+            assert_eq!(cover.name, "cover.jpg");
+
+            let error = archive.extract_entry(&cover).unwrap_err();
+            assert!(matches!(&error, CbxError::UnsupportedFormat(msg) if msg == name), "{}", error);
+            assert!(matches!(archive.read_entry_prefix(&cover, 4), Err(CbxError::UnsupportedFormat(_))));
+        }
+    }
+
+    #[test]
+    fn test_compressed_size_past_end_of_archive_is_corrupt() {
+        let mut zip = create_test_zip_with_comment(&[("cover.jpg", b"\xFF\xD8\xFF cover")], "");
+        let len = zip.len() as u32;
+        let central = zip.windows(4).position(|w| w == CENTRAL_HEADER_SIGNATURE).unwrap();
+        zip[central + 20..central + 24].copy_from_slice(&(len + 1).to_le_bytes());
+
+        let archive = open_archive_from_memory(zip).unwrap();
+        let cover = archive.find_first_image(true).unwrap();
+        let error = archive.extract_entry(&cover).unwrap_err();
+        assert!(matches!(&error, CbxError::Corrupt(msg) if msg.contains("cover.jpg")), "{}", error);
+        assert!(matches!(archive.read_entry_prefix(&cover, 4), Err(CbxError::Corrupt(_))));
+    }
+
+    #[test]
+    fn test_find_first_decodable_image_skips_corrupt_cover() {
+        let mut jpeg = Vec::new();
+        image::RgbImage::new(4, 4)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+        let truncated = &jpeg[..jpeg.len() / 4];
+        let mut decode = |_: &ArchiveEntry, data: Vec<u8>| crate::image_processor::decoder::decode_image(&data).map(drop);
+
+        let archive = open_archive_from_memory(create_test_zip(&[
+            ("page2.jpg", &jpeg[..]),
+            ("page1.jpg", truncated),
+        ]))
+        .unwrap();
+        assert_eq!(archive.find_first_image(true).unwrap().name, "page1.jpg");
+        assert_eq!(archive.find_first_decodable_image(true, &mut decode).unwrap().name, "page2.jpg");
+
+        // Nothing decodes: the first candidate's error
+        let archive = open_archive_from_memory(create_test_zip(&[
+            ("page1.jpg", truncated),
+            ("page2.jpg", b"\xFF\xD8\xFF not a jpeg"),
+        ]))
+        .unwrap();
+        assert!(archive.find_first_decodable_image(false, &mut decode).is_err());
+    }
+}
+
+/// ZIP archive handler for in-memory data (IStream support)
+pub struct ZipArchiveFromMemory {
+    archive: RefCell<ZipReader<SharedReader<Cursor<Vec<u8>>>>>,
+    recovery: CentralDirectoryOnly<Cursor<Vec<u8>>>,
+    #[allow(dead_code)] // Used in get_metadata() method for compressed_size
+    data_size: usize,
+    /// Password for encrypted entries (`ArchivePassword`)
+    password: Option<String>,
+}
+
+impl ZipArchiveFromMemory {
+    /// Create a ZIP archive from in-memory data
+    pub fn new(data: Vec<u8>) -> Result<Self> {
+        let reader = open_shared_reader(Cursor::new(data))?;
+        let archive = ZipReader::new(reader.clone())
+            .map_err(|e| CbxError::Archive(format!("Failed to open ZIP from memory: {}", e)))?;
+
+        let data_size = archive.len();
+        Ok(Self {
+            archive: RefCell::new(archive),
+            recovery: CentralDirectoryOnly::new(reader),
+            data_size,
+            password: read_archive_password(),
+        })
+    }
+
+    /// Get all entry names (for internal use)
+    fn get_entry_names(&self) -> Vec<String> {
+        let mut archive = self.archive.borrow_mut();
+        (0..archive.len())
+            .filter_map(|i| {
+                self.recovery.entry_at(&mut archive, i, false)
+                    .ok()
+                    .map(|e| e.name)
+            })
+            .collect()
+    }
+
+    /// Get entry details by name
+    fn get_entry_by_name(&self, name: &str) -> Result<ArchiveEntry> {
+        let mut archive = self.archive.borrow_mut();
+
+        for i in 0..archive.len() {
+            let entry = self.recovery.entry_at(&mut archive, i, false)?;
+            if entry.name == name {
+                return Ok(entry);
+            }
+        }
+
+        Err(CbxError::Archive(format!("Entry not found: {}", name)))
+    }
+}
+
+impl Archive for ZipArchiveFromMemory {
+    fn open(_path: &Path) -> Result<Box<dyn Archive>> {
+        // Not used for in-memory archives
+        Err(CbxError::Archive("Use open_archive_from_memory instead".to_string()))
+    }
+
+    /// List all entries in archive order
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        let mut archive = self.archive.borrow_mut();
+        let mut entries = Vec::with_capacity(archive.len());
+
+        for i in 0..archive.len() {
+            // Raw access: listing must not fail on encrypted entries
+            entries.push(self.recovery.entry_at(&mut archive, i, true)?);
+        }
+
+        Ok(entries)
+    }
+
+    fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
+        tracing::debug!("Finding first image in ZIP from memory (sort={})", sort);
+
+        if !sort {
+            // OPTIMIZATION: When not sorting, find first image immediately
+            tracing::debug!("Fast path: finding first image without full listing");
+
+            let mut archive = self.archive.borrow_mut();
+            for i in 0..archive.len() {
+                if let Ok(entry) = self.recovery.entry_at(&mut archive, i, false) {
+                    if is_image_file(&entry.name) {
+                        tracing::info!("Found first image (unsorted): {}", bounded_name(&entry.name));
+                        return Ok(entry);
+                    }
+                }
+            }
+
+            return Err(CbxError::Archive("No images found in archive".to_string()));
+        }
+
+        // STANDARD PATH: List all entries and sort
+        let entry_names = self.get_entry_names();
+
+        if entry_names.is_empty() {
+            return Err(CbxError::Archive("Archive is empty".to_string()));
+        }
+
+        // Find first image using shared utility
+        let image_name = find_first_image(entry_names.iter().map(|s| s.as_str()), sort)
+            .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
+
+        tracing::info!("Found first image (sorted): {}", bounded_name(&image_name));
+
+        // Get entry details
+        self.get_entry_by_name(&image_name)
+    }
+
+    fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
+        tracing::debug!("Extracting entry from memory: {} ({} bytes)", bounded_name(&entry.name), entry.size);
+
+        // Safety check: prevent memory exhaustion
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
+            return Err(CbxError::Archive(format!(
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
+            )));
+        }
+
+        self.recovery.ensure_readable(&entry.name, self.password.is_some())?;
+        let mut archive = self.archive.borrow_mut();
+
+        // Find and extract entry by name
+        let mut zip_entry = match by_normalized_name(&mut archive, &entry.name, self.password.as_deref()) {
+            Ok(Ok(zip_entry)) => zip_entry,
+            Ok(Err(InvalidPassword)) => return Err(wrong_password(&entry.name)),
+            Err(e) if is_local_header_error(&e) => {
+                tracing::warn!("Damaged local header for {} ({}), using central directory", bounded_name(&entry.name), e);
+                return self.recovery.read(&entry.name, u64::MAX);
+            }
+            Err(e) => return Err(CbxError::Archive(format!("Entry not found: {}", e))),
+        };
+
+        // Read to buffer
+        let mut buffer = Vec::with_capacity(entry.size as usize);
+        zip_entry
+            .read_to_end(&mut buffer)
+            .map_err(|e| CbxError::Archive(format!("Failed to extract entry: {}", e)))?;
+
+        tracing::debug!("Extracted {} bytes", buffer.len());
+        Ok(buffer)
+    }
+
+    fn read_entry_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        self.recovery.ensure_readable(&entry.name, self.password.is_some())?;
+        let mut archive = self.archive.borrow_mut();
+        let zip_entry = match by_normalized_name(&mut archive, &entry.name, self.password.as_deref()) {
+            Ok(Ok(zip_entry)) => zip_entry,
+            Ok(Err(InvalidPassword)) => return Err(wrong_password(&entry.name)),
+            Err(e) if is_local_header_error(&e) => {
+                return self.recovery.read(&entry.name, max_len as u64);
+            }
+            Err(e) => return Err(CbxError::Archive(format!("Entry not found: {}", e))),
+        };
+
+        let mut buffer = Vec::with_capacity(max_len);
+        zip_entry
+            .take(max_len as u64)
+            .read_to_end(&mut buffer)
+            .map_err(|e| CbxError::Archive(format!("Failed to read entry: {}", e)))?;
+
+        Ok(buffer)
+    }
+
+    fn extract_entry_raw(&self, entry: &ArchiveEntry) -> Result<(CompressionMethod, Vec<u8>)> {
+        read_raw_entry(&mut self.archive.borrow_mut(), entry)
+    }
+
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        entry_compression(&mut self.archive.borrow_mut(), entry)
+    }
+
+    fn get_metadata(&self) -> Result<ArchiveMetadata> {
+        let entry_names = self.get_entry_names();
+        let total_files = entry_names.len();
+        let image_count = entry_names
+            .iter()
+            .filter(|name| is_image_file(name))
+            .count();
+
+        tracing::debug!(
+            "ZIP metadata (from memory): {} files, {} images",
+            total_files,
+            image_count
+        );
+
+        Ok(ArchiveMetadata {
+            total_files,
+            image_count,
+            compressed_size: self.data_size as u64,
+            archive_type: ArchiveType::Zip,
+            modified: newest_entry_time(&mut self.archive.borrow_mut()),
+        })
+    }
+
+    fn archive_type(&self) -> ArchiveType {
+        ArchiveType::Zip
+    }
+}
+
+/// Look up an entry by its normalized name (see `normalize_entry_name`)
+///
+/// `by_name` needs the name as stored, which differs when the archive uses
+/// backslash separators. With a `password`, an encrypted entry is decrypted
+/// (unencrypted entries ignore it), and a wrong one is `Ok(Err(InvalidPassword))`.
+fn by_normalized_name<'a, R: Read + Seek>(
+    archive: &'a mut ZipReader<R>,
+    name: &str,
+    password: Option<&str>,
+) -> ZipResult<std::result::Result<ZipFile<'a>, InvalidPassword>> {
+    let stored = if name.contains('/') && !archive.file_names().any(|stored| stored == name) {
+        archive
+            .file_names()
+            .find(|stored| normalize_entry_name(stored) == name)
+            .map(str::to_string)
+    } else {
+        None
+    };
+
+    let name = stored.as_deref().unwrap_or(name);
+    match password {
+        Some(password) => archive.by_name_decrypt(name, password.as_bytes()),
+        None => archive.by_name(name).map(Ok),
+    }
+}
+
+/// Error for an encrypted entry the configured password doesn't decrypt
+///
+/// ZipCrypto only checks one byte of the password, so a wrong password
+/// usually passes here and fails on read with a CRC error instead.
+fn wrong_password(name: &str) -> CbxError {
+    tracing::info!("The configured password does not decrypt {}", bounded_name(name));
+    CbxError::Encrypted(format!("{} (wrong password)", name))
+}
+
+/// Index of an entry, found without decrypting or decompressing anything
+fn raw_index<R: Read + Seek>(archive: &mut ZipReader<R>, entry: &ArchiveEntry) -> Result<usize> {
+    (0..archive.len())
+        .find(|&i| {
+            archive
+                .by_index_raw(i)
+                .is_ok_and(|zip_entry| normalize_entry_name(zip_entry.name()) == entry.name)
+        })
+        .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", entry.name)))
+}
+
+/// Human name of a ZIP compression method
+///
+/// AES entries report the method of the data under the encryption.
+fn compression_name(method: CompressionMethod) -> String {
+    const NAMES: &[(CompressionMethod, &str)] = &[
+        (CompressionMethod::STORE, "Stored"),
+        (CompressionMethod::DEFLATE, "Deflate"),
+        (CompressionMethod::DEFLATE64, "Deflate64"),
+        (CompressionMethod::BZIP2, "BZip2"),
+        (CompressionMethod::LZMA, "LZMA"),
+        (CompressionMethod::ZSTD, "Zstd"),
+        (CompressionMethod::XZ, "XZ"),
+        (CompressionMethod::PPMD, "PPMd"),
+        (CompressionMethod::AES, "AES"),
+        (CompressionMethod::SHRINK, "Shrink"),
+        (CompressionMethod::REDUCE_1, "Reduce"),
+        (CompressionMethod::REDUCE_2, "Reduce"),
+        (CompressionMethod::REDUCE_3, "Reduce"),
+        (CompressionMethod::REDUCE_4, "Reduce"),
+        (CompressionMethod::IMPLODE, "Implode"),
+    ];
+
+    match NAMES.iter().find(|(known, _)| *known == method) {
+        Some((_, name)) => name.to_string(),
+        #[allow(deprecated)] // The numeric method is all there is to report
+        None => format!("Method {}", method.to_u16()),
+    }
+}
+
+/// Compression method of an entry, from its header (see `Archive::compression_method`)
+fn entry_compression<R: Read + Seek>(archive: &mut ZipReader<R>, entry: &ArchiveEntry) -> Result<String> {
+    let index = raw_index(archive, entry)?;
+    let zip_entry = archive
+        .by_index_raw(index)
+        .map_err(|e| CbxError::Archive(format!("Failed to get entry {}: {}", entry.name, e)))?;
+    Ok(compression_name(zip_entry.compression()))
+}
+
+/// Read an entry's stored bytes without decompressing them
+///
+/// Encrypted entries come back as stored, still encrypted.
+fn read_raw_entry<R: Read + Seek>(
+    archive: &mut ZipReader<R>,
+    entry: &ArchiveEntry,
+) -> Result<(CompressionMethod, Vec<u8>)> {
+    let index = raw_index(archive, entry)?;
+
+    let mut zip_entry = archive
+        .by_index_raw(index)
+        .map_err(|e| CbxError::Archive(format!("Failed to get entry {}: {}", entry.name, e)))?;
+
+    let max_entry_size = read_max_entry_size();
+    if zip_entry.compressed_size() > max_entry_size {
+        return Err(CbxError::Archive(format!(
+            "Entry too large: {} bytes (max {} bytes)",
+            zip_entry.compressed_size(),
+            max_entry_size
+        )));
+    }
+
+    let mut buffer = Vec::with_capacity(zip_entry.compressed_size() as usize);
+    zip_entry
+        .read_to_end(&mut buffer)
+        .map_err(|e| CbxError::Archive(format!("Failed to read raw entry: {}", e)))?;
+
+    tracing::debug!("Read {} raw bytes of {}", buffer.len(), bounded_name(&entry.name));
+    Ok((zip_entry.compression(), buffer))
+}
+
+/// Newest last-modified time among the archive's entries
+///
+/// Uses the central directory only; no entry data is read.
+fn newest_entry_time<R: Read + Seek>(archive: &mut ZipReader<R>) -> Option<SystemTime> {
+    (0..archive.len())
+        .filter_map(|i| {
+            let entry = archive.by_index_raw(i).ok()?;
+            let modified = entry.last_modified();
+            dos_datetime_to_system_time(modified.datepart(), modified.timepart())
+        })
+        .max()
+}
+
+/// End of central directory record signature (PK\x05\x06)
+const EOCD_SIGNATURE: [u8; 4] = [0x50, 0x4B, 0x05, 0x06];
+
+/// Size of the fixed part of the end of central directory record
+const EOCD_RECORD_LEN: u64 = 22;
+
+/// How far from the end of the archive the EOCD record can start
+///
+/// The record is followed by a comment of up to 65535 bytes, so the window
+/// must cover the fixed record plus the largest comment (65557 bytes).
+pub(crate) const EOCD_SEARCH_WINDOW: u64 = EOCD_RECORD_LEN + u16::MAX as u64;
+
+/// Locate the end of central directory record, returning its offset
+///
+/// The tail of the archive is read in one go and scanned backward. A long
+/// trailing comment moves the record further from the end, up to
+/// `EOCD_SEARCH_WINDOW` bytes. A candidate whose comment length reaches
+/// exactly to the end of the archive is preferred, so comment text that
+/// happens to contain the signature is not mistaken for the record; if none
+/// matches exactly (junk appended after the comment), the last candidate is
+/// used.
+pub(crate) fn locate_eocd<R: Read + Seek>(reader: &mut R) -> Result<u64> {
+    let len = reader.seek(SeekFrom::End(0))
+        .map_err(|e| CbxError::Archive(format!("Failed to seek to ZIP end: {}", e)))?;
+
+    if len < EOCD_RECORD_LEN {
+        return Err(CbxError::Archive("File too small to be a ZIP archive".to_string()));
+    }
+
+    let window = len.min(EOCD_SEARCH_WINDOW);
+    let window_start = len - window;
+
+    let mut tail = vec![0u8; window as usize];
+    reader.seek(SeekFrom::Start(window_start))
+        .and_then(|_| reader.read_exact(&mut tail))
+        .map_err(|e| CbxError::Archive(format!("Failed to read ZIP tail: {}", e)))?;
+
+    let mut fallback = None;
+    for pos in (0..=tail.len() - EOCD_RECORD_LEN as usize).rev() {
+        if tail[pos..pos + 4] != EOCD_SIGNATURE {
+            continue;
+        }
+
+        let comment_len = u16::from_le_bytes([tail[pos + 20], tail[pos + 21]]) as usize;
+        if pos + EOCD_RECORD_LEN as usize + comment_len == tail.len() {
+            return Ok(window_start + pos as u64);
+        }
+        fallback.get_or_insert(window_start + pos as u64);
+    }
+
+    fallback.ok_or_else(|| {
+        CbxError::Archive(format!(
+            "No ZIP end of central directory within the last {} bytes",
+            window
+        ))
+    })
+}
+
+/// How much data appended after a ZIP archive is searched for its EOCD record
+///
+/// Within `EOCD_SEARCH_WINDOW` of the end the record is found as usual;
+/// beyond it, `find_zip_end` scans back this far.
+const MAX_TRAILING_DATA: u64 = 64 * 1024 * 1024;
+
+/// Bytes scanned per read by `find_zip_end`
+const TRAILING_SCAN_CHUNK: u64 = 1024 * 1024;
+
+/// Logical end of a ZIP archive followed by more data than the EOCD search
+/// window covers (e.g. another file appended to it)
+///
+/// Scans backward for an EOCD record whose central directory ends right
+/// before it with a central directory header, and returns the offset just
+/// past the record and its comment. Returns `None` if there is none within
+/// `MAX_TRAILING_DATA` of the end.
+fn find_zip_end<R: Read + Seek>(reader: &mut R) -> Option<u64> {
+    let len = reader.seek(SeekFrom::End(0)).ok()?;
+    let scan_start = len.saturating_sub(MAX_TRAILING_DATA + EOCD_SEARCH_WINDOW);
+
+    // Chunks overlap by a record, so one straddling a chunk boundary is seen whole
+    let mut chunk_end = len;
+    while chunk_end > scan_start {
+        let chunk_start = chunk_end.saturating_sub(TRAILING_SCAN_CHUNK).max(scan_start);
+        let read_end = (chunk_end + EOCD_RECORD_LEN - 1).min(len);
+        let mut chunk = vec![0u8; (read_end - chunk_start) as usize];
+        reader.seek(SeekFrom::Start(chunk_start)).and_then(|_| reader.read_exact(&mut chunk)).ok()?;
+
+        for pos in (0..chunk.len().saturating_sub(EOCD_RECORD_LEN as usize - 1)).rev() {
+            if chunk[pos..pos + 4] != EOCD_SIGNATURE {
+                continue;
+            }
+
+            let record = &chunk[pos..pos + EOCD_RECORD_LEN as usize];
+            let eocd_offset = chunk_start + pos as u64;
+            let size = le32(record, 12) as u64;
+            let offset = le32(record, 16) as u64;
+            let end = eocd_offset + EOCD_RECORD_LEN + le16(record, 20) as u64;
+            if offset + size != eocd_offset || end > len {
+                continue;
+            }
+
+            let mut signature = [0u8; 4];
+            let directory_found = size == 0
+                || reader.seek(SeekFrom::Start(offset))
+                    .and_then(|_| reader.read_exact(&mut signature))
+                    .is_ok_and(|_| signature == CENTRAL_HEADER_SIGNATURE);
+            if directory_found {
+                return Some(end);
+            }
+        }
+
+        chunk_end = chunk_start;
+    }
+    None
+}
+
+/// Shared reader over a ZIP archive, ignoring data appended to it
+///
+/// The EOCD record is located up front with a single tail read, so a
+/// missing record (truncated download, not a ZIP) fails with a clear error.
+/// Data appended within the EOCD search window is skipped by that search
+/// (and by the zip crate's); if the record is further from the end, the
+/// archive is searched for it (`find_zip_end`) and the reader ends there.
+fn open_shared_reader<R: Read + Seek>(mut reader: R) -> Result<SharedReader<R>> {
+    let reader = match locate_eocd(&mut reader) {
+        Ok(eocd_offset) => {
+            tracing::debug!("ZIP end of central directory at offset {}", eocd_offset);
+            SharedReader::new(reader)
+        }
+        Err(e) => {
+            let end = find_zip_end(&mut reader).ok_or(e)?;
+            tracing::warn!("Ignoring data after the ZIP archive end at offset {}", end);
+            SharedReader::with_end(reader, end)
+        }
+    };
+
+    reader.clone().seek(SeekFrom::Start(0))
+        .map_err(|e| CbxError::Archive(format!("Failed to seek to start: {}", e)))?;
+    Ok(reader)
+}
+
+/// Shared handle to the archive bytes
+///
+/// `ZipReader` owns its reader, so the central-directory-only recovery keeps
+/// a second handle to the same reader. Both sides seek before every read.
+///
+/// With an end offset, the handle only shows the bytes before it, so data
+/// appended to the archive is invisible to both sides.
+struct SharedReader<R> {
+    inner: Rc<RefCell<R>>,
+    end: Option<u64>,
+}
+
+impl<R> SharedReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            inner: Rc::new(RefCell::new(reader)),
+            end: None,
+        }
+    }
+
+    /// Handle showing only the bytes before offset `end`
+    fn with_end(reader: R, end: u64) -> Self {
+        Self {
+            end: Some(end),
+            ..Self::new(reader)
+        }
+    }
+}
+
+impl<R> Clone for SharedReader<R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+            end: self.end,
+        }
+    }
+}
+
+impl<R: Read + Seek> Read for SharedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut inner = self.inner.borrow_mut();
+        let Some(end) = self.end else {
+            return inner.read(buf);
+        };
+
+        let remaining = end.saturating_sub(inner.stream_position()?);
+        let len = buf.len().min(usize::try_from(remaining).unwrap_or(usize::MAX));
+        inner.read(&mut buf[..len])
+    }
+}
+
+impl<R: Seek> Seek for SharedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let pos = match (pos, self.end) {
+            (SeekFrom::End(delta), Some(end)) => {
+                let target = end.checked_add_signed(delta).ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before the start of the archive")
+                })?;
+                SeekFrom::Start(target)
+            }
+            (pos, _) => pos,
+        };
+        self.inner.borrow_mut().seek(pos)
+    }
+}
+
+/// Local file header signature (PK\x03\x04)
+const LOCAL_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4B, 0x03, 0x04];
+
+/// Central directory file header signature (PK\x01\x02)
+const CENTRAL_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4B, 0x01, 0x02];
+
+/// Size of the fixed part of a local file header
+const LOCAL_HEADER_LEN: usize = 30;
+
+/// Size of the fixed part of a central directory file header
+const CENTRAL_HEADER_LEN: usize = 46;
+
+/// General purpose flag bits: encrypted, sizes in a trailing data
+/// descriptor, and UTF-8 file name
+const FLAG_ENCRYPTED: u16 = 0x0001;
+const FLAG_DATA_DESCRIPTOR: u16 = 0x0008;
+const FLAG_UTF8: u16 = 0x0800;
+
+/// Compression method of WinZip AES entries (the real method is in the extra field)
+const METHOD_AES: u16 = 99;
+
+/// Legacy compression methods (PKZIP 1.x) the zip crate can't decompress
+const LEGACY_METHODS: &[(u16, &str)] = &[
+    (1, "Shrink"),
+    (2, "Reduce"),
+    (3, "Reduce"),
+    (4, "Reduce"),
+    (5, "Reduce"),
+    (6, "Implode"),
+];
+
+/// WinZip AES extra field: header ID and vendor ID ("AE")
+const AES_EXTRA_FIELD_ID: u16 = 0x9901;
+const AES_VENDOR_ID: u16 = 0x4541;
+
+/// Whether a zip crate error came from reading an entry's local header
+fn is_local_header_error(error: &ZipError) -> bool {
+    matches!(error, ZipError::InvalidArchive(_) | ZipError::Io(_))
+}
+
+/// The fields of a central directory file header needed for recovery
+#[derive(Debug, Clone)]
+struct CentralDirectoryRecord {
+    name: String,
+    flags: u16,
+    method: u16,
+    crc32: u32,
+    compressed_size: u32,
+    size: u32,
+    name_len: u16,
+    extra_len: u16,
+    header_offset: u32,
+    /// Vendor version (1 = `AE-1`, 2 = `AE-2`) of a WinZip AES extra field
+    aes_version: Option<u16>,
+}
+
+impl CentralDirectoryRecord {
+    fn to_entry(&self) -> ArchiveEntry {
+        ArchiveEntry {
+            name: self.name.clone(),
+            size: self.size as u64,
+            is_directory: self.name.ends_with('/'),
+        }
+    }
+
+    /// The encryption scheme of the entry, if it is encrypted
+    fn encryption(&self) -> Option<&'static str> {
+        match self.aes_version {
+            Some(1) => Some("WinZip AES, AE-1"),
+            Some(2) => Some("WinZip AES, AE-2"),
+            Some(_) => Some("WinZip AES"),
+            None if self.method == METHOD_AES => Some("WinZip AES"),
+            None if self.flags & FLAG_ENCRYPTED != 0 => Some("ZipCrypto"),
+            None => None,
+        }
+    }
+
+    /// Whether the zip crate can decrypt the entry given a password
+    ///
+    /// It relies on the encryption flag; an AES entry without it makes the
+    /// crate panic.
+    fn is_decryptable(&self) -> bool {
+        self.flags & FLAG_ENCRYPTED != 0
+    }
+
+    /// Name of the entry's legacy compression method, if it uses one
+    fn legacy_method(&self) -> Option<&'static str> {
+        LEGACY_METHODS
+            .iter()
+            .find(|(method, _)| *method == self.method)
+            .map(|&(_, name)| name)
+    }
+
+    /// Refuse an entry the zip crate can't read: encrypted without a
+    /// `password` (or undecryptable), or compressed with a legacy method
+    fn ensure_readable(&self, has_password: bool) -> Result<()> {
+        if let Some(scheme) = self.encryption() {
+            if !self.is_decryptable() {
+                tracing::info!("Skipping encrypted entry {} ({})", bounded_name(&self.name), scheme);
+                return Err(CbxError::Encrypted(format!("{} ({})", self.name, scheme)));
+            }
+            if !has_password {
+                tracing::info!("Encrypted entry {} ({}) and no ArchivePassword set", bounded_name(&self.name), scheme);
+                return Err(CbxError::PasswordRequired(format!("{} ({})", self.name, scheme)));
+            }
+        }
+        if let Some(method) = self.legacy_method() {
+            tracing::warn!(
+                "{} uses the legacy ZIP compression method {} ({}), which is not supported",
+                self.name,
+                method,
+                self.method
+            );
+            return Err(CbxError::UnsupportedFormat(method.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Refuse an entry whose compressed size runs past the end of the archive
+    ///
+    /// A crafted central directory can claim far more data than the file
+    /// holds. ZIP64 sizes live in the extra field and aren't checked here.
+    fn ensure_within(&self, archive_len: u64) -> Result<()> {
+        if [self.compressed_size, self.header_offset].contains(&u32::MAX) {
+            return Ok(());
+        }
+
+        let data_start = self.header_offset as u64 + LOCAL_HEADER_LEN as u64 + self.name_len as u64;
+        let remaining = archive_len.saturating_sub(data_start);
+        if self.compressed_size as u64 > remaining {
+            tracing::warn!(
+                "{} claims {} compressed bytes, but only {} remain in the archive",
+                self.name,
+                self.compressed_size,
+                remaining
+            );
+            return Err(CbxError::Corrupt(format!(
+                "{}: compressed size {} exceeds the {} bytes left in the archive",
+                self.name, self.compressed_size, remaining
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Vendor version of the WinZip AES field in an extra field block, if any
+fn aes_vendor_version(extra: &[u8]) -> Option<u16> {
+    let mut pos = 0;
+    while pos + 4 <= extra.len() {
+        let id = le16(extra, pos);
+        let len = le16(extra, pos + 2) as usize;
+        let data = extra.get(pos + 4..pos + 4 + len)?;
+
+        if id == AES_EXTRA_FIELD_ID && len >= 4 && le16(data, 2) == AES_VENDOR_ID {
+            return Some(le16(data, 0));
+        }
+        pos += 4 + len;
+    }
+    None
+}
+
+/// A parsed central directory and where it starts
+struct CentralDirectory {
+    offset: u64,
+    records: Vec<CentralDirectoryRecord>,
+    /// Total length of the archive
+    archive_len: u64,
+}
+
+/// Central-directory-only recovery mode
+///
+/// The zip crate validates an entry's local file header before every access,
+/// so a damaged header makes the entry impossible to list or extract even
+/// though the central directory still describes it. In this mode the central
+/// directory is parsed directly and the entry data is located from the
+/// offset, compression method and sizes stated there.
+///
+/// The same directory tells which entries are encrypted, which has to be
+/// known before the zip crate opens one (see `ensure_readable`).
+///
+/// ZIP64 and encrypted entries are not recovered.
+struct CentralDirectoryOnly<R> {
+    reader: SharedReader<R>,
+    directory: OnceCell<CentralDirectory>,
+}
+
+impl<R: Read + Seek> CentralDirectoryOnly<R> {
+    fn new(reader: SharedReader<R>) -> Self {
+        Self {
+            reader,
+            directory: OnceCell::new(),
+        }
+    }
+
+    fn directory(&self) -> Result<&CentralDirectory> {
+        if let Some(directory) = self.directory.get() {
+            return Ok(directory);
+        }
+        let directory = read_central_directory(&mut self.reader.clone())?;
+        Ok(self.directory.get_or_init(|| directory))
+    }
+
+    /// Entry `index` from the zip crate, or from the central directory if its
+    /// local header is damaged
+    ///
+    /// With `raw`, every entry is listed (`ZipReader::by_index_raw`).
+    /// Otherwise entries the zip crate can't read fail: AES entries without
+    /// the encryption flag with `CbxError::Encrypted`, legacy methods with
+    /// `CbxError::UnsupportedFormat`. Password-protected entries are listed
+    /// either way; the password is checked when they are extracted.
+    fn entry_at(
+        &self,
+        archive: &mut ZipReader<SharedReader<R>>,
+        index: usize,
+        raw: bool,
+    ) -> Result<ArchiveEntry> {
+        let record = self.record_at(index);
+        if !raw {
+            if let Some(scheme) = record.filter(|r| !r.is_decryptable()).and_then(|r| r.encryption()) {
+                return Err(CbxError::Encrypted(format!("entry {} ({})", index, scheme)));
+            }
+            if let Some(method) = record.and_then(|r| r.legacy_method()) {
+                return Err(CbxError::UnsupportedFormat(method.to_string()));
+            }
+        }
+
+        // `by_index` would fail on an encrypted entry without the password
+        let encrypted = record.is_some_and(|r| r.encryption().is_some());
+        let zip_entry = if raw || encrypted { archive.by_index_raw(index) } else { archive.by_index(index) };
+
+        match zip_entry {
+            Ok(zip_entry) => Ok(ArchiveEntry {
+                name: normalize_entry_name(zip_entry.name()),
+                size: zip_entry.size(),
+                is_directory: zip_entry.is_dir(),
+            }),
+            Err(e) if is_local_header_error(&e) => {
+                tracing::warn!("Entry {} has a damaged local header ({}), using central directory", index, e);
+                self.directory()?
+                    .records
+                    .get(index)
+                    .map(CentralDirectoryRecord::to_entry)
+                    .ok_or_else(|| CbxError::Archive(format!("Failed to get entry {}: {}", index, e)))
+            }
+            Err(e) => Err(CbxError::Archive(format!("Failed to get entry {}: {}", index, e))),
+        }
+    }
+
+    /// Central directory record of entry `index`, if the directory parses
+    ///
+    /// A directory that can't be parsed here (e.g. ZIP64) is left to the zip crate.
+    fn record_at(&self, index: usize) -> Option<&CentralDirectoryRecord> {
+        self.directory().ok()?.records.get(index)
+    }
+
+    /// Refuse an entry the zip crate can't read, before it reads it
+    ///
+    /// WinZip AES entries (method 99 with an `AE-1`/`AE-2` extra field) and
+    /// ZipCrypto entries need a password; without one (`has_password`) they
+    /// fail with `CbxError::PasswordRequired`. The zip crate only recognizes
+    /// entries with the encryption flag set; an AES entry without the flag
+    /// makes it panic, so such entries never reach `by_name` and fail with
+    /// `CbxError::Encrypted`.
+    ///
+    /// Entries compressed with the PKZIP 1.x methods Shrink, Reduce or
+    /// Implode fail with `CbxError::UnsupportedFormat` naming the method,
+    /// instead of the zip crate's generic "not supported" error.
+    ///
+    /// An entry claiming more compressed data than the archive has left
+    /// fails with `CbxError::Corrupt`.
+    fn ensure_readable(&self, name: &str, has_password: bool) -> Result<()> {
+        let Ok(directory) = self.directory() else {
+            return Ok(());
+        };
+
+        match directory.records.iter().find(|r| r.name == name) {
+            Some(record) => {
+                record.ensure_readable(has_password)?;
+                record.ensure_within(directory.archive_len)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Decompress up to `limit` bytes of the named entry, ignoring its local header
+    fn read(&self, name: &str, limit: u64) -> Result<Vec<u8>> {
+        let directory = self.directory()?;
+        let record = directory
+            .records
+            .iter()
+            .find(|r| r.name == name)
+            .ok_or_else(|| CbxError::Archive(format!("Entry not found: {}", name)))?;
+
+        // The rebuilt entry is read without a password
+        if let Some(scheme) = record.encryption() {
+            return Err(CbxError::Encrypted(format!("{} ({}, damaged local header)", name, scheme)));
+        }
+        record.ensure_readable(false)?;
+        if [record.compressed_size, record.size, record.header_offset].contains(&u32::MAX) {
+            return Err(CbxError::Archive(format!("Cannot recover ZIP64 entry: {}", name)));
+        }
+
+        let mut reader = self.reader.clone();
+        let data_start = locate_entry_data(&mut reader, record, directory.offset)?;
+
+        let mut data = vec![0u8; record.compressed_size as usize];
+        reader.seek(SeekFrom::Start(data_start))
+            .and_then(|_| reader.read_exact(&mut data))
+            .map_err(|e| CbxError::Archive(format!("Failed to read entry data: {}", e)))?;
+
+        // Let the zip crate decompress and verify the CRC
+        let mut rebuilt = ZipReader::new(Cursor::new(rebuild_single_entry_zip(record, &data)))
+            .map_err(|e| CbxError::Archive(format!("Failed to rebuild entry {}: {}", name, e)))?;
+        let zip_entry = rebuilt.by_index(0)
+            .map_err(|e| CbxError::Archive(format!("Failed to rebuild entry {}: {}", name, e)))?;
+
+        let mut buffer = Vec::with_capacity(record.size.min(limit.min(u32::MAX as u64) as u32) as usize);
+        zip_entry
+            .take(limit)
+            .read_to_end(&mut buffer)
+            .map_err(|e| CbxError::Archive(format!("Failed to extract entry: {}", e)))?;
+
+        tracing::info!("Recovered {} ({} bytes) from the central directory", bounded_name(name), buffer.len());
+        Ok(buffer)
+    }
+}
+
+fn le16(bytes: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([bytes[pos], bytes[pos + 1]])
+}
+
+fn le32(bytes: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]])
+}
+
+/// Parse the central directory without touching any local header
+fn read_central_directory<R: Read + Seek>(reader: &mut R) -> Result<CentralDirectory> {
+    let eocd_offset = locate_eocd(reader)?;
+
+    let mut eocd = [0u8; EOCD_RECORD_LEN as usize];
+    reader.seek(SeekFrom::Start(eocd_offset))
+        .and_then(|_| reader.read_exact(&mut eocd))
+        .map_err(|e| CbxError::Archive(format!("Failed to read ZIP end record: {}", e)))?;
+
+    let count = le16(&eocd, 10);
+    let size = le32(&eocd, 12);
+    let offset = le32(&eocd, 16);
+    if count == u16::MAX || offset == u32::MAX {
+        return Err(CbxError::Archive("Cannot recover ZIP64 central directory".to_string()));
+    }
+    if offset as u64 + size as u64 > eocd_offset {
+        return Err(CbxError::Archive("ZIP central directory lies outside the archive".to_string()));
+    }
+
+    let mut bytes = vec![0u8; size as usize];
+    reader.seek(SeekFrom::Start(offset as u64))
+        .and_then(|_| reader.read_exact(&mut bytes))
+        .map_err(|e| CbxError::Archive(format!("Failed to read ZIP central directory: {}", e)))?;
+
+    let truncated = || CbxError::Archive("ZIP central directory is truncated".to_string());
+    let mut records = Vec::with_capacity(count as usize);
+    let mut pos = 0;
+    for _ in 0..count {
+        let header = bytes.get(pos..pos + CENTRAL_HEADER_LEN).ok_or_else(truncated)?;
+        if header[..4] != CENTRAL_HEADER_SIGNATURE {
+            return Err(CbxError::Archive("Invalid ZIP central directory header".to_string()));
+        }
+
+        let name_len = le16(header, 28);
+        let extra_len = le16(header, 30);
+        let comment_len = le16(header, 32) as usize;
+        let name_start = pos + CENTRAL_HEADER_LEN;
+        let name = bytes.get(name_start..name_start + name_len as usize).ok_or_else(truncated)?;
+        let extra_start = name_start + name_len as usize;
+        let extra = bytes.get(extra_start..extra_start + extra_len as usize).ok_or_else(truncated)?;
+
+        records.push(CentralDirectoryRecord {
+            name: normalize_entry_name(&String::from_utf8_lossy(name)),
+            flags: le16(header, 8),
+            method: le16(header, 10),
+            crc32: le32(header, 16),
+            compressed_size: le32(header, 20),
+            size: le32(header, 24),
+            name_len,
+            extra_len,
+            header_offset: le32(header, 42),
+            aes_version: aes_vendor_version(extra),
+        });
+
+        pos = name_start + name_len as usize + extra_len as usize + comment_len;
+    }
+
+    let archive_len = reader.seek(SeekFrom::End(0))
+        .map_err(|e| CbxError::Archive(format!("Failed to seek to end: {}", e)))?;
+
+    Ok(CentralDirectory {
+        offset: offset as u64,
+        records,
+        archive_len,
+    })
+}
+
+/// Find where an entry's data starts
+///
+/// The name and extra field lengths from the local header are used when they
+/// are plausible (the name length agrees with the central directory and the
+/// data ends before the central directory); otherwise the lengths stated in
+/// the central directory are assumed.
+fn locate_entry_data<R: Read + Seek>(
+    reader: &mut R,
+    record: &CentralDirectoryRecord,
+    directory_offset: u64,
+) -> Result<u64> {
+    let header_offset = record.header_offset as u64;
+    let fits = |start: u64| start + record.compressed_size as u64 <= directory_offset;
+
+    let mut header = [0u8; LOCAL_HEADER_LEN];
+    let local = reader.seek(SeekFrom::Start(header_offset))
+        .and_then(|_| reader.read_exact(&mut header))
+        .ok()
+        .filter(|_| le16(&header, 26) == record.name_len)
+        .map(|_| header_offset + LOCAL_HEADER_LEN as u64 + record.name_len as u64 + le16(&header, 28) as u64)
+        .filter(|&start| fits(start));
+
+    let stated = header_offset + LOCAL_HEADER_LEN as u64 + record.name_len as u64 + record.extra_len as u64;
+
+    local
+        .or_else(|| Some(stated).filter(|&start| fits(start)))
+        .ok_or_else(|| CbxError::Archive(format!("Entry data lies outside the archive: {}", record.name)))
+}
+
+/// Wrap recovered entry data in a minimal single-entry ZIP
+///
+/// This lets the zip crate handle decompression and the CRC check. Sizes and
+/// CRC are written into the header, so the data descriptor flag is dropped.
+fn rebuild_single_entry_zip(record: &CentralDirectoryRecord, data: &[u8]) -> Vec<u8> {
+    const VERSION: u16 = 20;
+    const DOS_DATE_1980_01_01: u16 = 0x21;
+
+    let name = record.name.as_bytes();
+    let flags = record.flags & FLAG_UTF8;
+    let mut zip = Vec::with_capacity(data.len() + 2 * name.len() + 128);
+    let put16 = |zip: &mut Vec<u8>, value: u16| zip.extend_from_slice(&value.to_le_bytes());
+    let put32 = |zip: &mut Vec<u8>, value: u32| zip.extend_from_slice(&value.to_le_bytes());
+
+    zip.extend_from_slice(&LOCAL_HEADER_SIGNATURE);
+    for value in [VERSION, flags, record.method, 0, DOS_DATE_1980_01_01] {
+        put16(&mut zip, value);
+    }
+    for value in [record.crc32, record.compressed_size, record.size] {
+        put32(&mut zip, value);
+    }
+    put16(&mut zip, name.len() as u16);
+    put16(&mut zip, 0);
+    zip.extend_from_slice(name);
+    zip.extend_from_slice(data);
+
+    let directory_offset = zip.len() as u32;
+    zip.extend_from_slice(&CENTRAL_HEADER_SIGNATURE);
+    for value in [VERSION, VERSION, flags, record.method, 0, DOS_DATE_1980_01_01] {
+        put16(&mut zip, value);
+    }
+    for value in [record.crc32, record.compressed_size, record.size] {
+        put32(&mut zip, value);
+    }
+    for value in [name.len() as u16, 0, 0, 0, 0] {
+        put16(&mut zip, value);
+    }
+    put32(&mut zip, 0); // external attributes
+    put32(&mut zip, 0); // local header offset
+    zip.extend_from_slice(name);
+    let directory_len = zip.len() as u32 - directory_offset;
+
+    zip.extend_from_slice(&EOCD_SIGNATURE);
+    for value in [0, 0, 1, 1] {
+        put16(&mut zip, value);
+    }
+    put32(&mut zip, directory_len);
+    put32(&mut zip, directory_offset);
+    put16(&mut zip, 0);
+
+    zip
+}
+
+/// Leading bytes of the first image entry in a prefix of a ZIP archive
+///
+/// Walks the local file headers from the start of the archive (the central
+/// directory, at the end, is not available), skipping non-image and
+/// encrypted entries. The walk stops at an entry whose sizes are only in a
+/// trailing data descriptor, since the next header can't be located. The
+/// image's data may be cut off by the end of the prefix; whatever
+/// decompresses is returned, up to `max_len` bytes.
+pub(crate) fn first_image_prefix(prefix: &[u8], max_len: usize) -> Option<Vec<u8>> {
+    let mut pos = 0;
+    while prefix.get(pos..pos + 4)? == LOCAL_HEADER_SIGNATURE {
+        let header = prefix.get(pos..pos + LOCAL_HEADER_LEN)?;
+        let flags = le16(header, 6);
+        let compressed_size = le32(header, 18);
+        let name_len = le16(header, 26);
+        let extra_len = le16(header, 28);
+
+        let name_start = pos + LOCAL_HEADER_LEN;
+        let name = String::from_utf8_lossy(prefix.get(name_start..name_start + name_len as usize)?);
+        let data_start = name_start + name_len as usize + extra_len as usize;
+        let has_descriptor = flags & FLAG_DATA_DESCRIPTOR != 0;
+
+        if flags & FLAG_ENCRYPTED == 0 && !name.ends_with('/') && is_image_file(&name) {
+            let available = prefix.get(data_start..)?;
+            let data = if has_descriptor {
+                available
+            } else {
+                &available[..available.len().min(compressed_size as usize)]
+            };
+
+            let record = CentralDirectoryRecord {
+                name: name.into_owned(),
+                flags,
+                method: le16(header, 8),
+                crc32: le32(header, 14),
+                compressed_size: data.len() as u32,
+                // Unknown with a data descriptor; only used for the header
+                size: le32(header, 22),
+                name_len,
+                extra_len: 0,
+                header_offset: 0,
+                aes_version: None,
+            };
+            return decompress_prefix(&record, data, max_len);
+        }
+
+        if has_descriptor {
+            return None;
+        }
+        pos = data_start + compressed_size as usize;
+    }
+    None
+}
+
+/// Decompress up to `max_len` bytes of possibly truncated entry data
+fn decompress_prefix(record: &CentralDirectoryRecord, data: &[u8], max_len: usize) -> Option<Vec<u8>> {
+    let mut rebuilt = ZipReader::new(Cursor::new(rebuild_single_entry_zip(record, data))).ok()?;
+    let mut entry = rebuilt.by_index(0).ok()?;
+
+    // Keep what was read before the data ran out (or the CRC check failed)
+    let mut buffer = vec![0u8; max_len];
+    let mut filled = 0;
+    while filled < max_len {
+        match entry.read(&mut buffer[filled..]) {
+            Ok(0) | Err(_) => break,
+            Ok(n) => filled += n,
+        }
+    }
+
+    buffer.truncate(filled);
+    (filled > 0).then_some(buffer)
+}
 
 /// ZIP archive handler for IStream (direct streaming, no memory copy)
 ///
@@ -503,17 +2412,40 @@ impl Archive for ZipArchiveFromMemory {
 /// - Old approach: Load 1GB to memory (~3sec) + process
 /// - New approach: Stream directly (~50ms for metadata + image)
 pub struct ZipArchiveFromStream<R: Read + Seek> {
-    archive: RefCell<ZipReader<R>>,
+    archive: RefCell<ZipReader<SharedReader<R>>>,
+    recovery: CentralDirectoryOnly<R>,
+    /// Password for encrypted entries (`ArchivePassword` unless given)
+    password: Option<String>,
 }
 
 impl<R: Read + Seek> ZipArchiveFromStream<R> {
     /// Create a ZIP archive from a streaming reader
+    ///
+    /// Data appended after the archive is ignored (see `open_shared_reader`).
+    ///
+    /// Encrypted entries are decrypted with the configured `ArchivePassword`.
     pub fn new(reader: R) -> Result<Self> {
-        let archive = ZipReader::new(reader)
+        Self::open_with_password(reader, read_archive_password())
+    }
+
+    /// Create a ZIP archive from a streaming reader, decrypting with `password`
+    ///
+    /// Encrypted entries (ZipCrypto or WinZip AES) are decrypted with the
+    /// given password instead of the configured one.
+    #[allow(dead_code)] // Part of public API, used by tests
+    pub fn with_password(reader: R, password: &str) -> Result<Self> {
+        Self::open_with_password(reader, Some(password.to_string()))
+    }
+
+    fn open_with_password(reader: R, password: Option<String>) -> Result<Self> {
+        let reader = open_shared_reader(reader)?;
+        let archive = ZipReader::new(reader.clone())
             .map_err(|e| CbxError::Archive(format!("Failed to open ZIP from stream: {}", e)))?;
 
         Ok(Self {
             archive: RefCell::new(archive),
+            recovery: CentralDirectoryOnly::new(reader),
+            password,
         })
     }
 
@@ -522,9 +2454,9 @@ impl<R: Read + Seek> ZipArchiveFromStream<R> {
         let mut archive = self.archive.borrow_mut();
         (0..archive.len())
             .filter_map(|i| {
-                archive.by_index(i)
+                self.recovery.entry_at(&mut archive, i, false)
                     .ok()
-                    .map(|f| f.name().to_string())
+                    .map(|e| e.name)
             })
             .collect()
     }
@@ -534,15 +2466,9 @@ impl<R: Read + Seek> ZipArchiveFromStream<R> {
         let mut archive = self.archive.borrow_mut();
 
         for i in 0..archive.len() {
-            let zip_entry = archive.by_index(i)
-                .map_err(|e| CbxError::Archive(format!("Failed to get entry {}: {}", i, e)))?;
-
-            if zip_entry.name() == name {
-                return Ok(ArchiveEntry {
-                    name: name.to_string(),
-                    size: zip_entry.size(),
-                    is_directory: zip_entry.is_dir(),
-                });
+            let entry = self.recovery.entry_at(&mut archive, i, false)?;
+            if entry.name == name {
+                return Ok(entry);
             }
         }
 
@@ -556,6 +2482,19 @@ impl<R: Read + Seek> Archive for ZipArchiveFromStream<R> {
         Err(CbxError::Archive("Use open_archive_from_stream instead".to_string()))
     }
 
+    /// List all entries in archive order
+    fn list_entries(&self) -> Result<Vec<ArchiveEntry>> {
+        let mut archive = self.archive.borrow_mut();
+        let mut entries = Vec::with_capacity(archive.len());
+
+        for i in 0..archive.len() {
+            // Raw access: listing must not fail on encrypted entries
+            entries.push(self.recovery.entry_at(&mut archive, i, true)?);
+        }
+
+        Ok(entries)
+    }
+
     fn find_first_image(&self, sort: bool) -> Result<ArchiveEntry> {
         tracing::debug!("Finding first image in ZIP from stream (sort={})", sort);
 
@@ -565,15 +2504,10 @@ impl<R: Read + Seek> Archive for ZipArchiveFromStream<R> {
 
             let mut archive = self.archive.borrow_mut();
             for i in 0..archive.len() {
-                if let Ok(entry) = archive.by_index(i) {
-                    let name = entry.name().to_string();
-                    if is_image_file(&name) {
-                        tracing::info!("Found first image (unsorted): {}", name);
-                        return Ok(ArchiveEntry {
-                            name,
-                            size: entry.size(),
-                            is_directory: entry.is_dir(),
-                        });
+                if let Ok(entry) = self.recovery.entry_at(&mut archive, i, false) {
+                    if is_image_file(&entry.name) {
+                        tracing::info!("Found first image (unsorted): {}", bounded_name(&entry.name));
+                        return Ok(entry);
                     }
                 }
             }
@@ -592,30 +2526,38 @@ impl<R: Read + Seek> Archive for ZipArchiveFromStream<R> {
         let image_name = find_first_image(entry_names.iter().map(|s| s.as_str()), sort)
             .ok_or_else(|| CbxError::Archive("No images found in archive".to_string()))?;
 
-        tracing::info!("Found first image (sorted): {}", image_name);
+        tracing::info!("Found first image (sorted): {}", bounded_name(&image_name));
 
         // Get entry details
         self.get_entry_by_name(&image_name)
     }
 
     fn extract_entry(&self, entry: &ArchiveEntry) -> Result<Vec<u8>> {
-        tracing::debug!("Extracting entry from stream: {} ({} bytes)", entry.name, entry.size);
+        tracing::debug!("Extracting entry from stream: {} ({} bytes)", bounded_name(&entry.name), entry.size);
 
         // Safety check: prevent memory exhaustion
-        if entry.size > MAX_ENTRY_SIZE {
-            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, MAX_ENTRY_SIZE);
+        let max_entry_size = read_max_entry_size();
+        if entry.size > max_entry_size {
+            tracing::warn!("Entry too large: {} bytes (max {})", entry.size, max_entry_size);
             return Err(CbxError::Archive(format!(
-                "Entry too large: {} bytes (max 32MB)",
-                entry.size
+                "Entry too large: {} bytes (max {} bytes)",
+                entry.size, max_entry_size
             )));
         }
 
+        self.recovery.ensure_readable(&entry.name, self.password.is_some())?;
         let mut archive = self.archive.borrow_mut();
 
         // Find and extract entry by name
-        let mut zip_entry = archive
-            .by_name(&entry.name)
-            .map_err(|e| CbxError::Archive(format!("Entry not found: {}", e)))?;
+        let mut zip_entry = match by_normalized_name(&mut archive, &entry.name, self.password.as_deref()) {
+            Ok(Ok(zip_entry)) => zip_entry,
+            Ok(Err(InvalidPassword)) => return Err(wrong_password(&entry.name)),
+            Err(e) if is_local_header_error(&e) => {
+                tracing::warn!("Damaged local header for {} ({}), using central directory", bounded_name(&entry.name), e);
+                return self.recovery.read(&entry.name, u64::MAX);
+            }
+            Err(e) => return Err(CbxError::Archive(format!("Entry not found: {}", e))),
+        };
 
         // Read to buffer
         let mut buffer = Vec::with_capacity(entry.size as usize);
@@ -627,6 +2569,35 @@ impl<R: Read + Seek> Archive for ZipArchiveFromStream<R> {
         Ok(buffer)
     }
 
+    fn read_entry_prefix(&self, entry: &ArchiveEntry, max_len: usize) -> Result<Vec<u8>> {
+        self.recovery.ensure_readable(&entry.name, self.password.is_some())?;
+        let mut archive = self.archive.borrow_mut();
+        let zip_entry = match by_normalized_name(&mut archive, &entry.name, self.password.as_deref()) {
+            Ok(Ok(zip_entry)) => zip_entry,
+            Ok(Err(InvalidPassword)) => return Err(wrong_password(&entry.name)),
+            Err(e) if is_local_header_error(&e) => {
+                return self.recovery.read(&entry.name, max_len as u64);
+            }
+            Err(e) => return Err(CbxError::Archive(format!("Entry not found: {}", e))),
+        };
+
+        let mut buffer = Vec::with_capacity(max_len);
+        zip_entry
+            .take(max_len as u64)
+            .read_to_end(&mut buffer)
+            .map_err(|e| CbxError::Archive(format!("Failed to read entry: {}", e)))?;
+
+        Ok(buffer)
+    }
+
+    fn extract_entry_raw(&self, entry: &ArchiveEntry) -> Result<(CompressionMethod, Vec<u8>)> {
+        read_raw_entry(&mut self.archive.borrow_mut(), entry)
+    }
+
+    fn compression_method(&self, entry: &ArchiveEntry) -> Result<String> {
+        entry_compression(&mut self.archive.borrow_mut(), entry)
+    }
+
     fn get_metadata(&self) -> Result<ArchiveMetadata> {
         let entry_names = self.get_entry_names();
         let total_files = entry_names.len();
@@ -646,6 +2617,7 @@ impl<R: Read + Seek> Archive for ZipArchiveFromStream<R> {
             image_count,
             compressed_size: 0, // Not available from stream without full scan
             archive_type: ArchiveType::Zip,
+            modified: newest_entry_time(&mut self.archive.borrow_mut()),
         })
     }
 
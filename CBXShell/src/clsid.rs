@@ -0,0 +1,8 @@
+//! Shell extension CLSID
+//!
+//! Generated by build.rs. The released CLSID is
+//! {9E6ECB90-5A61-42BD-B851-D3297D9C7F39}; setting `CBXSHELL_CLSID` at build
+//! time registers a dev build under a different CLSID (and settings key), so
+//! it can be installed side by side with the released version.
+
+include!(concat!(env!("OUT_DIR"), "/clsid.rs"));
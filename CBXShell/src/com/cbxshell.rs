@@ -11,20 +11,37 @@ use windows::{
     Win32::System::Com::*,
 };
 use std::sync::atomic::AtomicU32;
-use std::sync::Mutex;
+use std::sync::{Mutex, OnceLock};
+
+use super::property_store::{read_archive_properties, ArchiveProperties};
+use crate::image_processor::thumbnail::Thumbnail;
+
+/// Entry names in logs are clipped to this many characters
+const MAX_LOGGED_NAME_CHARS: usize = 120;
 
 /// CBXShell COM object
-/// Implements: IThumbnailProvider, IInitializeWithStream, IQueryInfo
+/// Implements: IThumbnailProvider, IInitializeWithStream, IQueryInfo, IPropertyStore
 ///
 /// CRITICAL: Modern thumbnail API (IThumbnailProvider) replaces legacy IExtractImage
 /// - IThumbnailProvider: Modern thumbnail extraction (Vista+)
 /// - IInitializeWithStream: Stream-based initialization (replaces IPersistFile)
 /// - IQueryInfo: Tooltips (unchanged)
-#[implement(IThumbnailProvider, IInitializeWithStream, IQueryInfo)]
+/// - IPropertyStore: Page count and cover size (see `property_store`)
+///
+/// Thread safety: Explorer may extract thumbnails for the same file from
+/// several threads at once, each through its own instance (the class is
+/// registered `Apartment`, so one instance is only called on its own thread).
+/// Per-instance state (the stream, the opened archive) is therefore never
+/// shared. Process-wide state is limited to the DLL reference count (atomic),
+/// the debug log (mutex), the worker pool (`OnceLock`) and the decode memory
+/// budget (atomics), all of which are safe to use concurrently.
+#[implement(IThumbnailProvider, IInitializeWithStream, IQueryInfo, IPropertyStore)]
 pub struct CBXShell {
     #[allow(dead_code)] // Used by COM infrastructure through #[implement] macro
     ref_count: AtomicU32,
     stream: Mutex<Option<IStream>>,
+    /// Properties for IPropertyStore, read from the stream on first use
+    properties: OnceLock<ArchiveProperties>,
 }
 
 impl CBXShell {
@@ -36,6 +53,7 @@ impl CBXShell {
         let cbxshell = CBXShell {
             ref_count: AtomicU32::new(1),
             stream: Mutex::new(None),
+            properties: OnceLock::new(),
         };
 
         crate::add_dll_ref();
@@ -45,7 +63,50 @@ impl CBXShell {
 
     /// Get the stored IStream
     fn get_stream(&self) -> Option<IStream> {
-        self.stream.lock().unwrap().clone()
+        self.lock_stream().clone()
+    }
+
+    /// Lock the stream slot, recovering from a poisoned mutex
+    ///
+    /// A panic while holding the lock must not turn every later call into a
+    /// panic across the COM boundary; the slot only holds an `Option`, so the
+    /// inner value is always consistent.
+    fn lock_stream(&self) -> std::sync::MutexGuard<'_, Option<IStream>> {
+        self.stream.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Properties of the archive for IPropertyStore, read once
+    ///
+    /// An archive that can't be read reports no properties instead of
+    /// failing every call; before `Initialize` there are none either.
+    pub(super) fn archive_properties(&self) -> ArchiveProperties {
+        if let Some(properties) = self.properties.get() {
+            return *properties;
+        }
+        let Some(stream) = self.get_stream() else {
+            return ArchiveProperties::default();
+        };
+
+        *self.properties.get_or_init(|| {
+            read_archive_properties(stream).unwrap_or_else(|e| {
+                tracing::warn!("Failed to read archive properties: {}", e);
+                crate::utils::debug_log::debug_log(&format!("ERROR: Failed to read archive properties - {}", e));
+                ArchiveProperties::default()
+            })
+        })
+    }
+
+    /// Extract the thumbnail unless the `Enabled` kill-switch is off
+    ///
+    /// Checked before anything else, so a disabled handler never reads the
+    /// stream and Explorer falls back to the default icon.
+    fn extract_thumbnail_if_enabled(&self, cx: u32, enabled: bool) -> crate::utils::error::Result<Thumbnail> {
+        if !enabled {
+            tracing::info!("Thumbnails are disabled, declining");
+            crate::utils::debug_log::debug_log("Kill-switch: Enabled = 0, declining");
+            return Err(crate::utils::error::CbxError::Disabled);
+        }
+        self.extract_thumbnail_internal(cx)
     }
 
     /// Extract thumbnail from archive (internal implementation)
@@ -55,8 +116,9 @@ impl CBXShell {
     /// 2. Reads archive data from stream into memory
     /// 3. Detects archive type from magic bytes
     /// 4. Opens the archive from memory
-    /// 5. Reads sort preference from registry
-    /// 6. Finds the first image (alphabetically if sorted)
+    /// 5. Reads cover selection preferences from registry
+    /// 6. Finds the cover image (alphabetically if sorted, or the first
+    ///    archive entry in HonorArchiveOrderCover mode)
     /// 7. Extracts the image data
     /// 8. Creates thumbnail HBITMAP with requested size
     ///
@@ -64,13 +126,21 @@ impl CBXShell {
     /// * `cx` - Maximum thumbnail width/height in pixels
     ///
     /// # Returns
-    /// * `Ok(HBITMAP)` - Successfully created thumbnail
+    /// * `Ok(Thumbnail)` - Successfully created thumbnail and its alpha type
     /// * `Err(CbxError)` - Failed to extract or create thumbnail
-    fn extract_thumbnail_internal(&self, cx: u32) -> crate::utils::error::Result<HBITMAP> {
-        use crate::archive::{open_archive_from_stream, IStreamReader, should_sort_images};
-        use crate::image_processor::thumbnail::create_thumbnail_with_size;
+    fn extract_thumbnail_internal(&self, cx: u32) -> crate::utils::error::Result<Thumbnail> {
+        use crate::archive::{
+            file_extension, note_type_mismatch, open_archive_from_stream_with_settings, stream_file_name,
+            ArchiveSession, ExtractionSettings, IStreamReader,
+        };
+        use crate::image_processor::memory_budget::set_max_total_decode_bytes;
+        use crate::utils::text::truncate_chars_with_ellipsis;
+        use crate::utils::thread_pool::{execute, init_thread_pool};
+        use crate::image_processor::thumbnail::{create_thumbnail, BitDepth, ThumbnailBackground, ThumbnailConfig};
         use crate::utils::error::CbxError;
+        use std::time::Instant;
 
+        let started = Instant::now();
         crate::utils::debug_log::debug_log(">>>>> extract_thumbnail_internal STARTING (OPTIMIZED STREAMING) <<<<<");
         crate::utils::debug_log::debug_log(&format!("Requested thumbnail size: {}x{}", cx, cx));
 
@@ -84,6 +154,14 @@ impl CBXShell {
         tracing::info!("Extracting thumbnail from IStream (streaming mode)");
         crate::utils::debug_log::debug_log("Step 1: IStream retrieved successfully");
 
+        // The file extension selects per-extension overrides (`Extensions\.epub` etc.)
+        let extension = stream_file_name(&stream).as_deref().and_then(file_extension);
+        tracing::debug!("Archive extension: {:?}", extension);
+
+        // Every setting below comes from this one snapshot
+        let settings = ExtractionSettings::read(extension.as_deref());
+        let config = &settings.config;
+
         // Step 2: Create streaming reader (NO MEMORY COPY!)
         crate::utils::debug_log::debug_log("Step 2: Creating streaming reader (OPTIMIZED)...");
         let reader = IStreamReader::new(stream);
@@ -92,61 +170,184 @@ impl CBXShell {
 
         // Step 3: Open archive from stream (OPTIMIZED!)
         crate::utils::debug_log::debug_log("Step 3: Opening archive from stream (NO FULL LOAD)...");
-        let archive = open_archive_from_stream(reader)?;
+        // One session serves the marker check, cover lookup and extraction,
+        // so the directory is listed at most once
+        // The strategy (memory/stream/temp file) can be overridden per extension
+        let archive = ArchiveSession::new(open_archive_from_stream_with_settings(reader, &settings)?);
         tracing::debug!("Archive opened successfully from stream");
         crate::utils::debug_log::debug_log("Step 3: Archive opened successfully in streaming mode");
+        let opened = Instant::now();
+        note_type_mismatch(extension.as_deref(), archive.archive_type());
+
+        // Step 3b: Honor a `.nothumb`/`NOTHUMB` opt-out marker (Explorer shows the default icon)
+        if config.honor_no_thumb_marker && archive.has_no_thumb_marker()? {
+            tracing::info!("Archive contains a no-thumbnail marker, declining");
+            crate::utils::debug_log::debug_log("Step 3b: No-thumbnail marker found, declining");
+            return Err(CbxError::NoThumbnailMarker);
+        }
 
-        // Step 4: Read sort preference from registry
-        let sort = should_sort_images();
-        tracing::debug!("Sort preference: {}", sort);
-        crate::utils::debug_log::debug_log(&format!("Step 4: Sort preference: {}", sort));
+        // Step 4: Read cover selection preferences from registry
+        let cover_options = settings.cover_options();
+        tracing::debug!("Cover options: {:?}", cover_options);
+        crate::utils::debug_log::debug_log(&format!("Step 4: Cover options: {:?}", cover_options));
 
-        // Step 5: Find first image in archive
-        crate::utils::debug_log::debug_log("Step 5: Finding first image...");
-        let entry = archive.find_first_image(sort)?;
-        tracing::info!("Found image: {} ({} bytes)", entry.name, entry.size);
-        crate::utils::debug_log::debug_log(&format!("Step 5: Found image: {} ({} bytes)", entry.name, entry.size));
+        // IThumbnailProvider provides cx (max dimension), we create square thumbnails
+        let thumbnail_size = if cx == 0 { 256 } else { cx };
+
+        // Step 5: Find cover image in archive (an embedded cover cache rendered with
+        // these options wins if enabled; stream opens only read it, `embed_cover_cache`
+        // writes it for path opens)
+        crate::utils::debug_log::debug_log("Step 5: Finding cover image...");
+        let cached = if config.embed_cover_cache {
+            archive.cover_cache(&cover_options, thumbnail_size)?
+        } else {
+            None
+        };
+        let (entry, cached_data) = match cached {
+            Some((entry, data)) => (entry, Some(data)),
+            None => (archive.cover(&cover_options)?, None),
+        };
+        let found = Instant::now();
+        let logged_name = truncate_chars_with_ellipsis(&entry.name, MAX_LOGGED_NAME_CHARS);
+        tracing::info!("Found image: {} ({} bytes)", logged_name, entry.size);
+        crate::utils::debug_log::debug_log(&format!("Step 5: Found image: {} ({} bytes)", logged_name, entry.size));
 
         // Step 6: Extract image data
         crate::utils::debug_log::debug_log("Step 6: Extracting image data...");
-        let image_data = archive.extract_entry(&entry)?;
+        let (entry, image_data) = match cached_data {
+            Some(data) => (entry, data),
+            // A cover without a decoder (e.g. HEIC) is skipped for the next candidate
+            None => archive.extract_cover(entry, &cover_options)?,
+        };
         tracing::debug!("Extracted {} bytes of image data", image_data.len());
-        crate::utils::debug_log::debug_log(&format!("Step 6: Extracted {} bytes of image data", image_data.len()));
-
-        // Step 6b: Verify image format using magic headers
+        crate::utils::debug_log::debug_log(&format!(
+            "Step 6: Extracted {} bytes of image data from {}",
+            image_data.len(),
+            truncate_chars_with_ellipsis(&entry.name, MAX_LOGGED_NAME_CHARS)
+        ));
+        let extracted = Instant::now();
+
+        // Step 6b: Verify image format using magic headers; a corrupt cover is
+        // replaced in step 8 by the first image that renders
         crate::utils::debug_log::debug_log("Step 6b: Verifying image format with magic headers...");
-        crate::archive::verify_image_data(&image_data, &entry.name)?;
-        crate::utils::debug_log::debug_log("Step 6b: Image format verification passed");
+        let verified = crate::archive::verify_image_data(&image_data, &entry.name);
+        if verified.is_ok() {
+            crate::utils::debug_log::debug_log("Step 6b: Image format verification passed");
+        }
 
         // Step 7: Use requested size from IThumbnailProvider::GetThumbnail
-        // IThumbnailProvider provides cx (max dimension), we create square thumbnails
-        let thumbnail_size = if cx == 0 { 256 } else { cx };
         tracing::debug!("Creating thumbnail with size: {}x{}", thumbnail_size, thumbnail_size);
         crate::utils::debug_log::debug_log(&format!("Step 7: Creating thumbnail with size: {}x{}", thumbnail_size, thumbnail_size));
 
-        // Step 8: Create thumbnail HBITMAP
+        // Step 8: Create thumbnail HBITMAP (on the worker pool if enabled, inline otherwise)
         crate::utils::debug_log::debug_log("Step 8: Creating thumbnail HBITMAP...");
-        let hbitmap = match create_thumbnail_with_size(&image_data, thumbnail_size, thumbnail_size) {
-            Ok(bmp) => {
-                tracing::info!("Thumbnail created successfully: {:?}", bmp);
+        let worker_threads = config.worker_threads;
+        if worker_threads > 0 {
+            if let Err(e) = init_thread_pool(worker_threads as usize) {
+                tracing::warn!("Failed to start thread pool, decoding inline: {}", e);
+            }
+        }
+
+        // Concurrent decodes share one memory budget; over budget fails with CbxError::Busy (E_PENDING)
+        let max_total_decode_bytes = usize::try_from(config.max_total_decode_bytes).unwrap_or(usize::MAX);
+        set_max_total_decode_bytes(max_total_decode_bytes);
+
+        let background = ThumbnailBackground::from_value(config.thumbnail_background).unwrap_or_default();
+        let thumbnail_config = ThumbnailConfig {
+            max_width: thumbnail_size,
+            max_height: thumbnail_size,
+            no_upscale: config.no_upscale,
+            auto_levels: config.auto_levels,
+            trim_borders: config.trim_borders,
+            bit_depth: BitDepth::from_bits(config.thumbnail_bit_depth).unwrap_or_default(),
+            keep_transparency: background == ThumbnailBackground::Transparent,
+            background_color: background.color(),
+            ..Default::default()
+        };
+
+        let render = |data: Vec<u8>, name: &str| {
+            let config = ThumbnailConfig {
+                format_hint: image::ImageFormat::from_path(name).ok(),
+                ..thumbnail_config.clone()
+            };
+            execute(move || create_thumbnail(&data, config)).and_then(|result| result)
+        };
+
+        let image_data_len = image_data.len();
+        let mut decode_result = verified.and_then(|()| render(image_data, &entry.name));
+
+        // A corrupt cover, or one that passed verification but is truncated, is
+        // replaced by the first image that renders
+        if let Err(CbxError::Image(_) | CbxError::UnsupportedFormat(_)) = &decode_result {
+            if let Some(thumbnail) = decodable_fallback(&archive, cover_options.sort, &entry, &render) {
+                decode_result = Ok(thumbnail);
+            }
+        }
+
+        let thumbnail = match decode_result {
+            Ok(thumbnail) => {
+                tracing::info!("Thumbnail created successfully: {:?}", thumbnail.bitmap);
                 crate::utils::debug_log::debug_log(&format!("Step 8: Thumbnail created successfully - HBITMAP: {:?} (handle: 0x{:x})",
-                    bmp, bmp.0 as usize));
-                bmp
+                    thumbnail.bitmap, thumbnail.bitmap.0 as usize));
+                thumbnail
             }
             Err(e) => {
                 tracing::error!("Failed to create thumbnail: {}", e);
                 crate::utils::debug_log::debug_log(&format!("ERROR Step 8: Thumbnail creation failed: {}", e));
                 crate::utils::debug_log::debug_log(&format!("ERROR: Image data size: {} bytes, requested size: {}x{}",
-                    image_data.len(), thumbnail_size, thumbnail_size));
+                    image_data_len, thumbnail_size, thumbnail_size));
                 return Err(e);
             }
         };
 
+        // Timing summary; the cover's compression explains slow extracts (e.g. solid LZMA 7z)
+        let compression = archive.cover_compression().unwrap_or_else(|_| "unknown".to_string());
+        let summary = format!(
+            "Timing: open {} ms, cover {} ms, extract {} ms ({}), decode {} ms, total {} ms",
+            (opened - started).as_millis(),
+            (found - opened).as_millis(),
+            (extracted - found).as_millis(),
+            compression,
+            extracted.elapsed().as_millis(),
+            started.elapsed().as_millis()
+        );
+        tracing::info!("{}", summary);
+        crate::utils::debug_log::debug_log(&summary);
+
         crate::utils::debug_log::debug_log(">>>>> extract_thumbnail_internal COMPLETED SUCCESSFULLY <<<<<");
-        Ok(hbitmap)
+        Ok(thumbnail)
     }
 }
 
+/// The thumbnail of the first image that renders, to replace a cover that didn't
+///
+/// Candidates go through `render` (the worker pool and the decode memory
+/// budget, like the cover), so the winner is decoded only once. `None` if no
+/// image other than the `failed` cover renders (see
+/// `Archive::find_first_decodable_image`).
+fn decodable_fallback(
+    archive: &crate::archive::ArchiveSession,
+    sort: bool,
+    failed: &crate::archive::ArchiveEntry,
+    render: &dyn Fn(Vec<u8>, &str) -> crate::utils::error::Result<Thumbnail>,
+) -> Option<Thumbnail> {
+    let mut thumbnail = None;
+    let entry = archive
+        .first_decodable_image(sort, &mut |entry, data| {
+            if entry.name == failed.name {
+                return Err(crate::utils::error::CbxError::Image("Cover already failed".to_string()));
+            }
+            thumbnail = Some(render(data, &entry.name)?);
+            Ok(())
+        })
+        .ok()?;
+    let logged_name = crate::utils::text::truncate_chars_with_ellipsis(&entry.name, MAX_LOGGED_NAME_CHARS);
+    tracing::info!("Cover doesn't decode, fell back to {}", logged_name);
+    crate::utils::debug_log::debug_log(&format!("Cover doesn't decode, fell back to {}", logged_name));
+
+    thumbnail
+}
+
 impl Drop for CBXShell {
     fn drop(&mut self) {
         crate::release_dll_ref();
@@ -171,7 +372,7 @@ impl IInitializeWithStream_Impl for CBXShell {
         crate::utils::debug_log::debug_log("IStream received and cloned successfully");
 
         // Store the cloned stream (properly ref-counted)
-        *self.stream.lock().unwrap() = Some(stream);
+        *self.lock_stream() = Some(stream);
 
         crate::utils::debug_log::debug_log("SUCCESS: IInitializeWithStream::Initialize completed");
         Ok(())
@@ -191,8 +392,8 @@ impl IThumbnailProvider_Impl for CBXShell {
         }
 
         // Call internal extraction method
-        match self.extract_thumbnail_internal(cx) {
-            Ok(hbitmap) => {
+        match self.extract_thumbnail_if_enabled(cx, crate::archive::is_enabled()) {
+            Ok(Thumbnail { bitmap: hbitmap, alpha }) => {
                 tracing::info!("GetThumbnail succeeded, returning HBITMAP: {:?}", hbitmap);
                 crate::utils::debug_log::debug_log(&format!("SUCCESS: GetThumbnail completed - HBITMAP: {:?} (handle: 0x{:x})",
                     hbitmap, hbitmap.0 as usize));
@@ -212,17 +413,26 @@ impl IThumbnailProvider_Impl for CBXShell {
                     *phbmp = hbitmap;
 
                     // Set alpha type if requested
-                    // We composite images with white background, removing transparency
                     // WTS_ALPHATYPE: WTSAT_UNKNOWN=0, WTSAT_RGB=1 (no alpha), WTSAT_ARGB=2 (has alpha)
-                    // Use WTSAT_RGB since we've removed all transparency
+                    // Transparent covers are kept as ARGB; everything else is opaque RGB
                     if !pdwalpha.is_null() {
-                        *pdwalpha = WTSAT_RGB; // Value should be 1
-                        crate::utils::debug_log::debug_log("Alpha type set to WTSAT_RGB (no alpha channel)");
+                        *pdwalpha = alpha;
+                        crate::utils::debug_log::debug_log(&format!("Alpha type set to {}", alpha.0));
                     }
                 }
 
                 Ok(())
             }
+            // An expected outcome, not a failure: Explorer shows the file's icon
+            Err(
+                e @ (crate::utils::error::CbxError::PasswordRequired(_)
+                | crate::utils::error::CbxError::Disabled
+                | crate::utils::error::CbxError::NoThumbnailMarker),
+            ) => {
+                tracing::info!("GetThumbnail declined: {}", e);
+                crate::utils::debug_log::debug_log(&format!("GetThumbnail declined - {}", e));
+                Err(Error::from(HRESULT::from(e)))
+            }
             Err(e) => {
                 tracing::error!("GetThumbnail failed: {}", e);
                 crate::utils::debug_log::debug_log(&format!("ERROR: GetThumbnail failed - {}", e));
@@ -257,7 +467,7 @@ mod tests {
     use super::*;
     use windows::Win32::Graphics::Gdi::DeleteObject;
     use windows::Win32::System::Com::{
-        CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED, IStream, STREAM_SEEK_SET,
+        CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED, IStream, STREAM_SEEK_CUR, STREAM_SEEK_SET,
     };
     use windows::Win32::System::Com::StructuredStorage::CreateStreamOnHGlobal;
     use windows::Win32::Foundation::BOOL;
@@ -350,6 +560,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_kill_switch_skips_extraction() {
+        unsafe {
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+
+            let stream = create_test_cbz_stream().expect("Failed to create test stream");
+            let provider = CBXShell::new().expect("Failed to create CBXShell");
+            let init_stream: IInitializeWithStream = provider.cast().unwrap();
+            init_stream.Initialize(Some(&stream), STGM_READ.0).unwrap();
+            let shell: &CBXShell = provider.as_impl();
+
+            // Declined before the stream is read
+            let result = shell.extract_thumbnail_if_enabled(256, false);
+            assert!(matches!(result, Err(crate::utils::error::CbxError::Disabled)));
+            assert_eq!(HRESULT::from(crate::utils::error::CbxError::Disabled), WTS_E_FAILEDEXTRACTION);
+            let mut position = 0u64;
+            stream.Seek(0, STREAM_SEEK_CUR, Some(&mut position)).unwrap();
+            assert_eq!(position, 0, "stream must not be touched");
+
+            let thumbnail = shell.extract_thumbnail_if_enabled(256, true).unwrap();
+            DeleteObject(thumbnail.bitmap).unwrap();
+
+            CoUninitialize();
+        }
+    }
+
     #[test]
     fn test_extract_without_initialize_fails() {
         unsafe {
@@ -397,4 +633,61 @@ mod tests {
             CoUninitialize();
         }
     }
+
+    #[test]
+    fn test_concurrent_extraction_same_file() {
+        use std::sync::{Arc, Barrier};
+        use windows::Win32::Graphics::Gdi::{GetObjectW, BITMAP};
+
+        const THREADS: usize = 16;
+        const ITERATIONS: usize = 8;
+
+        // Every thread extracts the same archive bytes at the same time,
+        // mimicking Explorer requesting one file from several threads
+        let barrier = Arc::new(Barrier::new(THREADS));
+
+        let handles: Vec<_> = (0..THREADS)
+            .map(|_| {
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || unsafe {
+                    let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+                    barrier.wait();
+
+                    for _ in 0..ITERATIONS {
+                        let stream = create_test_cbz_stream().expect("Failed to create test stream");
+                        let thumbnail_provider = CBXShell::new().expect("Failed to create CBXShell");
+
+                        let init_stream: IInitializeWithStream = thumbnail_provider.cast().unwrap();
+                        init_stream.Initialize(Some(&stream), STGM_READ.0).unwrap();
+
+                        let thumb_provider: IThumbnailProvider = init_stream.cast().unwrap();
+
+                        let mut hbitmap = HBITMAP::default();
+                        let mut alpha_type = WTS_ALPHATYPE::default();
+                        thumb_provider.GetThumbnail(64, &mut hbitmap, &mut alpha_type)
+                            .expect("Concurrent GetThumbnail failed");
+                        assert_ne!(hbitmap.0, 0, "HBITMAP should not be null");
+
+                        // Every thread must see the same (1x1) cover
+                        let mut bitmap = BITMAP::default();
+                        let written = GetObjectW(
+                            hbitmap,
+                            std::mem::size_of::<BITMAP>() as i32,
+                            Some(&mut bitmap as *mut _ as *mut _),
+                        );
+                        assert!(written > 0, "GetObjectW failed");
+                        assert_eq!((bitmap.bmWidth, bitmap.bmHeight.abs()), (1, 1));
+
+                        DeleteObject(hbitmap).expect("Failed to delete HBITMAP");
+                    }
+
+                    CoUninitialize();
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().expect("Extraction thread panicked");
+        }
+    }
 }
@@ -0,0 +1,236 @@
+//! IPropertyStore implementation
+//!
+//! Read-only property handler for Explorer's Details pane, columns and
+//! tooltips: the number of pages and the cover's dimensions. The page count
+//! is reported as the built-in `System.Document.PageCount` ("Pages"), since a
+//! custom property would need a property schema registered machine-wide.
+//! Properties are read from the stream on first use and cached by `CBXShell`.
+
+use windows::{
+    core::*,
+    Win32::Foundation::*,
+    Win32::Storage::EnhancedStorage::{PKEY_Document_PageCount, PKEY_Image_HorizontalSize, PKEY_Image_VerticalSize},
+    Win32::System::Com::IStream,
+    Win32::System::Com::StructuredStorage::PROPVARIANT,
+    Win32::System::Variant::VT_UI4,
+    Win32::UI::Shell::PropertiesSystem::*,
+};
+
+use super::cbxshell::CBXShell;
+
+/// Leading bytes of the cover read to find its dimensions
+const COVER_HEADER_LEN: usize = 256 * 1024;
+
+/// Properties reported for an archive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveProperties {
+    /// Number of image entries (`ArchiveMetadata::image_count`)
+    pub page_count: Option<u32>,
+    /// Cover width and height, if its header could be read
+    pub cover_size: Option<(u32, u32)>,
+}
+
+impl ArchiveProperties {
+    /// The known properties with their values, in `GetAt` order
+    fn values(&self) -> Vec<(PROPERTYKEY, u32)> {
+        let mut values = Vec::new();
+        if let Some(pages) = self.page_count {
+            values.push((PKEY_Document_PageCount, pages));
+        }
+        if let Some((width, height)) = self.cover_size {
+            values.push((PKEY_Image_HorizontalSize, width));
+            values.push((PKEY_Image_VerticalSize, height));
+        }
+        values
+    }
+}
+
+/// Read the properties of the archive in `stream`
+///
+/// The cover is the one the thumbnail shows; its dimensions come from its
+/// header, so the whole entry is only extracted when the header isn't in
+/// the first `COVER_HEADER_LEN` bytes.
+pub fn read_archive_properties(stream: IStream) -> crate::utils::error::Result<ArchiveProperties> {
+    use crate::archive::{
+        file_extension, open_archive_from_stream_with_settings, stream_file_name, ArchiveSession,
+        ExtractionSettings, IStreamReader,
+    };
+    use crate::image_processor::decoder::primary_dimensions;
+
+    let extension = stream_file_name(&stream).as_deref().and_then(file_extension);
+    let settings = ExtractionSettings::read(extension.as_deref());
+    let reader = IStreamReader::new(stream);
+    let archive = ArchiveSession::new(open_archive_from_stream_with_settings(reader, &settings)?);
+
+    let metadata = archive.metadata()?;
+    let page_count = u32::try_from(metadata.image_count).unwrap_or(u32::MAX);
+
+    let cover_size = match archive.cover(&settings.cover_options()) {
+        Ok(cover) => {
+            let header = archive.extract_prefix(&cover, COVER_HEADER_LEN)?;
+            match primary_dimensions(&header) {
+                Some(size) => Some(size),
+                None if cover.size > COVER_HEADER_LEN as u64 => primary_dimensions(&archive.extract(&cover)?),
+                None => None,
+            }
+        }
+        Err(e) => {
+            tracing::debug!("No cover for the size properties: {}", e);
+            None
+        }
+    };
+
+    Ok(ArchiveProperties {
+        page_count: Some(page_count),
+        cover_size,
+    })
+}
+
+/// `VT_UI4` variant holding `value`
+fn variant_u32(value: u32) -> PROPVARIANT {
+    let mut variant = PROPVARIANT::default();
+    // Safety: sets the type tag and the matching member of a zeroed variant
+    unsafe {
+        let inner = &mut variant.Anonymous.Anonymous;
+        inner.vt = VT_UI4;
+        inner.Anonymous.ulVal = value;
+    }
+    variant
+}
+
+// IPropertyStore implementation (read-only)
+impl IPropertyStore_Impl for CBXShell {
+    fn GetCount(&self) -> Result<u32> {
+        Ok(self.archive_properties().values().len() as u32)
+    }
+
+    fn GetAt(&self, iprop: u32, pkey: *mut PROPERTYKEY) -> Result<()> {
+        if pkey.is_null() {
+            return Err(Error::from(E_POINTER));
+        }
+
+        let values = self.archive_properties().values();
+        let (key, _) = values.get(iprop as usize).ok_or_else(|| Error::from(E_INVALIDARG))?;
+        // Safety: checked for null; the caller owns the out parameter
+        unsafe { *pkey = *key };
+        Ok(())
+    }
+
+    fn GetValue(&self, key: *const PROPERTYKEY) -> Result<PROPVARIANT> {
+        if key.is_null() {
+            return Err(Error::from(E_POINTER));
+        }
+
+        // Safety: checked for null; the caller owns the key
+        let key = unsafe { *key };
+        // Properties this handler doesn't know are empty (VT_EMPTY), not an error
+        Ok(self
+            .archive_properties()
+            .values()
+            .into_iter()
+            .find(|(k, _)| *k == key)
+            .map_or_else(PROPVARIANT::default, |(_, value)| variant_u32(value)))
+    }
+
+    fn SetValue(&self, _key: *const PROPERTYKEY, _propvar: *const PROPVARIANT) -> Result<()> {
+        Err(Error::from(STG_E_ACCESSDENIED))
+    }
+
+    fn Commit(&self) -> Result<()> {
+        Err(Error::from(STG_E_ACCESSDENIED))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::mock_stream::MemoryStream;
+    use std::io::Write as _;
+    use windows::Win32::Storage::EnhancedStorage::PKEY_Image_Dimensions;
+    use windows::Win32::System::Variant::VT_EMPTY;
+    use windows::Win32::System::Com::{CoInitializeEx, CoUninitialize, COINIT_APARTMENTTHREADED, STGM_READ};
+    use zip::write::{FileOptions, ZipWriter};
+
+    /// A 3x2 cover, a second page, a directory and non-image entries
+    fn create_comic_zip() -> Vec<u8> {
+        let mut cover = Vec::new();
+        image::RgbaImage::new(3, 2)
+            .write_to(&mut std::io::Cursor::new(&mut cover), image::ImageFormat::Png)
+            .unwrap();
+
+        let mut buffer = Vec::new();
+        {
+            let mut zip = ZipWriter::new(std::io::Cursor::new(&mut buffer));
+            zip.add_directory("pages/", FileOptions::default()).unwrap();
+            for (name, data) in [
+                ("pages/001.png", &cover[..]),
+                ("pages/002.png", &cover[..]),
+                ("ComicInfo.xml", b"<ComicInfo/>"),
+                ("notes.txt", b"not a page"),
+            ] {
+                zip.start_file(name, FileOptions::default()).unwrap();
+                zip.write_all(data).unwrap();
+            }
+            zip.finish().unwrap();
+        }
+        buffer
+    }
+
+    #[test]
+    fn test_values_order() {
+        let properties = ArchiveProperties {
+            page_count: Some(12),
+            cover_size: Some((800, 1200)),
+        };
+        assert_eq!(
+            properties.values(),
+            vec![
+                (PKEY_Document_PageCount, 12),
+                (PKEY_Image_HorizontalSize, 800),
+                (PKEY_Image_VerticalSize, 1200),
+            ]
+        );
+        assert!(ArchiveProperties::default().values().is_empty());
+    }
+
+    #[test]
+    fn test_read_archive_properties() {
+        let stream = MemoryStream::create_named(create_comic_zip(), "comic.cbz");
+        let properties = read_archive_properties(stream).unwrap();
+        assert_eq!(properties.page_count, Some(2));
+        assert_eq!(properties.cover_size, Some((3, 2)));
+    }
+
+    #[test]
+    fn test_property_store_reports_pages_and_cover_size() {
+        unsafe {
+            let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+
+            let provider = CBXShell::new().expect("Failed to create CBXShell");
+            let init_stream: IInitializeWithStream = provider.cast().unwrap();
+            let stream = MemoryStream::create_named(create_comic_zip(), "comic.cbz");
+            init_stream.Initialize(Some(&stream), STGM_READ.0).unwrap();
+
+            let store: IPropertyStore = init_stream.cast().expect("Failed to cast to IPropertyStore");
+            assert_eq!(store.GetCount().unwrap(), 3);
+
+            let mut key = PROPERTYKEY::default();
+            store.GetAt(0, &mut key).unwrap();
+            assert_eq!(key, PKEY_Document_PageCount);
+            assert!(store.GetAt(3, &mut key).is_err());
+
+            let pages = store.GetValue(&PKEY_Document_PageCount).unwrap();
+            assert_eq!(pages.Anonymous.Anonymous.vt, VT_UI4);
+            assert_eq!(pages.Anonymous.Anonymous.Anonymous.ulVal, 2);
+            let height = store.GetValue(&PKEY_Image_VerticalSize).unwrap();
+            assert_eq!(height.Anonymous.Anonymous.Anonymous.ulVal, 2);
+
+            // Unknown properties are empty; nothing can be written
+            let other = store.GetValue(&PKEY_Image_Dimensions).unwrap();
+            assert_eq!(other.Anonymous.Anonymous.vt, VT_EMPTY);
+            assert!(store.SetValue(&PKEY_Document_PageCount, &pages).is_err());
+
+            CoUninitialize();
+        }
+    }
+}
@@ -3,8 +3,12 @@
 //! Supports all image formats provided by the `image` crate including:
 //! JPEG, PNG, GIF, BMP, TIFF, ICO, WebP, and more.
 
+use super::magic::{detect_image_format, ImageFormat};
+use super::memory_budget;
 use crate::utils::error::CbxError;
-use image::{DynamicImage, ImageReader};
+use image::codecs::gif::GifDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, ColorType, DynamicImage, ImageDecoder, ImageReader};
 use std::io::Cursor;
 
 type Result<T> = std::result::Result<T, CbxError>;
@@ -19,8 +23,37 @@ type Result<T> = std::result::Result<T, CbxError>;
 ///
 /// # Returns
 /// * `Ok(DynamicImage)` - Successfully decoded image
+/// * `Err(CbxError::UnsupportedFormat)` - The format was recognized, but its
+///   decoder isn't compiled into this build (e.g. AVIF)
 /// * `Err(CbxError::Image)` - Failed to decode (invalid format or corrupt data)
 ///
+/// # Animated images
+/// Animated GIF, APNG and animated WebP decode to their first frame, which
+/// is what a cover thumbnail shows. GIF and animated WebP go through the
+/// frame decoder, which stops after the first frame (and copes with
+/// animated WebPs the still-image path rejects); if that fails, they are
+/// decoded as usual. AVIF image sequences (`avis` brand) are
+/// detected as AVIF and handled the same way by the AVIF decoder when it is
+/// compiled in.
+///
+/// # TIFF
+/// Both byte orders (`II` and `MM`), striped and tiled layouts and 8/16-bit
+/// samples are handled by the `tiff` crate; multi-page files decode their
+/// first page. Compression schemes it doesn't implement fail with its
+/// "unsupported" message in the `CbxError::Image` text.
+///
+/// # System codecs
+/// With the `wic-fallback` feature, data none of the built-in decoders can
+/// handle is handed to WIC (Windows Imaging Component), which decodes any
+/// format with an installed system codec (e.g. HEIC). If WIC fails too, the
+/// built-in decoder's error is returned.
+///
+/// # Progressive JPEG
+/// Progressive (SOF2) JPEGs are fully supported by the `image` crate's JPEG
+/// decoder. The whole entry is always extracted before decoding, so all scans
+/// are available; a truncated progressive stream fails to decode instead of
+/// producing a partial (blurry) image.
+///
 /// # Examples
 /// ```no_run
 /// let jpeg_data = std::fs::read("image.jpg")?;
@@ -28,23 +61,338 @@ type Result<T> = std::result::Result<T, CbxError>;
 /// println!("Image dimensions: {}x{}", img.width(), img.height());
 /// ```
 pub fn decode_image(data: &[u8]) -> Result<DynamicImage> {
+    decode_image_with_hint(data, None)
+}
+
+/// Decode image from raw bytes, with a format hint for when guessing fails
+///
+/// `hint` is the format implied by the file name (e.g. an archive entry's
+/// extension). The format is still guessed from the data first; only if
+/// that decode fails is it retried as `hint`, so a misnamed entry decodes
+/// as what it really is. Formats without a signature (e.g. TGA) can only
+/// be decoded this way.
+pub fn decode_image_with_hint(data: &[u8], hint: Option<image::ImageFormat>) -> Result<DynamicImage> {
+    let decoded = decode_builtin(data, hint);
+
+    #[cfg(feature = "wic-fallback")]
+    if decoded.is_err() && !data.is_empty() {
+        match super::wic::decode_wic(data) {
+            Ok(img) => return Ok(img),
+            Err(e) => tracing::debug!("WIC fallback decode failed: {}", e),
+        }
+    }
+
+    decoded
+}
+
+/// JPEG DCT scaling factors (1/8, 1/4, 1/2, 1), smallest image first
+const JPEG_SCALE_DENOMINATORS: [u32; 4] = [8, 4, 2, 1];
+
+/// Decode an image at a reduced size, for a thumbnail `max_dim` pixels across
+///
+/// JPEG can be decoded at 1/2, 1/4 or 1/8 scale by dropping DCT
+/// coefficients, which is several times faster than decoding a large scan
+/// at native resolution only to downscale it. The largest reduction whose
+/// longer side is still at least `max_dim` is used, so the result never
+/// needs upscaling; a 6000x9000 page for a 256px thumbnail decodes at 1/8
+/// (750x1125).
+///
+/// The `image` crate's JPEG decoder can't scale, so the scaled decode goes
+/// through WIC's JPEG codec with the `wic-fallback` feature. Other formats,
+/// builds without the feature and JPEGs WIC fails on are decoded in full
+/// by `decode_image`; callers resize the result either way.
+pub fn decode_image_scaled(data: &[u8], max_dim: u32, hint: Option<image::ImageFormat>) -> Result<DynamicImage> {
+    #[cfg(feature = "wic-fallback")]
+    if let Some((width, height)) = jpeg_scaled_size(data, max_dim) {
+        match super::wic::decode_wic_scaled(data, width, height) {
+            Ok(img) => return Ok(img),
+            Err(e) => tracing::debug!("Scaled JPEG decode failed, decoding in full: {}", e),
+        }
+    }
+    #[cfg(not(feature = "wic-fallback"))]
+    let _ = max_dim;
+
+    decode_image_with_hint(data, hint)
+}
+
+/// Size a JPEG decodes to at its best scaling factor, if it can be reduced
+#[cfg_attr(not(feature = "wic-fallback"), allow(dead_code))]
+fn jpeg_scaled_size(data: &[u8], max_dim: u32) -> Option<(u32, u32)> {
+    if detect_image_format(data).ok()? != ImageFormat::Jpeg {
+        return None;
+    }
+    let (width, height) = read_image_dimensions(data).ok()?;
+    let denominator = scale_denominator(width, height, max_dim);
+    (denominator > 1).then(|| (scaled_dim(width, denominator), scaled_dim(height, denominator)))
+}
+
+/// Largest JPEG scale denominator keeping the longer side at least `max_dim`
+fn scale_denominator(width: u32, height: u32, max_dim: u32) -> u32 {
+    let longer = width.max(height);
+    JPEG_SCALE_DENOMINATORS
+        .into_iter()
+        .find(|&denominator| scaled_dim(longer, denominator) >= max_dim)
+        .unwrap_or(1)
+}
+
+/// A dimension divided by a scale denominator, rounded up like libjpeg does
+fn scaled_dim(dim: u32, denominator: u32) -> u32 {
+    dim / denominator + u32::from(dim % denominator != 0)
+}
+
+/// Decode with the `image` crate and the built-in decoders only
+fn decode_builtin(data: &[u8], hint: Option<image::ImageFormat>) -> Result<DynamicImage> {
     if data.is_empty() {
         return Err(CbxError::Image("Empty image data".to_string()));
     }
 
+    // A recognized format without a decoder is a build configuration issue,
+    // not a corrupt file; say so instead of a generic decode failure
+    if let Ok(format) = detect_image_format(data) {
+        if !format.is_supported() {
+            return Err(CbxError::UnsupportedFormat(format!(
+                "{} decoder not compiled in",
+                format.as_str()
+            )));
+        }
+        if let Some(img) = decode_first_frame(data, format) {
+            return Ok(img);
+        }
+    }
+
     // Create a reader from the byte slice
     let reader = ImageReader::new(Cursor::new(data))
         .with_guessed_format()
         .map_err(|e| CbxError::Image(format!("Format detection failed: {}", e)))?;
 
     // Decode the image
-    reader
+    let guessed = reader.format();
+    let decoded = reader
         .decode()
-        .map_err(|e| CbxError::Image(format!("Failed to decode image: {}", e)))
+        .map_err(|e| CbxError::Image(format!("Failed to decode image: {}", e)));
+
+    // A wrong (or no) guess gets a second try as the hinted format
+    if let Some(hint) = hint.filter(|hint| decoded.is_err() && guessed != Some(*hint)) {
+        let mut reader = ImageReader::new(Cursor::new(data));
+        reader.set_format(hint);
+        match reader.decode() {
+            Ok(img) => {
+                tracing::debug!("Decoded as hinted {:?} (guessed {:?})", hint, guessed);
+                return Ok(img);
+            }
+            Err(e) => tracing::debug!("Decode as hinted {:?} failed: {}", hint, e),
+        }
+    }
+
+    // Formats the `image` crate can't handle fall back to built-in decoders
+    #[cfg(feature = "qoi")]
+    if decoded.is_err() && super::qoi::is_qoi(data) {
+        return super::qoi::decode_qoi(data);
+    }
+
+    decoded
+}
+
+/// First frame of a GIF or animated WebP, decoded without the rest
+///
+/// Returns `None` for other formats (including still WebP) and when the
+/// frame decoder fails, so the caller decodes the data as usual.
+fn decode_first_frame(data: &[u8], format: ImageFormat) -> Option<DynamicImage> {
+    /// The first frame, or an error for an animation without frames
+    fn first<'a>(decoder: impl AnimationDecoder<'a>) -> image::ImageResult<image::Frame> {
+        decoder
+            .into_frames()
+            .next()
+            .unwrap_or_else(|| Err(image::ImageError::IoError(std::io::ErrorKind::UnexpectedEof.into())))
+    }
+
+    let frame = match format {
+        ImageFormat::Gif => GifDecoder::new(Cursor::new(data)).and_then(first),
+        ImageFormat::WebP => {
+            let decoder = WebPDecoder::new(Cursor::new(data)).ok()?;
+            if !decoder.has_animation() {
+                return None;
+            }
+            first(decoder)
+        }
+        _ => return None,
+    };
+
+    match frame {
+        Ok(frame) => Some(DynamicImage::ImageRgba8(frame.into_buffer())),
+        Err(e) => {
+            tracing::debug!("First-frame {} decode failed, decoding as usual: {}", format.as_str(), e);
+            None
+        }
+    }
+}
+
+/// Decode an image into a caller-allocated RGBA8 buffer
+///
+/// Pixels are written row by row, 4 bytes each, to the front of `buf`;
+/// bytes past `width * height * 4` are left untouched. Images that decode
+/// natively to RGBA8 (e.g. RGBA PNG) are decoded straight into `buf`;
+/// other color types go through a temporary image and are converted.
+///
+/// # Returns
+/// * `Ok((width, height))` - Dimensions of the decoded image
+/// * `Err(CbxError::Image)` - `buf` is too small (the message states the
+///   required size), or the data can't be decoded
+/// * `Err(CbxError::UnsupportedFormat)` - As for `decode_image`
+pub fn decode_into_rgba(data: &[u8], buf: &mut [u8]) -> Result<(u32, u32)> {
+    let native = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .filter(|decoder| decoder.color_type() == ColorType::Rgba8);
+
+    if let Some(decoder) = native {
+        let (width, height) = decoder.dimensions();
+        let target = rgba_target(buf, width, height)?;
+        decoder
+            .read_image(target)
+            .map_err(|e| CbxError::Image(format!("Failed to decode image: {}", e)))?;
+        return Ok((width, height));
+    }
+
+    // Reject a too-small buffer before paying for the decode
+    if let Some((width, height)) = primary_dimensions(data) {
+        rgba_target(buf, width, height)?;
+    }
+
+    let rgba = decode_image(data)?.into_rgba8();
+    let (width, height) = rgba.dimensions();
+    rgba_target(buf, width, height)?.copy_from_slice(rgba.as_raw());
+    Ok((width, height))
+}
+
+/// The first `width * height * 4` bytes of `buf`, or an error naming that size
+fn rgba_target(buf: &mut [u8], width: u32, height: u32) -> Result<&mut [u8]> {
+    let required = u64::from(width) * u64::from(height) * 4;
+    match usize::try_from(required) {
+        Ok(required) if required <= buf.len() => Ok(&mut buf[..required]),
+        _ => Err(CbxError::Image(format!(
+            "Buffer too small for {}x{} RGBA image: {} bytes, {} required",
+            width,
+            height,
+            buf.len(),
+            required
+        ))),
+    }
+}
+
+/// Read image dimensions from the header without decoding pixel data
+///
+/// Used to estimate decode memory before committing to a full decode.
+pub fn read_image_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    #[cfg(feature = "qoi")]
+    if super::qoi::is_qoi(data) {
+        return super::qoi::read_qoi_dimensions(data);
+    }
+
+    ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| CbxError::Image(format!("Format detection failed: {}", e)))?
+        .into_dimensions()
+        .map_err(|e| CbxError::Image(format!("Failed to read image dimensions: {}", e)))
+}
+
+/// Dimensions of the frame that defines an image's size
+///
+/// Some formats are containers holding several images, so "the dimensions"
+/// are ambiguous. Every dimension-based feature (decode memory estimates,
+/// cover validation) goes through this function so they agree:
+/// - ICO: the largest entry (embedded PNGs report their real size)
+/// - TIFF: the first page, which is the page that gets decoded
+/// - AVIF and HEIC: the largest `ispe` (image spatial extents) property, i.e.
+///   the primary image rather than its grid tiles, thumbnails or alpha plane
+/// - Everything else: the header dimensions (animated GIF/WebP: the canvas)
+///
+/// Returns `None` if the header can't be parsed.
+pub fn primary_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match detect_image_format(data) {
+        Ok(ImageFormat::Ico) => largest_ico_entry(data),
+        Ok(ImageFormat::Avif | ImageFormat::Heic) => largest_avif_extent(data),
+        _ => read_image_dimensions(data).ok(),
+    }
+}
+
+/// Estimated size of the RGBA8 buffer `data` decodes to (`width * height * 4`)
+///
+/// Only the header is read, through `primary_dimensions`, so this is cheap
+/// enough to run before every decode; the decode memory budget reserves this
+/// amount up front.
+///
+/// # Returns
+/// * `Ok(bytes)` - Estimated decoded size
+/// * `Err(CbxError::Image)` - The header's dimensions can't be read (unknown
+///   or corrupt data, or a format without a header parser such as JPEG XL)
+pub fn estimated_decoded_bytes(data: &[u8]) -> Result<u64> {
+    let (width, height) = primary_dimensions(data).ok_or_else(|| {
+        let format = detect_image_format(data).map_or("image", |format| format.as_str());
+        CbxError::Image(format!("Can't read {} dimensions from the header", format))
+    })?;
+    Ok(memory_budget::decoded_size(width, height) as u64)
+}
+
+/// Size of an ICO directory entry
+const ICO_ENTRY_LEN: usize = 16;
+
+/// Largest entry of an ICO directory
+fn largest_ico_entry(data: &[u8]) -> Option<(u32, u32)> {
+    const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    let count = u16::from_le_bytes([*data.get(4)?, *data.get(5)?]) as usize;
+
+    (0..count)
+        .filter_map(|i| {
+            let entry = data.get(6 + i * ICO_ENTRY_LEN..6 + (i + 1) * ICO_ENTRY_LEN)?;
+            let offset = u32::from_le_bytes([entry[12], entry[13], entry[14], entry[15]]) as usize;
+
+            // PNG entries store their real size in IHDR (the directory caps at 256)
+            let payload = data.get(offset..)?;
+            if payload.starts_with(PNG_SIGNATURE) {
+                let ihdr = payload.get(16..24)?;
+                return Some((
+                    u32::from_be_bytes([ihdr[0], ihdr[1], ihdr[2], ihdr[3]]),
+                    u32::from_be_bytes([ihdr[4], ihdr[5], ihdr[6], ihdr[7]]),
+                ));
+            }
+
+            // A stored size of 0 means 256
+            let side = |b: u8| if b == 0 { 256 } else { u32::from(b) };
+            Some((side(entry[0]), side(entry[1])))
+        })
+        .filter(|&(w, h)| w > 0 && h > 0)
+        .max_by_key(|&(w, h)| u64::from(w) * u64::from(h))
+}
+
+/// Bytes of an AVIF file scanned for `ispe` properties (they live in the
+/// `meta` box at the front of the file)
+const AVIF_SCAN_LEN: usize = 64 * 1024;
+
+/// Largest `ispe` property in an AVIF (HEIF) header
+fn largest_avif_extent(data: &[u8]) -> Option<(u32, u32)> {
+    let header = &data[..data.len().min(AVIF_SCAN_LEN)];
+
+    // Box layout: size(4) "ispe" version/flags(4) width(4) height(4)
+    header
+        .windows(4)
+        .enumerate()
+        .filter(|(_, tag)| *tag == b"ispe")
+        .filter_map(|(pos, _)| {
+            let fields = header.get(pos + 8..pos + 16)?;
+            Some((
+                u32::from_be_bytes([fields[0], fields[1], fields[2], fields[3]]),
+                u32::from_be_bytes([fields[4], fields[5], fields[6], fields[7]]),
+            ))
+        })
+        .filter(|&(w, h)| w > 0 && h > 0)
+        .max_by_key(|&(w, h)| u64::from(w) * u64::from(h))
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
     /// Minimal valid JPEG file (1x1 red pixel)
@@ -78,6 +426,41 @@ mod tests {
         0xAE, 0x42, 0x60, 0x82,
     ];
 
+    /// Progressive JPEG (SOF2, 32x24: left half blue, right half red)
+    /// Encoded with optimized Huffman tables and multiple scans (also used by the thumbnail tests)
+    pub(crate) const PROGRESSIVE_JPEG: &[u8] = &[
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x10, 0x4A, 0x46, 0x49, 0x46, 0x00, 0x01, 0x02, 0x00,
+        0x00, 0x01, 0x00, 0x01, 0x00, 0x00, 0xFF, 0xC2, 0x00, 0x11, 0x08, 0x00, 0x18, 0x00,
+        0x20, 0x03, 0x00, 0x22, 0x00, 0x01, 0x11, 0x01, 0x02, 0x11, 0x01, 0xFF, 0xDB, 0x00,
+        0x43, 0x00, 0x08, 0x06, 0x06, 0x07, 0x06, 0x05, 0x08, 0x07, 0x07, 0x07, 0x09, 0x09,
+        0x08, 0x0A, 0x0C, 0x14, 0x0D, 0x0C, 0x0B, 0x0B, 0x0C, 0x19, 0x12, 0x13, 0x0F, 0x14,
+        0x1D, 0x1A, 0x1F, 0x1E, 0x1D, 0x1A, 0x1C, 0x1C, 0x20, 0x24, 0x2E, 0x27, 0x20, 0x22,
+        0x2C, 0x23, 0x1C, 0x1C, 0x28, 0x37, 0x29, 0x2C, 0x30, 0x31, 0x34, 0x34, 0x34, 0x1F,
+        0x27, 0x39, 0x3D, 0x38, 0x32, 0x3C, 0x2E, 0x33, 0x34, 0x32, 0xFF, 0xDB, 0x00, 0x43,
+        0x01, 0x09, 0x09, 0x09, 0x0C, 0x0B, 0x0C, 0x18, 0x0D, 0x0D, 0x18, 0x32, 0x21, 0x1C,
+        0x21, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32,
+        0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32,
+        0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32,
+        0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0x32, 0xFF, 0xC4, 0x00, 0x16, 0x00,
+        0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x06, 0x07, 0xFF, 0xC4, 0x00, 0x14, 0x10, 0x01, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF,
+        0xC4, 0x00, 0x15, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x04, 0xFF, 0xC4, 0x00, 0x14, 0x11, 0x01,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0xCC, 0x14,
+        0x09, 0xF5, 0x02, 0x7D, 0x40, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x11, 0x00, 0x00,
+        0x00, 0x4C, 0x19, 0x66, 0x19, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x02, 0x11, 0x00, 0x00,
+        0x00, 0x8D, 0x60, 0x9D, 0x63, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x00, 0x00, 0x01, 0x14,
+        0x00, 0x00, 0x0F, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x11, 0x01, 0x14, 0x00, 0x0F,
+        0xFF, 0xDA, 0x00, 0x08, 0x01, 0x02, 0x11, 0x01, 0x14, 0x00, 0x0F, 0xFF, 0xDA, 0x00,
+        0x08, 0x01, 0x00, 0x00, 0x15, 0x29, 0x00, 0x00, 0x0F, 0xFF, 0xDA, 0x00, 0x08, 0x01,
+        0x01, 0x11, 0x15, 0x29, 0x00, 0x0F, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x02, 0x11, 0x15,
+        0x29, 0x00, 0x0F, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x00, 0x00, 0x2A, 0x3F, 0x00, 0x00,
+        0x0F, 0xFF, 0xDA, 0x00, 0x08, 0x01, 0x01, 0x11, 0x2A, 0x3F, 0x00, 0x0F, 0xFF, 0xDA,
+        0x00, 0x08, 0x01, 0x02, 0x11, 0x2A, 0x3F, 0x00, 0x0F, 0xFF, 0xD9,
+    ];
+
     #[test]
     fn test_decode_jpeg() {
         let result = decode_image(MINIMAL_JPEG);
@@ -128,4 +511,486 @@ mod tests {
         let result = decode_image(not_image);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_decode_progressive_jpeg() {
+        assert_eq!(&PROGRESSIVE_JPEG[20..22], &[0xFF, 0xC2], "fixture must be SOF2");
+
+        let img = decode_image(PROGRESSIVE_JPEG).expect("progressive JPEG should decode");
+        assert_eq!(img.width(), 32);
+        assert_eq!(img.height(), 24);
+
+        // All scans applied: both halves have their final colors
+        let rgb = img.to_rgb8();
+        let left = rgb.get_pixel(0, 0);
+        let right = rgb.get_pixel(31, 23);
+        assert!(left[2] > 150 && left[0] < 80, "left half should be blue: {:?}", left);
+        assert!(right[0] > 150 && right[2] < 80, "right half should be red: {:?}", right);
+    }
+
+    #[test]
+    fn test_decode_truncated_progressive_jpeg_fails() {
+        let truncated = &PROGRESSIVE_JPEG[..PROGRESSIVE_JPEG.len() * 2 / 3];
+        assert!(decode_image(truncated).is_err());
+    }
+
+    /// 2x2 RGBA QOI: red, green / green, red
+    #[cfg(feature = "qoi")]
+    const QOI_2X2: &[u8] = &[
+        b'q', b'o', b'i', b'f', 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00,
+        0xFF, 0xFF, 0x00, 0x00, 0xFF, 0xFE, 0x00, 0xFF, 0x00, 0xC0, 0x32,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01,
+    ];
+
+    #[test]
+    #[cfg(feature = "qoi")]
+    fn test_decode_qoi_fallback() {
+        let img = decode_image(QOI_2X2).expect("QOI should decode via the built-in fallback");
+        assert_eq!((img.width(), img.height()), (2, 2));
+        assert_eq!(img.to_rgba8().get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(read_image_dimensions(QOI_2X2).unwrap(), (2, 2));
+    }
+
+    #[test]
+    fn test_decode_image_with_hint() {
+        // TGA has no signature, so only the hint can identify it
+        let mut tga = Vec::new();
+        image::RgbImage::from_pixel(3, 2, image::Rgb([10, 20, 30]))
+            .write_to(&mut Cursor::new(&mut tga), image::ImageFormat::Tga)
+            .unwrap();
+        assert!(decode_image(&tga).is_err());
+
+        let img = decode_image_with_hint(&tga, Some(image::ImageFormat::Tga)).expect("hinted TGA should decode");
+        assert_eq!((img.width(), img.height()), (3, 2));
+        assert_eq!(img.to_rgb8().get_pixel(2, 1).0, [10, 20, 30]);
+
+        // A recognized format is decoded as guessed, whatever the hint says
+        let img = decode_image_with_hint(MINIMAL_PNG, Some(image::ImageFormat::Tga)).unwrap();
+        assert_eq!((img.width(), img.height()), (1, 1));
+        assert!(decode_image_with_hint(b"not an image", Some(image::ImageFormat::Png)).is_err());
+    }
+
+    #[test]
+    fn test_read_image_dimensions() {
+        assert_eq!(read_image_dimensions(MINIMAL_JPEG).unwrap(), (1, 1));
+        assert_eq!(read_image_dimensions(PROGRESSIVE_JPEG).unwrap(), (32, 24));
+        assert!(read_image_dimensions(b"not an image").is_err());
+    }
+
+    /// ICO with a 16x16 and a 48x32 BMP entry, plus a PNG entry whose
+    /// directory size (0 = 256) disagrees with its real 1x1 IHDR size
+    fn three_entry_ico() -> Vec<u8> {
+        let mut ico = vec![0, 0, 1, 0, 3, 0];
+        let png_offset = (6 + 3 * ICO_ENTRY_LEN) as u32;
+        for (w, h, offset) in [(16u8, 16u8, 0u32), (48, 32, 0), (0, 0, png_offset)] {
+            ico.extend_from_slice(&[w, h, 0, 0, 1, 0, 32, 0]);
+            ico.extend_from_slice(&(MINIMAL_PNG.len() as u32).to_le_bytes());
+            ico.extend_from_slice(&offset.to_le_bytes());
+        }
+        ico.extend_from_slice(MINIMAL_PNG);
+        ico
+    }
+
+    /// Uncompressed 8-bit grayscale TIFF with one page per (width, height)
+    fn multi_page_tiff(pages: &[(u32, u32)]) -> Vec<u8> {
+        const IFD_LEN: u32 = 2 + 9 * 12 + 4;
+
+        let mut tiff = b"II*\0".to_vec();
+        tiff.extend_from_slice(&8u32.to_le_bytes());
+
+        let mut pixel_offset = 8 + IFD_LEN * pages.len() as u32;
+        for (i, &(width, height)) in pages.iter().enumerate() {
+            let entries: [(u16, u16, u32); 9] = [
+                (256, 4, width),         // ImageWidth
+                (257, 4, height),        // ImageLength
+                (258, 3, 8),             // BitsPerSample
+                (259, 3, 1),             // Compression: none
+                (262, 3, 1),             // PhotometricInterpretation: BlackIsZero
+                (273, 4, pixel_offset),  // StripOffsets
+                (277, 3, 1),             // SamplesPerPixel
+                (278, 4, height),        // RowsPerStrip
+                (279, 4, width * height), // StripByteCounts
+            ];
+            tiff.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+            for (tag, kind, value) in entries {
+                tiff.extend_from_slice(&tag.to_le_bytes());
+                tiff.extend_from_slice(&kind.to_le_bytes());
+                tiff.extend_from_slice(&1u32.to_le_bytes());
+                tiff.extend_from_slice(&value.to_le_bytes());
+            }
+            let next_ifd = if i + 1 < pages.len() { 8 + IFD_LEN * (i as u32 + 1) } else { 0 };
+            tiff.extend_from_slice(&next_ifd.to_le_bytes());
+            pixel_offset += width * height;
+        }
+
+        for &(width, height) in pages {
+            tiff.extend(std::iter::repeat(0x80).take((width * height) as usize));
+        }
+        tiff
+    }
+
+    /// Big-endian ("MM") TIFF with a single IFD, followed by `pixels`
+    ///
+    /// Entries are `(tag, type, values)` with SHORT (3) or LONG (4) values, in
+    /// ascending tag order. StripOffsets (273) and TileOffsets (324) values
+    /// are relative to the start of `pixels`.
+    fn big_endian_tiff(entries: &[(u16, u16, &[u32])], pixels: &[u8]) -> Vec<u8> {
+        const OFFSET_TAGS: [u16; 2] = [273, 324];
+
+        let encode = |tag: u16, kind: u16, values: &[u32], pixel_base: u32| -> Vec<u8> {
+            let base = if OFFSET_TAGS.contains(&tag) { pixel_base } else { 0 };
+            values
+                .iter()
+                .flat_map(|&v| match kind {
+                    3 => ((v + base) as u16).to_be_bytes().to_vec(),
+                    _ => (v + base).to_be_bytes().to_vec(),
+                })
+                .collect()
+        };
+
+        // Values longer than 4 bytes go between the IFD and the pixel data
+        let ifd_end = 8 + 2 + 12 * entries.len() as u32 + 4;
+        let external: u32 = entries
+            .iter()
+            .map(|&(tag, kind, values)| encode(tag, kind, values, 0).len() as u32)
+            .filter(|&len| len > 4)
+            .sum();
+        let pixel_base = ifd_end + external;
+
+        let mut tiff = b"MM\0*".to_vec();
+        tiff.extend_from_slice(&8u32.to_be_bytes());
+        tiff.extend_from_slice(&(entries.len() as u16).to_be_bytes());
+        let mut values_area = Vec::new();
+        for &(tag, kind, values) in entries {
+            let mut bytes = encode(tag, kind, values, pixel_base);
+            tiff.extend_from_slice(&tag.to_be_bytes());
+            tiff.extend_from_slice(&kind.to_be_bytes());
+            tiff.extend_from_slice(&(values.len() as u32).to_be_bytes());
+            if bytes.len() <= 4 {
+                bytes.resize(4, 0);
+                tiff.extend_from_slice(&bytes);
+            } else {
+                tiff.extend_from_slice(&(ifd_end + values_area.len() as u32).to_be_bytes());
+                values_area.extend_from_slice(&bytes);
+            }
+        }
+        tiff.extend_from_slice(&0u32.to_be_bytes());
+        tiff.extend_from_slice(&values_area);
+        tiff.extend_from_slice(pixels);
+        tiff
+    }
+
+    #[test]
+    fn test_decode_big_endian_tiled_tiff() {
+        // 24x20 RGB in 16x16 tiles; the right and bottom tiles are padded
+        const COLORS: [[u8; 3]; 4] = [[255, 0, 0], [0, 255, 0], [0, 0, 255], [255, 255, 255]];
+        const TILE_BYTES: u32 = 16 * 16 * 3;
+        let pixels: Vec<u8> = COLORS
+            .iter()
+            .flat_map(|color| color.repeat(16 * 16))
+            .collect();
+        let tiff = big_endian_tiff(
+            &[
+                (256, 4, &[24]),                // ImageWidth
+                (257, 4, &[20]),                // ImageLength
+                (258, 3, &[8, 8, 8]),           // BitsPerSample
+                (259, 3, &[1]),                 // Compression: none
+                (262, 3, &[2]),                 // PhotometricInterpretation: RGB
+                (277, 3, &[3]),                 // SamplesPerPixel
+                (284, 3, &[1]),                 // PlanarConfiguration: chunky
+                (322, 3, &[16]),                // TileWidth
+                (323, 3, &[16]),                // TileLength
+                (324, 4, &[0, TILE_BYTES, 2 * TILE_BYTES, 3 * TILE_BYTES]), // TileOffsets
+                (325, 4, &[TILE_BYTES; 4]),     // TileByteCounts
+            ],
+            &pixels,
+        );
+        assert_eq!(detect_image_format(&tiff).unwrap(), ImageFormat::Tiff);
+        assert_eq!(primary_dimensions(&tiff), Some((24, 20)));
+
+        let img = decode_image(&tiff).unwrap().to_rgb8();
+        assert_eq!(img.dimensions(), (24, 20));
+        assert_eq!(img.get_pixel(15, 15).0, COLORS[0]);
+        assert_eq!(img.get_pixel(23, 0).0, COLORS[1]);
+        assert_eq!(img.get_pixel(0, 19).0, COLORS[2]);
+        assert_eq!(img.get_pixel(23, 19).0, COLORS[3]);
+    }
+
+    #[test]
+    fn test_decode_big_endian_multi_strip_tiff() {
+        // 4x6 16-bit grayscale in three strips of two rows; samples are
+        // big-endian too, so a byte-order mixup changes the values
+        let pixels: Vec<u8> = (0..3u16)
+            .flat_map(|strip| (0x1234 + strip).to_be_bytes().repeat(4 * 2))
+            .collect();
+        let tiff = big_endian_tiff(
+            &[
+                (256, 4, &[4]),           // ImageWidth
+                (257, 4, &[6]),           // ImageLength
+                (258, 3, &[16]),          // BitsPerSample
+                (259, 3, &[1]),           // Compression: none
+                (262, 3, &[1]),           // PhotometricInterpretation: BlackIsZero
+                (273, 4, &[0, 16, 32]),   // StripOffsets
+                (277, 3, &[1]),           // SamplesPerPixel
+                (278, 3, &[2]),           // RowsPerStrip
+                (279, 4, &[16, 16, 16]),  // StripByteCounts
+            ],
+            &pixels,
+        );
+
+        let img = decode_image(&tiff).unwrap().to_luma16();
+        assert_eq!(img.dimensions(), (4, 6));
+        assert_eq!(img.get_pixel(0, 0).0, [0x1234]);
+        assert_eq!(img.get_pixel(3, 3).0, [0x1235]);
+        assert_eq!(img.get_pixel(3, 5).0, [0x1236]);
+    }
+
+    #[test]
+    fn test_estimated_decoded_bytes() {
+        let encode = |format| {
+            let img = image::RgbImage::from_pixel(40, 30, image::Rgb([10, 20, 30]));
+            let mut data = Vec::new();
+            img.write_to(&mut Cursor::new(&mut data), format).unwrap();
+            data
+        };
+
+        for format in [image::ImageFormat::Png, image::ImageFormat::Jpeg, image::ImageFormat::WebP] {
+            assert_eq!(estimated_decoded_bytes(&encode(format)).unwrap(), 40 * 30 * 4, "{:?}", format);
+        }
+        assert_eq!(estimated_decoded_bytes(MINIMAL_JPEG).unwrap(), 4);
+        assert_eq!(estimated_decoded_bytes(&three_entry_ico()).unwrap(), 48 * 32 * 4);
+    }
+
+    #[test]
+    fn test_estimated_decoded_bytes_unreadable_header() {
+        assert!(estimated_decoded_bytes(&[]).is_err());
+        assert!(estimated_decoded_bytes(b"not an image at all").is_err());
+        // Recognized, but there is no header parser for JPEG XL
+        match estimated_decoded_bytes(&[0xFF, 0x0A, 0x00, 0x00]) {
+            Err(CbxError::Image(msg)) => assert!(msg.contains("JPEG XL"), "{}", msg),
+            other => panic!("expected an Image error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_primary_dimensions_ico_uses_largest_entry() {
+        assert_eq!(primary_dimensions(&three_entry_ico()), Some((48, 32)));
+    }
+
+    #[test]
+    fn test_primary_dimensions_multi_page_tiff_uses_first_page() {
+        let tiff = multi_page_tiff(&[(4, 3), (8, 8)]);
+        assert_eq!(primary_dimensions(&tiff), Some((4, 3)));
+
+        // The decoder produces the same page
+        let img = decode_image(&tiff).unwrap();
+        assert_eq!((img.width(), img.height()), (4, 3));
+    }
+
+    #[test]
+    fn test_primary_dimensions_avif_uses_largest_extent() {
+        let ispe = |w: u32, h: u32| {
+            let mut prop = 20u32.to_be_bytes().to_vec();
+            prop.extend_from_slice(b"ispe\0\0\0\0");
+            prop.extend_from_slice(&w.to_be_bytes());
+            prop.extend_from_slice(&h.to_be_bytes());
+            prop
+        };
+
+        // Grid tile, primary image, thumbnail
+        let mut avif = b"\0\0\0\x1CftypavifZZZZ".to_vec();
+        avif.extend(ispe(512, 512));
+        avif.extend(ispe(1600, 2400));
+        avif.extend(ispe(160, 240));
+
+        assert_eq!(primary_dimensions(&avif), Some((1600, 2400)));
+    }
+
+    #[test]
+    fn test_decode_avif_without_decoder() {
+        // Only meaningful in builds lacking the `image` crate's AVIF feature
+        if ImageFormat::Avif.is_supported() {
+            return;
+        }
+
+        let avif = b"\0\0\0\x1CftypavifZZZZ\0\0\0\0mif1miaf\0\0\0\0";
+        match decode_image(avif) {
+            Err(CbxError::UnsupportedFormat(msg)) => assert_eq!(msg, "AVIF decoder not compiled in"),
+            other => panic!("expected UnsupportedFormat, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    /// Lossless WebP bitstream (`VP8L` chunk) of a solid 4x4 image
+    fn vp8l_chunk(color: [u8; 4]) -> Vec<u8> {
+        let img = image::RgbaImage::from_pixel(4, 4, image::Rgba(color));
+        let mut webp = Vec::new();
+        img.write_to(&mut Cursor::new(&mut webp), image::ImageFormat::WebP).unwrap();
+
+        let start = webp.windows(4).position(|w| w == b"VP8L").unwrap();
+        webp[start..].to_vec()
+    }
+
+    /// Animated 4x4 WebP with one solid frame per color
+    fn animated_webp(colors: &[[u8; 4]]) -> Vec<u8> {
+        let u24 = |v: u32| v.to_le_bytes()[..3].to_vec();
+
+        let mut chunks = b"VP8X\x0A\0\0\0\x12\0\0\0".to_vec(); // Animation + alpha flags
+        chunks.extend(u24(3));
+        chunks.extend(u24(3));
+        chunks.extend_from_slice(b"ANIM\x06\0\0\0\0\0\0\0\0\0");
+
+        for &color in colors {
+            let frame = vp8l_chunk(color);
+            chunks.extend_from_slice(b"ANMF");
+            chunks.extend_from_slice(&(16 + frame.len() as u32).to_le_bytes());
+            chunks.extend(u24(0)); // X / 2
+            chunks.extend(u24(0)); // Y / 2
+            chunks.extend(u24(3)); // Width - 1
+            chunks.extend(u24(3)); // Height - 1
+            chunks.extend(u24(100)); // Duration (ms)
+            chunks.push(0x02); // Overwrite the canvas instead of alpha-blending
+            chunks.extend(frame);
+        }
+
+        let mut webp = b"RIFF".to_vec();
+        webp.extend_from_slice(&(4 + chunks.len() as u32).to_le_bytes());
+        webp.extend_from_slice(b"WEBP");
+        webp.extend(chunks);
+        webp
+    }
+
+    #[test]
+    fn test_decode_animated_webp_first_frame() {
+        let webp = animated_webp(&[[255, 0, 0, 255], [0, 0, 255, 255]]);
+        assert_eq!(detect_image_format(&webp).unwrap(), ImageFormat::WebP);
+
+        let img = decode_image(&webp).unwrap().to_rgba8();
+        assert_eq!(img.dimensions(), (4, 4));
+        assert!(img.pixels().all(|p| p.0 == [255, 0, 0, 255]), "first frame is red");
+        assert!(decode_first_frame(&webp, ImageFormat::WebP).is_some());
+    }
+
+    /// Animated 3x2 GIF with one solid frame per color
+    fn animated_gif(colors: &[[u8; 4]]) -> Vec<u8> {
+        let mut gif = Vec::new();
+        {
+            let mut encoder = image::codecs::gif::GifEncoder::new(&mut gif);
+            encoder
+                .encode_frames(
+                    colors
+                        .iter()
+                        .map(|&color| image::Frame::new(image::RgbaImage::from_pixel(3, 2, image::Rgba(color)))),
+                )
+                .unwrap();
+        }
+        gif
+    }
+
+    #[test]
+    fn test_decode_animated_gif_first_frame() {
+        let gif = animated_gif(&[[255, 0, 0, 255], [0, 0, 255, 255]]);
+        assert_eq!(detect_image_format(&gif).unwrap(), ImageFormat::Gif);
+
+        let frame = decode_first_frame(&gif, ImageFormat::Gif).expect("first frame decodes").to_rgba8();
+        assert_eq!(frame.dimensions(), (3, 2));
+        assert!(frame.pixels().all(|p| p.0 == [255, 0, 0, 255]), "first frame is red");
+        assert_eq!(decode_image(&gif).unwrap().to_rgba8(), frame);
+
+        // Not a frame-decoded format, or broken: left to the usual decode
+        assert!(decode_first_frame(MINIMAL_PNG, ImageFormat::Png).is_none());
+        assert!(decode_first_frame(&gif[..gif.len() / 2], ImageFormat::Gif).is_none());
+        let mut still_webp = Vec::new();
+        image::RgbaImage::new(4, 4)
+            .write_to(&mut Cursor::new(&mut still_webp), image::ImageFormat::WebP)
+            .unwrap();
+        assert!(decode_first_frame(&still_webp, ImageFormat::WebP).is_none());
+    }
+
+    #[test]
+    fn test_decode_avif_sequence() {
+        // `avis` brand: an AVIF image sequence
+        let avis = b"\0\0\0\x1Cftypavis\0\0\0\0avisavifmsf1iso8\0\0\0\0";
+        assert_eq!(detect_image_format(avis).unwrap(), ImageFormat::Avif);
+
+        if !ImageFormat::Avif.is_supported() {
+            assert!(matches!(decode_image(avis), Err(CbxError::UnsupportedFormat(_))));
+        }
+    }
+
+    #[test]
+    fn test_primary_dimensions_plain_and_invalid() {
+        assert_eq!(primary_dimensions(PROGRESSIVE_JPEG), Some((32, 24)));
+        assert_eq!(primary_dimensions(b"not an image"), None);
+        assert_eq!(primary_dimensions(&[0, 0, 1, 0, 9, 0]), None); // Truncated ICO
+    }
+
+    #[test]
+    fn test_decode_into_rgba_buffer_too_small() {
+        let mut buf = vec![0u8; 32 * 24 * 4 - 1];
+        match decode_into_rgba(PROGRESSIVE_JPEG, &mut buf) {
+            Err(CbxError::Image(msg)) => assert!(msg.contains("3072 required"), "{}", msg),
+            other => panic!("expected a buffer size error, got {:?}", other),
+        }
+        assert!(buf.iter().all(|&b| b == 0), "nothing written on error");
+    }
+
+    #[test]
+    fn test_decode_into_rgba_in_place() {
+        // Converted path: RGB JPEG
+        let mut buf = vec![0xAAu8; 32 * 24 * 4 + 8];
+        assert_eq!(decode_into_rgba(PROGRESSIVE_JPEG, &mut buf).unwrap(), (32, 24));
+        let expected = decode_image(PROGRESSIVE_JPEG).unwrap().into_rgba8();
+        assert_eq!(&buf[..32 * 24 * 4], expected.as_raw().as_slice());
+        assert_eq!(&buf[32 * 24 * 4..], &[0xAA; 8], "bytes past the image untouched");
+
+        // Native path: RGBA PNG decodes straight into the buffer
+        let img = image::RgbaImage::from_fn(3, 2, |x, y| image::Rgba([x as u8, y as u8, 7, 128]));
+        let mut png = Vec::new();
+        img.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png).unwrap();
+
+        let mut buf = vec![0u8; 3 * 2 * 4];
+        assert_eq!(decode_into_rgba(&png, &mut buf).unwrap(), (3, 2));
+        assert_eq!(buf, img.into_raw());
+    }
+
+    #[test]
+    fn test_scale_denominator() {
+        // A 6000x9000 scan for a 256px thumbnail decodes at 1/8 (750x1125)
+        assert_eq!(scale_denominator(6000, 9000, 256), 8);
+        // 2000/8 = 250 would be too small
+        assert_eq!(scale_denominator(2000, 1500, 256), 4);
+        assert_eq!(scale_denominator(600, 400, 256), 2);
+        assert_eq!(scale_denominator(300, 200, 256), 1);
+        // Rounded up: 2041/8 -> 256 is still big enough
+        assert_eq!(scale_denominator(2041, 100, 256), 8);
+    }
+
+    #[test]
+    fn test_decode_image_scaled_large_jpeg() {
+        let img = image::RgbImage::from_fn(2400, 1600, |x, y| image::Rgb([x as u8, y as u8, 128]));
+        let mut jpeg = Vec::new();
+        img.write_to(&mut Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+        assert_eq!(jpeg_scaled_size(&jpeg, 256), Some((300, 200)));
+
+        let full = decode_image(&jpeg).unwrap();
+        let scaled = decode_image_scaled(&jpeg, 256, None).unwrap();
+
+        // Never smaller than requested, never larger than the source
+        assert_eq!((full.width(), full.height()), (2400, 1600));
+        assert!(scaled.width().max(scaled.height()) >= 256);
+        assert!(scaled.width() <= 2400 && scaled.height() <= 1600);
+
+        // WIC decodes at the predicted 1/8 scale
+        #[cfg(feature = "wic-fallback")]
+        assert_eq!((scaled.width(), scaled.height()), (300, 200));
+    }
+
+    #[test]
+    fn test_decode_image_scaled_other_formats_decode_in_full() {
+        assert_eq!(jpeg_scaled_size(MINIMAL_PNG, 256), None);
+        let img = decode_image_scaled(MINIMAL_PNG, 256, None).unwrap();
+        assert_eq!((img.width(), img.height()), (1, 1));
+        assert!(decode_image_scaled(&[], 256, None).is_err());
+    }
 }
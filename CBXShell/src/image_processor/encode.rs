@@ -0,0 +1,243 @@
+//! Re-encoding covers to compressed files under a byte budget
+//!
+//! Unlike the thumbnail pipeline, this produces an encoded file (JPEG) rather
+//! than an HBITMAP, so it has no Windows dependencies and can be used to
+//! export covers, e.g. for a web gallery with a per-thumbnail size limit.
+//!
+//! Budgeted output is JPEG only: the `image` crate's WebP encoder is
+//! lossless-only and has no quality setting to search over. Lossless PNG
+//! output (no budget) is used for the embedded cover cache and by
+//! `create_thumbnails_multi` for pre-generated thumbnail caches.
+
+use crate::utils::error::CbxError;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, RgbaImage};
+
+use super::decoder;
+use super::memory_budget;
+use super::resizer::{self, ResizeFilter};
+use super::thumbnail::apply_background;
+
+type Result<T> = std::result::Result<T, CbxError>;
+
+/// Lowest JPEG quality tried before giving up on the budget
+const MIN_JPEG_QUALITY: u8 = 1;
+
+/// Highest JPEG quality tried
+const MAX_JPEG_QUALITY: u8 = 100;
+
+/// Encode a cover as a JPEG no larger than `max_bytes`
+///
+/// The image is decoded, scaled to fit within `size`x`size` (aspect ratio
+/// preserved, no upscaling), flattened onto white, and then JPEG-encoded.
+/// The quality is binary-searched for the highest value whose output fits
+/// under `max_bytes`.
+///
+/// # Arguments
+/// * `data` - Raw image file bytes (any supported format)
+/// * `size` - Maximum width and height of the output in pixels
+/// * `max_bytes` - Byte budget for the encoded output
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - JPEG bytes; if even the lowest quality exceeds
+///   `max_bytes`, the smallest achievable encoding is returned
+/// * `Err(CbxError)` - Decoding or encoding failed
+pub fn encode_cover_under(data: &[u8], size: u32, max_bytes: usize) -> Result<Vec<u8>> {
+    let mut rgba = scale_cover(data, size)?;
+
+    // JPEG has no alpha channel
+    apply_background(&mut rgba, (255, 255, 255, 255));
+    let rgb = image::DynamicImage::ImageRgba8(rgba).to_rgb8();
+
+    let encode = |quality: u8| -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        JpegEncoder::new_with_quality(&mut out, quality)
+            .encode_image(&rgb)
+            .map_err(|e| CbxError::Image(format!("Failed to encode JPEG: {}", e)))?;
+        Ok(out)
+    };
+
+    // Output size grows with quality, so search for the highest quality that fits
+    let mut low = MIN_JPEG_QUALITY;
+    let mut high = MAX_JPEG_QUALITY;
+    let mut best: Option<Vec<u8>> = None;
+    let mut smallest: Option<Vec<u8>> = None;
+
+    while low <= high {
+        let quality = low + (high - low) / 2;
+        let encoded = encode(quality)?;
+
+        tracing::debug!("JPEG quality {} -> {} bytes", quality, encoded.len());
+
+        if encoded.len() <= max_bytes {
+            best = Some(encoded);
+            low = quality + 1;
+        } else {
+            if smallest.as_ref().map_or(true, |s| encoded.len() < s.len()) {
+                smallest = Some(encoded);
+            }
+            if quality == MIN_JPEG_QUALITY {
+                break;
+            }
+            high = quality - 1;
+        }
+    }
+
+    match best.or(smallest) {
+        Some(encoded) => Ok(encoded),
+        None => Err(CbxError::Image("JPEG encoding produced no output".to_string())),
+    }
+}
+
+/// Encode a cover as a PNG scaled to fit within `size`x`size`
+///
+/// Scaling matches `encode_cover_under` (aspect ratio preserved, no
+/// upscaling); transparency is kept.
+pub fn encode_cover_png(data: &[u8], size: u32) -> Result<Vec<u8>> {
+    encode_png(&scale_cover(data, size)?)
+}
+
+/// Encode a cover as PNGs at several sizes, decoding it only once
+///
+/// Each output is scaled like `encode_cover_png` (fits within `size`x`size`,
+/// aspect ratio preserved, no upscaling) from the same decoded image, e.g.
+/// to pre-generate a thumbnail cache at 96, 256 and 1024 pixels.
+///
+/// # Returns
+/// * `Ok(Vec<(u32, Vec<u8>)>)` - `(size, PNG bytes)` in the order of `sizes`
+/// * `Err(CbxError)` - Decoding, scaling or encoding failed
+pub fn create_thumbnails_multi(data: &[u8], sizes: &[u32]) -> Result<Vec<(u32, Vec<u8>)>> {
+    let _reservation = reserve_decode(data)?;
+    let img = decoder::decode_image(data)?;
+
+    sizes
+        .iter()
+        .map(|&size| {
+            let rgba = scale_decoded(&img, size)?;
+            Ok((size, encode_png(&rgba)?))
+        })
+        .collect()
+}
+
+/// PNG-encode an RGBA image
+fn encode_png(rgba: &RgbaImage) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    rgba.write_to(&mut std::io::Cursor::new(&mut out), image::ImageFormat::Png)
+        .map_err(|e| CbxError::Image(format!("Failed to encode PNG: {}", e)))?;
+    Ok(out)
+}
+
+/// Decode a cover and scale it to fit within `size`x`size`
+fn scale_cover(data: &[u8], size: u32) -> Result<RgbaImage> {
+    let _reservation = reserve_decode(data)?;
+    scale_decoded(&decoder::decode_image_scaled(data, size, None)?, size)
+}
+
+/// Reserve the decode memory budget for decoding `data`
+fn reserve_decode(data: &[u8]) -> Result<memory_budget::DecodeReservation<'static>> {
+    let decoded_bytes = decoder::estimated_decoded_bytes(data)
+        .map_or(0, |bytes| usize::try_from(bytes).unwrap_or(usize::MAX));
+    memory_budget::global_budget().try_reserve(decoded_bytes)
+}
+
+/// Scale a decoded cover to fit within `size`x`size`
+fn scale_decoded(img: &DynamicImage, size: u32) -> Result<RgbaImage> {
+    let (target_width, target_height) =
+        resizer::calculate_thumbnail_size(img.width(), img.height(), size, size);
+    if target_width == 0 || target_height == 0 {
+        return Err(CbxError::Image(
+            "Invalid image dimensions (0x0)".to_string(),
+        ));
+    }
+
+    let mut rgba = img.to_rgba8();
+    if (target_width, target_height) != rgba.dimensions() {
+        rgba = resizer::resize_image(&rgba, target_width, target_height, ResizeFilter::Lanczos3)?;
+    }
+
+    Ok(rgba)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use std::io::Cursor;
+
+    /// Detailed fixture: 512x512 noise, which compresses poorly at high quality
+    fn noisy_png() -> Vec<u8> {
+        let mut state: u32 = 0x1234_5678;
+        let img = RgbImage::from_fn(512, 512, |x, y| {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let noise = (state >> 24) as u8;
+            Rgb([noise, (x as u8) ^ noise, (y as u8).wrapping_add(noise)])
+        });
+
+        let mut out = Vec::new();
+        img.write_to(&mut Cursor::new(&mut out), ImageFormat::Png).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_encode_cover_under_budget() {
+        let png = noisy_png();
+        let max_bytes = 20 * 1024;
+
+        let jpeg = encode_cover_under(&png, 256, max_bytes).unwrap();
+        assert!(jpeg.len() <= max_bytes, "{} bytes exceeds budget", jpeg.len());
+
+        // Output is a valid JPEG scaled to the requested size
+        assert_eq!(&jpeg[..3], &[0xFF, 0xD8, 0xFF]);
+        assert_eq!(decoder::read_image_dimensions(&jpeg).unwrap(), (256, 256));
+    }
+
+    #[test]
+    fn test_encode_cover_under_uses_highest_fitting_quality() {
+        let png = noisy_png();
+
+        // A generous budget keeps (close to) full quality
+        let large = encode_cover_under(&png, 256, usize::MAX).unwrap();
+        let small = encode_cover_under(&png, 256, 20 * 1024).unwrap();
+        assert!(large.len() > small.len());
+    }
+
+    #[test]
+    fn test_encode_cover_under_unreachable_budget_returns_smallest() {
+        let png = noisy_png();
+
+        let jpeg = encode_cover_under(&png, 256, 16).unwrap();
+        assert!(jpeg.len() > 16);
+        assert!(decoder::decode_image(&jpeg).is_ok());
+    }
+
+    #[test]
+    fn test_encode_cover_under_invalid_data() {
+        assert!(encode_cover_under(&[0x00, 0x01, 0x02], 256, 1024).is_err());
+    }
+
+    #[test]
+    fn test_create_thumbnails_multi() {
+        let mut png = Vec::new();
+        RgbImage::from_fn(600, 300, |x, y| Rgb([x as u8, y as u8, 128]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+
+        let thumbnails = create_thumbnails_multi(&png, &[96, 256, 1024]).unwrap();
+        let dimensions: Vec<(u32, (u32, u32))> = thumbnails
+            .iter()
+            .map(|(size, data)| (*size, decoder::read_image_dimensions(data).unwrap()))
+            .collect();
+
+        // Aspect ratio kept; 1024 would upscale, so the original size is used
+        assert_eq!(dimensions, vec![(96, (96, 48)), (256, (256, 128)), (1024, (600, 300))]);
+
+        // Same pixels as encoding each size on its own
+        assert_eq!(thumbnails[1].1, encode_cover_png(&png, 256).unwrap());
+    }
+
+    #[test]
+    fn test_create_thumbnails_multi_invalid_data() {
+        assert!(create_thumbnails_multi(&[0x00, 0x01, 0x02], &[96]).is_err());
+        assert!(create_thumbnails_multi(&[0x00, 0x01, 0x02], &[]).is_err());
+    }
+}
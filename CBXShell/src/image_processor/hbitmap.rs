@@ -42,13 +42,86 @@ pub fn rgba_to_bgra(rgba: &[u8]) -> Vec<u8> {
     bgra
 }
 
+/// Row stride in bytes of a 32bpp DIB
+///
+/// DIB rows are padded to a DWORD boundary; at 4 bytes per pixel every row
+/// is already aligned, so the stride is exactly `width * 4` with no padding.
+pub fn dib_stride(width: u32) -> usize {
+    width as usize * 4
+}
+
+/// Pixel format of a thumbnail DIB (`ThumbnailBitDepth`)
+///
+/// Opaque covers don't need an alpha channel, and Explorer keeps thumbnails
+/// in memory at the depth they were handed over, so 24bpp saves a quarter
+/// and 16bpp half of each bitmap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BitDepth {
+    /// 16bpp RGB 5-5-5 (visible banding on gradients)
+    Rgb555,
+    /// 24bpp BGR
+    Rgb24,
+    /// 32bpp BGRA, the only depth that keeps alpha
+    #[default]
+    Bgra32,
+}
+
+impl BitDepth {
+    /// Depth for a bit count (16, 24 or 32)
+    pub fn from_bits(bits: u32) -> Option<Self> {
+        match bits {
+            16 => Some(BitDepth::Rgb555),
+            24 => Some(BitDepth::Rgb24),
+            32 => Some(BitDepth::Bgra32),
+            _ => None,
+        }
+    }
+
+    /// Bits per pixel (`biBitCount`)
+    pub fn bits(self) -> u16 {
+        match self {
+            BitDepth::Rgb555 => 16,
+            BitDepth::Rgb24 => 24,
+            BitDepth::Bgra32 => 32,
+        }
+    }
+
+    /// Row stride in bytes, padded to a DWORD boundary
+    pub fn stride(self, width: u32) -> usize {
+        (width as usize * self.bits() as usize + 31) / 32 * 4
+    }
+
+    /// Pack one row of BGRA pixels into `dst` (at least `stride(width)` bytes)
+    fn pack_row(self, bgra: &[u8], dst: &mut [u8]) {
+        match self {
+            BitDepth::Bgra32 => dst[..bgra.len()].copy_from_slice(bgra),
+            BitDepth::Rgb24 => {
+                for (src, out) in bgra.chunks_exact(4).zip(dst.chunks_exact_mut(3)) {
+                    out.copy_from_slice(&src[..3]);
+                }
+            }
+            BitDepth::Rgb555 => {
+                for (src, out) in bgra.chunks_exact(4).zip(dst.chunks_exact_mut(2)) {
+                    let (b, g, r) = (src[0] as u16 >> 3, src[1] as u16 >> 3, src[2] as u16 >> 3);
+                    out.copy_from_slice(&((r << 10) | (g << 5) | b).to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
 /// Create Windows HBITMAP from BGRA pixel data
 ///
 /// This function creates a device-independent bitmap (DIB) using CreateDIBSection,
 /// matching the C++ implementation in cbxArchive.h:628-666.
 ///
+/// The bitmap is always a **top-down** 32bpp BGRA DIB (negative `biHeight`),
+/// which is what Explorer's thumbnail cache expects. A bottom-up DIB would be
+/// shown upside down. The alpha type reported to Explorer (`WTSAT_RGB` or
+/// `WTSAT_ARGB`) comes from `thumbnail::Thumbnail::alpha`.
+///
 /// # Arguments
-/// * `bgra_data` - BGRA pixel data (4 bytes per pixel, bottom-up)
+/// * `bgra_data` - BGRA pixel data (4 bytes per pixel, rows top to bottom)
 /// * `width` - Image width in pixels
 /// * `height` - Image height in pixels
 ///
@@ -83,6 +156,20 @@ pub fn create_hbitmap_from_bgra(
     bgra_data: &[u8],
     width: u32,
     height: u32,
+) -> Result<HBITMAP> {
+    create_hbitmap_with_depth(bgra_data, width, height, BitDepth::Bgra32)
+}
+
+/// Create a top-down DIB of the given depth from BGRA pixel data
+///
+/// Like `create_hbitmap_from_bgra`, but the pixels are packed to `depth`
+/// (alpha is dropped below 32bpp). Callers are expected to pick 32bpp for
+/// images with transparency.
+pub fn create_hbitmap_with_depth(
+    bgra_data: &[u8],
+    width: u32,
+    height: u32,
+    depth: BitDepth,
 ) -> Result<HBITMAP> {
     if width == 0 || height == 0 {
         return Err(CbxError::Image(
@@ -90,7 +177,12 @@ pub fn create_hbitmap_from_bgra(
         ));
     }
 
-    let expected_size = (width * height * 4) as usize;
+    let stride = dib_stride(width);
+    let expected_size = stride.checked_mul(height as usize).ok_or_else(|| {
+        CbxError::Image(format!("Bitmap too large: {}x{}", width, height))
+    })?;
+    let dib_stride = depth.stride(width);
+    let dib_size = dib_stride * height as usize;
     if bgra_data.len() != expected_size {
         return Err(CbxError::Image(format!(
             "Invalid data size: expected {} bytes, got {}",
@@ -115,6 +207,7 @@ pub fn create_hbitmap_from_bgra(
     // Safety guarantees:
     // - Dimensions validated (width, height > 0)
     // - Data size validated (matches width * height * 4)
+    // - Each packed row is exactly the DIB stride for `depth`
     // - pv_bits null-checked before use
     // - HBITMAP validity checked before returning
     // - copy_nonoverlapping: src and dst are valid, non-overlapping, properly aligned
@@ -127,9 +220,9 @@ pub fn create_hbitmap_from_bgra(
                 biWidth: width as i32,
                 biHeight: -(height as i32), // Negative for top-down DIB
                 biPlanes: 1,
-                biBitCount: 32, // 32-bit RGBA
+                biBitCount: depth.bits(), // 32-bit BGRA unless a smaller depth was asked for
                 biCompression: BI_RGB.0 as u32,
-                biSizeImage: 0,
+                biSizeImage: dib_size as u32,
                 biXPelsPerMeter: 0,
                 biYPelsPerMeter: 0,
                 biClrUsed: 0,
@@ -162,17 +255,94 @@ pub fn create_hbitmap_from_bgra(
             ));
         }
 
-        // Copy pixel data to DIB section
-        ptr::copy_nonoverlapping(
-            bgra_data.as_ptr(),
-            pv_bits as *mut u8,
-            bgra_data.len(),
-        );
+        // Copy pixel data to DIB section row by row (top row first)
+        let dst = pv_bits as *mut u8;
+        if depth == BitDepth::Bgra32 {
+            for (row, src_row) in bgra_data.chunks_exact(stride).enumerate() {
+                ptr::copy_nonoverlapping(src_row.as_ptr(), dst.add(row * stride), stride);
+            }
+        } else {
+            let mut packed = vec![0u8; dib_stride];
+            for (row, src_row) in bgra_data.chunks_exact(stride).enumerate() {
+                depth.pack_row(src_row, &mut packed);
+                ptr::copy_nonoverlapping(packed.as_ptr(), dst.add(row * dib_stride), dib_stride);
+            }
+        }
 
         Ok(hbitmap)
     }
 }
 
+/// Read back the pixels of a 32bpp DIB section created by this module
+///
+/// Returns `(width, height, bgra)` with rows ordered top to bottom, regardless
+/// of the DIB's orientation. Fails if the bitmap is not a 32bpp DIB section
+/// or is bottom-up (which Explorer would display upside down).
+pub fn read_dib_bgra(hbitmap: HBITMAP) -> Result<(u32, u32, Vec<u8>)> {
+    // UNAVOIDABLE UNSAFE: GetObjectW and reading the DIB's pixel buffer
+    // Safety guarantees:
+    // - GetObjectW fills a correctly sized DIBSECTION or reports failure
+    // - bmBits is null-checked and the read length is derived from the
+    //   DIB's own width, height and stride
+    unsafe {
+        let mut ds = DIBSECTION::default();
+        let written = GetObjectW(
+            hbitmap,
+            std::mem::size_of::<DIBSECTION>() as i32,
+            Some(&mut ds as *mut DIBSECTION as *mut std::ffi::c_void),
+        );
+
+        if written as usize != std::mem::size_of::<DIBSECTION>() || ds.dsBm.bmBits.is_null() {
+            return Err(CbxError::Image("Bitmap is not a DIB section".to_string()));
+        }
+
+        if ds.dsBm.bmBitsPixel != 32 {
+            return Err(CbxError::Image(format!(
+                "Expected 32bpp DIB, got {}bpp",
+                ds.dsBm.bmBitsPixel
+            )));
+        }
+
+        if ds.dsBmih.biHeight >= 0 {
+            return Err(CbxError::Image("DIB is bottom-up, expected top-down".to_string()));
+        }
+
+        let width = ds.dsBm.bmWidth as u32;
+        let height = ds.dsBm.bmHeight.unsigned_abs();
+        let stride = ds.dsBm.bmWidthBytes as usize;
+
+        if stride != dib_stride(width) {
+            return Err(CbxError::Image(format!(
+                "Unexpected DIB stride: {} (expected {})",
+                stride,
+                dib_stride(width)
+            )));
+        }
+
+        let pixels = std::slice::from_raw_parts(ds.dsBm.bmBits as *const u8, stride * height as usize);
+        Ok((width, height, pixels.to_vec()))
+    }
+}
+
+/// Bits per pixel of a DIB section
+#[allow(dead_code)] // Used by tests to verify the configured bit depth
+pub fn dib_bit_count(hbitmap: HBITMAP) -> Result<u16> {
+    // UNAVOIDABLE UNSAFE: GetObjectW fills a correctly sized DIBSECTION or reports failure
+    unsafe {
+        let mut ds = DIBSECTION::default();
+        let written = GetObjectW(
+            hbitmap,
+            std::mem::size_of::<DIBSECTION>() as i32,
+            Some(&mut ds as *mut DIBSECTION as *mut std::ffi::c_void),
+        );
+
+        if written as usize != std::mem::size_of::<DIBSECTION>() {
+            return Err(CbxError::Image("Bitmap is not a DIB section".to_string()));
+        }
+        Ok(ds.dsBm.bmBitsPixel)
+    }
+}
+
 /// Convert RGBA image to HBITMAP (convenience function)
 ///
 /// This is a high-level wrapper that combines rgba_to_bgra and create_hbitmap_from_bgra.
@@ -359,6 +529,65 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_create_hbitmap_top_down_readback() {
+        // 3x2 image with a distinguishable top-left pixel (red)
+        let rgba = vec![
+            255, 0, 0, 255,   0, 0, 0, 255,   0, 0, 0, 255,   // Top row: red, black, black
+            0, 0, 255, 255,   0, 0, 0, 255,   0, 255, 0, 255, // Bottom row: blue, black, green
+        ];
+
+        let hbitmap = create_hbitmap_from_rgba(&rgba, 3, 2).unwrap();
+        let readback = read_dib_bgra(hbitmap);
+        unsafe {
+            DeleteObject(hbitmap);
+        }
+
+        let (width, height, bgra) = readback.unwrap();
+        assert_eq!((width, height), (3, 2));
+        assert_eq!(bgra.len(), dib_stride(3) * 2);
+
+        // Top-left is red in BGRA, bottom-left blue, bottom-right green
+        assert_eq!(&bgra[0..4], &[0, 0, 255, 255]);
+        assert_eq!(&bgra[12..16], &[255, 0, 0, 255]);
+        assert_eq!(&bgra[20..24], &[0, 255, 0, 255]);
+    }
+
+    #[test]
+    fn test_bit_depth_stride_and_packing() {
+        assert_eq!(BitDepth::Bgra32.stride(3), 12);
+        assert_eq!(BitDepth::Rgb24.stride(3), 12); // 9 bytes padded to a DWORD
+        assert_eq!(BitDepth::Rgb555.stride(3), 8);
+        assert_eq!(BitDepth::from_bits(24), Some(BitDepth::Rgb24));
+        assert_eq!(BitDepth::from_bits(8), None);
+
+        // Red and white pixels (BGRA)
+        let bgra = [0, 0, 255, 255, 255, 255, 255, 255];
+
+        let mut rgb24 = [0u8; 8];
+        BitDepth::Rgb24.pack_row(&bgra, &mut rgb24);
+        assert_eq!(rgb24, [0, 0, 255, 255, 255, 255, 0, 0]);
+
+        let mut rgb555 = [0u8; 4];
+        BitDepth::Rgb555.pack_row(&bgra, &mut rgb555);
+        assert_eq!(rgb555, [0x00, 0x7C, 0xFF, 0x7F]);
+    }
+
+    #[test]
+    fn test_create_hbitmap_with_depth() {
+        // 3x2 so that 24bpp rows need padding
+        let bgra = [128u8, 64, 32, 255].repeat(6);
+
+        for depth in [BitDepth::Rgb555, BitDepth::Rgb24, BitDepth::Bgra32] {
+            let hbitmap = create_hbitmap_with_depth(&bgra, 3, 2, depth).unwrap();
+            let bits = dib_bit_count(hbitmap);
+            unsafe {
+                DeleteObject(hbitmap);
+            }
+            assert_eq!(bits.unwrap(), depth.bits());
+        }
+    }
+
     #[test]
     fn test_hbitmap_handle_not_null() {
         let bgra = vec![128, 128, 128, 255]; // Gray pixel
@@ -12,7 +12,11 @@
 //! - **TIFF**: `49 49 2A 00` (little-endian) or `4D 4D 00 2A` (big-endian)
 //! - **ICO**: `00 00 01 00` (icon format)
 //! - **WebP**: `52 49 46 46 ... 57 45 42 50` (RIFF...WEBP)
-//! - **AVIF**: `... 66 74 79 70 61 76 69 66` (...ftypavif in ftyp box)
+//! - **AVIF**: `... 66 74 79 70` (ftyp box) with an `avif`/`avis` major or compatible brand
+//! - **QOI**: `71 6F 69 66` (qoif)
+//! - **JPEG XL**: `FF 0A` (bare codestream) or an `ftyp` box with the `jxl ` brand (container)
+//! - **HEIC/HEIF**: an `ftyp` box with a `heic`/`heix`/`hevc`/`hevx`/`mif1`/`msf1` brand
+//!   (recognized, but there is no built-in decoder)
 //!
 //! ## Why Magic Headers?
 //!
@@ -20,11 +24,18 @@
 //!
 //! 1. **Security**: Prevents misidentified files from being processed
 //! 2. **Accuracy**: Works even when files have wrong extensions
-//! 3. **Performance**: Very fast (only reads first ~32 bytes)
+//! 3. **Performance**: Very fast (only reads the first few dozen bytes)
 //! 4. **Reliability**: Industry-standard file identification method
 
+use crate::utils::detection::Detection;
 use crate::utils::error::{CbxError, Result};
 
+/// Default number of leading bytes searched for an AVIF/JPEG XL `ftyp` box
+pub const DEFAULT_FTYP_SCAN_WINDOW: usize = 64;
+
+/// Largest `ftyp` scan window accepted; bigger requests are clamped
+pub const MAX_FTYP_SCAN_WINDOW: usize = 4096;
+
 /// Represents a detected image format
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
@@ -44,9 +55,30 @@ pub enum ImageFormat {
     WebP,
     /// AVIF image (ftyp box with 'avif' brand)
     Avif,
+    /// QOI image (71 6F 69 66, "qoif")
+    Qoi,
+    /// JPEG XL image (FF 0A, or ftyp box with 'jxl ' brand)
+    Jxl,
+    /// HEIC/HEIF image (ftyp box with a HEIF brand such as 'heic' or 'mif1')
+    Heic,
 }
 
 impl ImageFormat {
+    /// Every detected format, supported or not
+    pub const ALL: [Self; 11] = [
+        Self::Jpeg,
+        Self::Png,
+        Self::Gif,
+        Self::Bmp,
+        Self::Tiff,
+        Self::Ico,
+        Self::WebP,
+        Self::Avif,
+        Self::Qoi,
+        Self::Jxl,
+        Self::Heic,
+    ];
+
     /// Get format name as string
     pub fn as_str(&self) -> &'static str {
         match self {
@@ -58,13 +90,51 @@ impl ImageFormat {
             Self::Ico => "ICO",
             Self::WebP => "WebP",
             Self::Avif => "AVIF",
+            Self::Qoi => "QOI",
+            Self::Jxl => "JPEG XL",
+            Self::Heic => "HEIC",
         }
     }
 
-    /// Check if format is supported by the image decoder
+    /// Check if a decoder for this format is compiled in
     pub fn is_supported(&self) -> bool {
-        // All formats are currently supported by the `image` crate
-        true
+        match self {
+            // Decoded by the built-in decoder when the `qoi` feature is enabled
+            Self::Qoi => cfg!(feature = "qoi"),
+            // Everything else depends on the `image` crate's enabled features
+            _ => self.image_format().is_some_and(|format| format.reading_enabled()),
+        }
+    }
+
+    /// The corresponding `image` crate format
+    ///
+    /// `None` for JPEG XL and HEIC: the `image` crate (0.25) has no decoder
+    /// for them, so they are never supported by the built-in decoders. With
+    /// the `wic-fallback` feature they still decode when Windows has the
+    /// JPEG XL or HEIF codec installed.
+    pub fn image_format(&self) -> Option<image::ImageFormat> {
+        match self {
+            Self::Jpeg => Some(image::ImageFormat::Jpeg),
+            Self::Png => Some(image::ImageFormat::Png),
+            Self::Gif => Some(image::ImageFormat::Gif),
+            Self::Bmp => Some(image::ImageFormat::Bmp),
+            Self::Tiff => Some(image::ImageFormat::Tiff),
+            Self::Ico => Some(image::ImageFormat::Ico),
+            Self::WebP => Some(image::ImageFormat::WebP),
+            Self::Avif => Some(image::ImageFormat::Avif),
+            Self::Qoi => Some(image::ImageFormat::Qoi),
+            Self::Jxl | Self::Heic => None,
+        }
+    }
+
+    /// Check if this format might decode at all
+    ///
+    /// True for supported formats, and for every format with the
+    /// `wic-fallback` feature (whether WIC has a codec is only known when
+    /// decoding). Cover selection skips images that are recognized but can't
+    /// be decoded.
+    pub fn may_decode(&self) -> bool {
+        self.is_supported() || cfg!(feature = "wic-fallback")
     }
 }
 
@@ -87,6 +157,37 @@ impl ImageFormat {
 /// assert_eq!(format, ImageFormat::Jpeg);
 /// ```
 pub fn detect_image_format(data: &[u8]) -> Result<ImageFormat> {
+    detect_image_format_with_scan_window(data, DEFAULT_FTYP_SCAN_WINDOW)
+}
+
+/// Detect image format, searching the first `scan_window` bytes for an AVIF/JPEG XL `ftyp` box
+///
+/// `detect_image_format` uses `DEFAULT_FTYP_SCAN_WINDOW`. A larger window
+/// finds `ftyp` boxes with long compatible-brand lists or behind leading
+/// boxes; it is clamped to `MAX_FTYP_SCAN_WINDOW`. Other formats are
+/// identified from fixed offsets and don't depend on the window.
+pub fn detect_image_format_with_scan_window(data: &[u8], scan_window: usize) -> Result<ImageFormat> {
+    detect_detailed(data, scan_window).map(|detection| detection.kind)
+}
+
+/// Detect image format, reporting which signature matched and where
+///
+/// Same detection as `detect_image_format`. The signature is the part that
+/// identifies the format: the `WEBP` tag at offset 8 for WebP and the
+/// `avif`/`avis` (AVIF), `jxl ` (JPEG XL) or HEIF brand inside the `ftyp` box.
+pub fn detect_image_format_detailed(data: &[u8]) -> Result<Detection<ImageFormat>> {
+    detect_detailed(data, DEFAULT_FTYP_SCAN_WINDOW)
+}
+
+fn detect_detailed(data: &[u8], scan_window: usize) -> Result<Detection<ImageFormat>> {
+    let found = |kind: ImageFormat, matched_signature: &'static [u8], offset: usize| {
+        Ok(Detection {
+            kind,
+            matched_signature,
+            offset,
+        })
+    };
+
     if data.is_empty() {
         return Err(CbxError::Image("Empty data".to_string()));
     }
@@ -103,108 +204,58 @@ pub fn detect_image_format(data: &[u8]) -> Result<ImageFormat> {
 
     // JPEG: FF D8 FF
     // Most common format in comic archives, check first
-    if data.len() >= 3 && data[0] == 0xFF && data[1] == 0xD8 && data[2] == 0xFF {
-        return Ok(ImageFormat::Jpeg);
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return found(ImageFormat::Jpeg, &[0xFF, 0xD8, 0xFF], 0);
     }
 
     // PNG: 89 50 4E 47 0D 0A 1A 0A (‰PNG\r\n\x1A\n)
     // Second most common format
-    if data.len() >= 8
-        && data[0] == 0x89
-        && data[1] == 0x50
-        && data[2] == 0x4E
-        && data[3] == 0x47
-        && data[4] == 0x0D
-        && data[5] == 0x0A
-        && data[6] == 0x1A
-        && data[7] == 0x0A
-    {
-        return Ok(ImageFormat::Png);
+    if data.starts_with(b"\x89PNG\r\n\x1A\n") {
+        return found(ImageFormat::Png, b"\x89PNG\r\n\x1A\n", 0);
     }
 
     // GIF: 47 49 46 38 (GIF8)
-    if data.len() >= 4
-        && data[0] == 0x47
-        && data[1] == 0x49
-        && data[2] == 0x46
-        && data[3] == 0x38
-    {
-        return Ok(ImageFormat::Gif);
+    if data.starts_with(b"GIF8") {
+        return found(ImageFormat::Gif, b"GIF8", 0);
     }
 
     // BMP: 42 4D (BM)
-    if data.len() >= 2 && data[0] == 0x42 && data[1] == 0x4D {
-        return Ok(ImageFormat::Bmp);
+    if data.starts_with(b"BM") {
+        return found(ImageFormat::Bmp, b"BM", 0);
     }
 
     // TIFF: 49 49 2A 00 (little-endian) or 4D 4D 00 2A (big-endian)
-    if data.len() >= 4 {
-        if (data[0] == 0x49 && data[1] == 0x49 && data[2] == 0x2A && data[3] == 0x00)
-            || (data[0] == 0x4D && data[1] == 0x4D && data[2] == 0x00 && data[3] == 0x2A)
-        {
-            return Ok(ImageFormat::Tiff);
+    for signature in [b"II\x2A\x00", b"MM\x00\x2A"] {
+        if data.starts_with(signature) {
+            return found(ImageFormat::Tiff, signature, 0);
         }
     }
 
     // ICO: 00 00 01 00
-    if data.len() >= 4
-        && data[0] == 0x00
-        && data[1] == 0x00
-        && data[2] == 0x01
-        && data[3] == 0x00
-    {
-        return Ok(ImageFormat::Ico);
+    if data.starts_with(&[0x00, 0x00, 0x01, 0x00]) {
+        return found(ImageFormat::Ico, &[0x00, 0x00, 0x01, 0x00], 0);
     }
 
     // WebP: 52 49 46 46 ... 57 45 42 50 (RIFF....WEBP)
     // Need at least 12 bytes: RIFF (4) + size (4) + WEBP (4)
-    if data.len() >= 12
-        && data[0] == 0x52
-        && data[1] == 0x49
-        && data[2] == 0x46
-        && data[3] == 0x46 // RIFF
-        && data[8] == 0x57
-        && data[9] == 0x45
-        && data[10] == 0x42
-        && data[11] == 0x50
-    // WEBP
-    {
-        return Ok(ImageFormat::WebP);
-    }
-
-    // AVIF: Check for 'ftyp' box with 'avif' brand
-    // AVIF files are ISO Base Media File Format (similar to MP4)
-    // Structure: [size:4][type:4='ftyp'][brand:4='avif']...
-    // We need at least 12 bytes to check
-    if data.len() >= 12 {
-        // Check for ftyp box (can start at offset 4 or 8 depending on implementation)
-        for offset in [4, 8, 0] {
-            if offset + 12 <= data.len() {
-                // Check for 'ftyp' box type
-                if data[offset..offset + 4] == *b"ftyp" {
-                    // Check for 'avif' brand (can be in different positions)
-                    if data[offset + 4..offset + 8] == *b"avif" {
-                        return Ok(ImageFormat::Avif);
-                    }
-                    // Some AVIF files use 'avis' for sequence
-                    if data[offset + 4..offset + 8] == *b"avis" {
-                        return Ok(ImageFormat::Avif);
-                    }
-                }
-            }
-        }
+    if data.starts_with(b"RIFF") && data.get(8..12) == Some(&b"WEBP"[..]) {
+        return found(ImageFormat::WebP, b"WEBP", 8);
+    }
 
-        // Alternative AVIF detection: search for 'ftypavif' anywhere in first 32 bytes
-        if data.len() >= 32 {
-            for i in 0..=data.len().saturating_sub(8) {
-                if i >= 32 {
-                    break;
-                }
-                if &data[i..i + 8] == b"ftypavif" {
-                    return Ok(ImageFormat::Avif);
-                }
-            }
-        }
+    // QOI: 71 6F 69 66 (qoif)
+    if data.starts_with(b"qoif") {
+        return found(ImageFormat::Qoi, b"qoif", 0);
+    }
+
+    // JPEG XL bare codestream: FF 0A
+    if data.starts_with(&[0xFF, 0x0A]) {
+        return found(ImageFormat::Jxl, &[0xFF, 0x0A], 0);
+    }
+
+    // AVIF, HEIC and the JPEG XL container: ISO Base Media File Format (like
+    // MP4), identified by the brands of their 'ftyp' box
+    if let Some((kind, offset, brand)) = find_ftyp_brand(data, scan_window) {
+        return found(kind, brand, offset);
     }
 
     // No recognized format
@@ -214,6 +265,63 @@ pub fn detect_image_format(data: &[u8]) -> Result<ImageFormat> {
     )))
 }
 
+/// The image brand (with its format and offset) declared by an `ftyp` box in the first `scan_window` bytes
+///
+/// Box layout: `[size:4]["ftyp"][major brand:4][minor version:4][compatible brands:4*n]`.
+/// Both the major brand and the compatible brands are checked (`avif` for
+/// still images, `avis` for sequences, `jxl ` for JPEG XL), so files with a
+/// generic major brand such as `mif1` are found too. Only if none of those is
+/// declared do HEIF brands (`heic`, `heix`, `hevc`, `hevx`, `mif1`, `msf1`)
+/// make it HEIC, as AVIF files also declare `mif1`. The JPEG XL container's
+/// `ftyp` box follows its 12-byte signature box, well inside the default
+/// window. Everything is bounds-checked slicing; any input length and window
+/// is safe.
+fn find_ftyp_brand(data: &[u8], scan_window: usize) -> Option<(ImageFormat, usize, &'static [u8])> {
+    let window = &data[..data.len().min(scan_window.min(MAX_FTYP_SCAN_WINDOW))];
+
+    let pos = window.windows(4).position(|w| w == b"ftyp")?;
+
+    // Stop at the end of the box when its size is known (0 and 1 mean "to end
+    // of file" and "64-bit size"); the window bounds it either way
+    let size = pos.checked_sub(4).map(|start| {
+        let size = &window[start..pos];
+        (start, u32::from_be_bytes([size[0], size[1], size[2], size[3]]) as usize)
+    });
+    let box_end = match size {
+        Some((start, size)) if size > 1 => start.saturating_add(size).min(window.len()),
+        _ => window.len(),
+    };
+
+    let brands = window.get(pos + 4..box_end).unwrap_or_default();
+    let find = |known: &[(&'static [u8], ImageFormat)]| {
+        brands
+            .chunks_exact(4)
+            .enumerate()
+            // The second field is the minor version, not a brand
+            .filter(|(i, _)| *i != 1)
+            .find_map(|(i, brand)| {
+                let &(brand, kind) = known.iter().find(|(known, _)| *known == brand)?;
+                Some((kind, pos + 4 + i * 4, brand))
+            })
+    };
+
+    find(&[
+        (b"avif", ImageFormat::Avif),
+        (b"avis", ImageFormat::Avif),
+        (b"jxl ", ImageFormat::Jxl),
+    ])
+    .or_else(|| {
+        find(&[
+            (b"heic", ImageFormat::Heic),
+            (b"heix", ImageFormat::Heic),
+            (b"hevc", ImageFormat::Heic),
+            (b"hevx", ImageFormat::Heic),
+            (b"mif1", ImageFormat::Heic),
+            (b"msf1", ImageFormat::Heic),
+        ])
+    })
+}
+
 /// Verify that data is a valid image and return its format
 ///
 /// This is a convenience wrapper around `detect_image_format` that
@@ -281,6 +389,15 @@ mod tests {
     /// AVIF header (simplified)
     const AVIF_HEADER: &[u8] = b"\x00\x00\x00\x18ftypavif";
 
+    /// JPEG XL bare codestream (SizeHeader follows the signature)
+    const JXL_CODESTREAM: &[u8] = &[0xFF, 0x0A, 0xFA, 0x1F, 0x42, 0x0A, 0x08];
+
+    /// JPEG XL container: signature box, then the ftyp box
+    const JXL_CONTAINER: &[u8] = b"\0\0\0\x0CJXL \r\n\x87\n\0\0\0\x14ftypjxl \0\0\0\0jxl ";
+
+    /// QOI header (2x2, RGBA, sRGB)
+    const QOI_HEADER: &[u8] = b"qoif\x00\x00\x00\x02\x00\x00\x00\x02\x04\x00";
+
     #[test]
     fn test_detect_jpeg() {
         let format = detect_image_format(MINIMAL_JPEG).unwrap();
@@ -343,6 +460,161 @@ mod tests {
         assert_eq!(format.as_str(), "AVIF");
     }
 
+    #[test]
+    fn test_detect_jxl_codestream() {
+        let format = detect_image_format(JXL_CODESTREAM).unwrap();
+        assert_eq!(format, ImageFormat::Jxl);
+        assert_eq!(format.as_str(), "JPEG XL");
+
+        let detection = detect_image_format_detailed(JXL_CODESTREAM).unwrap();
+        assert_eq!((detection.matched_signature, detection.offset), (&[0xFF, 0x0A][..], 0));
+
+        // Other FF-prefixed data is not JPEG XL
+        assert!(detect_image_format(&[0xFF, 0x0B, 0x00, 0x00]).is_err());
+    }
+
+    #[test]
+    fn test_detect_jxl_container() {
+        let detection = detect_image_format_detailed(JXL_CONTAINER).unwrap();
+        assert_eq!(detection.kind, ImageFormat::Jxl);
+        assert_eq!((detection.matched_signature, detection.offset), (&b"jxl "[..], 20));
+
+        // Bare ftyp box, and the brand among the compatible brands
+        assert_eq!(detect_image_format(b"\0\0\0\x10ftypjxl \0\0\0\0").unwrap(), ImageFormat::Jxl);
+        assert_eq!(
+            detect_image_format(b"\0\0\0\x18ftypisom\0\0\0\0isomjxl ").unwrap(),
+            ImageFormat::Jxl
+        );
+    }
+
+    #[test]
+    fn test_detect_avif_compatible_brand() {
+        // Generic HEIF major brand, AVIF only among the compatible brands
+        let mif1 = b"\0\0\0\x1Cftypmif1\0\0\0\0mif1miafavif\0\0\0\0meta";
+        assert_eq!(detect_image_format(mif1).unwrap(), ImageFormat::Avif);
+
+        // HEIC is not AVIF, and brands past the end of the box don't count
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic\0\0\0\x08freeavif";
+        assert_eq!(detect_image_format(heic).unwrap(), ImageFormat::Heic);
+
+        // The minor version field is not a brand
+        assert_eq!(detect_image_format(b"\0\0\0\x10ftypmif1avif").unwrap(), ImageFormat::Heic);
+        assert!(detect_image_format(b"\0\0\0\x10ftypisomavif").is_err());
+    }
+
+    #[test]
+    fn test_detect_heic() {
+        // iOS photos: `heic` major brand
+        let heic = b"\0\0\0\x18ftypheic\0\0\0\0mif1heic";
+        let detection = detect_image_format_detailed(heic).unwrap();
+        assert_eq!((detection.kind, detection.matched_signature, detection.offset), (ImageFormat::Heic, &b"heic"[..], 8));
+
+        // Generic HEIF: `mif1` major brand, no AVIF or JPEG XL brand
+        let mif1 = b"\0\0\0\x18ftypmif1\0\0\0\0mif1miaf";
+        let detection = detect_image_format_detailed(mif1).unwrap();
+        assert_eq!((detection.kind, detection.matched_signature), (ImageFormat::Heic, &b"mif1"[..]));
+
+        // Recognized, but only WIC could decode it
+        assert_eq!(ImageFormat::Heic.as_str(), "HEIC");
+        assert!(!ImageFormat::Heic.is_supported());
+        assert_eq!(ImageFormat::Heic.may_decode(), cfg!(feature = "wic-fallback"));
+        assert!(ImageFormat::Jpeg.may_decode());
+    }
+
+    #[test]
+    fn test_ftyp_scan_window() {
+        // ftyp box preceded by 60 bytes of padding: outside the default window
+        let mut late = vec![0u8; 60];
+        late.extend_from_slice(b"\0\0\0\x10ftypavif\0\0\0\0");
+        assert!(detect_image_format(&late).is_err());
+        assert_eq!(
+            detect_image_format_with_scan_window(&late, 128).unwrap(),
+            ImageFormat::Avif
+        );
+
+        // The window is clamped, not trusted
+        let mut far = vec![0u8; MAX_FTYP_SCAN_WINDOW];
+        far.extend_from_slice(b"\0\0\0\x10ftypavif\0\0\0\0");
+        assert!(detect_image_format_with_scan_window(&far, usize::MAX).is_err());
+
+        // Too small a window to see the brand
+        assert!(detect_image_format_with_scan_window(AVIF_HEADER, 8).is_err());
+    }
+
+    #[test]
+    fn test_detection_never_panics_on_short_or_odd_input() {
+        // Deterministic pseudo-random bytes (xorshift), seeded per case
+        fn noise(seed: u32, len: usize) -> Vec<u8> {
+            let mut state = seed.wrapping_mul(0x9E37_79B9) | 1;
+            (0..len)
+                .map(|_| {
+                    state ^= state << 13;
+                    state ^= state >> 17;
+                    state ^= state << 5;
+                    state as u8
+                })
+                .collect()
+        }
+
+        let avif_like = b"\0\0\0\x01ftypmif1\0\0\0\0avisftyp\xFF\xFF\xFF\xFFftyp";
+        let windows = [0, 1, 3, 4, 7, 8, 11, 12, 13, 63, 64, 65, MAX_FTYP_SCAN_WINDOW, usize::MAX];
+
+        for len in 0..=97 {
+            let inputs = [
+                noise(len as u32, len),
+                noise(len as u32 + 1000, len),
+                avif_like.iter().copied().cycle().take(len).collect(),
+                b"ftyp".iter().copied().cycle().take(len).collect(),
+                vec![0xFF; len],
+            ];
+            for data in &inputs {
+                for &window in &windows {
+                    let _ = detect_image_format_with_scan_window(data, window);
+                }
+                // Every prefix of a valid header
+                let _ = detect_image_format(&AVIF_HEADER[..len.min(AVIF_HEADER.len())]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_detect_image_format_detailed() {
+        let detection = detect_image_format_detailed(MINIMAL_PNG).unwrap();
+        assert_eq!(detection.kind, ImageFormat::Png);
+        assert_eq!(detection.matched_signature, b"\x89PNG\r\n\x1A\n");
+        assert_eq!(detection.offset, 0);
+
+        let detection = detect_image_format_detailed(TIFF_HEADER_BE).unwrap();
+        assert_eq!((detection.kind, detection.matched_signature), (ImageFormat::Tiff, &b"MM\x00\x2A"[..]));
+
+        let detection = detect_image_format_detailed(WEBP_HEADER).unwrap();
+        assert_eq!((detection.kind, detection.matched_signature, detection.offset), (ImageFormat::WebP, &b"WEBP"[..], 8));
+
+        // AVIF reports the brand, wherever in the ftyp box it was found
+        let detection = detect_image_format_detailed(AVIF_HEADER).unwrap();
+        assert_eq!((detection.matched_signature, detection.offset), (&b"avif"[..], 8));
+        let mif1 = b"\0\0\0\x1Cftypmif1\0\0\0\0mif1miafavis\0\0\0\0";
+        let detection = detect_image_format_detailed(mif1).unwrap();
+        assert_eq!((detection.kind, detection.matched_signature, detection.offset), (ImageFormat::Avif, &b"avis"[..], 24));
+
+        // The simple function agrees
+        for data in [MINIMAL_JPEG, GIF_HEADER, BMP_HEADER, ICO_HEADER, QOI_HEADER, &mif1[..]] {
+            assert_eq!(detect_image_format_detailed(data).unwrap().kind, detect_image_format(data).unwrap());
+        }
+        assert!(detect_image_format_detailed(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_detect_qoi() {
+        let format = detect_image_format(QOI_HEADER).unwrap();
+        assert_eq!(format, ImageFormat::Qoi);
+        assert_eq!(format.as_str(), "QOI");
+
+        // Magic must be at the start and exact
+        assert!(detect_image_format(b"QOIF\x00\x00\x00\x02").is_err());
+        assert!(detect_image_format(b"xqoif\x00\x00\x00").is_err());
+    }
+
     #[test]
     fn test_empty_data() {
         let result = detect_image_format(&[]);
@@ -387,7 +659,11 @@ mod tests {
         assert!(ImageFormat::Tiff.is_supported());
         assert!(ImageFormat::Ico.is_supported());
         assert!(ImageFormat::WebP.is_supported());
-        assert!(ImageFormat::Avif.is_supported());
+        // Not among the `image` crate features this project enables
+        assert_eq!(ImageFormat::Avif.is_supported(), image::ImageFormat::Avif.reading_enabled());
+        assert_eq!(ImageFormat::Qoi.is_supported(), cfg!(feature = "qoi"));
+        // The `image` crate has no JPEG XL decoder
+        assert!(!ImageFormat::Jxl.is_supported());
     }
 
     #[test]
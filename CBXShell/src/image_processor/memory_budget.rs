@@ -0,0 +1,161 @@
+//! Process-wide accounting of memory committed to decode buffers
+//!
+//! The per-entry size cap bounds the *compressed* cover, but a small JPEG can
+//! still decode to hundreds of megabytes, and Explorer runs several thumbnail
+//! extractions in parallel inside its own process. Every decode reserves its
+//! estimated decoded size (`width * height * 4`) from a shared budget first;
+//! if the reservation would push the total past `MaxTotalDecodeBytes`, the
+//! extraction fails fast with `CbxError::Busy` instead of waiting, so no
+//! Explorer thread is blocked. `Busy` is returned as `E_PENDING`, which
+//! tells the caller the thumbnail is not available yet rather than that the
+//! file has none; whether and when it asks again is up to the caller.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::utils::error::{CbxError, Result};
+
+/// Default budget shared by all concurrent decodes (512MB)
+pub const DEFAULT_MAX_TOTAL_DECODE_BYTES: usize = 512 * 1024 * 1024;
+
+/// Budget used by the thumbnail pipeline
+static GLOBAL_BUDGET: DecodeBudget = DecodeBudget::new(DEFAULT_MAX_TOTAL_DECODE_BYTES);
+
+/// Tracks bytes currently committed to decode buffers against a limit
+pub struct DecodeBudget {
+    committed: AtomicUsize,
+    limit: AtomicUsize,
+}
+
+impl DecodeBudget {
+    /// Create a budget; a `limit` of 0 means unlimited
+    pub const fn new(limit: usize) -> Self {
+        Self {
+            committed: AtomicUsize::new(0),
+            limit: AtomicUsize::new(limit),
+        }
+    }
+
+    /// Change the limit (affects new reservations only)
+    pub fn set_limit(&self, limit: usize) {
+        self.limit.store(limit, Ordering::Relaxed);
+    }
+
+    /// Bytes currently reserved by in-flight decodes
+    #[allow(dead_code)] // Diagnostics, used in tests
+    pub fn committed(&self) -> usize {
+        self.committed.load(Ordering::Acquire)
+    }
+
+    /// Reserve `bytes` until the returned guard is dropped
+    ///
+    /// A reservation that does not fit while other decodes are in flight
+    /// fails with `CbxError::Busy`. When nothing else is committed, a single
+    /// oversized decode is still allowed so very large covers are not
+    /// permanently rejected; it simply runs alone.
+    pub fn try_reserve(&self, bytes: usize) -> Result<DecodeReservation<'_>> {
+        let limit = self.limit.load(Ordering::Relaxed);
+
+        let mut current = self.committed.load(Ordering::Acquire);
+        loop {
+            let new_total = current.saturating_add(bytes);
+
+            if limit != 0 && current != 0 && new_total > limit {
+                tracing::warn!(
+                    "Decode budget exhausted: {} bytes committed, {} requested, limit {}",
+                    current,
+                    bytes,
+                    limit
+                );
+                return Err(CbxError::Busy);
+            }
+
+            match self.committed.compare_exchange_weak(
+                current,
+                new_total,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return Ok(DecodeReservation { budget: self, bytes }),
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+/// RAII guard returning reserved bytes to its budget on drop
+pub struct DecodeReservation<'a> {
+    budget: &'a DecodeBudget,
+    bytes: usize,
+}
+
+impl Drop for DecodeReservation<'_> {
+    fn drop(&mut self) {
+        self.budget.committed.fetch_sub(self.bytes, Ordering::AcqRel);
+    }
+}
+
+/// The budget shared by all thumbnail extractions in this process
+pub fn global_budget() -> &'static DecodeBudget {
+    &GLOBAL_BUDGET
+}
+
+/// Set the process-wide limit (`MaxTotalDecodeBytes`, 0 = unlimited)
+pub fn set_max_total_decode_bytes(limit: usize) {
+    GLOBAL_BUDGET.set_limit(limit);
+}
+
+/// Estimated size of the RGBA8 buffer for an image of the given dimensions
+pub fn decoded_size(width: u32, height: u32) -> usize {
+    (width as usize)
+        .saturating_mul(height as usize)
+        .saturating_mul(4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_large_decodes_against_small_limit() {
+        // Two 4000x3000 covers (~48MB each) against a 64MB budget
+        let budget = DecodeBudget::new(64 * 1024 * 1024);
+        let size = decoded_size(4000, 3000);
+        assert_eq!(size, 48_000_000);
+
+        let first = budget.try_reserve(size).expect("first decode fits");
+        assert_eq!(budget.committed(), size);
+
+        let second = budget.try_reserve(size);
+        assert!(matches!(second, Err(CbxError::Busy)));
+        assert_eq!(budget.committed(), size);
+
+        // Once the first decode finishes, the second can proceed
+        drop(first);
+        assert_eq!(budget.committed(), 0);
+        let second = budget.try_reserve(size).expect("second decode fits after release");
+        drop(second);
+        assert_eq!(budget.committed(), 0);
+    }
+
+    #[test]
+    fn test_single_oversized_decode_allowed_when_idle() {
+        let budget = DecodeBudget::new(1024);
+
+        let big = budget.try_reserve(10_000).expect("idle budget admits one decode");
+        assert!(matches!(budget.try_reserve(1), Err(CbxError::Busy)));
+        drop(big);
+    }
+
+    #[test]
+    fn test_zero_limit_is_unlimited() {
+        let budget = DecodeBudget::new(0);
+        let _a = budget.try_reserve(usize::MAX / 2).unwrap();
+        let _b = budget.try_reserve(usize::MAX / 2).unwrap();
+    }
+
+    #[test]
+    fn test_decoded_size_saturates() {
+        assert_eq!(decoded_size(1, 1), 4);
+        assert_eq!(decoded_size(u32::MAX, u32::MAX), usize::MAX);
+    }
+}
@@ -5,12 +5,13 @@
 //!
 //! # Architecture
 //!
-//! The module is organized into four main components:
+//! The module is organized into five main components:
 //!
 //! - **decoder**: Decodes images from raw bytes using the `image` crate
 //! - **resizer**: Calculates thumbnail dimensions and performs high-quality resizing
 //! - **hbitmap**: Converts pixel data to Windows HBITMAP format
 //! - **thumbnail**: Orchestrates the complete pipeline
+//! - **encode**: Re-encodes a cover to a compressed file under a byte budget
 //!
 //! # Pipeline
 //!
@@ -19,7 +20,7 @@
 //! 1. Decode image from compressed archive data
 //! 2. Calculate target size (aspect ratio preserved, no upscaling)
 //! 3. Resize using high-quality algorithm (Triangle/Lanczos3)
-//! 4. Apply white background to transparent areas (Windows compatibility)
+//! 4. Keep transparency as ARGB, or apply a white background (C++ behavior)
 //! 5. Convert RGBA to BGRA format (Windows native)
 //! 6. Create HBITMAP using CreateDIBSection
 //!
@@ -33,6 +34,8 @@
 //! - AVIF (.avif) - NEW in Rust version!
 //! - TIFF (.tif, .tiff)
 //! - ICO (.ico)
+//! - QOI (.qoi) - built-in decoder, `qoi` feature
+//! - Anything with an installed WIC codec (e.g. HEIC) - `wic-fallback` feature
 //!
 //! # Examples
 //!
@@ -42,12 +45,12 @@
 //! // Load image from file or archive
 //! let image_data = std::fs::read("comic_page.jpg")?;
 //!
-//! // Create thumbnail with default settings (256x256, white background)
+//! // Create thumbnail with default settings (256x256, transparency kept)
 //! let config = ThumbnailConfig::default();
-//! let hbitmap = create_thumbnail(&image_data, config)?;
+//! let thumbnail = create_thumbnail(&image_data, config)?;
 //!
-//! // Use hbitmap with Windows APIs
-//! // Don't forget to call DeleteObject(hbitmap) when done!
+//! // Use thumbnail.bitmap with Windows APIs, reporting thumbnail.alpha
+//! // Don't forget to call DeleteObject(thumbnail.bitmap) when done!
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 //!
@@ -64,12 +67,18 @@
 //!
 //! - Same aspect ratio calculation algorithm
 //! - Same "no upscaling" behavior
-//! - Same white background for transparent images
+//! - Same white background for transparent images (`keep_transparency: false`)
 //! - Same HALFTONE-equivalent resize quality (Triangle/Bilinear)
 
-mod decoder;
+pub(crate) mod decoder;
+pub mod encode;
 mod hbitmap;
+#[cfg(feature = "qoi")]
+mod qoi;
 mod resizer;
+#[cfg(feature = "wic-fallback")]
+mod wic;
+pub mod memory_budget;
 pub mod thumbnail;
 pub mod magic;
 
@@ -86,6 +95,7 @@ const SUPPORTED_EXTENSIONS: &[&str] = &[
     "avif",                        // AVIF (NEW!)
     "tif", "tiff",                 // TIFF
     "ico",                         // Icon
+    "qoi",                         // QOI (`qoi` feature)
 ];
 
 /// Check if a file is a supported image format
@@ -157,6 +167,7 @@ mod tests {
         assert!(is_image_file("image.tif"));
         assert!(is_image_file("image.tiff"));
         assert!(is_image_file("icon.ico"));
+        assert!(is_image_file("page.qoi"));
     }
 
     #[test]
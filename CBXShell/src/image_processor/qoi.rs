@@ -0,0 +1,211 @@
+//! QOI ("Quite OK Image") decoder
+//!
+//! The `image` crate features enabled for this project don't include QOI, so
+//! this small decoder is used as a fallback by `decoder::decode_image`. It
+//! implements the complete QOI 1.0 specification (https://qoiformat.org).
+
+use crate::utils::error::CbxError;
+use image::{DynamicImage, RgbImage, RgbaImage};
+
+type Result<T> = std::result::Result<T, CbxError>;
+
+/// File magic ("qoif")
+pub const QOI_MAGIC: &[u8; 4] = b"qoif";
+
+/// Header size: magic, width, height, channels, colorspace
+const HEADER_LEN: usize = 14;
+
+/// Pixel count limit from the reference implementation
+const MAX_PIXELS: u64 = 400_000_000;
+
+/// Most pixels a single byte can produce (a maximal QOI_OP_RUN)
+const MAX_RUN: u64 = 62;
+
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const MASK_2: u8 = 0xC0;
+
+/// Whether the data starts with the QOI magic
+pub fn is_qoi(data: &[u8]) -> bool {
+    data.starts_with(QOI_MAGIC)
+}
+
+/// Read width and height from a QOI header
+pub fn read_qoi_dimensions(data: &[u8]) -> Result<(u32, u32)> {
+    parse_header(data).map(|(width, height, _)| (width, height))
+}
+
+/// Decode a QOI image (3-channel images decode to RGB, 4-channel to RGBA)
+pub fn decode_qoi(data: &[u8]) -> Result<DynamicImage> {
+    let (width, height, channels) = parse_header(data)?;
+    let pixel_count = width as usize * height as usize;
+
+    // Reject headers claiming more pixels than the payload could possibly encode
+    let payload = &data[HEADER_LEN..];
+    if (payload.len() as u64).saturating_mul(MAX_RUN) < pixel_count as u64 {
+        return Err(CbxError::Image("QOI data truncated".to_string()));
+    }
+
+    let mut pixels = Vec::with_capacity(pixel_count * 4);
+    let mut index = [[0u8; 4]; 64];
+    let mut px = [0u8, 0, 0, 255];
+    let mut run = 0usize;
+    let mut pos = 0usize;
+
+    let mut next = || -> Result<u8> {
+        let byte = *payload
+            .get(pos)
+            .ok_or_else(|| CbxError::Image("QOI data truncated".to_string()))?;
+        pos += 1;
+        Ok(byte)
+    };
+
+    for _ in 0..pixel_count {
+        if run > 0 {
+            run -= 1;
+        } else {
+            let b1 = next()?;
+
+            if b1 == OP_RGB {
+                px[0] = next()?;
+                px[1] = next()?;
+                px[2] = next()?;
+            } else if b1 == OP_RGBA {
+                px[0] = next()?;
+                px[1] = next()?;
+                px[2] = next()?;
+                px[3] = next()?;
+            } else {
+                match b1 & MASK_2 {
+                    OP_INDEX => px = index[b1 as usize],
+                    OP_DIFF => {
+                        px[0] = px[0].wrapping_add(((b1 >> 4) & 0x03).wrapping_sub(2));
+                        px[1] = px[1].wrapping_add(((b1 >> 2) & 0x03).wrapping_sub(2));
+                        px[2] = px[2].wrapping_add((b1 & 0x03).wrapping_sub(2));
+                    }
+                    OP_LUMA => {
+                        let b2 = next()?;
+                        let dg = (b1 & 0x3F).wrapping_sub(32);
+                        px[0] = px[0].wrapping_add(dg.wrapping_sub(8).wrapping_add(b2 >> 4));
+                        px[1] = px[1].wrapping_add(dg);
+                        px[2] = px[2].wrapping_add(dg.wrapping_sub(8).wrapping_add(b2 & 0x0F));
+                    }
+                    // QOI_OP_RUN (0xC0); this pixel is the first of the run
+                    _ => run = (b1 & 0x3F) as usize,
+                }
+            }
+
+            index[hash(px)] = px;
+        }
+
+        pixels.extend_from_slice(&px);
+    }
+
+    let image = RgbaImage::from_raw(width, height, pixels)
+        .ok_or_else(|| CbxError::Image("QOI pixel buffer size mismatch".to_string()))?;
+
+    Ok(if channels == 3 {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let [r, g, b, _] = image.get_pixel(x, y).0;
+            image::Rgb([r, g, b])
+        }))
+    } else {
+        DynamicImage::ImageRgba8(image)
+    })
+}
+
+/// Validate the header and return (width, height, channels)
+fn parse_header(data: &[u8]) -> Result<(u32, u32, u8)> {
+    if data.len() < HEADER_LEN || !is_qoi(data) {
+        return Err(CbxError::Image("Not a QOI image".to_string()));
+    }
+
+    let width = u32::from_be_bytes([data[4], data[5], data[6], data[7]]);
+    let height = u32::from_be_bytes([data[8], data[9], data[10], data[11]]);
+    let channels = data[12];
+
+    if width == 0 || height == 0 || u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return Err(CbxError::Image(format!("Invalid QOI dimensions {}x{}", width, height)));
+    }
+    if channels != 3 && channels != 4 {
+        return Err(CbxError::Image(format!("Invalid QOI channel count {}", channels)));
+    }
+
+    Ok((width, height, channels))
+}
+
+/// Position of a pixel in the running index
+fn hash([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2x2 RGBA QOI: red, green / green, red
+    /// Uses QOI_OP_RGBA, QOI_OP_RGB, QOI_OP_RUN and QOI_OP_INDEX
+    const QOI_2X2: &[u8] = &[
+        b'q', b'o', b'i', b'f', 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x04, 0x00,
+        0xFF, 0xFF, 0x00, 0x00, 0xFF, // RGBA red
+        0xFE, 0x00, 0xFF, 0x00, // RGB green
+        0xC0, // RUN x1 (green)
+        0x32, // INDEX 50 (red)
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, // end marker
+    ];
+
+    #[test]
+    fn test_decode_qoi_fixture() {
+        let img = decode_qoi(QOI_2X2).unwrap().to_rgba8();
+        assert_eq!(img.dimensions(), (2, 2));
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(1, 0).0, [0, 255, 0, 255]);
+        assert_eq!(img.get_pixel(0, 1).0, [0, 255, 0, 255]);
+        assert_eq!(img.get_pixel(1, 1).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_decode_qoi_diff_and_luma() {
+        let data = [
+            b'q', b'o', b'i', b'f', 0, 0, 0, 3, 0, 0, 0, 1, 3, 0,
+            0xFE, 100, 100, 100, // RGB (100, 100, 100)
+            0x40 | (3 << 4) | (2 << 2) | 1, // DIFF +1, 0, -1
+            0x80 | (32 + 10), 0x8 << 4 | 0x6, // LUMA dg=+10, dr-dg=0, db-dg=-2
+            0, 0, 0, 0, 0, 0, 0, 1,
+        ];
+
+        let img = decode_qoi(&data).unwrap();
+        assert!(matches!(img, DynamicImage::ImageRgb8(_)));
+
+        let img = img.to_rgb8();
+        assert_eq!(img.get_pixel(0, 0).0, [100, 100, 100]);
+        assert_eq!(img.get_pixel(1, 0).0, [101, 100, 99]);
+        assert_eq!(img.get_pixel(2, 0).0, [111, 110, 107]);
+    }
+
+    #[test]
+    fn test_read_qoi_dimensions() {
+        assert_eq!(read_qoi_dimensions(QOI_2X2).unwrap(), (2, 2));
+        assert!(read_qoi_dimensions(b"qoif").is_err());
+        assert!(read_qoi_dimensions(b"\x89PNG\r\n\x1a\n\0\0\0\0\0\0").is_err());
+    }
+
+    #[test]
+    fn test_decode_qoi_rejects_bad_input() {
+        // Truncated payload
+        assert!(decode_qoi(&QOI_2X2[..20]).is_err());
+
+        // Huge dimensions with a tiny payload must not allocate
+        let mut huge = QOI_2X2.to_vec();
+        huge[4..12].copy_from_slice(&[0x00, 0x00, 0x4E, 0x20, 0x00, 0x00, 0x4E, 0x20]);
+        assert!(decode_qoi(&huge).is_err());
+
+        // Invalid channel count
+        let mut bad_channels = QOI_2X2.to_vec();
+        bad_channels[12] = 5;
+        assert!(decode_qoi(&bad_channels).is_err());
+    }
+}
@@ -96,6 +96,28 @@ pub fn calculate_thumbnail_size(
     (new_width.max(1), new_height.max(1))
 }
 
+/// Calculate dimensions that fill the bounds, enlarging small images
+///
+/// Same aspect-ratio math as `calculate_thumbnail_size`, but images smaller
+/// than the bounds are scaled up. Only used when `NoUpscale` is turned off.
+pub fn calculate_fit_size(
+    src_width: u32,
+    src_height: u32,
+    max_width: u32,
+    max_height: u32,
+) -> (u32, u32) {
+    if src_width == 0 || src_height == 0 {
+        return (0, 0);
+    }
+
+    let scale = (max_width as f32 / src_width as f32).min(max_height as f32 / src_height as f32);
+
+    let new_width = (src_width as f32 * scale).round() as u32;
+    let new_height = (src_height as f32 * scale).round() as u32;
+
+    (new_width.max(1), new_height.max(1))
+}
+
 /// Resize image to target dimensions using high-quality algorithm
 ///
 /// Uses fast_image_resize for efficient SIMD-optimized resizing.
@@ -203,6 +225,16 @@ mod tests {
         assert_eq!(h, 256);
     }
 
+    #[test]
+    fn test_fit_size_upscales() {
+        assert_eq!(calculate_fit_size(40, 20, 256, 256), (256, 128));
+        assert_eq!(calculate_fit_size(50, 75, 256, 256), (171, 256));
+
+        // Large images are still scaled down
+        assert_eq!(calculate_fit_size(1000, 500, 256, 256), (256, 128));
+        assert_eq!(calculate_fit_size(0, 10, 256, 256), (0, 0));
+    }
+
     #[test]
     fn test_no_upscale() {
         // Small image 100x100 should not be upscaled
@@ -3,21 +3,29 @@
 //! This module orchestrates the complete thumbnail generation process:
 //! 1. Decode image from raw bytes
 //! 2. Calculate target thumbnail size (aspect ratio preserved)
-//! 3. Resize image using high-quality algorithm
-//! 4. Apply white background for transparent images (C++ behavior)
-//! 5. Convert RGBA to BGRA format
-//! 6. Create Windows HBITMAP
+//! 3. Optionally stretch the tonal range (auto-levels) for dark scans
+//! 4. Resize image using high-quality algorithm
+//! 5. Apply white background for transparent images, unless transparency is kept
+//! 6. Convert RGBA to BGRA format
+//! 7. Create Windows HBITMAP, together with the alpha type to report to Explorer
 //!
 //! This matches the C++ implementation in cbxArchive.h:628-666 (OnExtract).
 
 use crate::utils::error::CbxError;
-use image::{GenericImageView, RgbaImage};
+use image::{DynamicImage, GenericImageView, RgbaImage};
 use windows::Win32::Graphics::Gdi::HBITMAP;
+use windows::Win32::UI::Shell::{WTSAT_ARGB, WTSAT_RGB, WTS_ALPHATYPE};
 
 use super::decoder;
 use super::hbitmap;
+use super::memory_budget;
 use super::resizer::{self, ResizeFilter};
 
+// Named in `ThumbnailConfig::bit_depth`
+pub use super::hbitmap::BitDepth;
+// Reads a `Thumbnail::bitmap` back (the manager's test thumbnail)
+pub use super::hbitmap::read_dib_bgra;
+
 type Result<T> = std::result::Result<T, CbxError>;
 
 /// Thumbnail generation configuration
@@ -32,31 +40,111 @@ pub struct ThumbnailConfig {
     /// Maximum thumbnail height in pixels
     pub max_height: u32,
 
-    /// Background color for transparent images (RGBA format)
+    /// Background color for transparent images (RGBA format), used when
+    /// `keep_transparency` is false
     /// Default: (255, 255, 255, 255) - opaque white
     pub background_color: (u8, u8, u8, u8),
 
+    /// Hand transparent covers to Explorer as ARGB instead of compositing them
+    /// on `background_color`
+    /// Default: true (PNG/WebP covers with transparent corners stay transparent)
+    pub keep_transparency: bool,
+
     /// Resize algorithm to use
     /// Default: Triangle (matches C++ HALFTONE mode)
     pub resize_filter: ResizeFilter,
+
+    /// Keep covers smaller than the requested size at native resolution
+    /// Default: true (C++ behavior; upscaling looks blurry at high DPI)
+    pub no_upscale: bool,
+
+    /// Stretch the tonal range before scaling so dark scans stay readable
+    /// Default: false (colors are passed through unchanged)
+    pub auto_levels: bool,
+
+    /// Crop uniform scan borders before scaling (see `trim_border_bounds`)
+    /// Default: false (the whole page is shown)
+    pub trim_borders: bool,
+
+    /// DIB depth for opaque covers; covers with transparency always use 32bpp
+    /// Default: Bgra32 (C++ behavior)
+    pub bit_depth: BitDepth,
+
+    /// Format implied by the cover's file name, tried if the format guessed
+    /// from its bytes fails to decode
+    /// Default: None
+    pub format_hint: Option<image::ImageFormat>,
 }
 
 impl Default for ThumbnailConfig {
     /// Default configuration matching C++ behavior
     ///
     /// - Max size: 256x256 (Windows default thumbnail size)
-    /// - Background: White (RGB 255, 255, 255)
+    /// - Background: White (RGB 255, 255, 255), transparency kept
     /// - Filter: Triangle/Bilinear (matches HALFTONE)
+    /// - No upscaling
+    /// - No auto-levels
+    /// - No border trimming
+    /// - 32bpp bitmaps
+    /// - No format hint
     fn default() -> Self {
         Self {
             max_width: 256,
             max_height: 256,
             background_color: (255, 255, 255, 255), // White background
+            keep_transparency: true,
             resize_filter: ResizeFilter::Triangle,   // Match C++ HALFTONE
+            no_upscale: true,
+            auto_levels: false,
+            trim_borders: false,
+            bit_depth: BitDepth::Bgra32,
+            format_hint: None,
+        }
+    }
+}
+
+/// Fill behind transparent covers (`ThumbnailBackground` setting)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ThumbnailBackground {
+    /// Keep the alpha channel and report the bitmap as `WTSAT_ARGB`
+    #[default]
+    Transparent,
+    /// Composite on opaque white
+    White,
+    /// Composite on opaque black
+    Black,
+}
+
+impl ThumbnailBackground {
+    /// Background for a registry value (0, 1 or 2)
+    pub fn from_value(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(ThumbnailBackground::Transparent),
+            1 => Some(ThumbnailBackground::White),
+            2 => Some(ThumbnailBackground::Black),
+            _ => None,
+        }
+    }
+
+    /// `ThumbnailConfig::background_color` for this fill (white for `Transparent`,
+    /// used only for covers that keep no alpha)
+    pub fn color(self) -> (u8, u8, u8, u8) {
+        match self {
+            ThumbnailBackground::Transparent | ThumbnailBackground::White => (255, 255, 255, 255),
+            ThumbnailBackground::Black => (0, 0, 0, 255),
         }
     }
 }
 
+/// A thumbnail bitmap and how Explorer should treat its alpha channel
+#[derive(Debug, Clone, Copy)]
+pub struct Thumbnail {
+    /// Top-down DIB; the caller owns it and must `DeleteObject` it
+    pub bitmap: HBITMAP,
+    /// `WTSAT_ARGB` if the bitmap carries transparency, `WTSAT_RGB` otherwise
+    pub alpha: WTS_ALPHATYPE,
+}
+
 /// Create thumbnail HBITMAP from image data
 ///
 /// This is the main entry point for thumbnail generation. It orchestrates
@@ -67,16 +155,21 @@ impl Default for ThumbnailConfig {
 /// * `config` - Thumbnail generation configuration
 ///
 /// # Returns
-/// * `Ok(HBITMAP)` - Successfully created thumbnail bitmap
+/// * `Ok(Thumbnail)` - Successfully created thumbnail bitmap and its alpha type
 /// * `Err(CbxError)` - Failed to create thumbnail
 ///
 /// # Pipeline Steps
-/// 1. Decode: Parse image format and decode to RGBA
-/// 2. Calculate: Determine thumbnail size (aspect ratio preserved, no upscaling)
-/// 3. Resize: High-quality downscale using selected algorithm
-/// 4. Composite: Apply white background to transparent areas
-/// 5. Convert: RGBA to BGRA for Windows compatibility
-/// 6. Create: Generate HBITMAP using CreateDIBSection
+/// 1. Decode: Parse image format and decode to RGBA, then crop uniform scan
+///    borders (only if `config.trim_borders`)
+/// 2. Calculate: Determine thumbnail size (aspect ratio preserved, no upscaling
+///    unless `config.no_upscale` is false)
+/// 3. Levels: Percentile stretch of dark covers (only if `config.auto_levels`)
+/// 4. Resize: High-quality downscale using selected algorithm
+/// 5. Composite: Apply white background to transparent areas (only if
+///    `config.keep_transparency` is false)
+/// 6. Convert: RGBA to BGRA for Windows compatibility
+/// 7. Create: Generate HBITMAP using CreateDIBSection, at `config.bit_depth`
+///    unless the cover has transparency
 ///
 /// # C++ Equivalent (cbxArchive.h:628-666)
 /// ```cpp
@@ -97,15 +190,24 @@ impl Default for ThumbnailConfig {
 ///
 /// let jpeg_data = std::fs::read("comic_page.jpg")?;
 /// let config = ThumbnailConfig::default();
-/// let hbitmap = create_thumbnail(&jpeg_data, config)?;
+/// let thumbnail = create_thumbnail(&jpeg_data, config)?;
 ///
-/// // Use hbitmap with Windows APIs
-/// // Remember to DeleteObject(hbitmap) when done
+/// // Use thumbnail.bitmap with Windows APIs, reporting thumbnail.alpha
+/// // Remember to DeleteObject(thumbnail.bitmap) when done
 /// ```
-pub fn create_thumbnail(image_data: &[u8], config: ThumbnailConfig) -> Result<HBITMAP> {
-    // Step 1: Decode image from bytes
+pub fn create_thumbnail(image_data: &[u8], config: ThumbnailConfig) -> Result<Thumbnail> {
+    // Step 0: Reserve the estimated decoded size from the process-wide budget.
+    // The reservation is held until the thumbnail is built. If the header
+    // can't be read, decoding below will fail anyway, so nothing is reserved.
+    let decoded_bytes = decoder::estimated_decoded_bytes(image_data)
+        .map_or(0, |bytes| usize::try_from(bytes).unwrap_or(usize::MAX));
+    let _reservation = memory_budget::global_budget().try_reserve(decoded_bytes)?;
+
+    // Step 1: Decode image from bytes, at a reduced JPEG scale when that is
+    // still at least as large as the thumbnail
     crate::utils::debug_log::debug_log(&format!("Decoding image from {} bytes...", image_data.len()));
-    let img = match decoder::decode_image(image_data) {
+    let max_dim = config.max_width.max(config.max_height);
+    let img = match decoder::decode_image_scaled(image_data, max_dim, config.format_hint) {
         Ok(img) => {
             crate::utils::debug_log::debug_log(&format!("Image decoded successfully: {}x{}", img.width(), img.height()));
             img
@@ -130,14 +232,19 @@ pub fn create_thumbnail(image_data: &[u8], config: ThumbnailConfig) -> Result<HB
         }
     };
 
-    // Step 2: Calculate target thumbnail size
+    // Step 1b: Crop uniform scan borders, so the art fills more of the thumbnail
+    let img = if config.trim_borders { trim_borders(img) } else { img };
+
+    // Step 2: Calculate target thumbnail size. Explorer scales `cx` with the
+    // monitor DPI, so a large request for a small cover returns it at native size.
     let (src_width, src_height) = img.dimensions();
-    let (target_width, target_height) = resizer::calculate_thumbnail_size(
-        src_width,
-        src_height,
-        config.max_width,
-        config.max_height,
-    );
+    let size_fn = if config.no_upscale {
+        resizer::calculate_thumbnail_size
+    } else {
+        resizer::calculate_fit_size
+    };
+    let (target_width, target_height) =
+        size_fn(src_width, src_height, config.max_width, config.max_height);
 
     // Handle edge case: zero dimensions
     if target_width == 0 || target_height == 0 {
@@ -149,21 +256,177 @@ pub fn create_thumbnail(image_data: &[u8], config: ThumbnailConfig) -> Result<HB
     // Step 3: Convert to RGBA format
     let mut rgba = img.to_rgba8();
 
+    // Step 3b: Stretch the tonal range on the full-resolution image, so the
+    // percentiles are not skewed by resampling
+    if config.auto_levels {
+        apply_auto_levels(&mut rgba);
+    }
+
     // Step 4: Resize if dimensions changed
     if (target_width, target_height) != (src_width, src_height) {
         rgba = resizer::resize_image(&rgba, target_width, target_height, config.resize_filter)?;
     }
 
-    // Step 5: Apply white background for transparency (C++ behavior)
-    // This matches the C++ code which fills the background with white (RGB 255,255,255)
-    // before drawing the image
-    apply_background(&mut rgba, config.background_color);
+    // Transparent covers keep 32bpp whatever depth was configured
+    let has_alpha = rgba.pixels().any(|p| p[3] != 255);
+    let bit_depth = if has_alpha { BitDepth::Bgra32 } else { config.bit_depth };
+
+    // Step 5: Without transparency support, apply the background like the C++
+    // code, which fills it with white (RGB 255,255,255) before drawing the image
+    let alpha = if has_alpha && config.keep_transparency {
+        WTSAT_ARGB
+    } else {
+        apply_background(&mut rgba, config.background_color);
+        WTSAT_RGB
+    };
 
     // Step 6: Convert RGBA to BGRA (Windows format)
     let bgra = hbitmap::rgba_to_bgra(rgba.as_raw());
 
     // Step 7: Create Windows HBITMAP
-    hbitmap::create_hbitmap_from_bgra(&bgra, target_width, target_height)
+    let bitmap = hbitmap::create_hbitmap_with_depth(&bgra, target_width, target_height, bit_depth)?;
+    Ok(Thumbnail { bitmap, alpha })
+}
+
+/// Largest per-channel difference from the border color still counted as border
+const TRIM_TOLERANCE: u8 = 24;
+
+/// Fraction of a row or column allowed to differ from the border color (dust, scan noise)
+const TRIM_NOISE: f32 = 0.01;
+
+/// `trim_border_bounds` crops at most 1/`TRIM_MAX_DIVISOR` of the image per side
+const TRIM_MAX_DIVISOR: u32 = 4;
+
+/// `img` without its uniform borders (see `trim_border_bounds`)
+fn trim_borders(img: DynamicImage) -> DynamicImage {
+    match trim_border_bounds(&img.to_rgba8()) {
+        Some((x, y, width, height)) => {
+            crate::utils::debug_log::debug_log(&format!(
+                "Trimmed borders: {}x{} -> {}x{} at ({}, {})",
+                img.width(),
+                img.height(),
+                width,
+                height,
+                x,
+                y
+            ));
+            img.crop_imm(x, y, width, height)
+        }
+        None => img,
+    }
+}
+
+/// The part of an image inside its uniform borders, as `(x, y, width, height)`
+///
+/// Each side is scanned inward from the edge. A row or column is border
+/// while all but `TRIM_NOISE` of its pixels are within `TRIM_TOLERANCE` of
+/// the corner pixel on that side (top-left for the top and left sides,
+/// bottom-right for the bottom and right sides), so white and black scan
+/// borders are both found. At most a quarter of the width or height is
+/// cropped per side, so even a nearly flat page keeps half of each.
+///
+/// Returns `None` if there is no border to trim.
+pub(super) fn trim_border_bounds(rgba: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = rgba.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    let top_left = rgba.get_pixel(0, 0).0;
+    let bottom_right = rgba.get_pixel(width - 1, height - 1).0;
+    let row = |y: u32, xs: std::ops::Range<u32>| xs.map(move |x| rgba.get_pixel(x, y).0);
+    let column = |x: u32, ys: std::ops::Range<u32>| ys.map(move |y| rgba.get_pixel(x, y).0);
+
+    let top = (0..height / TRIM_MAX_DIVISOR)
+        .take_while(|&y| is_border_line(row(y, 0..width), top_left))
+        .count() as u32;
+    let bottom = (0..height / TRIM_MAX_DIVISOR)
+        .take_while(|&i| is_border_line(row(height - 1 - i, 0..width), bottom_right))
+        .count() as u32;
+
+    // Columns only span the rows that are kept
+    let rows = top..height - bottom;
+    let left = (0..width / TRIM_MAX_DIVISOR)
+        .take_while(|&x| is_border_line(column(x, rows.clone()), top_left))
+        .count() as u32;
+    let right = (0..width / TRIM_MAX_DIVISOR)
+        .take_while(|&i| is_border_line(column(width - 1 - i, rows.clone()), bottom_right))
+        .count() as u32;
+
+    if top + bottom + left + right == 0 {
+        return None;
+    }
+    Some((left, top, width - left - right, height - top - bottom))
+}
+
+/// Whether a line of pixels is (nearly) all `border` colored
+fn is_border_line(pixels: impl ExactSizeIterator<Item = [u8; 4]>, border: [u8; 4]) -> bool {
+    let allowed = (pixels.len() as f32 * TRIM_NOISE) as usize;
+    let near = |pixel: &[u8; 4]| pixel.iter().zip(border).all(|(a, b)| a.abs_diff(b) <= TRIM_TOLERANCE);
+    pixels.filter(|pixel| !near(pixel)).take(allowed + 1).count() <= allowed
+}
+
+/// Fraction of pixels clipped at each end of the histogram by `apply_auto_levels`
+const AUTO_LEVELS_CLIP: f32 = 0.01;
+
+/// Stretch the tonal range of an image (auto-levels)
+///
+/// Builds a luminance histogram of the visible pixels, takes the 1st and 99th
+/// percentiles as the new black and white points and linearly remaps every
+/// color channel between them. Using percentiles instead of the absolute
+/// minimum/maximum keeps a few specks of dust or a white page border from
+/// cancelling the stretch on scanned covers.
+///
+/// Images that already span the full range (or are a single flat color) are
+/// left unchanged. Alpha is not modified.
+pub(super) fn apply_auto_levels(rgba: &mut RgbaImage) {
+    let mut histogram = [0u64; 256];
+    let mut total = 0u64;
+    for pixel in rgba.pixels().filter(|p| p[3] != 0) {
+        histogram[luminance(pixel.0) as usize] += 1;
+        total += 1;
+    }
+    if total == 0 {
+        return;
+    }
+
+    let clip = (total as f32 * AUTO_LEVELS_CLIP) as u64;
+    let low = clipped_level(&histogram, clip, 0..256);
+    let high = clipped_level(&histogram, clip, (0..256).rev());
+
+    if high <= low || (low == 0 && high == 255) {
+        return;
+    }
+
+    let range = (high - low) as f32;
+    let mut lut = [0u8; 256];
+    for (value, entry) in lut.iter_mut().enumerate() {
+        let stretched = (value as f32 - low as f32) * 255.0 / range;
+        *entry = stretched.round().clamp(0.0, 255.0) as u8;
+    }
+
+    for pixel in rgba.pixels_mut() {
+        pixel[0] = lut[pixel[0] as usize];
+        pixel[1] = lut[pixel[1] as usize];
+        pixel[2] = lut[pixel[2] as usize];
+    }
+}
+
+/// First level (in `levels` order) past the `clip` darkest/brightest pixels
+fn clipped_level(histogram: &[u64; 256], clip: u64, levels: impl Iterator<Item = usize>) -> usize {
+    let mut seen = 0u64;
+    for level in levels {
+        seen += histogram[level];
+        if seen > clip {
+            return level;
+        }
+    }
+    0
+}
+
+/// Rec. 601 luma of an RGBA pixel
+fn luminance([r, g, b, _]: [u8; 4]) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
 }
 
 /// Apply background color to transparent areas
@@ -176,8 +439,8 @@ pub fn create_thumbnail(image_data: &[u8], config: ThumbnailConfig) -> Result<HB
 /// final_color = pixel_color * alpha + background_color * (1 - alpha)
 /// ```
 ///
-/// After blending, the alpha channel is set to 255 (fully opaque) since
-/// Windows Explorer doesn't properly handle alpha in thumbnails.
+/// After blending, the alpha channel is set to 255 (fully opaque), so the
+/// bitmap can be reported to Explorer as `WTSAT_RGB`.
 ///
 /// # Arguments
 /// * `rgba` - Image to modify (in-place)
@@ -189,18 +452,21 @@ pub fn create_thumbnail(image_data: &[u8], config: ThumbnailConfig) -> Result<HB
 /// FillRect(hdcDest, &rcDest, hBrush);
 /// DeleteObject(hBrush);
 /// ```
-fn apply_background(rgba: &mut RgbaImage, bg: (u8, u8, u8, u8)) {
+pub(super) fn apply_background(rgba: &mut RgbaImage, bg: (u8, u8, u8, u8)) {
+    // Integer blend, rounded to nearest, so results don't depend on float error
+    let blend = |fg: u8, bg: u8, alpha: u32| ((fg as u32 * alpha + bg as u32 * (255 - alpha) + 127) / 255) as u8;
+
     for pixel in rgba.pixels_mut() {
-        let alpha = pixel[3] as f32 / 255.0;
+        let alpha = pixel[3] as u32;
 
-        if alpha < 1.0 {
+        if alpha < 255 {
             // Blend with background using alpha compositing
-            pixel[0] = ((pixel[0] as f32 * alpha) + (bg.0 as f32 * (1.0 - alpha))) as u8;
-            pixel[1] = ((pixel[1] as f32 * alpha) + (bg.1 as f32 * (1.0 - alpha))) as u8;
-            pixel[2] = ((pixel[2] as f32 * alpha) + (bg.2 as f32 * (1.0 - alpha))) as u8;
+            pixel[0] = blend(pixel[0], bg.0, alpha);
+            pixel[1] = blend(pixel[1], bg.1, alpha);
+            pixel[2] = blend(pixel[2], bg.2, alpha);
         }
 
-        // Set alpha to fully opaque (Windows Explorer doesn't handle transparency well)
+        // Set alpha to fully opaque (the bitmap is reported as WTSAT_RGB)
         pixel[3] = 255;
     }
 }
@@ -208,6 +474,7 @@ fn apply_background(rgba: &mut RgbaImage, bg: (u8, u8, u8, u8)) {
 /// Create thumbnail with custom dimensions
 ///
 /// Convenience function for quick thumbnail creation with custom size.
+/// Covers smaller than the requested size are not upscaled.
 ///
 /// # Arguments
 /// * `image_data` - Raw image file bytes
@@ -215,13 +482,13 @@ fn apply_background(rgba: &mut RgbaImage, bg: (u8, u8, u8, u8)) {
 /// * `max_height` - Maximum thumbnail height
 ///
 /// # Returns
-/// * `Ok(HBITMAP)` - Successfully created thumbnail
+/// * `Ok(Thumbnail)` - Successfully created thumbnail and its alpha type
 /// * `Err(CbxError)` - Failed to create thumbnail
 pub fn create_thumbnail_with_size(
     image_data: &[u8],
     max_width: u32,
     max_height: u32,
-) -> Result<HBITMAP> {
+) -> Result<Thumbnail> {
     let config = ThumbnailConfig {
         max_width,
         max_height,
@@ -233,6 +500,7 @@ pub fn create_thumbnail_with_size(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::image_processor::decoder::tests::PROGRESSIVE_JPEG;
     use image::Rgba;
     use windows::Win32::Graphics::Gdi::DeleteObject;
 
@@ -262,10 +530,10 @@ mod tests {
         );
 
         // Clean up
-        if let Ok(hbitmap) = result {
+        if let Ok(thumbnail) = result {
             unsafe {
-                assert_ne!(hbitmap.0, 0);
-                DeleteObject(hbitmap);
+                assert_ne!(thumbnail.bitmap.0, 0);
+                DeleteObject(thumbnail.bitmap);
             }
         }
     }
@@ -281,9 +549,9 @@ mod tests {
         let result = create_thumbnail(MINIMAL_JPEG, config);
         assert!(result.is_ok());
 
-        if let Ok(hbitmap) = result {
+        if let Ok(thumbnail) = result {
             unsafe {
-                DeleteObject(hbitmap);
+                DeleteObject(thumbnail.bitmap);
             }
         }
     }
@@ -293,13 +561,104 @@ mod tests {
         let result = create_thumbnail_with_size(MINIMAL_JPEG, 64, 64);
         assert!(result.is_ok());
 
-        if let Ok(hbitmap) = result {
+        if let Ok(thumbnail) = result {
             unsafe {
-                DeleteObject(hbitmap);
+                DeleteObject(thumbnail.bitmap);
+            }
+        }
+    }
+
+    #[test]
+    fn test_create_thumbnail_progressive_jpeg() {
+        // Regression: progressive covers must go through the full decode path
+        let result = create_thumbnail_with_size(PROGRESSIVE_JPEG, 64, 64);
+        assert!(
+            result.is_ok(),
+            "Failed to create thumbnail from progressive JPEG: {:?}",
+            result.err()
+        );
+
+        if let Ok(thumbnail) = result {
+            unsafe {
+                assert_ne!(thumbnail.bitmap.0, 0);
+                DeleteObject(thumbnail.bitmap);
             }
         }
     }
 
+    #[test]
+    fn test_create_thumbnail_orientation_readback() {
+        // 40x20 PNG: top-left quadrant red, everything else blue
+        let mut img = RgbaImage::from_pixel(40, 20, Rgba([0, 0, 255, 255]));
+        for y in 0..10 {
+            for x in 0..20 {
+                img.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+            }
+        }
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        // No resize needed: the thumbnail keeps the source dimensions
+        let hbitmap = create_thumbnail_with_size(&png, 64, 64).unwrap().bitmap;
+        let readback = hbitmap::read_dib_bgra(hbitmap);
+        unsafe {
+            DeleteObject(hbitmap);
+        }
+
+        let (width, height, bgra) = readback.unwrap();
+        assert_eq!((width, height), (40, 20));
+
+        let pixel = |x: usize, y: usize| {
+            let i = y * hbitmap::dib_stride(width) + x * 4;
+            [bgra[i], bgra[i + 1], bgra[i + 2], bgra[i + 3]]
+        };
+        assert_eq!(pixel(0, 0), [0, 0, 255, 255], "top-left should be red (BGRA)");
+        assert_eq!(pixel(0, 19), [255, 0, 0, 255], "bottom-left should be blue (BGRA)");
+        assert_eq!(pixel(39, 0), [255, 0, 0, 255], "top-right should be blue (BGRA)");
+    }
+
+    /// Encode a solid 40x20 PNG
+    fn small_png() -> Vec<u8> {
+        let img = RgbaImage::from_pixel(40, 20, Rgba([0, 128, 255, 255]));
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_create_thumbnail_no_upscale_beyond_native_size() {
+        // A high-DPI request (cx=512) for a 40x20 cover returns it at native size
+        let hbitmap = create_thumbnail_with_size(&small_png(), 512, 512).unwrap().bitmap;
+        let readback = hbitmap::read_dib_bgra(hbitmap);
+        unsafe {
+            DeleteObject(hbitmap);
+        }
+
+        let (width, height, _) = readback.unwrap();
+        assert_eq!((width, height), (40, 20));
+    }
+
+    #[test]
+    fn test_create_thumbnail_upscale_when_allowed() {
+        let config = ThumbnailConfig {
+            max_width: 512,
+            max_height: 512,
+            no_upscale: false,
+            ..Default::default()
+        };
+
+        let hbitmap = create_thumbnail(&small_png(), config).unwrap().bitmap;
+        let readback = hbitmap::read_dib_bgra(hbitmap);
+        unsafe {
+            DeleteObject(hbitmap);
+        }
+
+        let (width, height, _) = readback.unwrap();
+        assert_eq!((width, height), (512, 256));
+    }
+
     #[test]
     fn test_create_thumbnail_invalid_data() {
         let invalid_data = b"This is not an image";
@@ -362,6 +721,51 @@ mod tests {
         assert_eq!(pixel[3], 255); // Alpha (opaque)
     }
 
+    #[test]
+    fn test_apply_background_semi_transparent_over_white_and_black() {
+        // (200, 100, 0) at alpha 128: c * 128/255 + bg * 127/255, rounded
+        let semi = Rgba([200, 100, 0, 128]);
+
+        let mut img = RgbaImage::from_pixel(1, 1, semi);
+        apply_background(&mut img, ThumbnailBackground::White.color());
+        assert_eq!(*img.get_pixel(0, 0), Rgba([227, 177, 127, 255]));
+
+        let mut img = RgbaImage::from_pixel(1, 1, semi);
+        apply_background(&mut img, ThumbnailBackground::Black.color());
+        assert_eq!(*img.get_pixel(0, 0), Rgba([100, 50, 0, 255]));
+    }
+
+    #[test]
+    fn test_thumbnail_background_setting() {
+        assert_eq!(ThumbnailBackground::from_value(0), Some(ThumbnailBackground::Transparent));
+        assert_eq!(ThumbnailBackground::from_value(2), Some(ThumbnailBackground::Black));
+        assert_eq!(ThumbnailBackground::from_value(3), None);
+
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(1, 1, Rgba([200, 100, 0, 128]))
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let render = |background: ThumbnailBackground| {
+            let config = ThumbnailConfig {
+                keep_transparency: background == ThumbnailBackground::Transparent,
+                background_color: background.color(),
+                ..Default::default()
+            };
+            let thumbnail = create_thumbnail(&png, config).unwrap();
+            let readback = hbitmap::read_dib_bgra(thumbnail.bitmap);
+            unsafe {
+                DeleteObject(thumbnail.bitmap);
+            }
+            (thumbnail.alpha, readback.unwrap().2)
+        };
+
+        // BGRA readback
+        assert_eq!(render(ThumbnailBackground::White), (WTSAT_RGB, vec![127, 177, 227, 255]));
+        assert_eq!(render(ThumbnailBackground::Black), (WTSAT_RGB, vec![0, 50, 100, 255]));
+        assert_eq!(render(ThumbnailBackground::Transparent).0, WTSAT_ARGB);
+    }
+
     #[test]
     fn test_config_default_values() {
         let config = ThumbnailConfig::default();
@@ -369,6 +773,194 @@ mod tests {
         assert_eq!(config.max_height, 256);
         assert_eq!(config.background_color, (255, 255, 255, 255));
         assert_eq!(config.resize_filter, ResizeFilter::Triangle);
+        assert!(config.no_upscale);
+        assert!(config.keep_transparency);
+        assert!(!config.auto_levels);
+        assert_eq!(config.bit_depth, BitDepth::Bgra32);
+    }
+
+    #[test]
+    fn test_create_thumbnail_bit_depth_follows_transparency() {
+        let encode = |img: RgbaImage| {
+            let mut png = Vec::new();
+            img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+                .unwrap();
+            png
+        };
+        let opaque = small_png();
+        let transparent = encode(RgbaImage::from_pixel(40, 20, Rgba([0, 128, 255, 100])));
+
+        let bit_count = |data: &[u8]| {
+            let config = ThumbnailConfig {
+                bit_depth: BitDepth::Rgb24,
+                ..Default::default()
+            };
+            let hbitmap = create_thumbnail(data, config).unwrap().bitmap;
+            let bits = hbitmap::dib_bit_count(hbitmap);
+            unsafe {
+                DeleteObject(hbitmap);
+            }
+            bits.unwrap()
+        };
+
+        assert_eq!(bit_count(&opaque), 24);
+        assert_eq!(bit_count(&transparent), 32);
+    }
+
+    #[test]
+    fn test_create_thumbnail_alpha_type() {
+        // Transparent corners on an opaque 40x20 cover
+        let mut img = RgbaImage::from_pixel(40, 20, Rgba([0, 128, 255, 255]));
+        img.put_pixel(0, 0, Rgba([0, 0, 0, 0]));
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let render = |data: &[u8], keep_transparency| {
+            let config = ThumbnailConfig {
+                keep_transparency,
+                ..Default::default()
+            };
+            let thumbnail = create_thumbnail(data, config).unwrap();
+            let readback = hbitmap::read_dib_bgra(thumbnail.bitmap);
+            unsafe {
+                DeleteObject(thumbnail.bitmap);
+            }
+            (thumbnail.alpha, readback.unwrap().2)
+        };
+
+        let (alpha, bgra) = render(&png, true);
+        assert_eq!(alpha, WTSAT_ARGB);
+        assert_eq!(bgra[3], 0, "transparent corner keeps its alpha");
+
+        let (alpha, bgra) = render(&png, false);
+        assert_eq!(alpha, WTSAT_RGB);
+        assert_eq!(&bgra[..4], &[255, 255, 255, 255], "corner composited on white");
+
+        let (alpha, _) = render(&small_png(), true);
+        assert_eq!(alpha, WTSAT_RGB, "opaque covers stay RGB");
+        let (alpha, _) = render(MINIMAL_JPEG, true);
+        assert_eq!(alpha, WTSAT_RGB);
+    }
+
+    /// Encode a dark 64x64 gray gradient (levels 20..=83)
+    fn dark_gradient_png() -> Vec<u8> {
+        let img = RgbaImage::from_fn(64, 64, |x, _| {
+            let v = 20 + x as u8;
+            Rgba([v, v, v, 255])
+        });
+        let mut png = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    /// Lowest and highest blue value in a BGRA buffer
+    fn blue_range(bgra: &[u8]) -> (u8, u8) {
+        let blues = bgra.chunks_exact(4).map(|px| px[0]);
+        (blues.clone().min().unwrap(), blues.max().unwrap())
+    }
+
+    /// `width`x`height` red page inside a `border`-pixel frame of `frame` color
+    fn bordered(width: u32, height: u32, border: u32, frame: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let inside = (border..width - border).contains(&x) && (border..height - border).contains(&y);
+            if inside {
+                Rgba([200, 30, 30, 255])
+            } else {
+                Rgba(frame)
+            }
+        })
+    }
+
+    #[test]
+    fn test_trim_border_bounds() {
+        // White scan border with a little noise and a speck of dust
+        let mut page = bordered(100, 120, 10, [255, 255, 255, 255]);
+        page.put_pixel(50, 2, Rgba([240, 245, 250, 255]));
+        page.put_pixel(3, 60, Rgba([0, 0, 0, 255]));
+        assert_eq!(trim_border_bounds(&page), Some((10, 10, 80, 100)));
+
+        // Black borders too
+        assert_eq!(trim_border_bounds(&bordered(40, 40, 4, [0, 0, 0, 255])), Some((4, 4, 32, 32)));
+
+        // Never more than a quarter per side
+        assert_eq!(trim_border_bounds(&bordered(100, 100, 40, [255, 255, 255, 255])), Some((25, 25, 50, 50)));
+
+        // Nothing to trim
+        let gradient = RgbaImage::from_fn(40, 40, |x, y| Rgba([(x * 6) as u8, (y * 6) as u8, 0, 255]));
+        assert_eq!(trim_border_bounds(&gradient), None);
+        assert_eq!(trim_border_bounds(&RgbaImage::new(1, 1)), None);
+    }
+
+    #[test]
+    fn test_trim_borders_in_thumbnail() {
+        let mut png = Vec::new();
+        bordered(64, 64, 8, [255, 255, 255, 255])
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+
+        let render = |trim_borders| {
+            let config = ThumbnailConfig { trim_borders, ..Default::default() };
+            let hbitmap = create_thumbnail(&png, config).unwrap().bitmap;
+            let readback = hbitmap::read_dib_bgra(hbitmap);
+            unsafe {
+                DeleteObject(hbitmap);
+            }
+            readback.unwrap()
+        };
+
+        // Untrimmed: the white frame is in the corner
+        let (width, height, bgra) = render(false);
+        assert_eq!((width, height), (64, 64));
+        assert_eq!(&bgra[..4], &[255, 255, 255, 255]);
+
+        // Trimmed: only the red page is left
+        let (width, height, bgra) = render(true);
+        assert_eq!((width, height), (48, 48));
+        assert_eq!(&bgra[..4], &[30, 30, 200, 255]);
+    }
+
+    #[test]
+    fn test_auto_levels_expands_dark_cover_range() {
+        let render = |auto_levels| {
+            let config = ThumbnailConfig {
+                max_width: 32,
+                max_height: 32,
+                auto_levels,
+                ..Default::default()
+            };
+            let hbitmap = create_thumbnail(&dark_gradient_png(), config).unwrap().bitmap;
+            let readback = hbitmap::read_dib_bgra(hbitmap);
+            unsafe {
+                DeleteObject(hbitmap);
+            }
+            readback.unwrap().2
+        };
+
+        let (plain_min, plain_max) = blue_range(&render(false));
+        let (leveled_min, leveled_max) = blue_range(&render(true));
+
+        assert!(plain_max < 100, "disabled: colors pass through unchanged");
+        assert!(leveled_max - leveled_min > plain_max - plain_min);
+        assert!(leveled_min < 10 && leveled_max > 245);
+    }
+
+    #[test]
+    fn test_apply_auto_levels_ignores_specks_and_flat_images() {
+        // A dark page with one white speck is still stretched
+        let mut dark = RgbaImage::from_fn(100, 2, |x, _| {
+            let v = 10 + (x / 2) as u8;
+            Rgba([v, v, v, 255])
+        });
+        dark.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        apply_auto_levels(&mut dark);
+        assert_eq!(dark.get_pixel(0, 1).0, [0, 0, 0, 255]);
+        assert_eq!(dark.get_pixel(98, 1).0, [255, 255, 255, 255]);
+
+        let mut flat = RgbaImage::from_pixel(4, 4, Rgba([30, 30, 30, 255]));
+        apply_auto_levels(&mut flat);
+        assert_eq!(flat.get_pixel(0, 0).0, [30, 30, 30, 255]);
     }
 
     #[test]
@@ -381,9 +973,9 @@ mod tests {
         let result = create_thumbnail(MINIMAL_JPEG, config);
         assert!(result.is_ok());
 
-        if let Ok(hbitmap) = result {
+        if let Ok(thumbnail) = result {
             unsafe {
-                DeleteObject(hbitmap);
+                DeleteObject(thumbnail.bitmap);
             }
         }
     }
@@ -399,9 +991,9 @@ mod tests {
         let result = create_thumbnail(MINIMAL_JPEG, config);
         assert!(result.is_ok());
 
-        if let Ok(hbitmap) = result {
+        if let Ok(thumbnail) = result {
             unsafe {
-                DeleteObject(hbitmap);
+                DeleteObject(thumbnail.bitmap);
             }
         }
     }
@@ -419,9 +1011,9 @@ mod tests {
         let result = create_thumbnail(MINIMAL_JPEG, config);
         assert!(result.is_ok());
 
-        if let Ok(hbitmap) = result {
+        if let Ok(thumbnail) = result {
             unsafe {
-                DeleteObject(hbitmap);
+                DeleteObject(thumbnail.bitmap);
             }
         }
     }
@@ -438,9 +1030,9 @@ mod tests {
         let result = create_thumbnail(MINIMAL_JPEG, config);
         assert!(result.is_ok());
 
-        if let Ok(hbitmap) = result {
+        if let Ok(thumbnail) = result {
             unsafe {
-                DeleteObject(hbitmap);
+                DeleteObject(thumbnail.bitmap);
             }
         }
     }
@@ -0,0 +1,197 @@
+//! Windows Imaging Component (WIC) decode fallback
+//!
+//! WIC decodes every format with a codec installed on the system, which
+//! covers formats the `image` crate features used here don't (HEIC and AVIF
+//! via the Store extensions, JPEG XR, DDS, camera RAW, ...). `decode_image`
+//! tries it only after the built-in decoders fail, when the `wic-fallback`
+//! feature is enabled.
+
+use crate::utils::error::CbxError;
+use image::{DynamicImage, RgbaImage};
+use windows::core::ComInterface;
+use windows::Win32::Foundation::WINCODEC_ERR_COMPONENTNOTFOUND;
+use windows::Win32::Graphics::Imaging::{
+    CLSID_WICImagingFactory, GUID_WICPixelFormat32bppRGBA, IWICBitmapSource, IWICImagingFactory,
+    IWICPalette, WICBitmapDitherTypeNone, WICBitmapInterpolationModeFant, WICBitmapPaletteTypeCustom,
+    WICDecodeMetadataCacheOnDemand,
+};
+use windows::Win32::System::Com::{
+    CoCreateInstance, CoInitializeEx, CoUninitialize, CLSCTX_INPROC_SERVER, COINIT_MULTITHREADED,
+};
+
+type Result<T> = std::result::Result<T, CbxError>;
+
+/// Decode the first frame of an image with WIC, converted to RGBA8
+///
+/// # Returns
+/// * `Ok(DynamicImage)` - Successfully decoded image
+/// * `Err(CbxError::UnsupportedFormat)` - No installed WIC codec recognizes the data
+/// * `Err(CbxError::Windows)` - WIC failed to decode or convert the image
+pub fn decode_wic(data: &[u8]) -> Result<DynamicImage> {
+    decode_wic_frame(data, None)
+}
+
+/// Decode the first frame of an image with WIC, scaled to `width` x `height`
+///
+/// The scaler asks the codec for a reduced decode first; WIC's JPEG codec
+/// implements it by DCT scaling, so a size from `decoder::decode_image_scaled`
+/// (the image divided by 2, 4 or 8) is decoded directly at that size.
+pub fn decode_wic_scaled(data: &[u8], width: u32, height: u32) -> Result<DynamicImage> {
+    decode_wic_frame(data, Some((width, height)))
+}
+
+fn decode_wic_frame(data: &[u8], size: Option<(u32, u32)>) -> Result<DynamicImage> {
+    let _com = ComGuard::init();
+
+    unsafe {
+        let factory: IWICImagingFactory =
+            CoCreateInstance(&CLSID_WICImagingFactory, None, CLSCTX_INPROC_SERVER)?;
+
+        // The stream reads `data` in place; it is dropped before `data` goes away
+        let stream = factory.CreateStream()?;
+        stream.InitializeFromMemory(data)?;
+
+        let decoder = match factory.CreateDecoderFromStream(
+            &stream,
+            std::ptr::null(),
+            WICDecodeMetadataCacheOnDemand,
+        ) {
+            Ok(decoder) => decoder,
+            Err(e) if e.code() == WINCODEC_ERR_COMPONENTNOTFOUND => {
+                return Err(CbxError::UnsupportedFormat(
+                    "No WIC codec for this image".to_string(),
+                ))
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let frame = decoder.GetFrame(0)?;
+        let source: IWICBitmapSource = match size {
+            Some((width, height)) => {
+                let scaler = factory.CreateBitmapScaler()?;
+                scaler.Initialize(&frame, width, height, WICBitmapInterpolationModeFant)?;
+                scaler.cast()?
+            }
+            None => frame.cast()?,
+        };
+
+        let converter = factory.CreateFormatConverter()?;
+        converter.Initialize(
+            &source,
+            &GUID_WICPixelFormat32bppRGBA,
+            WICBitmapDitherTypeNone,
+            None::<&IWICPalette>,
+            0.0,
+            WICBitmapPaletteTypeCustom,
+        )?;
+
+        let (mut width, mut height) = (0u32, 0u32);
+        converter.GetSize(&mut width, &mut height)?;
+        if width == 0 || height == 0 {
+            return Err(CbxError::Image("Invalid image dimensions (0x0)".to_string()));
+        }
+
+        let stride = width
+            .checked_mul(4)
+            .ok_or_else(|| CbxError::Image(format!("Image too large: {}x{}", width, height)))?;
+        let mut pixels = vec![0u8; stride as usize * height as usize];
+        converter.CopyPixels(std::ptr::null(), stride, &mut pixels)?;
+
+        let image = RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| CbxError::Image("WIC pixel buffer size mismatch".to_string()))?;
+        Ok(DynamicImage::ImageRgba8(image))
+    }
+}
+
+/// Initializes COM for the current thread, uninitializing on drop if it did
+///
+/// Decode workers have no COM apartment of their own; Explorer threads
+/// already do (`RPC_E_CHANGED_MODE` / `S_FALSE`), which is fine for WIC.
+struct ComGuard {
+    initialized: bool,
+}
+
+impl ComGuard {
+    fn init() -> Self {
+        let initialized = unsafe { CoInitializeEx(None, COINIT_MULTITHREADED) }.is_ok();
+        Self { initialized }
+    }
+}
+
+impl Drop for ComGuard {
+    fn drop(&mut self) {
+        if self.initialized {
+            unsafe { CoUninitialize() };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 4x4 DDS with one red DXT1 (BC1) block
+    ///
+    /// WIC ships a DDS codec (Windows 8+); the `image` crate's DDS support
+    /// isn't enabled in this build.
+    fn dds_red_4x4() -> Vec<u8> {
+        let mut dds = b"DDS ".to_vec();
+        let header: [u32; 31] = [
+            124,     // dwSize
+            0x81007, // CAPS | HEIGHT | WIDTH | PIXELFORMAT | LINEARSIZE
+            4,       // dwHeight
+            4,       // dwWidth
+            8,       // dwPitchOrLinearSize (one BC1 block)
+            0, 0,    // dwDepth, dwMipMapCount
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // dwReserved1
+            32,      // ddspf.dwSize
+            0x4,     // ddspf.dwFlags (FOURCC)
+            u32::from_le_bytes(*b"DXT1"),
+            0, 0, 0, 0, 0, // ddspf bit count and masks
+            0x1000,  // dwCaps (TEXTURE)
+            0, 0, 0, 0, // dwCaps2..4, dwReserved2
+        ];
+        for value in header {
+            dds.extend_from_slice(&value.to_le_bytes());
+        }
+        // color0 = color1 = red (RGB565), all indices 0
+        dds.extend_from_slice(&[0x00, 0xF8, 0x00, 0xF8, 0, 0, 0, 0]);
+        dds
+    }
+
+    #[test]
+    fn test_decode_wic_only_format() {
+        let dds = dds_red_4x4();
+        assert!(image::load_from_memory(&dds).is_err());
+
+        let img = match decode_wic(&dds) {
+            Ok(img) => img.to_rgba8(),
+            // No DDS codec installed on this system
+            Err(CbxError::UnsupportedFormat(_)) => return,
+            Err(e) => panic!("WIC decode failed: {}", e),
+        };
+
+        assert_eq!(img.dimensions(), (4, 4));
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(3, 3).0, [255, 0, 0, 255]);
+
+        // `decode_image` reaches the same decoder once its own decoders fail
+        let fallback = crate::image_processor::decoder::decode_image(&dds).unwrap();
+        assert_eq!(fallback.to_rgba8(), img);
+    }
+
+    #[test]
+    fn test_decode_wic_scaled_jpeg() {
+        let img = image::RgbImage::from_pixel(64, 48, image::Rgb([0, 0, 255]));
+        let mut jpeg = Vec::new();
+        img.write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+
+        let scaled = decode_wic_scaled(&jpeg, 16, 12).unwrap();
+        assert_eq!((scaled.width(), scaled.height()), (16, 12));
+    }
+
+    #[test]
+    fn test_decode_wic_unknown_data() {
+        assert!(decode_wic(b"definitely not an image").is_err());
+    }
+}
@@ -16,12 +16,28 @@ use std::sync::atomic::{AtomicU32, Ordering};
 
 pub mod com;
 mod archive;
+mod clsid;
 mod image_processor;
+pub mod prelude;
+pub mod registration;
 pub mod registry;
 mod utils;
 
 pub use com::CBXShell;
 pub use utils::error::CbxError;
+pub use archive::{apply_config, read_config, read_no_sort_setting, CbxConfig, CoverStrategy, OpenStrategy};
+pub use archive::{embed_cover_cache, CoverOptions, COVER_CACHE_ENTRY, COVER_OVERRIDE_ENTRY};
+pub use archive::{extract_cover_image, extract_cover_image_from_stream, preview_cover, CoverPreview};
+pub use archive::{render_thumbnail, RenderedThumbnail};
+pub use utils::thread_pool::{init_thread_pool, is_thread_pool_initialized};
+pub use utils::file::cache_key;
+pub use utils::debug_log::{log_path, set_debug_logging};
+pub use image_processor::encode::{create_thumbnails_multi, encode_cover_png, encode_cover_under};
+pub use image_processor::decoder::{decode_into_rgba, estimated_decoded_bytes};
+pub use archive::{detect_archive_type_from_bytes_detailed, detect_archive_type_or_extension, type_mismatch_count};
+pub use archive::{probe, ArchiveType, Probe};
+pub use image_processor::magic::{detect_image_format_detailed, ImageFormat};
+pub use utils::detection::Detection;
 
 /// Global reference count for COM objects
 /// Used to determine when DLL can be safely unloaded
@@ -58,7 +74,7 @@ pub fn get_dll_module() -> Option<HINSTANCE> {
 pub extern "system" fn DllMain(
     hinst_dll: HINSTANCE,
     fdw_reason: u32,
-    _lpv_reserved: *mut std::ffi::c_void,
+    lpv_reserved: *mut std::ffi::c_void,
 ) -> BOOL {
     const DLL_PROCESS_ATTACH: u32 = 1;
     const DLL_PROCESS_DETACH: u32 = 0;
@@ -85,8 +101,15 @@ pub extern "system" fn DllMain(
             TRUE
         }
         DLL_PROCESS_DETACH => {
-            tracing::info!("CBXShell DLL unloaded");
-            utils::debug_log::debug_log("===== DLL_PROCESS_DETACH - CBXShell DLL unloaded =====");
+            // A non-null `lpv_reserved` means the process is terminating: other
+            // threads are already gone, possibly holding locks, so touch nothing
+            if lpv_reserved.is_null() {
+                tracing::info!("CBXShell DLL unloaded");
+                // Last thing the DLL does; never blocks under the loader lock
+                utils::debug_log::shutdown_debug_log(Some(
+                    "===== DLL_PROCESS_DETACH - CBXShell DLL unloaded =====",
+                ));
+            }
             TRUE
         }
         _ => TRUE,
@@ -97,8 +120,17 @@ pub extern "system" fn DllMain(
 ///
 /// Determines whether the DLL can be unloaded from memory
 /// Returns S_OK if no objects are in use, S_FALSE otherwise
+///
+/// The worker pool's threads run the DLL's code and are never stopped, so
+/// once the pool is running the DLL stays loaded.
 #[no_mangle]
 pub extern "system" fn DllCanUnloadNow() -> HRESULT {
+    if utils::thread_pool::is_thread_pool_initialized() {
+        tracing::debug!("DllCanUnloadNow: S_FALSE (worker threads running)");
+        utils::debug_log::debug_log("DllCanUnloadNow: S_FALSE (worker threads running)");
+        return S_FALSE;
+    }
+
     let ref_count = get_dll_ref_count();
     if ref_count == 0 {
         tracing::debug!("DllCanUnloadNow: S_OK (ref count = 0)");
@@ -120,6 +152,7 @@ pub extern "system" fn DllGetClassObject(
     riid: *const GUID,
     ppv: *mut *mut std::ffi::c_void,
 ) -> HRESULT {
+    utils::debug_log::init_debug_logging();
     utils::debug_log::debug_log("===== DllGetClassObject CALLED =====");
 
     // UNAVOIDABLE UNSAFE: Dereferencing COM raw pointers for logging
@@ -4,6 +4,10 @@
 ///!
 ///! Built with egui for a clean, modern interface
 
+#[path = "../clsid.rs"]
+#[allow(dead_code)] // Shared with the DLL; the manager only needs the strings
+mod clsid;
+mod preview;
 mod state;
 mod registry_ops;
 mod ui;
@@ -12,7 +16,7 @@ mod utils;
 fn main() -> Result<(), eframe::Error> {
     let options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
-            .with_inner_size([360.0, 370.0])
+            .with_inner_size([360.0, 450.0])
             .with_resizable(false)
             .with_title("CBXShell Manager"),
         ..Default::default()
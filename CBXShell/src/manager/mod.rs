@@ -1,5 +1,6 @@
 ///! CBXManager - Configuration utility modules
 
+pub mod preview;
 pub mod state;
 pub mod registry_ops;
 pub mod ui;
@@ -0,0 +1,150 @@
+//! Cover preview for the Tools menu and the "Test thumbnail" button
+//!
+//! Runs the shell extension's own cover selection on a file the user picks
+//! and converts the result into RGBA pixels egui can upload as a texture.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+/// Longest side of the preview image in pixels
+pub const PREVIEW_SIZE: u32 = 192;
+
+/// The cover Explorer would show for a file, ready for display
+pub struct CoverPreview {
+    /// Archive entry chosen as the cover
+    pub entry_name: String,
+    /// Width and height of `rgba`
+    pub size: [usize; 2],
+    /// Unmultiplied RGBA pixels, row by row
+    pub rgba: Vec<u8>,
+}
+
+/// Choose and decode the cover of `path` with the saved settings
+///
+/// Images larger than `PREVIEW_SIZE` are scaled down, keeping the aspect ratio.
+pub fn load_preview(path: &Path) -> anyhow::Result<CoverPreview> {
+    let cover = cbxshell::preview_cover(path)?;
+
+    let image = if cover.image.width() > PREVIEW_SIZE || cover.image.height() > PREVIEW_SIZE {
+        cover.image.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE)
+    } else {
+        cover.image
+    };
+    let rgba = image.into_rgba8();
+
+    Ok(CoverPreview {
+        entry_name: cover.entry_name,
+        size: [rgba.width() as usize, rgba.height() as usize],
+        rgba: rgba.into_raw(),
+    })
+}
+
+/// Size requested for a test thumbnail (Explorer's "Large icons")
+pub const TEST_THUMBNAIL_SIZE: u32 = 256;
+
+/// A thumbnail rendered by the extension for a file the user picked
+pub struct TestThumbnail {
+    /// Detected archive type, e.g. "RAR"
+    pub archive_type: &'static str,
+    /// Archive entry the thumbnail was made from
+    pub entry_name: String,
+    /// Width and height of `rgba`
+    pub size: [usize; 2],
+    /// Unmultiplied RGBA pixels, row by row
+    pub rgba: Vec<u8>,
+}
+
+/// Render the thumbnail of `path` like the extension does for its first image
+pub fn render_test_thumbnail(path: &Path, sort: bool) -> anyhow::Result<TestThumbnail> {
+    let thumbnail = cbxshell::render_thumbnail(path, TEST_THUMBNAIL_SIZE, sort)?;
+
+    Ok(TestThumbnail {
+        archive_type: thumbnail.archive_type.as_str(),
+        entry_name: thumbnail.entry_name,
+        size: [thumbnail.width as usize, thumbnail.height as usize],
+        rgba: thumbnail.rgba,
+    })
+}
+
+/// Run `render_test_thumbnail` on a worker thread
+///
+/// Large or broken archives can take seconds to decode; the UI polls the
+/// returned channel instead of blocking. The sender is dropped without a
+/// result only if the worker panics.
+pub fn spawn_test_thumbnail(path: PathBuf, sort: bool) -> mpsc::Receiver<anyhow::Result<TestThumbnail>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        // The UI may have stopped waiting; nothing to report then
+        let _ = sender.send(render_test_thumbnail(&path, sort));
+    });
+    receiver
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut data = Vec::new();
+        image::DynamicImage::new_rgb8(width, height)
+            .write_to(&mut Cursor::new(&mut data), image::ImageFormat::Png)
+            .unwrap();
+        data
+    }
+
+    #[test]
+    fn test_load_preview() {
+        let path = std::env::temp_dir().join("cbxmanager_preview_test.cbz");
+        {
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+            let options = zip::write::FileOptions::default();
+            zip.start_file("page01.png", options).unwrap();
+            zip.write_all(&png(400, 600)).unwrap();
+            zip.start_file("page02.png", options).unwrap();
+            zip.write_all(&png(10, 10)).unwrap();
+            zip.finish().unwrap();
+        }
+
+        let preview = load_preview(&path);
+        std::fs::remove_file(&path).ok();
+
+        let preview = preview.unwrap();
+        assert_eq!(preview.entry_name, "page01.png");
+        assert_eq!(preview.size, [128, 192]);
+        assert_eq!(preview.rgba.len(), 128 * 192 * 4);
+    }
+
+    #[test]
+    fn test_load_preview_missing_file() {
+        assert!(load_preview(Path::new("does_not_exist.cbz")).is_err());
+    }
+
+    #[test]
+    fn test_spawn_test_thumbnail() {
+        let path = std::env::temp_dir().join("cbxmanager_test_thumbnail.cbz");
+        {
+            let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+            let options = zip::write::FileOptions::default();
+            zip.start_file("b.png", options).unwrap();
+            zip.write_all(&png(10, 10)).unwrap();
+            zip.start_file("a.png", options).unwrap();
+            zip.write_all(&png(600, 300)).unwrap();
+            zip.finish().unwrap();
+        }
+
+        let result = spawn_test_thumbnail(path.clone(), true).recv_timeout(std::time::Duration::from_secs(30));
+        std::fs::remove_file(&path).ok();
+
+        let thumbnail = result.unwrap().unwrap();
+        assert_eq!(thumbnail.archive_type, "ZIP");
+        assert_eq!(thumbnail.entry_name, "a.png");
+        assert_eq!(thumbnail.size, [256, 128]);
+        assert_eq!(thumbnail.rgba.len(), 256 * 128 * 4);
+    }
+
+    #[test]
+    fn test_render_test_thumbnail_unsupported_file() {
+        assert!(render_test_thumbnail(Path::new("notes.txt"), false).is_err());
+    }
+}
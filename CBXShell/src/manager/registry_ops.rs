@@ -1,24 +1,18 @@
 ///! Registry operations for CBXManager
 ///!
 ///! Read and write configuration from/to Windows registry
+///!
+///! Writes are planned as `RegistryOp` lists (see `cbxshell::registration`)
+///! and executed by `apply`, so the plans are testable without a registry.
 
+use super::clsid::{CLSID_STR, CONFIG_KEY_PATH};
 use super::state::AppState;
 use anyhow::{Context, Result};
+use cbxshell::registration::{self, RegistryOp, IID_IQUERYINFO, IID_ITHUMBNAILPROVIDER};
+use cbxshell::CbxError;
 use winreg::RegKey;
 use winreg::enums::*;
 
-/// CBXShell CLSID string
-const CLSID_STR: &str = "{9E6ECB90-5A61-42BD-B851-D3297D9C7F39}";
-
-/// Configuration registry path
-const CONFIG_KEY_PATH: &str = "Software\\CBXShell-rs\\{9E6ECB90-5A61-42BD-B851-D3297D9C7F39}";
-
-/// IThumbnailProvider interface GUID
-const IID_ITHUMBNAILPROVIDER: &str = "{E357FCCD-A995-4576-B01F-234630154E96}";
-
-/// IQueryInfo interface GUID (tooltips)
-const IID_IQUERYINFO: &str = "{00021500-0000-0000-C000-000000000046}";
-
 /// Read current application state from registry
 pub fn read_app_state() -> Result<AppState> {
     let mut state = AppState::default();
@@ -29,7 +23,10 @@ pub fn read_app_state() -> Result<AppState> {
     // 2. Read sort setting
     state.sort_enabled = read_sort_setting()?;
 
-    // 3. Check each extension's handler registration
+    // 3. Read debug logging setting
+    state.debug_logging = cbxshell::read_config().debug_logging;
+
+    // 4. Check each extension's handler registration
     for ext_config in &mut state.extensions {
         let (thumbnail, infotip) = check_extension_handlers(&ext_config.extension)?;
         ext_config.thumbnail_enabled = thumbnail;
@@ -41,27 +38,58 @@ pub fn read_app_state() -> Result<AppState> {
 
 /// Write application state to registry
 pub fn write_app_state(state: &AppState) -> Result<()> {
-    // 1. Write sort setting
-    write_sort_setting(state.sort_enabled)?;
+    apply(&app_state_ops(state))
+}
 
-    // 2. Update extension handlers
+/// The registry changes that store `state`
+///
+/// The sort and debug logging settings first, then each extension's
+/// handlers in list order.
+pub fn app_state_ops(state: &AppState) -> Vec<RegistryOp> {
+    let mut ops = vec![
+        sort_setting_op(state.sort_enabled),
+        debug_logging_op(state.debug_logging),
+    ];
     for ext_config in &state.extensions {
-        set_extension_handlers(
+        ops.extend(registration::extension_ops(
             &ext_config.extension,
             ext_config.thumbnail_enabled,
             ext_config.infotip_enabled,
-        )?;
+        ));
     }
+    ops
+}
+
+/// Execute planned registry changes
+///
+/// The `CbxError` stays the error's source (see `error_message`).
+fn apply(ops: &[RegistryOp]) -> Result<()> {
+    cbxshell::registry::apply_ops(ops).context("Failed to update registry")
+}
 
-    Ok(())
+/// User-facing text for a failed registry operation
+///
+/// Access denied (e.g. a key locked by group policy) gets a hint to run the
+/// manager as administrator instead of the raw OS error alone.
+pub fn error_message(error: &anyhow::Error) -> String {
+    let denied = error
+        .downcast_ref::<CbxError>()
+        .is_some_and(CbxError::is_permission_denied);
+
+    if denied {
+        format!(
+            "Insufficient permissions to change the registry. Try running CBXManager as administrator.\n\n{:#}",
+            error
+        )
+    } else {
+        format!("{:#}", error)
+    }
 }
 
 /// Check if the DLL is registered as a COM server
 pub fn check_dll_registration() -> bool {
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let clsid_path = format!("Software\\Classes\\CLSID\\{}", CLSID_STR);
-
-    hkcu.open_subkey(clsid_path).is_ok()
+    hkcu.open_subkey(registration::clsid_key_path()).is_ok()
 }
 
 /// Check if handlers are registered for an extension
@@ -69,10 +97,9 @@ pub fn check_dll_registration() -> bool {
 /// Returns (thumbnail_enabled, infotip_enabled)
 pub fn check_extension_handlers(extension: &str) -> Result<(bool, bool)> {
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let base_path = format!("Software\\Classes\\{}\\shellex", extension);
 
     // Check thumbnail handler
-    let thumbnail_path = format!("{}\\{}", base_path, IID_ITHUMBNAILPROVIDER);
+    let thumbnail_path = registration::handler_key_path(extension, IID_ITHUMBNAILPROVIDER);
     let thumbnail_enabled = if let Ok(key) = hkcu.open_subkey(&thumbnail_path) {
         // Check if the default value matches our CLSID
         match key.get_value::<String, _>("") {
@@ -84,7 +111,7 @@ pub fn check_extension_handlers(extension: &str) -> Result<(bool, bool)> {
     };
 
     // Check infotip handler
-    let infotip_path = format!("{}\\{}", base_path, IID_IQUERYINFO);
+    let infotip_path = registration::handler_key_path(extension, IID_IQUERYINFO);
     let infotip_enabled = if let Ok(key) = hkcu.open_subkey(&infotip_path) {
         match key.get_value::<String, _>("") {
             Ok(value) => value == CLSID_STR,
@@ -97,84 +124,31 @@ pub fn check_extension_handlers(extension: &str) -> Result<(bool, bool)> {
     Ok((thumbnail_enabled, infotip_enabled))
 }
 
-/// Set handlers for an extension
-fn set_extension_handlers(extension: &str, thumbnail: bool, infotip: bool) -> Result<()> {
-    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let base_path = format!("Software\\Classes\\{}", extension);
-
-    // Ensure base extension key exists with PerceivedType
-    let (ext_key, _) = hkcu
-        .create_subkey(&base_path)
-        .context("Failed to create extension key")?;
-    ext_key.set_value("PerceivedType", &"image")
-        .context("Failed to set PerceivedType")?;
-
-    // Create shellex key
-    let shellex_path = format!("{}\\shellex", base_path);
-    hkcu.create_subkey(&shellex_path)
-        .context("Failed to create shellex key")?;
-
-    // Handle thumbnail provider
-    let thumbnail_path = format!("{}\\shellex\\{}", base_path, IID_ITHUMBNAILPROVIDER);
-    if thumbnail {
-        let (thumb_key, _) = hkcu
-            .create_subkey(&thumbnail_path)
-            .context("Failed to create thumbnail key")?;
-        thumb_key.set_value("", &CLSID_STR)
-            .context("Failed to set thumbnail CLSID")?;
-    } else {
-        // Remove thumbnail handler
-        let _ = hkcu.delete_subkey_all(&thumbnail_path);
-    }
-
-    // Handle infotip provider
-    let infotip_path = format!("{}\\shellex\\{}", base_path, IID_IQUERYINFO);
-    if infotip {
-        let (info_key, _) = hkcu
-            .create_subkey(&infotip_path)
-            .context("Failed to create infotip key")?;
-        info_key.set_value("", &CLSID_STR)
-            .context("Failed to set infotip CLSID")?;
-    } else {
-        // Remove infotip handler
-        let _ = hkcu.delete_subkey_all(&infotip_path);
-    }
-
-    Ok(())
-}
-
 /// Read the sorting preference from registry
 fn read_sort_setting() -> Result<bool> {
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
 
     match hkcu.open_subkey(CONFIG_KEY_PATH) {
-        Ok(key) => {
-            match key.get_value::<u32, _>("NoSort") {
-                Ok(value) => Ok(value == 0),  // NoSort=0 means sort enabled
-                Err(_) => Ok(false),  // Default: sorting disabled (NoSort=1) for better performance
-            }
-        }
+        // NoSort=0 means sort enabled; DWORD and legacy REG_SZ values are both accepted
+        Ok(key) => Ok(cbxshell::read_no_sort_setting(&key).unwrap_or(false)),
         Err(_) => Ok(false),  // Default: sorting disabled (NoSort=1) for better performance
     }
 }
 
-/// Write the sorting preference to registry
-fn write_sort_setting(sort_enabled: bool) -> Result<()> {
-    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-    let (key, _) = hkcu
-        .create_subkey(CONFIG_KEY_PATH)
-        .context("Failed to create config key")?;
-
+/// Store the sorting preference as `NoSort` (DWORD, 0 = sort)
+fn sort_setting_op(sort_enabled: bool) -> RegistryOp {
     let no_sort_value: u32 = if sort_enabled { 0 } else { 1 };
-    key.set_value("NoSort", &no_sort_value)
-        .context("Failed to set NoSort value")?;
+    RegistryOp::set_dword(CONFIG_KEY_PATH, "NoSort", no_sort_value)
+}
 
-    Ok(())
+/// Store the debug logging preference as `DebugLogging` (DWORD, 1 = on)
+fn debug_logging_op(enabled: bool) -> RegistryOp {
+    RegistryOp::set_dword(CONFIG_KEY_PATH, "DebugLogging", enabled as u32)
 }
 
 /// Register the DLL as a COM server
 ///
-/// This function calls the library's register_server function directly.
+/// Applies the library's registration plan for the DLL next to the manager.
 /// Since the binary and library are in the same crate but use different module roots,
 /// we need to access it through cbxshell:: path.
 pub fn register_dll() -> Result<()> {
@@ -197,14 +171,14 @@ pub fn register_dll() -> Result<()> {
     let dll_path_str = dll_path.to_str()
         .context("Failed to convert DLL path to string")?;
 
-    cbxshell::registry::register_server(Some(dll_path_str))
-        .map_err(|e| anyhow::anyhow!("DLL registration failed: {}", e))
+    cbxshell::registry::apply_ops(&registration::register_server_ops(dll_path_str))
+        .context("DLL registration failed")
 }
 
 /// Unregister the DLL as a COM server
 pub fn unregister_dll() -> Result<()> {
-    cbxshell::registry::unregister_server()
-        .map_err(|e| anyhow::anyhow!("DLL unregistration failed: {}", e))
+    cbxshell::registry::apply_ops(&registration::unregister_server_ops())
+        .context("DLL unregistration failed")
 }
 
 #[cfg(test)]
@@ -246,23 +220,79 @@ mod tests {
         assert!(result.is_ok());
 
         let state = result.unwrap();
-        assert_eq!(state.extensions.len(), 6);
+        assert_eq!(state.extensions.len(), 7);
+    }
+
+    #[test]
+    fn test_app_state_ops() {
+        let mut state = AppState {
+            sort_enabled: true,
+            debug_logging: true,
+            ..Default::default()
+        };
+        state.get_extension_mut(".cbz").unwrap().thumbnail_enabled = true;
+
+        let ops = app_state_ops(&state);
+        assert_eq!(ops.len(), 2 + 3 * state.extensions.len());
+        assert_eq!(ops[0], RegistryOp::set_dword(CONFIG_KEY_PATH, "NoSort", 0));
+        assert_eq!(ops[1], RegistryOp::set_dword(CONFIG_KEY_PATH, "DebugLogging", 1));
+        assert_eq!(ops[2..5], registration::extension_ops(".cbz", true, false)[..]);
+        assert_eq!(
+            ops[3],
+            RegistryOp::set_string(
+                format!("Software\\Classes\\.cbz\\shellex\\{}", IID_ITHUMBNAILPROVIDER),
+                "",
+                CLSID_STR
+            )
+        );
+
+        // Disabled handlers are removed
+        let cbr_infotip = format!("Software\\Classes\\.cbr\\shellex\\{}", IID_IQUERYINFO);
+        assert!(ops.contains(&RegistryOp::delete_tree(cbr_infotip)));
+    }
+
+    #[test]
+    fn test_sort_setting_op() {
+        assert_eq!(sort_setting_op(false), RegistryOp::set_dword(CONFIG_KEY_PATH, "NoSort", 1));
     }
 
     #[test]
     fn test_write_and_read_sort_setting() {
         // Try to write and read back (may fail without permissions)
-        if write_sort_setting(true).is_ok() {
+        if apply(&[sort_setting_op(true)]).is_ok() {
             let result = read_sort_setting().unwrap();
             assert_eq!(result, true);
         }
 
-        if write_sort_setting(false).is_ok() {
+        if apply(&[sort_setting_op(false)]).is_ok() {
             let result = read_sort_setting().unwrap();
             assert_eq!(result, false);
         }
 
         // Cleanup: restore to default
-        let _ = write_sort_setting(true);
+        let _ = apply(&[sort_setting_op(true)]);
+    }
+
+    #[test]
+    fn test_error_message_permission_denied() {
+        let denied = CbxError::registry_access(
+            "Software\\Classes\\.cbz",
+            std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+        );
+        let error = Err::<(), _>(denied).context("Failed to update registry").unwrap_err();
+
+        let message = error_message(&error);
+        assert!(message.starts_with("Insufficient permissions"), "{}", message);
+        assert!(message.contains("Failed to update registry: Registry error at Software\\Classes\\.cbz"));
+    }
+
+    #[test]
+    fn test_error_message_other_errors() {
+        let error = anyhow::anyhow!("cbxshell.dll not found at: C:\\Tools\\cbxshell.dll");
+        assert_eq!(error_message(&error), "cbxshell.dll not found at: C:\\Tools\\cbxshell.dll");
+
+        let missing = CbxError::registry_access("Software\\Key", std::io::Error::from(std::io::ErrorKind::NotFound));
+        let error = Err::<(), _>(missing).context("DLL unregistration failed").unwrap_err();
+        assert!(!error_message(&error).starts_with("Insufficient permissions"));
     }
 }
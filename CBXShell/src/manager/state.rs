@@ -41,6 +41,8 @@ pub struct AppState {
     pub extensions: Vec<ExtensionConfig>,
     /// Whether alphabetical sorting is enabled (true) or first-found mode (false)
     pub sort_enabled: bool,
+    /// Whether the extension writes its debug log (`DebugLogging`)
+    pub debug_logging: bool,
     /// Whether the DLL is registered as a COM server
     pub dll_registered: bool,
 }
@@ -52,11 +54,13 @@ impl Default for AppState {
                 ExtensionConfig::new(".cbz"),
                 ExtensionConfig::new(".cbr"),
                 ExtensionConfig::new(".zip"),
+                ExtensionConfig::new(".phz"),
                 ExtensionConfig::new(".rar"),
                 ExtensionConfig::new(".7z"),
                 ExtensionConfig::new(".cb7"),
             ],
             sort_enabled: false,  // Default: sort disabled (NoSort=1) for better performance with large archives
+            debug_logging: false,
             dll_registered: false,
         }
     }
@@ -111,8 +115,9 @@ mod tests {
     #[test]
     fn test_app_state_default() {
         let state = AppState::default();
-        assert_eq!(state.extensions.len(), 6);
+        assert_eq!(state.extensions.len(), 7);
         assert!(!state.sort_enabled);  // Default: sort disabled for performance
+        assert!(!state.debug_logging);
         assert!(!state.dll_registered);
         assert!(!state.has_any_handlers_enabled());
     }
@@ -2,22 +2,55 @@
 ///!
 ///! Compact, professional interface with proper alignment and spacing
 
-use super::{registry_ops, state::AppState, utils};
+use super::{preview, registry_ops, state::AppState, utils};
 use eframe::egui;
+use std::sync::mpsc::{Receiver, TryRecvError};
+use std::time::{Duration, Instant};
+
+/// How long the UI waits for a test thumbnail before giving up on it
+const TEST_THUMBNAIL_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct CBXManagerApp {
     state: AppState,
     needs_restart_prompt: bool,
+    preview: PreviewWindow,
+    test_thumbnail: TestThumbnailWindow,
+    /// The About window, with the report gathered when it was opened
+    about: Option<utils::CapabilityReport>,
+}
+
+/// State of the "Preview cover" window
+#[derive(Default)]
+struct PreviewWindow {
+    open: bool,
+    path: String,
+    texture: Option<egui::TextureHandle>,
+    message: String,
+}
+
+/// State of the "Test thumbnail" window
+#[derive(Default)]
+struct TestThumbnailWindow {
+    open: bool,
+    /// Render running on a worker thread, and when it started
+    job: Option<(Receiver<anyhow::Result<preview::TestThumbnail>>, Instant)>,
+    texture: Option<egui::TextureHandle>,
+    message: String,
 }
 
 impl Default for CBXManagerApp {
     fn default() -> Self {
         // Load current state from registry
         let state = registry_ops::read_app_state().unwrap_or_default();
+        // The cover preview runs the extension's code in this process
+        cbxshell::set_debug_logging(state.debug_logging);
 
         Self {
             state,
             needs_restart_prompt: false,
+            preview: PreviewWindow::default(),
+            test_thumbnail: TestThumbnailWindow::default(),
+            about: None,
         }
     }
 }
@@ -33,8 +66,9 @@ impl CBXManagerApp {
         }
 
         if let Err(e) = registry_ops::write_app_state(&self.state) {
-            eprintln!("Failed to save settings: {}", e);
+            utils::show_error("Failed to save settings", &registry_ops::error_message(&e));
         } else {
+            cbxshell::set_debug_logging(self.state.debug_logging);
             self.needs_restart_prompt = true;
         }
     }
@@ -45,9 +79,180 @@ impl CBXManagerApp {
                 self.state = registry_ops::read_app_state().unwrap_or_default();
             }
             Err(e) => {
-                eprintln!("Failed to register DLL: {}", e);
+                utils::show_error("Failed to register DLL", &registry_ops::error_message(&e));
+            }
+        }
+    }
+
+    fn load_preview(&mut self, ctx: &egui::Context) {
+        let path = std::path::PathBuf::from(self.preview.path.trim());
+        match preview::load_preview(&path) {
+            Ok(cover) => {
+                let image = egui::ColorImage::from_rgba_unmultiplied(cover.size, &cover.rgba);
+                self.preview.texture = Some(ctx.load_texture("cover_preview", image, Default::default()));
+                self.preview.message = format!("Cover: {}", cover.entry_name);
+            }
+            Err(e) => {
+                self.preview.texture = None;
+                self.preview.message = format!("No thumbnail: {}", e);
+            }
+        }
+    }
+
+    fn show_preview_window(&mut self, ctx: &egui::Context) {
+        // Dropping a file anywhere on the manager previews it
+        if let Some(path) = ctx.input(|i| i.raw.dropped_files.first().and_then(|f| f.path.clone())) {
+            self.preview.path = path.display().to_string();
+            self.preview.open = true;
+            self.load_preview(ctx);
+        }
+
+        let mut open = self.preview.open;
+        let mut load = false;
+        egui::Window::new("Preview cover")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(egui::TextEdit::singleline(&mut self.preview.path).hint_text("Drop a file or enter its path"));
+                    load = ui.button("Preview").clicked();
+                });
+                ui.label(
+                    egui::RichText::new("Uses the saved settings; Apply changes first.")
+                        .small()
+                        .color(egui::Color32::GRAY),
+                );
+                ui.add_space(4.0);
+
+                if let Some(texture) = &self.preview.texture {
+                    ui.image((texture.id(), texture.size_vec2()));
+                }
+                if !self.preview.message.is_empty() {
+                    ui.label(&self.preview.message);
+                }
+            });
+        self.preview.open = open;
+
+        if load {
+            self.load_preview(ctx);
+        }
+    }
+
+    fn show_about_window(&mut self, ctx: &egui::Context) {
+        let Some(report) = &self.about else {
+            return;
+        };
+
+        let mut open = true;
+        egui::Window::new("About CBXShell")
+            .open(&mut open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                egui::Grid::new("about_grid").num_columns(2).spacing([12.0, 4.0]).show(ui, |ui| {
+                    let mut row = |label: &str, value: String| {
+                        ui.label(egui::RichText::new(label).strong());
+                        ui.label(value);
+                        ui.end_row();
+                    };
+                    row("Version", report.version.to_string());
+                    row("Build date", report.build_date.to_string());
+                    row("Archives", report.archive_formats.join(", "));
+                    row("CLSID", report.clsid.to_string());
+                    row("Log file", report.log_path.display().to_string());
+                });
+
+                ui.add_space(4.0);
+                ui.label(egui::RichText::new("Image formats").strong());
+                ui.horizontal_wrapped(|ui| {
+                    for (name, supported) in &report.image_formats {
+                        let color = if *supported { egui::Color32::DARK_GREEN } else { egui::Color32::GRAY };
+                        ui.label(egui::RichText::new(*name).color(color));
+                    }
+                });
+
+                ui.add_space(4.0);
+                if ui.button("Copy").clicked() {
+                    ui.output_mut(|o| o.copied_text = report.lines().join("\n"));
+                }
+            });
+
+        if !open {
+            self.about = None;
+        }
+    }
+
+    fn start_test_thumbnail(&mut self) {
+        let Some(path) = utils::pick_archive("Test thumbnail") else {
+            return;
+        };
+
+        let test = &mut self.test_thumbnail;
+        test.open = true;
+        test.texture = None;
+        test.message = format!("Rendering {}...", path.display());
+        test.job = Some((preview::spawn_test_thumbnail(path, self.state.sort_enabled), Instant::now()));
+    }
+
+    fn poll_test_thumbnail(&mut self, ctx: &egui::Context) {
+        let test = &mut self.test_thumbnail;
+        let Some((receiver, started)) = &test.job else {
+            return;
+        };
+
+        match receiver.try_recv() {
+            Ok(Ok(thumbnail)) => {
+                let image = egui::ColorImage::from_rgba_unmultiplied(thumbnail.size, &thumbnail.rgba);
+                test.texture = Some(ctx.load_texture("test_thumbnail", image, Default::default()));
+                test.message = format!(
+                    "{} archive, entry: {}\n{}x{} thumbnail",
+                    thumbnail.archive_type, thumbnail.entry_name, thumbnail.size[0], thumbnail.size[1]
+                );
+            }
+            Ok(Err(e)) => test.message = format!("Failed: {}", e),
+            Err(TryRecvError::Empty) if started.elapsed() < TEST_THUMBNAIL_TIMEOUT => {
+                ctx.request_repaint_after(Duration::from_millis(100));
+                return;
+            }
+            // The worker keeps running; its result is dropped if it ever finishes
+            Err(TryRecvError::Empty) => {
+                test.message = format!(
+                    "No result after {} seconds; Explorer would likely hang on this file too.",
+                    TEST_THUMBNAIL_TIMEOUT.as_secs()
+                )
             }
+            Err(TryRecvError::Disconnected) => test.message = "Failed: the renderer crashed".to_string(),
         }
+        test.job = None;
+    }
+
+    fn show_test_thumbnail_window(&mut self, ctx: &egui::Context) {
+        self.poll_test_thumbnail(ctx);
+
+        let test = &mut self.test_thumbnail;
+        egui::Window::new("Test thumbnail")
+            .open(&mut test.open)
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if test.job.is_some() {
+                        ui.spinner();
+                    }
+                    ui.label(&test.message);
+                });
+                ui.label(
+                    egui::RichText::new("Uses the first image and the sort setting above; other settings are ignored.")
+                        .small()
+                        .color(egui::Color32::GRAY),
+                );
+
+                if let Some(texture) = &test.texture {
+                    ui.add_space(4.0);
+                    ui.image((texture.id(), texture.size_vec2()));
+                }
+            });
     }
 
     fn unregister_dll(&mut self) {
@@ -57,7 +262,7 @@ impl CBXManagerApp {
                 self.needs_restart_prompt = true;
             }
             Err(e) => {
-                eprintln!("Failed to unregister DLL: {}", e);
+                utils::show_error("Failed to unregister DLL", &registry_ops::error_message(&e));
             }
         }
     }
@@ -78,13 +283,25 @@ impl eframe::App for CBXManagerApp {
                         ui.close_menu();
                     }
                     ui.separator();
+                    if ui.button("Preview cover...").clicked() {
+                        self.preview.open = true;
+                        ui.close_menu();
+                    }
+                    ui.separator();
                     if ui.button("About").clicked() {
+                        self.about = Some(utils::gather_capabilities());
                         ui.close_menu();
                     }
                 });
             });
         });
 
+        self.show_preview_window(ctx);
+        self.show_test_thumbnail_window(ctx);
+        self.show_about_window(ctx);
+
+        let mut test_thumbnail = false;
+
         egui::CentralPanel::default().show(ctx, |ui| {
             // Compact top padding
             ui.add_space(8.0);
@@ -119,7 +336,7 @@ impl eframe::App for CBXManagerApp {
                     ui.label(egui::RichText::new("File types").strong());
                     ui.add_space(4.0);
 
-                    // CBZ + ZIP (tight)
+                    // CBZ + ZIP + PHZ (tight)
                     ui.checkbox(
                         self.state.get_extension_mut(".cbz").map(|e| &mut e.thumbnail_enabled).unwrap(),
                         "CBZ Image Archives",
@@ -128,6 +345,10 @@ impl eframe::App for CBXManagerApp {
                         self.state.get_extension_mut(".zip").map(|e| &mut e.thumbnail_enabled).unwrap(),
                         "ZIP Archives",
                     );
+                    ui.checkbox(
+                        self.state.get_extension_mut(".phz").map(|e| &mut e.thumbnail_enabled).unwrap(),
+                        "PHZ Comic Archives",
+                    );
 
                     ui.add_space(6.0);
 
@@ -175,10 +396,30 @@ impl eframe::App for CBXManagerApp {
                             .small()
                             .color(egui::Color32::GRAY),
                     );
+
+                    ui.add_space(6.0);
+                    ui.checkbox(&mut self.state.debug_logging, "Write debug log");
+                    ui.add_space(2.0);
+                    ui.label(
+                        egui::RichText::new("Logs to %TEMP%\\cbxshell_debug.log, or to the DebugLogPath\nregistry value if set. Slows down thumbnailing.")
+                            .small()
+                            .color(egui::Color32::GRAY),
+                    );
+
+                    ui.add_space(6.0);
+                    let idle = self.test_thumbnail.job.is_none();
+                    test_thumbnail = ui
+                        .add_enabled(idle, egui::Button::new("Test thumbnail..."))
+                        .on_hover_text("Render a file's thumbnail without going through Explorer")
+                        .clicked();
                         });
                     });
             });
 
+            if test_thumbnail {
+                self.start_test_thumbnail();
+            }
+
             ui.add_space(12.0);
 
             // Buttons - right aligned
@@ -69,7 +69,6 @@ pub fn show_success(title: &str, message: &str) {
 }
 
 /// Show error message
-#[allow(dead_code)]
 pub fn show_error(title: &str, message: &str) {
     let title_wide = format!("{}\0", title).encode_utf16().collect::<Vec<_>>();
     let message_wide = format!("{}\0", message).encode_utf16().collect::<Vec<_>>();
@@ -83,3 +82,131 @@ pub fn show_error(title: &str, message: &str) {
         );
     }
 }
+
+/// Support information shown by the About dialog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CapabilityReport {
+    pub version: &'static str,
+    /// UTC date of the build (see build.rs)
+    pub build_date: &'static str,
+    /// Archive formats the extension opens
+    pub archive_formats: Vec<&'static str>,
+    /// Image formats with whether a decoder is compiled in
+    pub image_formats: Vec<(&'static str, bool)>,
+    /// CLSID the manager registers the extension under
+    pub clsid: &'static str,
+    /// Debug log file (used while debug logging is on)
+    pub log_path: std::path::PathBuf,
+}
+
+impl CapabilityReport {
+    /// The report as "label: value" lines, for copying into a bug report
+    pub fn lines(&self) -> Vec<String> {
+        let supported: Vec<&str> = self.image_formats.iter().filter(|(_, s)| *s).map(|(name, _)| *name).collect();
+        let unsupported: Vec<&str> = self.image_formats.iter().filter(|(_, s)| !*s).map(|(name, _)| *name).collect();
+
+        let mut lines = vec![
+            format!("Version: {}", self.version),
+            format!("Build date: {}", self.build_date),
+            format!("Archives: {}", self.archive_formats.join(", ")),
+            format!("Images: {}", supported.join(", ")),
+        ];
+        if !unsupported.is_empty() {
+            lines.push(format!("Not compiled in: {}", unsupported.join(", ")));
+        }
+        lines.push(format!("CLSID: {}", self.clsid));
+        lines.push(format!("Log file: {}", self.log_path.display()));
+        lines
+    }
+}
+
+/// Collect the version, build and format support of this build
+pub fn gather_capabilities() -> CapabilityReport {
+    CapabilityReport {
+        version: env!("CARGO_PKG_VERSION"),
+        build_date: env!("CBXSHELL_BUILD_DATE"),
+        archive_formats: cbxshell::ArchiveType::ALL.iter().map(|t| t.as_str()).collect(),
+        image_formats: cbxshell::ImageFormat::ALL
+            .iter()
+            .map(|f| (f.as_str(), f.is_supported()))
+            .collect(),
+        clsid: super::clsid::CLSID_STR,
+        log_path: cbxshell::log_path().to_path_buf(),
+    }
+}
+
+/// Archive extensions offered by `pick_archive`'s default filter
+const ARCHIVE_FILTER: &str = "*.cbz;*.zip;*.phz;*.epub;*.cbr;*.rar;*.cb7;*.7z;*.cbt;*.tar";
+
+/// Ask the user for an archive with the common "Open" dialog
+///
+/// Returns `None` if the dialog is cancelled or can't be shown.
+pub fn pick_archive(title: &str) -> Option<std::path::PathBuf> {
+    use windows::core::{HSTRING, PCWSTR};
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CoTaskMemFree, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+    };
+    use windows::Win32::UI::Shell::Common::COMDLG_FILTERSPEC;
+    use windows::Win32::UI::Shell::{FileOpenDialog, IFileOpenDialog, SIGDN_FILESYSPATH};
+
+    let title = HSTRING::from(title);
+    let archives_name = HSTRING::from("Archives");
+    let archives_spec = HSTRING::from(ARCHIVE_FILTER);
+    let all_name = HSTRING::from("All files");
+    let all_spec = HSTRING::from("*.*");
+    let filters = [
+        COMDLG_FILTERSPEC {
+            pszName: PCWSTR(archives_name.as_ptr()),
+            pszSpec: PCWSTR(archives_spec.as_ptr()),
+        },
+        COMDLG_FILTERSPEC {
+            pszName: PCWSTR(all_name.as_ptr()),
+            pszSpec: PCWSTR(all_spec.as_ptr()),
+        },
+    ];
+
+    // UNAVOIDABLE UNSAFE: the common item dialog is a COM object
+    // Safety guarantees:
+    // - COM is initialized on this (UI) thread before the dialog is created;
+    //   an already initialized apartment is fine
+    // - The filter strings outlive the dialog calls that read them
+    // - The path returned by GetDisplayName is copied, then freed once
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+
+        let dialog: IFileOpenDialog = CoCreateInstance(&FileOpenDialog, None, CLSCTX_INPROC_SERVER).ok()?;
+        dialog.SetTitle(&title).ok()?;
+        dialog.SetFileTypes(&filters).ok()?;
+        // Cancelling is reported as an error
+        dialog.Show(HWND::default()).ok()?;
+
+        let name = dialog.GetResult().ok()?.GetDisplayName(SIGDN_FILESYSPATH).ok()?;
+        let path = name.to_string();
+        CoTaskMemFree(Some(name.0 as *const std::ffi::c_void));
+        path.ok().map(std::path::PathBuf::from)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gather_capabilities() {
+        let report = gather_capabilities();
+        assert_eq!(report.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(report.build_date.len(), "YYYY-MM-DD".len());
+        assert_eq!(report.archive_formats, vec!["ZIP", "RAR", "7-Zip", "TAR"]);
+        assert!(report.image_formats.contains(&("JPEG", true)));
+        assert!(report.image_formats.contains(&("PNG", true)));
+        assert!(report.image_formats.contains(&("JPEG XL", false)));
+        assert_eq!(report.clsid, super::super::clsid::CLSID_STR);
+
+        let lines = report.lines();
+        assert_eq!(lines[0], format!("Version: {}", report.version));
+        assert!(lines.contains(&"Archives: ZIP, RAR, 7-Zip, TAR".to_string()));
+        assert!(lines.iter().any(|line| line.starts_with("Not compiled in: ") && line.contains("JPEG XL")));
+        assert!(lines.contains(&format!("CLSID: {}", report.clsid)));
+    }
+}
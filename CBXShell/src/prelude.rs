@@ -0,0 +1,27 @@
+//! Stable public API
+//!
+//! The items re-exported here are the supported surface for using CBXShell
+//! as a library; everything else may change between releases.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cbxshell::prelude::*;
+//! use std::path::Path;
+//!
+//! let archive = open_archive(Path::new("comic.cbz"))?;
+//! let cover = archive.find_first_image(true)?;
+//! let data = archive.extract_entry(&cover)?;
+//!
+//! let format = detect_image_format(&data)?;
+//! let image = decode_image(&data)?;
+//! println!("{}: {:?} {}x{}", cover.name, format, image.width(), image.height());
+//! # Ok::<(), CbxError>(())
+//! ```
+
+pub use crate::archive::{
+    open_archive, open_archive_from_stream, open_folder_as_archive, Archive, ArchiveEntry, ArchiveMetadata, ArchiveType,
+};
+pub use crate::image_processor::decoder::decode_image;
+pub use crate::image_processor::magic::{detect_image_format, ImageFormat};
+pub use crate::utils::error::CbxError;
@@ -0,0 +1,250 @@
+//! Shell registration plans
+//!
+//! Everything CBXShell writes to the registry, computed as a list of
+//! `RegistryOp`s without touching the registry. `registry::apply_ops`
+//! executes a plan; the DLL's (un)registration and the manager's extension
+//! settings both go through it, so the plans can be unit-tested on their own.
+
+use crate::clsid::CLSID_STR;
+
+/// IThumbnailProvider interface GUID (modern thumbnail API, replaces IExtractImage)
+pub const IID_ITHUMBNAILPROVIDER: &str = "{E357FCCD-A995-4576-B01F-234630154E96}";
+
+/// IQueryInfo interface GUID (tooltips)
+pub const IID_IQUERYINFO: &str = "{00021500-0000-0000-C000-000000000046}";
+
+/// Display name of the COM class
+const CLASS_NAME: &str = "CBXShell Class";
+
+/// Versioned ProgID (optional, for compatibility with the C++ version)
+const PROGID: &str = "CBXShell.CBXShell.1";
+
+/// Version-independent ProgID, removed on unregistration
+const PROGID_UNVERSIONED: &str = "CBXShell.CBXShell";
+
+/// Shell extensions approved for the current user
+const APPROVED_KEY_PATH: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Shell Extensions\\Approved";
+
+/// Property handlers by extension (only read from HKLM)
+const PROPERTY_HANDLERS_KEY_PATH: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\PropertySystem\\PropertyHandlers";
+
+/// Registry root a key path is relative to
+///
+/// Everything but the property handlers is per-user, so registering the
+/// thumbnail and infotip handlers never needs elevation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hive {
+    CurrentUser,
+    /// Machine-wide; writing needs elevation
+    LocalMachine,
+}
+
+/// What an operation does with the value (or key) it names
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryValue {
+    /// Create the key if needed and set a `REG_SZ` value
+    String(String),
+    /// Create the key if needed and set a `REG_DWORD` value
+    Dword(u32),
+    /// Delete the value, if present
+    DeleteValue,
+    /// Delete the key with all its subkeys, if present (`name` is unused)
+    DeleteTree,
+}
+
+/// One registry change
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegistryOp {
+    pub hive: Hive,
+    /// Key path below the hive
+    pub path: String,
+    /// Value name; empty for the key's default value
+    pub name: String,
+    pub value: RegistryValue,
+}
+
+impl RegistryOp {
+    /// Set a string value under HKCU
+    pub fn set_string(path: impl Into<String>, name: &str, data: &str) -> Self {
+        Self::new(path, name, RegistryValue::String(data.to_string()))
+    }
+
+    /// Set a DWORD value under HKCU
+    pub fn set_dword(path: impl Into<String>, name: &str, data: u32) -> Self {
+        Self::new(path, name, RegistryValue::Dword(data))
+    }
+
+    /// Delete a value under HKCU
+    pub fn delete_value(path: impl Into<String>, name: &str) -> Self {
+        Self::new(path, name, RegistryValue::DeleteValue)
+    }
+
+    /// Delete a key tree under HKCU
+    pub fn delete_tree(path: impl Into<String>) -> Self {
+        Self::new(path, "", RegistryValue::DeleteTree)
+    }
+
+    fn new(path: impl Into<String>, name: &str, value: RegistryValue) -> Self {
+        Self {
+            hive: Hive::CurrentUser,
+            path: path.into(),
+            name: name.to_string(),
+            value,
+        }
+    }
+}
+
+/// `Software\Classes\CLSID\{...}`
+pub fn clsid_key_path() -> String {
+    format!("Software\\Classes\\CLSID\\{}", CLSID_STR)
+}
+
+/// `Software\Classes\<extension>`, e.g. for `.cbz`
+pub fn extension_key_path(extension: &str) -> String {
+    format!("Software\\Classes\\{}", extension)
+}
+
+/// The `shellex` key naming the handler for interface `iid` of an extension
+pub fn handler_key_path(extension: &str, iid: &str) -> String {
+    format!("{}\\shellex\\{}", extension_key_path(extension), iid)
+}
+
+/// `Software\Microsoft\Windows\CurrentVersion\PropertySystem\PropertyHandlers\<extension>` (HKLM)
+pub fn property_handler_key_path(extension: &str) -> String {
+    format!("{}\\{}", PROPERTY_HANDLERS_KEY_PATH, extension)
+}
+
+/// Register the COM server implemented by the DLL at `module_path`
+///
+/// File extensions are not touched; they are configured by CBXManager
+/// (see `extension_ops`).
+pub fn register_server_ops(module_path: &str) -> Vec<RegistryOp> {
+    let clsid_key = clsid_key_path();
+    let inproc_key = format!("{}\\InprocServer32", clsid_key);
+    let progid_key = format!("Software\\Classes\\{}", PROGID);
+
+    vec![
+        RegistryOp::set_string(clsid_key, "", CLASS_NAME),
+        RegistryOp::set_string(inproc_key.clone(), "", module_path),
+        RegistryOp::set_string(inproc_key, "ThreadingModel", "Apartment"),
+        RegistryOp::set_string(progid_key.clone(), "", CLASS_NAME),
+        RegistryOp::set_string(format!("{}\\CLSID", progid_key), "", CLSID_STR),
+        RegistryOp::set_string(APPROVED_KEY_PATH, CLSID_STR, CLASS_NAME),
+    ]
+}
+
+/// Undo `register_server_ops`
+pub fn unregister_server_ops() -> Vec<RegistryOp> {
+    vec![
+        RegistryOp::delete_value(APPROVED_KEY_PATH, CLSID_STR),
+        RegistryOp::delete_tree(clsid_key_path()),
+        RegistryOp::delete_tree(format!("Software\\Classes\\{}", PROGID)),
+        RegistryOp::delete_tree(format!("Software\\Classes\\{}", PROGID_UNVERSIONED)),
+    ]
+}
+
+/// Enable or disable the thumbnail and infotip handlers of an extension
+///
+/// The extension is always marked `PerceivedType=image`, which Windows 11
+/// needs to show thumbnails in folder views. A disabled handler's key is
+/// removed.
+pub fn extension_ops(extension: &str, thumbnail: bool, infotip: bool) -> Vec<RegistryOp> {
+    let handler = |iid, enabled| {
+        let path = handler_key_path(extension, iid);
+        if enabled {
+            RegistryOp::set_string(path, "", CLSID_STR)
+        } else {
+            RegistryOp::delete_tree(path)
+        }
+    };
+
+    vec![
+        RegistryOp::set_string(extension_key_path(extension), "PerceivedType", "image"),
+        handler(IID_ITHUMBNAILPROVIDER, thumbnail),
+        handler(IID_IQUERYINFO, infotip),
+    ]
+}
+
+/// Enable or disable the property handler (page count, cover size) of an extension
+///
+/// The property system only reads handlers from HKLM, so unlike the other
+/// plans this one needs elevation.
+pub fn property_handler_ops(extension: &str, enabled: bool) -> Vec<RegistryOp> {
+    let path = property_handler_key_path(extension);
+    let op = if enabled {
+        RegistryOp::set_string(path, "", CLSID_STR)
+    } else {
+        RegistryOp::delete_tree(path)
+    };
+
+    vec![RegistryOp { hive: Hive::LocalMachine, ..op }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_server_ops() {
+        let ops = register_server_ops("C:\\Tools\\cbxshell.dll");
+        let clsid_key = format!("Software\\Classes\\CLSID\\{}", CLSID_STR);
+
+        assert!(ops.iter().all(|op| op.hive == Hive::CurrentUser));
+        assert_eq!(ops[0], RegistryOp::set_string(clsid_key.clone(), "", "CBXShell Class"));
+        assert!(ops.contains(&RegistryOp::set_string(
+            format!("{}\\InprocServer32", clsid_key),
+            "",
+            "C:\\Tools\\cbxshell.dll"
+        )));
+        assert!(ops.contains(&RegistryOp::set_string(
+            format!("{}\\InprocServer32", clsid_key),
+            "ThreadingModel",
+            "Apartment"
+        )));
+        assert!(ops.contains(&RegistryOp::set_string(APPROVED_KEY_PATH, CLSID_STR, "CBXShell Class")));
+    }
+
+    #[test]
+    fn test_unregister_server_ops_remove_what_register_adds() {
+        let removed = unregister_server_ops();
+        for op in register_server_ops("cbxshell.dll") {
+            let undone = removed.iter().any(|r| match r.value {
+                RegistryValue::DeleteValue => r.path == op.path && r.name == op.name,
+                RegistryValue::DeleteTree => op.path.starts_with(&r.path),
+                _ => false,
+            });
+            assert!(undone, "{:?} is never undone", op);
+        }
+    }
+
+    #[test]
+    fn test_extension_ops() {
+        let ops = extension_ops(".cbz", true, false);
+        assert_eq!(
+            ops,
+            vec![
+                RegistryOp::set_string("Software\\Classes\\.cbz", "PerceivedType", "image"),
+                RegistryOp::set_string(
+                    "Software\\Classes\\.cbz\\shellex\\{E357FCCD-A995-4576-B01F-234630154E96}",
+                    "",
+                    CLSID_STR
+                ),
+                RegistryOp::delete_tree("Software\\Classes\\.cbz\\shellex\\{00021500-0000-0000-C000-000000000046}"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_property_handler_ops() {
+        let path = "Software\\Microsoft\\Windows\\CurrentVersion\\PropertySystem\\PropertyHandlers\\.cbr";
+        let ops = property_handler_ops(".cbr", true);
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops[0].hive, Hive::LocalMachine);
+        assert_eq!((ops[0].path.as_str(), ops[0].name.as_str()), (path, ""));
+        assert_eq!(ops[0].value, RegistryValue::String(CLSID_STR.to_string()));
+
+        let ops = property_handler_ops(".cbr", false);
+        assert_eq!((ops[0].hive, ops[0].path.as_str()), (Hive::LocalMachine, path));
+        assert_eq!(ops[0].value, RegistryValue::DeleteTree);
+    }
+}
@@ -2,25 +2,21 @@
 //!
 //! Handles registry entries for:
 //! - CLSID registration
-//! - Shell extension handlers (.cbz, .cbr, .zip, .cb7)
+//! - Shell extension handlers (.cbz, .cbr, .zip, .phz, .cb7)
 //! - Approved shell extensions
+//! - Property handlers (HKLM, best effort)
 //!
-//! Based on CBXShell.rgs from the C++ implementation
+//! Based on CBXShell.rgs from the C++ implementation. What gets written is
+//! planned in `registration`; this module executes the plans.
 
+use crate::clsid::CLSID_U128;
+use crate::registration::{self, Hive, RegistryOp, RegistryValue};
 use crate::utils::error::{CbxError, Result};
 use windows::core::GUID;
 use windows::Win32::System::Registry::*;
 
-/// CBXShell CLSID: {9E6ECB90-5A61-42BD-B851-D3297D9C7F39}
-pub const CLSID_CBXSHELL: GUID = GUID::from_u128(0x9E6ECB90_5A61_42BD_B851_D3297D9C7F39);
-
-/// IThumbnailProvider interface GUID (modern thumbnail API, replaces IExtractImage)
-#[allow(dead_code)] // May be used in future for interface registration
-const IID_ITHUMBNAILPROVIDER: &str = "{E357FCCD-A995-4576-B01F-234630154E96}";
-
-/// IQueryInfo interface GUID (tooltips)
-#[allow(dead_code)] // May be used in future for interface registration
-const IID_IQUERYINFO: &str = "{00021500-0000-0000-C000-000000000046}";
+/// CBXShell CLSID: {9E6ECB90-5A61-42BD-B851-D3297D9C7F39} unless overridden at build time
+pub const CLSID_CBXSHELL: GUID = GUID::from_u128(CLSID_U128);
 
 /// Get the path to the current DLL
 ///
@@ -136,59 +132,105 @@ fn delete_key_recursive(hkey: HKEY, subkey: &str) -> Result<()> {
             Ok(_) => Ok(()),
             Err(e) => {
                 // ERROR_FILE_NOT_FOUND or ERROR_PATH_NOT_FOUND are acceptable
-                let code = e.code().0 as u32;
+                // (as HRESULT_FROM_WIN32, so compare the low word)
+                let code = e.code().0 as u32 & 0xFFFF;
                 if code == 2 || code == 3 {
                     Ok(())
                 } else {
-                    Err(CbxError::Registry(format!(
-                        "Failed to delete registry key: {} (error: {:?})",
-                        subkey, e
-                    )))
+                    Err(CbxError::from_registry_call(subkey, &e))
                 }
             }
         }
     }
 }
 
-/// Register shell extension handler for a file extension
-#[allow(dead_code)] // Helper function for per-extension registration
-fn register_extension(extension: &str, clsid_str: &str) -> Result<()> {
-    let base_key = format!("Software\\Classes\\{}", extension);
+/// Set a registry DWORD value (helper function)
+fn set_dword_value(hkey: HKEY, value_name: &str, data: u32) -> Result<()> {
+    let value_name_wide: Vec<u16> = value_name.encode_utf16().chain(Some(0)).collect();
 
-    // 1. Register PerceivedType as "image" so Windows treats these as media files
-    // This is CRITICAL for Windows 11 to show thumbnails in folder views
-    let ext_key = create_key(HKEY_CURRENT_USER, &base_key)?;
-    set_string_value(ext_key, Some("PerceivedType"), "image")?;
-    set_string_value(ext_key, Some("Content Type"), "application/x-cbz")?;
-    unsafe { RegCloseKey(ext_key).ok(); }
+    // UNAVOIDABLE UNSAFE: RegSetValueExW is a Windows FFI call
+    unsafe {
+        RegSetValueExW(
+            hkey,
+            windows::core::PCWSTR(value_name_wide.as_ptr()),
+            0,
+            REG_DWORD,
+            Some(&data.to_le_bytes()),
+        ).map_err(CbxError::Windows)?;
+    }
 
-    // 2. Create .ext\shellex key
-    let shellex_key_path = format!("{}\\shellex", base_key);
-    let shellex_key = create_key(HKEY_CURRENT_USER, &shellex_key_path)?;
+    Ok(())
+}
 
-    // 3. Register IThumbnailProvider handler (thumbnails - modern API)
-    let thumbnail_key_path = format!("{}\\shellex\\{}", base_key, IID_ITHUMBNAILPROVIDER);
-    let thumbnail_key = create_key(HKEY_CURRENT_USER, &thumbnail_key_path)?;
-    set_string_value(thumbnail_key, None, clsid_str)?;
-    unsafe { RegCloseKey(thumbnail_key).ok(); }
+/// Delete a registry value, ignoring a missing key or value
+fn delete_value(hkey: HKEY, subkey: &str, value_name: &str) -> Result<()> {
+    let subkey_wide: Vec<u16> = subkey.encode_utf16().chain(Some(0)).collect();
+    let value_name_wide: Vec<u16> = value_name.encode_utf16().chain(Some(0)).collect();
 
-    // 4. Register IQueryInfo handler (tooltips)
-    let infotip_key_path = format!("{}\\shellex\\{}", base_key, IID_IQUERYINFO);
-    let infotip_key = create_key(HKEY_CURRENT_USER, &infotip_key_path)?;
-    set_string_value(infotip_key, None, clsid_str)?;
-    unsafe { RegCloseKey(infotip_key).ok(); }
+    // UNAVOIDABLE UNSAFE: RegDeleteKeyValueW is a Windows FFI call
+    // Safety guarantees:
+    // - Both strings have null terminators
+    // - The key is opened and closed by the API itself
+    unsafe {
+        match RegDeleteKeyValueW(
+            hkey,
+            windows::core::PCWSTR(subkey_wide.as_ptr()),
+            windows::core::PCWSTR(value_name_wide.as_ptr()),
+        ) {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                // ERROR_FILE_NOT_FOUND or ERROR_PATH_NOT_FOUND are acceptable
+                let code = e.code().0 as u32 & 0xFFFF;
+                if code == 2 || code == 3 {
+                    Ok(())
+                } else {
+                    Err(CbxError::from_registry_call(format!("{}\\{}", subkey, value_name), &e))
+                }
+            }
+        }
+    }
+}
 
-    unsafe { RegCloseKey(shellex_key).ok(); }
+/// Execute a registration plan (see `registration`), in order
+///
+/// Stops at the first failing operation. Deleting something that doesn't
+/// exist is not a failure.
+pub fn apply_ops(ops: &[RegistryOp]) -> Result<()> {
+    for op in ops {
+        // Windows API failures become `CbxError::RegistryAccess` for the op's key
+        apply_op(op).map_err(|e| match e {
+            CbxError::Windows(e) => CbxError::from_registry_call(op.path.as_str(), &e),
+            e => e,
+        })?;
+    }
 
     Ok(())
 }
 
-/// Unregister shell extension handler for a file extension
-#[allow(dead_code)] // Helper function for per-extension unregistration
-fn unregister_extension(extension: &str) -> Result<()> {
-    let base_key = format!("Software\\Classes\\{}\\shellex", extension);
-    delete_key_recursive(HKEY_CURRENT_USER, &base_key)?;
-    Ok(())
+/// Execute a single registry change
+fn apply_op(op: &RegistryOp) -> Result<()> {
+    let hive = match op.hive {
+        Hive::CurrentUser => HKEY_CURRENT_USER,
+        Hive::LocalMachine => HKEY_LOCAL_MACHINE,
+    };
+    let name = Some(op.name.as_str()).filter(|name| !name.is_empty());
+
+    match &op.value {
+        RegistryValue::String(data) => {
+            let key = create_key(hive, &op.path)?;
+            let result = set_string_value(key, name, data);
+            unsafe { RegCloseKey(key).ok(); }
+            result
+        }
+        RegistryValue::Dword(data) => {
+            let key = create_key(hive, &op.path)?;
+            let result = set_dword_value(key, &op.name, *data);
+            unsafe { RegCloseKey(key).ok(); }
+            result
+        }
+        RegistryValue::DeleteValue => delete_value(hive, &op.path, &op.name),
+        RegistryValue::DeleteTree => delete_key_recursive(hive, &op.path),
+    }
 }
 
 /// Register the COM server and shell extension handlers
@@ -197,44 +239,16 @@ fn unregister_extension(extension: &str) -> Result<()> {
 /// * `dll_path` - Optional path to the DLL. If None, will attempt to get path from DllMain module handle.
 ///                When calling from an external executable (like CBXManager), you must provide this.
 pub fn register_server(dll_path: Option<&str>) -> Result<()> {
-    // Format CLSID with hyphens as Windows expects: {XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX}
-    let clsid_str = format!("{{{:?}}}", CLSID_CBXSHELL);
-
     // Get DLL path: use provided path or get from module handle
     let module_path = match dll_path {
         Some(path) => path.to_string(),
         None => get_module_path()?,
     };
 
-    // 1. Register CLSID
-    let clsid_key_path = format!("Software\\Classes\\CLSID\\{}", clsid_str);
-    let clsid_key = create_key(HKEY_CURRENT_USER, &clsid_key_path)?;
-    set_string_value(clsid_key, None, "CBXShell Class")?;
-
-    // 2. Register InprocServer32
-    let inproc_key_path = format!("{}\\InprocServer32", clsid_key_path);
-    let inproc_key = create_key(HKEY_CURRENT_USER, &inproc_key_path)?;
-    set_string_value(inproc_key, None, &module_path)?;
-    set_string_value(inproc_key, Some("ThreadingModel"), "Apartment")?;
-    unsafe { RegCloseKey(inproc_key).ok(); }
-
-    // 3. Register ProgID (optional, for compatibility)
-    let progid_key = create_key(HKEY_CURRENT_USER, "Software\\Classes\\CBXShell.CBXShell.1")?;
-    set_string_value(progid_key, None, "CBXShell Class")?;
-    let progid_clsid_key = create_key(HKEY_CURRENT_USER, "Software\\Classes\\CBXShell.CBXShell.1\\CLSID")?;
-    set_string_value(progid_clsid_key, None, &clsid_str)?;
-    unsafe {
-        RegCloseKey(progid_clsid_key).ok();
-        RegCloseKey(progid_key).ok();
-        RegCloseKey(clsid_key).ok();
-    }
-
-    // 4. Add to approved shell extensions (HKCU, not HKLM to avoid admin requirement)
-    // Note: File extension registration is now handled by CBXManager via registry_ops
-    let approved_key_path = "Software\\Microsoft\\Windows\\CurrentVersion\\Shell Extensions\\Approved";
-    let approved_key = create_key(HKEY_CURRENT_USER, approved_key_path)?;
-    set_string_value(approved_key, Some(&clsid_str), "CBXShell Class")?;
-    unsafe { RegCloseKey(approved_key).ok(); }
+    // CLSID, InprocServer32, ProgID and the approved shell extensions entry (HKCU, no admin needed)
+    // Note: File extension registration is handled by CBXManager via registry_ops
+    apply_ops(&registration::register_server_ops(&module_path))?;
+    update_property_handlers(true);
 
     tracing::info!(
         "Successfully registered CBXShell COM server (file extensions must be configured via CBXManager)"
@@ -245,41 +259,72 @@ pub fn register_server(dll_path: Option<&str>) -> Result<()> {
 
 /// Unregister the COM server and shell extension handlers
 pub fn unregister_server() -> Result<()> {
-    let clsid_str = format!("{{{:?}}}", CLSID_CBXSHELL);
-
-    // 1. Remove from approved shell extensions
     // Note: File extension cleanup is handled by CBXManager via registry_ops
-    let approved_key_path = "Software\\Microsoft\\Windows\\CurrentVersion\\Shell Extensions\\Approved";
-    if let Ok(approved_key) = create_key(HKEY_CURRENT_USER, approved_key_path) {
-        unsafe {
-            let value_name_wide: Vec<u16> = clsid_str.encode_utf16().chain(Some(0)).collect();
-            let _ = RegDeleteValueW(approved_key, windows::core::PCWSTR(value_name_wide.as_ptr()));
-            RegCloseKey(approved_key).ok();
-        }
-    }
-
-    // 2. Delete CLSID
-    let clsid_key_path = format!("Software\\Classes\\CLSID\\{}", clsid_str);
-    delete_key_recursive(HKEY_CURRENT_USER, &clsid_key_path)?;
-
-    // 3. Delete ProgID
-    let _ = delete_key_recursive(HKEY_CURRENT_USER, "Software\\Classes\\CBXShell.CBXShell.1");
-    let _ = delete_key_recursive(HKEY_CURRENT_USER, "Software\\Classes\\CBXShell.CBXShell");
+    apply_ops(&registration::unregister_server_ops())?;
+    update_property_handlers(false);
 
     tracing::info!("Successfully unregistered CBXShell");
 
     Ok(())
 }
 
+/// (Un)register the property handler of each enabled extension, if allowed
+///
+/// Property handlers are machine-wide, so this only succeeds when elevated;
+/// otherwise thumbnails still work, just without the page count and cover
+/// size in Explorer's Details pane. Another handler registered for an
+/// extension is left alone.
+fn update_property_handlers(enabled: bool) {
+    for extension in crate::archive::read_config().enabled_extensions {
+        match property_handler_owner(&extension) {
+            Some(owner) if owner != crate::clsid::CLSID_STR => {
+                tracing::info!("Leaving the property handler of {} ({}) alone", extension, owner);
+                continue;
+            }
+            None if !enabled => continue,
+            _ => {}
+        }
+
+        if let Err(e) = apply_ops(&registration::property_handler_ops(&extension, enabled)) {
+            if e.is_permission_denied() {
+                tracing::warn!("Property handlers need elevation, skipping them: {}", e);
+                return;
+            }
+            tracing::warn!("Failed to update the property handler of {}: {}", extension, e);
+        }
+    }
+}
+
+/// CLSID registered as the property handler of an extension, if any
+fn property_handler_owner(extension: &str) -> Option<String> {
+    winreg::RegKey::predef(winreg::enums::HKEY_LOCAL_MACHINE)
+        .open_subkey(registration::property_handler_key_path(extension))
+        .and_then(|key| key.get_value::<String, _>(""))
+        .ok()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::clsid::CLSID_STR;
 
     #[test]
     fn test_clsid_format() {
         let clsid_str = format!("{{{:?}}}", CLSID_CBXSHELL);
         // Debug format uses uppercase for GUIDs
-        assert_eq!(clsid_str, "{9E6ECB90-5A61-42BD-B851-D3297D9C7F39}");
+        assert_eq!(clsid_str, CLSID_STR);
+
+        if option_env!("CBXSHELL_CLSID").is_none() {
+            assert_eq!(clsid_str, "{9E6ECB90-5A61-42BD-B851-D3297D9C7F39}");
+        }
+    }
+
+    #[test]
+    fn test_generated_clsid_parses() {
+        // The generated string form must parse back to the same GUID
+        let parsed = GUID::from(CLSID_STR.trim_start_matches('{').trim_end_matches('}'));
+        assert_eq!(parsed, CLSID_CBXSHELL);
+        assert_eq!(parsed, crate::com::CLSID_CBXSHELL);
     }
 
     #[test]
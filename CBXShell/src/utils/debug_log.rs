@@ -2,38 +2,155 @@
 //!
 //! Provides file-based logging that persists across DLL loads/unloads
 //! to help diagnose why Windows Explorer may not be showing thumbnails.
+//!
+//! The log file stays open between messages and is closed by
+//! `shutdown_debug_log` when the DLL is unloaded (`DLL_PROCESS_DETACH`).
+//!
+//! The file is `DebugLogPath` from the registry, or `cbxshell_debug.log` in
+//! the user's temp directory. The path is resolved once per process.
+//!
+//! Logging is off unless `DebugLogging` is set. The setting is read once, by
+//! `init_debug_logging` on the first `DllGetClassObject` (the registry must
+//! not be read from `DllMain`); until then, and whenever it is off,
+//! `debug_log` returns after a single relaxed atomic load.
+
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, Once, OnceLock, PoisonError, TryLockError};
+
+use crate::utils::text::truncate_chars;
+
+/// Log file name in the temp directory, used unless `DebugLogPath` is set
+const DEFAULT_LOG_FILE_NAME: &str = "cbxshell_debug.log";
+
+/// Resolved log file path
+static LOG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Whether `debug_log` writes anything (`DebugLogging`)
+static LOGGING_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Guards the one-time registry read of `DebugLogging`
+static LOGGING_INIT: Once = Once::new();
 
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::sync::Mutex;
+/// Longest message written to the log (in characters, clipped on char boundaries)
+const MAX_LOG_MESSAGE_CHARS: usize = 2048;
+
+/// The open log file; the mutex also serializes log writes
+///
+/// `None` until the first message and after `shutdown_debug_log`. A
+/// `LineWriter` writes each complete line through, so only a partial line
+/// is ever buffered.
+static LOG_FILE: Mutex<Option<LineWriter<File>>> = Mutex::new(None);
+
+/// Load `DebugLogging` from the registry, once per process
+///
+/// Later calls, and calls after `set_debug_logging`, do nothing.
+pub fn init_debug_logging() {
+    LOGGING_INIT.call_once(|| {
+        LOGGING_ENABLED.store(crate::archive::should_debug_log(), Ordering::Relaxed);
+    });
+}
+
+/// Turn debug logging on or off for this process
+///
+/// Overrides the registry setting; `init_debug_logging` won't change it back.
+pub fn set_debug_logging(enabled: bool) {
+    LOGGING_INIT.call_once(|| {});
+    LOGGING_ENABLED.store(enabled, Ordering::Relaxed);
+}
 
-/// Global debug log file path
-const DEBUG_LOG_PATH: &str = "G:\\CBXTest\\cbxshell_debug.log";
+/// Path of the debug log file, resolved on first use
+///
+/// Changing `DebugLogPath` takes effect once the DLL is reloaded (e.g. after
+/// an Explorer restart).
+pub fn log_path() -> &'static Path {
+    LOG_PATH.get_or_init(|| resolve_log_path(crate::archive::read_debug_log_path()))
+}
 
-/// Global mutex to serialize log writes
-static LOG_MUTEX: Mutex<()> = Mutex::new(());
+/// The configured path, or `cbxshell_debug.log` in the temp directory
+fn resolve_log_path(configured: Option<String>) -> PathBuf {
+    configured
+        .map(PathBuf::from)
+        .unwrap_or_else(|| std::env::temp_dir().join(DEFAULT_LOG_FILE_NAME))
+}
 
 /// Log a debug message to file with timestamp
 ///
+/// Does nothing while debug logging is off (the default, see the module docs).
 /// This function is safe to call from any thread and will serialize writes.
 /// Errors are silently ignored to prevent logging from breaking functionality.
+///
+/// Never panics: a panic inside a shell extension takes Explorer down with it.
+/// If another thread panicked while holding the lock, the guard is recovered
+/// (at worst the file holds a partial line).
 pub fn debug_log(msg: &str) {
-    let _guard = LOG_MUTEX.lock().unwrap();
+    if !LOGGING_ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
 
-    let _ = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(DEBUG_LOG_PATH)
-        .and_then(|mut f| {
-            use std::time::SystemTime;
+    // Resolved before taking the lock: the first call reads the registry
+    let path = log_path();
+    let mut log = LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner);
 
-            let timestamp = SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap()
-                .as_secs();
+    if log.is_none() {
+        *log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .ok()
+            .map(LineWriter::new);
+    }
+    let Some(file) = log.as_mut() else {
+        return;
+    };
 
-            writeln!(f, "[{}] {}", timestamp, msg)
-        });
+    if write_message(file, msg).is_err() {
+        // Reopen on the next message (e.g. the file was deleted)
+        *log = None;
+    }
+}
+
+/// Write `msg` as one timestamped line
+fn write_message(file: &mut LineWriter<File>, msg: &str) -> std::io::Result<()> {
+    use std::time::SystemTime;
+
+    // A clock set before 1970 logs timestamp 0 rather than panicking
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+
+    writeln!(file, "[{}] {}", timestamp, truncate_chars(msg, MAX_LOG_MESSAGE_CHARS))
+}
+
+/// Write `last_message` (if logging is on), then flush and close the log file
+///
+/// Called on `DLL_PROCESS_DETACH` when the DLL is unloaded so the last lines
+/// are written and the file handle is released. Logging after this reopens
+/// the file. The message is only written to a file that is already open.
+///
+/// Never blocks, since it runs under the loader lock: if another thread
+/// holds the log lock, nothing is written and the file is left to the OS
+/// (which still has every complete line, see `LOG_FILE`). The file isn't
+/// synced to disk either; the OS writes it out after the handle is closed.
+///
+/// # Returns
+/// `true` if the log was closed (or wasn't open), `false` if the lock was held
+pub fn shutdown_debug_log(last_message: Option<&str>) -> bool {
+    let mut log = match LOG_FILE.try_lock() {
+        Ok(log) => log,
+        Err(TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+        Err(TryLockError::WouldBlock) => return false,
+    };
+
+    if let Some(mut file) = log.take() {
+        if let Some(msg) = last_message.filter(|_| LOGGING_ENABLED.load(Ordering::Relaxed)) {
+            let _ = write_message(&mut file, msg);
+        }
+        let _ = file.flush();
+    }
+    true
 }
 
 /// Log method entry with parameters
@@ -69,26 +186,66 @@ macro_rules! log_error {
 /// Clear the debug log file (useful for testing)
 #[allow(dead_code)] // Utility function for debugging and testing
 pub fn clear_debug_log() {
-    let _ = std::fs::remove_file(DEBUG_LOG_PATH);
+    // Close the file first; the next message creates a new one
+    let mut log = LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner);
+    *log = None;
+    let _ = std::fs::remove_file(log_path());
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::sync::MutexGuard;
+
+    /// Serializes the tests below, which toggle logging for the whole process
+    static TEST_LOCK: Mutex<()> = Mutex::new(());
+
+    /// Hold `TEST_LOCK` with logging set to `enabled`
+    fn logging(enabled: bool) -> MutexGuard<'static, ()> {
+        let guard = TEST_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        set_debug_logging(enabled);
+        guard
+    }
 
     #[test]
     fn test_debug_log_basic() {
+        let _logging = logging(true);
         clear_debug_log();
         debug_log("Test message");
 
-        let contents = std::fs::read_to_string(DEBUG_LOG_PATH).unwrap();
+        let contents = std::fs::read_to_string(log_path()).unwrap();
         assert!(contents.contains("Test message"));
     }
 
+    #[test]
+    fn test_disabled_logging_creates_no_file() {
+        let _logging = logging(false);
+        clear_debug_log();
+
+        debug_log("Message while logging is off");
+        assert!(!log_path().exists());
+        assert!(LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner).is_none());
+
+        // The registry setting doesn't override an explicit choice
+        init_debug_logging();
+        assert!(!LOGGING_ENABLED.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_log_path_defaults_to_temp_dir() {
+        let default = resolve_log_path(None);
+        assert_eq!(default.parent(), Some(std::env::temp_dir().as_path()));
+        assert_eq!(default.file_name().unwrap(), DEFAULT_LOG_FILE_NAME);
+
+        let configured = resolve_log_path(Some("C:\\Logs\\cbx.log".to_string()));
+        assert_eq!(configured, PathBuf::from("C:\\Logs\\cbx.log"));
+    }
+
     #[test]
     fn test_debug_log_concurrent() {
         use std::thread;
 
+        let _logging = logging(true);
         clear_debug_log();
 
         // Small delay to ensure file is deleted
@@ -106,7 +263,7 @@ mod tests {
             handle.join().unwrap();
         }
 
-        let contents = std::fs::read_to_string(DEBUG_LOG_PATH).unwrap();
+        let contents = std::fs::read_to_string(log_path()).unwrap();
 
         // Count only lines containing "Thread" and "message" from this test
         // Other tests may write to the log file concurrently
@@ -119,4 +276,58 @@ mod tests {
             "Expected 10 thread messages, found {} (total lines: {})",
             matching_lines, contents.lines().count());
     }
+
+    #[test]
+    fn test_debug_log_after_poisoned_mutex() {
+        let _logging = logging(true);
+        // Poison the log mutex by panicking while holding it
+        let result = std::thread::spawn(|| {
+            let _guard = LOG_FILE.lock().unwrap();
+            panic!("poison the log mutex");
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(LOG_FILE.is_poisoned());
+
+        // Logging keeps working instead of propagating the panic
+        debug_log("Message after poisoned mutex");
+        debug_log("Second message after poisoned mutex");
+    }
+
+    #[test]
+    fn test_shutdown_flushes_pending_messages() {
+        let _logging = logging(true);
+        debug_log("Message pending at shutdown");
+        assert!(shutdown_debug_log(Some("Last message at shutdown")));
+        assert!(LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner).is_none());
+
+        let contents = std::fs::read_to_string(log_path()).unwrap();
+        assert!(contents.contains("Message pending at shutdown"));
+        assert!(contents.contains("Last message at shutdown"));
+
+        // Logging after shutdown reopens the file
+        debug_log("Message after shutdown");
+        let contents = std::fs::read_to_string(log_path()).unwrap();
+        assert!(contents.contains("Message after shutdown"));
+    }
+
+    #[test]
+    fn test_shutdown_does_not_wait_for_held_lock() {
+        let _logging = logging(true);
+        // A thread killed during process termination may still own the lock
+        let (locked_tx, locked_rx) = std::sync::mpsc::channel();
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let holder = std::thread::spawn(move || {
+            let _guard = LOG_FILE.lock().unwrap_or_else(PoisonError::into_inner);
+            locked_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        });
+
+        locked_rx.recv().unwrap();
+        assert!(!shutdown_debug_log(Some("Not written while the lock is held")));
+
+        release_tx.send(()).unwrap();
+        holder.join().unwrap();
+        assert!(shutdown_debug_log(None));
+    }
 }
@@ -0,0 +1,46 @@
+//! Structured results of magic-byte detection
+//!
+//! `detect_archive_type_from_bytes` and `detect_image_format` answer "what
+//! is it"; their `*_detailed` counterparts also say which signature matched
+//! and at what offset, so logs can show exactly why data was classified the
+//! way it was (useful when a signature isn't at the start, e.g. AVIF brands
+//! or self-extracting archives).
+
+use std::fmt;
+
+/// What a signature scan matched and where
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection<K> {
+    /// The detected format
+    pub kind: K,
+    /// The signature bytes that matched
+    pub matched_signature: &'static [u8],
+    /// Offset of `matched_signature` in the inspected data
+    pub offset: usize,
+}
+
+impl<K: fmt::Debug> fmt::Display for Detection<K> {
+    /// e.g. `Zip (signature 50 4B 03 04 at offset 0)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} (signature", self.kind)?;
+        for byte in self.matched_signature {
+            write!(f, " {:02X}", byte)?;
+        }
+        write!(f, " at offset {})", self.offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detection_display() {
+        let detection = Detection {
+            kind: "Avif",
+            matched_signature: b"avif",
+            offset: 8,
+        };
+        assert_eq!(detection.to_string(), r#""Avif" (signature 61 76 69 66 at offset 8)"#);
+    }
+}
@@ -3,6 +3,10 @@
 use thiserror::Error;
 use windows::core::HRESULT;
 
+/// "The data necessary to complete this operation is not yet available"
+/// (only exported by the `windows` crate's Urlmon bindings)
+pub const E_PENDING: HRESULT = HRESULT(0x8000000Au32 as i32);
+
 #[derive(Error, Debug)]
 pub enum CbxError {
     #[error("Archive error: {0}")]
@@ -20,14 +24,77 @@ pub enum CbxError {
     #[error("Registry error: {0}")]
     Registry(String),
 
+    /// A registry call failed with an OS error, e.g. access denied on a
+    /// locked-down machine
+    #[error("Registry error at {key}: {source}")]
+    RegistryAccess {
+        key: String,
+        #[source]
+        source: std::io::Error,
+    },
+
     #[error("No image found in archive")]
     NoImageFound,
 
     #[error("Unsupported format: {0}")]
     UnsupportedFormat(String),
 
+    #[error("Encrypted entry: {0}")]
+    Encrypted(String),
+
+    #[error("Encrypted entry needs a password (ArchivePassword is not set): {0}")]
+    PasswordRequired(String),
+
+    #[error("Corrupt archive: {0}")]
+    Corrupt(String),
+
     #[error("Invalid file path")]
     InvalidPath,
+
+    #[error("Archive opted out of thumbnailing (marker file present)")]
+    NoThumbnailMarker,
+
+    #[error("Thumbnails are disabled (Enabled = 0)")]
+    Disabled,
+
+    #[error("Too many concurrent decodes (decode memory budget exhausted)")]
+    Busy,
+
+    #[error("Worker thread pool error: {0}")]
+    ThreadPool(String),
+}
+
+impl CbxError {
+    /// A registry operation on `key` failed with `source`
+    pub fn registry_access(key: impl Into<String>, source: std::io::Error) -> Self {
+        CbxError::RegistryAccess {
+            key: key.into(),
+            source,
+        }
+    }
+
+    /// Map an error returned by a Win32 registry function on `key`
+    ///
+    /// Registry functions report Win32 error codes, which the `windows`
+    /// crate wraps as `HRESULT_FROM_WIN32`; the code is unwrapped so the
+    /// resulting `std::io::Error` has the matching `ErrorKind`.
+    pub fn from_registry_call(key: impl Into<String>, error: &windows::core::Error) -> Self {
+        let hresult = error.code().0 as u32;
+        let code = if hresult & 0xFFFF_0000 == 0x8007_0000 {
+            hresult & 0xFFFF
+        } else {
+            hresult
+        };
+        Self::registry_access(key, std::io::Error::from_raw_os_error(code as i32))
+    }
+
+    /// True if a registry operation was refused for lack of permissions
+    pub fn is_permission_denied(&self) -> bool {
+        matches!(
+            self,
+            CbxError::RegistryAccess { source, .. } if source.kind() == std::io::ErrorKind::PermissionDenied
+        )
+    }
 }
 
 impl From<CbxError> for HRESULT {
@@ -35,10 +102,63 @@ impl From<CbxError> for HRESULT {
         match err {
             CbxError::NoImageFound => windows::Win32::Foundation::E_FAIL,
             CbxError::InvalidPath => windows::Win32::Foundation::E_INVALIDARG,
+            CbxError::NoThumbnailMarker => windows::Win32::UI::Shell::WTS_E_FAILEDEXTRACTION,
+            CbxError::Disabled => windows::Win32::UI::Shell::WTS_E_FAILEDEXTRACTION,
+            CbxError::PasswordRequired(_) => windows::Win32::UI::Shell::WTS_E_FAILEDEXTRACTION,
+            // Transient: the file is fine, it just can't be decoded right now
+            CbxError::Busy => E_PENDING,
             CbxError::Windows(e) => e.code(),
+            CbxError::RegistryAccess { source, .. } => match source.raw_os_error() {
+                Some(code) => HRESULT::from_win32(code as u32),
+                None => windows::Win32::Foundation::E_FAIL,
+            },
             _ => windows::Win32::Foundation::E_FAIL,
         }
     }
 }
 
 pub type Result<T> = std::result::Result<T, CbxError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use windows::Win32::Foundation::{ERROR_ACCESS_DENIED, ERROR_FILE_NOT_FOUND, E_ACCESSDENIED, E_FAIL};
+
+    #[test]
+    fn test_registry_access_denied_mapping() {
+        // What RegCreateKeyExW returns for a key the user can't write
+        let denied = windows::core::Error::from(HRESULT::from_win32(ERROR_ACCESS_DENIED.0));
+        let err = CbxError::from_registry_call("Software\\Classes\\.cbz", &denied);
+
+        assert!(err.is_permission_denied());
+        match &err {
+            CbxError::RegistryAccess { key, source } => {
+                assert_eq!(key, "Software\\Classes\\.cbz");
+                assert_eq!(source.raw_os_error(), Some(ERROR_ACCESS_DENIED.0 as i32));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(err.to_string().starts_with("Registry error at Software\\Classes\\.cbz: "));
+        assert_eq!(HRESULT::from(err), E_ACCESSDENIED);
+    }
+
+    #[test]
+    fn test_registry_access_other_errors() {
+        let missing = windows::core::Error::from(HRESULT::from_win32(ERROR_FILE_NOT_FOUND.0));
+        let err = CbxError::from_registry_call("Software\\Missing", &missing);
+        assert!(!err.is_permission_denied());
+        assert!(matches!(&err, CbxError::RegistryAccess { source, .. } if source.kind() == std::io::ErrorKind::NotFound));
+
+        // As winreg reports it
+        let err = CbxError::registry_access("Software\\Key", std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        assert!(err.is_permission_denied());
+
+        assert!(!CbxError::Registry("DLL module handle not initialized".to_string()).is_permission_denied());
+    }
+
+    #[test]
+    fn test_busy_is_retryable() {
+        assert_eq!(HRESULT::from(CbxError::Busy), E_PENDING);
+        assert_eq!(HRESULT::from(CbxError::NoImageFound), E_FAIL);
+    }
+}
@@ -1,6 +1,7 @@
 ///! File system utility functions
 
 use std::path::Path;
+use std::time::UNIX_EPOCH;
 use windows::Win32::Foundation::FILETIME;
 use windows::Win32::Storage::FileSystem::{
     GetFileTime, CreateFileW, FILE_SHARE_READ, OPEN_EXISTING, FILE_ATTRIBUTE_NORMAL,
@@ -108,6 +109,60 @@ pub fn detect_archive_type(path: &Path) -> Result<ArchiveType> {
         .ok_or_else(|| CbxError::UnsupportedFormat(extension.to_string()))
 }
 
+/// Compute a stable cache key for a file
+///
+/// The key combines the canonical path, size and last modified time, so it
+/// changes whenever the file is replaced or rewritten and is stable
+/// otherwise. It is a 64-bit FNV-1a hash formatted as 16 hex digits, which
+/// does not depend on the Rust version or process (unlike `DefaultHasher`).
+///
+/// # Arguments
+/// * `path` - Path to the file (need not be canonical)
+///
+/// # Returns
+/// * `Ok(String)` - Cache key, e.g. `"3f1c9a0b7d2e4c85"`
+/// * `Err(CbxError)` - The file does not exist or its metadata can't be read
+pub fn cache_key(path: &Path) -> Result<String> {
+    let canonical = std::fs::canonicalize(path)?;
+    let metadata = std::fs::metadata(&canonical)?;
+
+    // Times before 1970 can't come from a real archive; treat them as 0
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+
+    let mut hash = Fnv1a::new();
+    hash.write(canonical.to_string_lossy().as_bytes());
+    hash.write(&[0]);
+    hash.write(&metadata.len().to_le_bytes());
+    hash.write(&modified.to_le_bytes());
+
+    Ok(format!("{:016x}", hash.finish()))
+}
+
+/// 64-bit FNV-1a hash
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    fn new() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -141,6 +196,44 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_cache_key_changes_with_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("comic.cbz");
+        std::fs::write(&file_path, b"archive data").unwrap();
+
+        // Stable for an unchanged file, however the path is spelled
+        let key = cache_key(&file_path).unwrap();
+        assert_eq!(key.len(), 16);
+        assert_eq!(cache_key(&file_path).unwrap(), key);
+        assert_eq!(cache_key(&temp_dir.path().join(".").join("comic.cbz")).unwrap(), key);
+
+        // Rewrite the same bytes (same size) once the clock has moved on
+        let modified = std::fs::metadata(&file_path).unwrap().modified().unwrap();
+        for _ in 0..50 {
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            std::fs::write(&file_path, b"archive data").unwrap();
+            if std::fs::metadata(&file_path).unwrap().modified().unwrap() != modified {
+                break;
+            }
+        }
+        assert_ne!(cache_key(&file_path).unwrap(), key);
+
+        assert!(cache_key(&temp_dir.path().join("missing.cbz")).is_err());
+    }
+
+    #[test]
+    fn test_fnv1a_reference_values() {
+        let hash = |data: &[u8]| {
+            let mut fnv = Fnv1a::new();
+            fnv.write(data);
+            fnv.finish()
+        };
+        assert_eq!(hash(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(hash(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(hash(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
     #[test]
     fn test_detect_archive_type_zip() {
         assert_eq!(
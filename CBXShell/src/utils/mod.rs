@@ -1,3 +1,6 @@
 pub mod error;
 pub mod file;
 pub mod debug_log;
+pub mod thread_pool;
+pub mod text;
+pub mod detection;
@@ -0,0 +1,79 @@
+//! String helpers for tooltips and log messages
+//!
+//! Entry names and archive comments are arbitrary UTF-8 (often Japanese or
+//! Korean titles), so clipping them by byte length can split a multibyte
+//! character. These helpers always cut on `char` boundaries.
+
+/// Truncate `s` to at most `max_chars` characters
+///
+/// Returns a subslice of `s`, so the result is always valid UTF-8.
+///
+/// # Examples
+/// ```ignore
+/// assert_eq!(truncate_chars("ワンピース 第1巻", 5), "ワンピース");
+/// ```
+pub fn truncate_chars(s: &str, max_chars: usize) -> &str {
+    match s.char_indices().nth(max_chars) {
+        Some((byte_index, _)) => &s[..byte_index],
+        None => s,
+    }
+}
+
+/// Truncate `s` to at most `max_chars` characters, appending "…" if clipped
+///
+/// The ellipsis counts toward `max_chars`.
+pub fn truncate_chars_with_ellipsis(s: &str, max_chars: usize) -> String {
+    if s.chars().nth(max_chars).is_none() {
+        return s.to_string();
+    }
+
+    if max_chars == 0 {
+        return String::new();
+    }
+
+    let mut clipped = truncate_chars(s, max_chars.saturating_sub(1)).to_string();
+    clipped.push('…');
+    clipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_chars_ascii() {
+        assert_eq!(truncate_chars("page01.jpg", 4), "page");
+        assert_eq!(truncate_chars("page01.jpg", 100), "page01.jpg");
+        assert_eq!(truncate_chars("", 3), "");
+        assert_eq!(truncate_chars("abc", 0), "");
+    }
+
+    #[test]
+    fn test_truncate_chars_multibyte_boundary() {
+        // Each kana/kanji is 3 bytes in UTF-8
+        let title = "進撃の巨人 第1巻";
+        assert_eq!(truncate_chars(title, 1), "進");
+        assert_eq!(truncate_chars(title, 5), "進撃の巨人");
+        assert_eq!(truncate_chars(title, 5).len(), 15);
+
+        // Byte-based clipping at 4 would split "撃"
+        assert!(!title.is_char_boundary(4));
+        assert!(std::str::from_utf8(truncate_chars(title, 2).as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_truncate_chars_emoji_and_combining() {
+        // 4-byte emoji
+        assert_eq!(truncate_chars("📚📖📕", 2), "📚📖");
+        // "é" as e + combining acute: cut keeps whole chars only
+        assert_eq!(truncate_chars("e\u{301}x", 1), "e");
+    }
+
+    #[test]
+    fn test_truncate_chars_with_ellipsis() {
+        assert_eq!(truncate_chars_with_ellipsis("ワンピース", 5), "ワンピース");
+        assert_eq!(truncate_chars_with_ellipsis("ワンピース 第1巻", 6), "ワンピース…");
+        assert_eq!(truncate_chars_with_ellipsis("abc", 1), "…");
+        assert_eq!(truncate_chars_with_ellipsis("abc", 0), "");
+    }
+}
@@ -0,0 +1,184 @@
+//! Global worker thread pool for decode/scale work
+//!
+//! Explorer calls the thumbnail provider from many threads. Instead of
+//! spawning a thread per extraction, decode jobs can be submitted to a small
+//! pool initialized once per process. The pool is opt-in: until
+//! `init_thread_pool` is called, jobs run inline on the calling thread.
+//!
+//! Workers are never stopped, so once the pool is running the DLL reports
+//! that it can't be unloaded (see `DllCanUnloadNow`).
+
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+
+use crate::utils::error::{CbxError, Result};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// Process-wide pool (set at most once)
+static THREAD_POOL: OnceLock<ThreadPool> = OnceLock::new();
+
+/// Fixed-size pool of worker threads fed by a shared job queue
+pub struct ThreadPool {
+    sender: Mutex<Sender<Job>>,
+}
+
+impl ThreadPool {
+    /// Spawn `threads` workers (at least one)
+    pub fn new(threads: usize) -> Result<Self> {
+        let threads = threads.max(1);
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        for index in 0..threads {
+            let receiver = Arc::clone(&receiver);
+            thread::Builder::new()
+                .name(format!("cbx-worker-{}", index))
+                .spawn(move || worker_loop(receiver))?;
+        }
+
+        tracing::debug!("Thread pool started with {} workers", threads);
+
+        Ok(Self {
+            sender: Mutex::new(sender),
+        })
+    }
+
+    /// Run `job` on a worker and block until it returns
+    ///
+    /// Returns `Err` if the pool could not accept the job or the worker
+    /// exited without a result.
+    pub fn execute<F, T>(&self, job: F) -> Result<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (result_tx, result_rx) = mpsc::sync_channel::<T>(1);
+
+        let wrapped: Job = Box::new(move || {
+            let _ = result_tx.send(job());
+        });
+
+        self.sender
+            .lock()
+            .map_err(|_| CbxError::ThreadPool("Thread pool queue poisoned".to_string()))?
+            .send(wrapped)
+            .map_err(|_| CbxError::ThreadPool("Thread pool is shut down".to_string()))?;
+
+        result_rx
+            .recv()
+            .map_err(|_| CbxError::ThreadPool("Worker exited without a result".to_string()))
+    }
+}
+
+fn worker_loop(receiver: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        // Hold the lock only while dequeuing, not while running the job
+        let job = match receiver.lock() {
+            Ok(rx) => rx.recv(),
+            Err(_) => return,
+        };
+
+        match job {
+            Ok(job) => job(),
+            Err(_) => return, // All senders dropped
+        }
+    }
+}
+
+/// Initialize the global thread pool with `threads` workers
+///
+/// Returns `Ok(false)` if the pool was already initialized (the existing
+/// pool is kept), `Ok(true)` if this call created it.
+pub fn init_thread_pool(threads: usize) -> Result<bool> {
+    if THREAD_POOL.get().is_some() {
+        return Ok(false);
+    }
+
+    let pool = ThreadPool::new(threads)?;
+
+    // Another thread may have won the race; its pool is kept and ours is
+    // dropped, which closes the queue and lets our workers exit.
+    Ok(THREAD_POOL.set(pool).is_ok())
+}
+
+/// Whether the global thread pool has been initialized
+pub fn is_thread_pool_initialized() -> bool {
+    THREAD_POOL.get().is_some()
+}
+
+/// Run `job` on the global pool, or inline if it was never initialized
+pub fn execute<F, T>(job: F) -> Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    execute_on(THREAD_POOL.get(), job)
+}
+
+fn execute_on<F, T>(pool: Option<&ThreadPool>, job: F) -> Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    match pool {
+        Some(pool) => pool.execute(job),
+        None => Ok(job()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execute_multiple_jobs() {
+        let pool = Arc::new(ThreadPool::new(3).unwrap());
+
+        // Submit from several threads at once; each call blocks for its result
+        let handles: Vec<_> = (0..12u64)
+            .map(|i| {
+                let pool = Arc::clone(&pool);
+                thread::spawn(move || {
+                    pool.execute(move || {
+                        let name = thread::current().name().map(str::to_string);
+                        (i * i, name)
+                    })
+                    .unwrap()
+                })
+            })
+            .collect();
+
+        for (i, handle) in handles.into_iter().enumerate() {
+            let (square, name) = handle.join().unwrap();
+            assert_eq!(square, (i * i) as u64);
+            assert!(name.unwrap().starts_with("cbx-worker-"));
+        }
+    }
+
+    #[test]
+    fn test_execute_without_pool_runs_inline() {
+        let caller = thread::current().id();
+        let ran_on = execute_on(None, move || thread::current().id()).unwrap();
+        assert_eq!(ran_on, caller);
+    }
+
+    #[test]
+    fn test_panicking_job_returns_error() {
+        // Unwinding (test builds only; release builds abort) drops the result sender
+        let pool = ThreadPool::new(1).unwrap();
+        let result: Result<()> = pool.execute(|| panic!("boom"));
+        assert!(matches!(result, Err(CbxError::ThreadPool(_))));
+    }
+
+    #[test]
+    fn test_init_thread_pool_once() {
+        let _ = init_thread_pool(2).unwrap();
+        assert!(is_thread_pool_initialized());
+
+        // Second initialization keeps the existing pool
+        assert!(!init_thread_pool(4).unwrap());
+        assert_eq!(execute(|| 7).unwrap(), 7);
+    }
+}
@@ -0,0 +1,54 @@
+//! Integration test for decoding an archive's cover to a `DynamicImage`
+//! Verifies the decoded cover comes back at its original size
+
+use cbxshell::{extract_cover_image, extract_cover_image_from_stream};
+use std::io::{Cursor, Write};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// PNG of the given size in a single color
+fn png(width: u32, height: u32, color: [u8; 3]) -> Vec<u8> {
+    let img = image::RgbImage::from_pixel(width, height, image::Rgb(color));
+    let mut out = Vec::new();
+    img.write_to(&mut Cursor::new(&mut out), image::ImageFormat::Png).unwrap();
+    out
+}
+
+/// Comic archive whose natural-sorted cover (page1.png) is 120x180 and red,
+/// stored after a 60x40 blue page10.png
+fn fixture_cbz() -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, data) in [
+        ("page10.png", png(60, 40, [0, 0, 255])),
+        ("page1.png", png(120, 180, [255, 0, 0])),
+        ("notes.txt", b"not a page".to_vec()),
+    ] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(&data).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+#[test]
+fn test_extract_cover_image_from_path() {
+    let path = std::env::temp_dir().join("test_extract_cover_image.cbz");
+    std::fs::write(&path, fixture_cbz()).unwrap();
+
+    let cover = extract_cover_image(&path, true).unwrap();
+    assert_eq!((cover.width(), cover.height()), (120, 180));
+    assert_eq!(cover.to_rgb8().get_pixel(0, 0).0, [255, 0, 0]);
+
+    // Unsorted: first image in archive order
+    let first = extract_cover_image(&path, false).unwrap();
+    assert_eq!((first.width(), first.height()), (60, 40));
+
+    std::fs::remove_file(&path).ok();
+}
+
+#[test]
+fn test_extract_cover_image_from_stream() {
+    let cover = extract_cover_image_from_stream(Cursor::new(fixture_cbz()), true).unwrap();
+    assert_eq!((cover.width(), cover.height()), (120, 180));
+
+    assert!(extract_cover_image_from_stream(Cursor::new(b"not an archive".to_vec()), true).is_err());
+}